@@ -0,0 +1,162 @@
+use crate::prompt::{PendingPrompts, Unanswered};
+use anyhow::{bail, Result};
+use serde::Serialize;
+use std::time::Duration;
+
+/// How long a keyboard-interactive prompt waits for the user. Generous enough
+/// to fetch an OTP or approve a push notification.
+pub const AUTH_PROMPT_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// One field of a keyboard-interactive prompt.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct AuthPromptField {
+    pub prompt: String,
+    /// Whether the answer may be shown while typing (false for passwords/OTPs).
+    pub echo: bool,
+}
+
+/// A keyboard-interactive info request sent to the frontend.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthPrompt {
+    /// Assigned by [`AuthPrompter::ask`]; echoed back with the answers.
+    pub prompt_id: String,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub name: String,
+    pub instructions: String,
+    pub prompts: Vec<AuthPromptField>,
+}
+
+/// Relays keyboard-interactive questions (PAM passwords, OTP codes, Duo) to
+/// the user and waits for the answers.
+///
+/// Shared by every SSH transport. Without a prompt handler, any question that
+/// cannot be answered from stored credentials fails the authentication.
+pub struct AuthPrompter {
+    prompts: PendingPrompts<AuthPrompt, Option<Vec<String>>>,
+}
+
+impl AuthPrompter {
+    pub fn new() -> Self {
+        Self {
+            prompts: PendingPrompts::new("auth", AUTH_PROMPT_TIMEOUT),
+        }
+    }
+
+    /// Install the callback used to show prompts to the user.
+    pub fn set_prompt_handler(&self, handler: impl Fn(AuthPrompt) + Send + Sync + 'static) {
+        self.prompts.set_handler(handler);
+    }
+
+    /// Show `prompt` to the user and wait for one answer per field.
+    pub async fn ask(&self, prompt: AuthPrompt) -> Result<Vec<String>> {
+        let expected = prompt.prompts.len();
+        let target = format!("{}@{}", prompt.username, prompt.host);
+        let answer = self
+            .prompts
+            .ask(|prompt_id| AuthPrompt {
+                prompt_id,
+                ..prompt
+            })
+            .await;
+        match answer {
+            Ok(Some(responses)) if responses.len() == expected => Ok(responses),
+            Ok(Some(responses)) => bail!(
+                "Expected {} authentication responses, got {}.",
+                expected,
+                responses.len()
+            ),
+            Err(Unanswered::NoHandler) => bail!(
+                "{} requested interactive authentication, but no prompt is available.",
+                target
+            ),
+            Ok(None) | Err(Unanswered::Cancelled) => bail!("Authentication cancelled by user."),
+            Err(Unanswered::TimedOut) => bail!("Timed out waiting for authentication responses."),
+        }
+    }
+
+    /// Deliver the user's answers to a pending prompt; `None` cancels it.
+    /// Returns `false` when no prompt with that ID is waiting.
+    pub fn respond(&self, prompt_id: &str, responses: Option<Vec<String>>) -> bool {
+        self.prompts.respond(prompt_id, responses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    fn prompt(fields: &[&str]) -> AuthPrompt {
+        AuthPrompt {
+            prompt_id: String::new(),
+            host: "example.com".to_string(),
+            port: 22,
+            username: "root".to_string(),
+            name: String::new(),
+            instructions: String::new(),
+            prompts: fields
+                .iter()
+                .map(|p| AuthPromptField {
+                    prompt: p.to_string(),
+                    echo: false,
+                })
+                .collect(),
+        }
+    }
+
+    fn capturing_prompter() -> (Arc<AuthPrompter>, Arc<Mutex<Vec<AuthPrompt>>>) {
+        let prompter = Arc::new(AuthPrompter::new());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        prompter.set_prompt_handler(move |p| sink.lock().unwrap().push(p));
+        (prompter, seen)
+    }
+
+    async fn wait_for_prompt(seen: &Mutex<Vec<AuthPrompt>>) -> AuthPrompt {
+        loop {
+            if let Some(p) = seen.lock().unwrap().first().cloned() {
+                return p;
+            }
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn ask_without_handler_fails() {
+        let prompter = AuthPrompter::new();
+        assert!(prompter.ask(prompt(&["Password: "])).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn respond_delivers_answers() {
+        let (prompter, seen) = capturing_prompter();
+        let asker = prompter.clone();
+        let task = tokio::spawn(async move { asker.ask(prompt(&["Verification code: "])).await });
+
+        let shown = wait_for_prompt(&seen).await;
+        assert_eq!(shown.prompts[0].prompt, "Verification code: ");
+        assert!(prompter.respond(&shown.prompt_id, Some(vec!["123456".to_string()])));
+
+        assert_eq!(task.await.unwrap().unwrap(), vec!["123456".to_string()]);
+        assert!(!prompter.respond(&shown.prompt_id, None));
+    }
+
+    #[tokio::test]
+    async fn cancel_and_wrong_answer_count_fail() {
+        let (prompter, seen) = capturing_prompter();
+        let asker = prompter.clone();
+        let task = tokio::spawn(async move { asker.ask(prompt(&["Password: "])).await });
+        let shown = wait_for_prompt(&seen).await;
+        prompter.respond(&shown.prompt_id, None);
+        assert!(task.await.unwrap().is_err());
+
+        seen.lock().unwrap().clear();
+        let asker = prompter.clone();
+        let task = tokio::spawn(async move { asker.ask(prompt(&["Password: "])).await });
+        let shown = wait_for_prompt(&seen).await;
+        prompter.respond(&shown.prompt_id, Some(vec![]));
+        assert!(task.await.unwrap().is_err());
+    }
+}
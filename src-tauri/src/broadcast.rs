@@ -0,0 +1,233 @@
+//! Running one command on many SSH connections at once, with a limit on how
+//! many hosts run it concurrently and per-host progress events.
+
+use crate::ssh::ExecOutput;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Instant;
+
+pub const DEFAULT_PARALLELISM: usize = 8;
+
+/// Which connections a broadcast runs on: the listed ids, then every open
+/// SSH connection tagged with `tag`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct BroadcastTarget {
+    pub connection_ids: Vec<String>,
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct HostExecResult {
+    pub connection_id: String,
+    #[serde(flatten)]
+    pub output: ExecOutput,
+    /// Set when the command could not be run on this host at all.
+    pub error: Option<String>,
+}
+
+impl HostExecResult {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none() && self.output.status.exit_code == Some(0)
+    }
+}
+
+/// Reported through the `ssh-broadcast-event` event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BroadcastEvent {
+    Started {
+        broadcast_id: String,
+        connection_ids: Vec<String>,
+    },
+    HostStarted {
+        broadcast_id: String,
+        connection_id: String,
+    },
+    HostFinished {
+        broadcast_id: String,
+        #[serde(flatten)]
+        result: HostExecResult,
+    },
+    Finished {
+        broadcast_id: String,
+        succeeded: usize,
+        failed: usize,
+        duration_ms: u64,
+    },
+}
+
+/// Connection ids for `target`: the explicit ids in order, then the tagged
+/// connections sorted by id, without duplicates.
+pub fn resolve_targets(
+    target: &BroadcastTarget,
+    tags: &HashMap<String, Vec<String>>,
+) -> Vec<String> {
+    let mut ids = Vec::new();
+    for id in &target.connection_ids {
+        if !ids.contains(id) {
+            ids.push(id.clone());
+        }
+    }
+    if let Some(tag) = &target.tag {
+        let mut tagged: Vec<&String> = tags
+            .iter()
+            .filter(|(_, connection_tags)| connection_tags.contains(tag))
+            .map(|(id, _)| id)
+            .collect();
+        tagged.sort();
+        for id in tagged {
+            if !ids.contains(id) {
+                ids.push(id.clone());
+            }
+        }
+    }
+    ids
+}
+
+/// Run `run_host` for each connection, at most `parallelism` at a time,
+/// reporting progress through `emit`. Results are in `connection_ids` order.
+pub async fn run_broadcast<F, Fut>(
+    broadcast_id: &str,
+    connection_ids: Vec<String>,
+    parallelism: usize,
+    run_host: F,
+    emit: impl Fn(BroadcastEvent),
+) -> Vec<HostExecResult>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = HostExecResult>,
+{
+    let started = Instant::now();
+    emit(BroadcastEvent::Started {
+        broadcast_id: broadcast_id.to_string(),
+        connection_ids: connection_ids.clone(),
+    });
+
+    let mut results: Vec<Option<HostExecResult>> = vec![None; connection_ids.len()];
+    let mut running = futures::stream::iter(connection_ids.into_iter().enumerate())
+        .map(|(index, connection_id)| {
+            emit(BroadcastEvent::HostStarted {
+                broadcast_id: broadcast_id.to_string(),
+                connection_id: connection_id.clone(),
+            });
+            let host = run_host(connection_id);
+            async move { (index, host.await) }
+        })
+        .buffer_unordered(parallelism.max(1));
+    while let Some((index, result)) = running.next().await {
+        emit(BroadcastEvent::HostFinished {
+            broadcast_id: broadcast_id.to_string(),
+            result: result.clone(),
+        });
+        results[index] = Some(result);
+    }
+    drop(running);
+
+    let results: Vec<HostExecResult> = results.into_iter().flatten().collect();
+    let succeeded = results.iter().filter(|r| r.succeeded()).count();
+    emit(BroadcastEvent::Finished {
+        broadcast_id: broadcast_id.to_string(),
+        succeeded,
+        failed: results.len() - succeeded,
+        duration_ms: started.elapsed().as_millis() as u64,
+    });
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ssh::ExecStatus;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    #[test]
+    fn resolves_ids_then_tagged_connections_once() {
+        let tags = HashMap::from([
+            ("web-2".to_string(), vec!["web".to_string()]),
+            (
+                "web-1".to_string(),
+                vec!["web".to_string(), "prod".to_string()],
+            ),
+            ("db".to_string(), vec!["prod".to_string()]),
+        ]);
+        let target = BroadcastTarget {
+            connection_ids: vec!["db".into(), "web-2".into(), "db".into()],
+            tag: Some("web".into()),
+        };
+        assert_eq!(resolve_targets(&target, &tags), ["db", "web-2", "web-1"]);
+    }
+
+    #[tokio::test]
+    async fn limits_parallelism_and_keeps_result_order() {
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let events = Mutex::new(Vec::new());
+        let ids: Vec<String> = (0..6).map(|i| format!("host-{i}")).collect();
+
+        let results = run_broadcast(
+            "b1",
+            ids.clone(),
+            2,
+            |connection_id| {
+                let (running, peak) = (&running, &peak);
+                async move {
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    // Later hosts finish first.
+                    let delay = 60 - 10 * connection_id[5..].parse::<u64>().unwrap();
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    let failed = connection_id == "host-3";
+                    HostExecResult {
+                        connection_id,
+                        output: ExecOutput {
+                            status: ExecStatus {
+                                exit_code: Some(if failed { 1 } else { 0 }),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        error: None,
+                    }
+                }
+            },
+            |event| events.lock().unwrap().push(event),
+        )
+        .await;
+
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        let order: Vec<&str> = results.iter().map(|r| r.connection_id.as_str()).collect();
+        assert_eq!(order, ids);
+        let events = events.into_inner().unwrap();
+        assert_eq!(events.len(), 2 + 2 * ids.len());
+        match events.last().unwrap() {
+            BroadcastEvent::Finished {
+                succeeded, failed, ..
+            } => assert_eq!((*succeeded, *failed), (5, 1)),
+            other => panic!("unexpected final event {:?}", other),
+        }
+    }
+
+    #[test]
+    fn host_finished_event_flattens_the_result() {
+        let event = BroadcastEvent::HostFinished {
+            broadcast_id: "b1".into(),
+            result: HostExecResult {
+                connection_id: "web-1".into(),
+                error: Some("Connection not found".into()),
+                ..Default::default()
+            },
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "host_finished");
+        assert_eq!(json["connection_id"], "web-1");
+        assert_eq!(json["stdout"], "");
+        assert_eq!(json["exit_code"], serde_json::Value::Null);
+        assert_eq!(json["error"], "Connection not found");
+    }
+}
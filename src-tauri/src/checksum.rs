@@ -0,0 +1,187 @@
+//! Content hashes for verifying transfers and telling whether a local and a
+//! remote file hold the same bytes.
+
+use crate::shell;
+use crate::transfer::CHUNK_SIZE;
+use anyhow::Result;
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tokio::io::AsyncReadExt;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    #[default]
+    Sha256,
+}
+
+impl HashAlgorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Md5 => "md5",
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha256 => "sha256",
+        }
+    }
+
+    /// Length of the hex digest.
+    fn hex_len(self) -> usize {
+        match self {
+            HashAlgorithm::Md5 => 32,
+            HashAlgorithm::Sha1 => 40,
+            HashAlgorithm::Sha256 => 64,
+        }
+    }
+
+    /// Shell command printing the digest of `path` on a Unix host: GNU
+    /// coreutils first, then the tools macOS and the BSDs ship. `--` keeps a
+    /// path starting with `-` from being read as an option.
+    pub fn remote_command(self, path: &str) -> String {
+        let tools = match self {
+            HashAlgorithm::Md5 => ["md5sum", "md5 -q", "openssl md5 -r"],
+            HashAlgorithm::Sha1 => ["sha1sum", "shasum -a 1", "openssl sha1 -r"],
+            HashAlgorithm::Sha256 => ["sha256sum", "shasum -a 256", "openssl sha256 -r"],
+        };
+        let path = shell::quote(path);
+        tools
+            .iter()
+            .map(|tool| format!("{} -- {} 2>/dev/null", tool, path))
+            .collect::<Vec<_>>()
+            .join(" || ")
+    }
+
+    /// The digest in the output of `remote_command`, if it printed one.
+    pub fn parse_remote_output(self, output: &str) -> Option<String> {
+        let digest = output.split_whitespace().next()?.to_ascii_lowercase();
+        let valid = digest.len() == self.hex_len() && digest.chars().all(|c| c.is_ascii_hexdigit());
+        valid.then_some(digest)
+    }
+}
+
+/// A digest of one file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Checksum {
+    pub algorithm: HashAlgorithm,
+    /// Lowercase hex.
+    pub hash: String,
+    /// Computed by a command on the server rather than by streaming the
+    /// file here.
+    pub computed_remotely: bool,
+}
+
+/// Feeds chunks to whichever digest was asked for.
+pub enum Hasher {
+    Md5(Md5),
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl Hasher {
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Md5 => Hasher::Md5(Md5::new()),
+            HashAlgorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+        }
+    }
+
+    pub fn update(&mut self, chunk: &[u8]) {
+        match self {
+            Hasher::Md5(hasher) => hasher.update(chunk),
+            Hasher::Sha1(hasher) => hasher.update(chunk),
+            Hasher::Sha256(hasher) => hasher.update(chunk),
+        }
+    }
+
+    pub fn finish_hex(self) -> String {
+        match self {
+            Hasher::Md5(hasher) => to_hex(&hasher.finalize()),
+            Hasher::Sha1(hasher) => to_hex(&hasher.finalize()),
+            Hasher::Sha256(hasher) => to_hex(&hasher.finalize()),
+        }
+    }
+}
+
+/// Lowercase hex, as `sha256sum` prints it.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Digest of a local file, hex encoded.
+pub async fn local_checksum(path: &Path, algorithm: HashAlgorithm) -> Result<String> {
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to open '{}': {}", path.display(), e))?;
+    let mut hasher = Hasher::new(algorithm);
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finish_hex())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn hashes_local_files_with_each_algorithm() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("abc.txt");
+        std::fs::write(&path, "abc").unwrap();
+        let cases = [
+            (HashAlgorithm::Md5, "900150983cd24fb0d6963f7d28e17f72"),
+            (
+                HashAlgorithm::Sha1,
+                "a9993e364706816aba3e25717850c26c9cd0d89d",
+            ),
+            (
+                HashAlgorithm::Sha256,
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+        ];
+        for (algorithm, expected) in cases {
+            assert_eq!(local_checksum(&path, algorithm).await.unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn parses_digests_printed_by_remote_tools() {
+        let sha = HashAlgorithm::Sha256;
+        let digest = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        // sha256sum / shasum / openssl -r all print "<digest> <path>".
+        assert_eq!(
+            sha.parse_remote_output(&format!("{digest}  /srv/abc.txt\n")),
+            Some(digest.to_string())
+        );
+        assert_eq!(
+            sha.parse_remote_output(&format!("{} *abc", digest.to_uppercase())),
+            Some(digest.to_string())
+        );
+        assert_eq!(
+            sha.parse_remote_output("sha256sum: abc: No such file"),
+            None
+        );
+        assert_eq!(sha.parse_remote_output(""), None);
+        // md5 -q prints the bare digest.
+        assert_eq!(
+            HashAlgorithm::Md5.parse_remote_output("900150983cd24fb0d6963f7d28e17f72\n"),
+            Some("900150983cd24fb0d6963f7d28e17f72".to_string())
+        );
+        assert!(HashAlgorithm::Sha1
+            .remote_command("/srv/it's")
+            .starts_with(r"sha1sum -- '/srv/it'\''s'"));
+        assert!(HashAlgorithm::Sha256
+            .remote_command("-rf")
+            .contains("shasum -a 256 -- '-rf'"));
+    }
+}
@@ -1,23 +1,55 @@
+use crate::broadcast::{self, BroadcastTarget, HostExecResult};
+use crate::checksum::{Checksum, HashAlgorithm};
 use crate::connection_manager::ConnectionManager;
+use crate::dir_transfer::{validate_remote_relative_path, DirectoryTransferOptions};
 use crate::ftp_client::FtpConfig;
+use crate::local_shell::LocalShellConfig;
 use crate::os_detect::{self, OsInfo};
 use crate::proxy::{ProxyConfig, ProxyType};
+use crate::recording::{self, RecordingFile, RecordingInfo};
+use crate::remote_fs::{parse_mode, AttributeChanges, RemoteFileStat};
+use crate::remote_edit::RemoteEditSession;
 use crate::sftp_client::{FileEntry, FileEntryType, SftpAuthMethod, SftpConfig};
-use crate::ssh::{AuthMethod, SshConfig};
+use crate::shell::escape_single_quoted;
+use crate::ssh::{
+    AuthMethod, ExecOptions, ExecOutput, ForwardInfo, ForwardSpec, JumpHost, ReconnectPolicy,
+    SshConfig,
+};
+use crate::ssh_config::SshConfigFile;
+use crate::sync::{SyncPlan, SyncReport, SyncRequest};
+use crate::telnet_client::TelnetConfig;
+use crate::transfer::{TransferDirection, TransferSpec};
+use crate::transfer_queue::{QueueLimits, TransferJob};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tauri::State;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ConnectRequest {
     pub connection_id: String,
+    /// Alias from `~/.ssh/config`. When set, the connection settings come
+    /// from the config and host, port, username and auth_method may be
+    /// omitted; `passphrase` and `agent_forwarding` still apply.
+    pub ssh_config_host: Option<String>,
+    #[serde(default)]
     pub host: String,
+    #[serde(default)]
     pub port: u16,
+    #[serde(default)]
     pub username: String,
+    #[serde(default)]
     pub auth_method: String,
     pub password: Option<String>,
     pub key_path: Option<String>,
     pub passphrase: Option<String>,
+    /// OpenSSH certificate for `key_path`; `<key_path>-cert.pub` is used
+    /// when omitted and present.
+    pub certificate_path: Option<String>,
+    /// Methods tried after `auth_method` for servers requiring several
+    /// (e.g. `["keyboard-interactive"]` after "publickey"). They share the
+    /// credential fields above.
+    pub additional_auth_methods: Option<Vec<String>>,
     /// Advanced SSH options — `Option` so legacy callers that omit them keep
     /// the previous defaults (compression on, keepalive 60 s / 3).
     pub compression: Option<bool>,
@@ -30,6 +62,19 @@ pub struct ConnectRequest {
     pub proxy_port: Option<u16>,
     pub proxy_username: Option<String>,
     pub proxy_password: Option<String>,
+    /// Command line for proxy type "command" (OpenSSH `ProxyCommand`), with
+    /// `%h`, `%p` and `%r` substituted.
+    pub proxy_command: Option<String>,
+    /// Bastions to hop through, outermost first. The proxy, if any, is used
+    /// to reach the first hop.
+    pub jump_hosts: Option<Vec<JumpHost>>,
+    /// Forward the local SSH agent to the remote shell (default: off).
+    pub agent_forwarding: Option<bool>,
+    /// Automatic reconnect settings; reconnects with the default backoff
+    /// when omitted.
+    pub reconnect: Option<ReconnectPolicy>,
+    /// Groups this connection belongs to, for `ssh_broadcast_exec`.
+    pub tags: Option<Vec<String>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -70,7 +115,41 @@ pub async fn ssh_connect(
     request: ConnectRequest,
     state: State<'_, Arc<ConnectionManager>>,
 ) -> Result<CommandResponse, String> {
-    let proxy = build_proxy(&request)?;
+    let mut config = match request.ssh_config_host.as_deref() {
+        Some(alias) => resolve_ssh_config_host(alias, &request)?,
+        None => build_ssh_config(&request)?,
+    };
+    if let Some(reconnect) = &request.reconnect {
+        config.reconnect = reconnect.clone();
+    }
+
+    match state
+        .create_connection(request.connection_id.clone(), config)
+        .await
+    {
+        Ok(_) => {
+            if let Some(tags) = request.tags {
+                state
+                    .set_connection_tags(&request.connection_id, tags)
+                    .await;
+            }
+            Ok(CommandResponse {
+                success: true,
+                output: Some(format!("Connected: {}", request.connection_id)),
+                error: None,
+            })
+        }
+        Err(e) => Ok(CommandResponse {
+            success: false,
+            output: None,
+            error: Some(e.to_string()),
+        }),
+    }
+}
+
+/// Build an `SshConfig` from the request's explicit connection fields.
+fn build_ssh_config(request: &ConnectRequest) -> Result<SshConfig, String> {
+    let proxy = build_proxy(request)?;
 
     // Keepalive defaults match the connection dialog UI: enabled at 60 s / 3.
     let keepalive_enabled = request.keepalive_enabled.unwrap_or(true);
@@ -85,42 +164,69 @@ pub async fn ssh_connect(
         None
     };
 
-    let auth_method = match request.auth_method.as_str() {
-        "password" => AuthMethod::Password {
-            password: request.password.ok_or("Password required")?,
-        },
-        "publickey" => AuthMethod::PublicKey {
-            key_path: request.key_path.ok_or("Key path required")?,
-            passphrase: request.passphrase,
-        },
-        _ => return Err("Invalid auth method".to_string()),
-    };
+    let auth_method = build_auth_method(&request.auth_method, request)?;
+    let additional_auth_methods = request
+        .additional_auth_methods
+        .iter()
+        .flatten()
+        .map(|method| build_auth_method(method, request))
+        .collect::<Result<Vec<_>, _>>()?;
 
-    let config = SshConfig {
-        host: request.host,
+    Ok(SshConfig {
+        host: request.host.clone(),
         port: request.port,
-        username: request.username,
+        username: request.username.clone(),
         auth_method,
+        additional_auth_methods,
         compression: request.compression.unwrap_or(true),
         keepalive_interval,
         keepalive_max,
         proxy,
-    };
+        jump_hosts: request.jump_hosts.clone().unwrap_or_default(),
+        agent_forwarding: request.agent_forwarding.unwrap_or(false),
+        reconnect: ReconnectPolicy::default(),
+    })
+}
 
-    match state
-        .create_connection(request.connection_id.clone(), config)
-        .await
-    {
-        Ok(_) => Ok(CommandResponse {
-            success: true,
-            output: Some(format!("Connected: {}", request.connection_id)),
-            error: None,
+/// Resolve a `~/.ssh/config` alias, applying the request's passphrase to
+/// the configured identity files.
+fn resolve_ssh_config_host(alias: &str, request: &ConnectRequest) -> Result<SshConfig, String> {
+    let mut config = SshConfigFile::load_default()
+        .and_then(|file| file.ssh_config(alias))
+        .map_err(|e| e.to_string())?;
+    config.agent_forwarding = request.agent_forwarding.unwrap_or(false);
+    if let Some(passphrase) = &request.passphrase {
+        let methods = std::iter::once(&mut config.auth_method)
+            .chain(&mut config.additional_auth_methods);
+        for method in methods {
+            if let AuthMethod::PublicKey {
+                passphrase: slot, ..
+            } = method
+            {
+                *slot = Some(passphrase.clone());
+            }
+        }
+    }
+    Ok(config)
+}
+
+/// Map an auth method name plus the request's credential fields into an
+/// `AuthMethod`.
+fn build_auth_method(method: &str, request: &ConnectRequest) -> Result<AuthMethod, String> {
+    match method {
+        "password" => Ok(AuthMethod::Password {
+            password: request.password.clone().ok_or("Password required")?,
         }),
-        Err(e) => Ok(CommandResponse {
-            success: false,
-            output: None,
-            error: Some(e.to_string()),
+        "publickey" => Ok(AuthMethod::PublicKey {
+            key_path: request.key_path.clone().ok_or("Key path required")?,
+            passphrase: request.passphrase.clone(),
+            certificate_path: request.certificate_path.clone(),
         }),
+        "agent" => Ok(AuthMethod::Agent),
+        "keyboard-interactive" => Ok(AuthMethod::KeyboardInteractive {
+            password: request.password.clone(),
+        }),
+        _ => Err("Invalid auth method".to_string()),
     }
 }
 
@@ -129,6 +235,21 @@ pub async fn ssh_connect(
 fn build_proxy(request: &ConnectRequest) -> Result<Option<ProxyConfig>, String> {
     match request.proxy_type.as_deref() {
         None | Some("none") | Some("") => Ok(None),
+        Some("command") => {
+            let command = request
+                .proxy_command
+                .clone()
+                .filter(|c| !c.trim().is_empty())
+                .ok_or("Proxy command is required")?;
+            Ok(Some(ProxyConfig {
+                proxy_type: ProxyType::Command,
+                host: String::new(),
+                port: 0,
+                username: None,
+                password: None,
+                command: Some(command),
+            }))
+        }
         Some(kind) => {
             let host = request
                 .proxy_host
@@ -147,6 +268,7 @@ fn build_proxy(request: &ConnectRequest) -> Result<Option<ProxyConfig>, String>
                 port: request.proxy_port.unwrap_or(8080),
                 username: request.proxy_username.clone(),
                 password: request.proxy_password.clone(),
+                command: None,
             }))
         }
     }
@@ -172,6 +294,53 @@ pub async fn ssh_cancel_connect(
     }
 }
 
+/// Answer an `ssh-host-key-prompt` event. Accepting records the key in
+/// known_hosts and lets the pending handshake continue.
+#[tauri::command]
+pub async fn ssh_respond_host_key(
+    prompt_id: String,
+    accept: bool,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<CommandResponse, String> {
+    if state.host_key_verifier().respond(&prompt_id, accept) {
+        Ok(CommandResponse {
+            success: true,
+            output: Some(if accept { "Host key accepted" } else { "Host key rejected" }.to_string()),
+            error: None,
+        })
+    } else {
+        Ok(CommandResponse {
+            success: false,
+            output: None,
+            error: Some("No pending host key prompt with that id".to_string()),
+        })
+    }
+}
+
+/// Answer a keyboard-interactive prompt emitted as `ssh-auth-prompt`.
+/// `responses` must hold one entry per prompt field; `None` cancels.
+#[tauri::command]
+pub async fn ssh_respond_auth_prompt(
+    prompt_id: String,
+    responses: Option<Vec<String>>,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<CommandResponse, String> {
+    let cancelled = responses.is_none();
+    if state.auth_prompter().respond(&prompt_id, responses) {
+        Ok(CommandResponse {
+            success: true,
+            output: Some(if cancelled { "Authentication cancelled" } else { "Responses sent" }.to_string()),
+            error: None,
+        })
+    } else {
+        Ok(CommandResponse {
+            success: false,
+            output: None,
+            error: Some("No pending authentication prompt with that id".to_string()),
+        })
+    }
+}
+
 #[tauri::command]
 pub async fn ssh_disconnect(
     connection_id: String,
@@ -529,13 +698,6 @@ pub async fn sftp_upload_file(
 
 // File operation commands
 
-/// Escape a path for use inside a POSIX single-quoted shell argument.
-/// Single quotes cannot appear inside a single-quoted string, so we end the
-/// quote, emit the escaped quote, and reopen the quote: `'` → `'\''`.
-fn shell_escape_single_quoted(path: &str) -> String {
-    path.replace('\'', "'\\''")
-}
-
 #[tauri::command]
 pub async fn create_directory(
     connection_id: String,
@@ -548,7 +710,7 @@ pub async fn create_directory(
         .ok_or("Connection not found")?;
 
     let client = connection.read().await;
-    let command = format!("mkdir -p '{}'", shell_escape_single_quoted(&path));
+    let command = format!("mkdir -p '{}'", escape_single_quoted(&path));
 
     match client.execute_command(&command).await {
         Ok(_) => Ok(true),
@@ -570,9 +732,9 @@ pub async fn delete_file(
 
     let client = connection.read().await;
     let command = if is_directory {
-        format!("rm -rf '{}'", shell_escape_single_quoted(&path))
+        format!("rm -rf '{}'", escape_single_quoted(&path))
     } else {
-        format!("rm -f '{}'", shell_escape_single_quoted(&path))
+        format!("rm -f '{}'", escape_single_quoted(&path))
     };
 
     match client.execute_command(&command).await {
@@ -596,8 +758,8 @@ pub async fn rename_file(
     let client = connection.read().await;
     let command = format!(
         "mv '{}' '{}'",
-        shell_escape_single_quoted(&old_path),
-        shell_escape_single_quoted(&new_path)
+        escape_single_quoted(&old_path),
+        escape_single_quoted(&new_path)
     );
 
     match client.execute_command(&command).await {
@@ -1155,7 +1317,7 @@ pub async fn search_log(
     let client = connection.read().await;
 
     let limit = max_results.unwrap_or(500);
-    let escaped = pattern.replace('\'', "'\\''");
+    let escaped = escape_single_quoted(&pattern);
     let grep_flag = if is_regex.unwrap_or(false) {
         "-nE"
     } else {
@@ -2163,65 +2325,189 @@ pub async fn get_websocket_port() -> Result<u16, String> {
 // Use get_websocket_port() command to get the actual port
 // See src/websocket_server.rs for implementation
 
-// ========== Standalone SFTP Connection ==========
+// ========== SSH Config ==========
+
+/// Host aliases defined in `~/.ssh/config`, for importing into the
+/// connection list.
+#[tauri::command]
+pub async fn ssh_config_list_hosts() -> Result<Vec<String>, String> {
+    SshConfigFile::load_default()
+        .map(|file| file.hosts())
+        .map_err(|e| e.to_string())
+}
+
+/// Resolve an alias from `~/.ssh/config` into the `SshConfig` that
+/// `ssh_connect` would use for it.
+#[tauri::command]
+pub async fn ssh_config_resolve(alias: String) -> Result<SshConfig, String> {
+    SshConfigFile::load_default()
+        .and_then(|file| file.ssh_config(&alias))
+        .map_err(|e| e.to_string())
+}
+
+// ========== Port Forwarding ==========
+
+/// Start a local (`-L`), remote (`-R`) or dynamic SOCKS5 (`-D`) forward on an
+/// SSH connection. The returned info carries the actually bound port.
+#[tauri::command]
+pub async fn ssh_add_port_forward(
+    connection_id: String,
+    spec: ForwardSpec,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<ForwardInfo, String> {
+    state
+        .add_port_forward(&connection_id, spec)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// List forwards with their byte and connection counters. Without a
+/// `connection_id`, forwards of every connection are returned.
+#[tauri::command]
+pub async fn ssh_list_port_forwards(
+    connection_id: Option<String>,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<Vec<ForwardInfo>, String> {
+    Ok(state.list_port_forwards(connection_id.as_deref()).await)
+}
+
+#[tauri::command]
+pub async fn ssh_remove_port_forward(
+    forward_id: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<CommandResponse, String> {
+    match state.remove_port_forward(&forward_id).await {
+        Ok(()) => Ok(CommandResponse {
+            success: true,
+            output: Some("Port forward removed".to_string()),
+            error: None,
+        }),
+        Err(e) => Ok(CommandResponse {
+            success: false,
+            output: None,
+            error: Some(e.to_string()),
+        }),
+    }
+}
+
+// ========== Session Recording ==========
 
 #[derive(Debug, Deserialize)]
-pub struct SftpConnectRequest {
+pub struct StartRecordingRequest {
     pub connection_id: String,
-    pub host: String,
-    pub port: u16,
-    pub username: String,
-    pub auth_method: String,
-    pub password: Option<String>,
-    pub key_path: Option<String>,
-    pub passphrase: Option<String>,
+    /// Also record keystrokes. Off by default: input includes passwords
+    /// typed at prompts.
+    pub include_input: Option<bool>,
+    pub title: Option<String>,
+    /// Directory for the `.cast` file; defaults to the app's recordings
+    /// directory.
+    pub directory: Option<String>,
 }
 
+/// Start recording a terminal to an asciicast v2 (`.cast`) file.
 #[tauri::command]
-pub async fn sftp_connect(
-    request: SftpConnectRequest,
+pub async fn start_recording(
+    request: StartRecordingRequest,
     state: State<'_, Arc<ConnectionManager>>,
-) -> Result<CommandResponse, String> {
-    let auth = match request.auth_method.as_str() {
-        "password" => SftpAuthMethod::Password {
-            password: request.password.unwrap_or_default(),
-        },
-        "publickey" => SftpAuthMethod::PublicKey {
-            key_path: request.key_path.ok_or("Key path required for SFTP")?,
-            passphrase: request.passphrase,
-        },
-        _ => return Err("Invalid SFTP auth method".to_string()),
+) -> Result<RecordingInfo, String> {
+    let directory = match request.directory {
+        Some(dir) => PathBuf::from(crate::ssh::expand_tilde(&dir)),
+        None => recording::default_recordings_dir().map_err(|e| e.to_string())?,
     };
+    let path = directory.join(recording::recording_file_name(&request.connection_id));
+    state
+        .start_recording(
+            &request.connection_id,
+            path,
+            request.title,
+            request.include_input.unwrap_or(false),
+        )
+        .await
+        .map_err(|e| e.to_string())
+}
 
-    let config = SftpConfig {
-        host: request.host,
-        port: request.port,
-        username: request.username,
-        auth_method: auth,
+#[tauri::command]
+pub async fn stop_recording(
+    connection_id: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<RecordingInfo, String> {
+    state
+        .stop_recording(&connection_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecordingList {
+    /// Recordings currently being written.
+    pub active: Vec<RecordingInfo>,
+    /// `.cast` files in the directory, newest first.
+    pub files: Vec<RecordingFile>,
+}
+
+#[tauri::command]
+pub async fn list_recordings(
+    directory: Option<String>,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<RecordingList, String> {
+    let directory = match directory {
+        Some(dir) => PathBuf::from(crate::ssh::expand_tilde(&dir)),
+        None => recording::default_recordings_dir().map_err(|e| e.to_string())?,
     };
+    let files = recording::list_recordings(&directory).map_err(|e| e.to_string())?;
+    Ok(RecordingList {
+        active: state.list_active_recordings().await,
+        files,
+    })
+}
+
+// ========== Local Terminal ==========
+
+#[derive(Debug, Deserialize)]
+pub struct LocalConnectRequest {
+    pub connection_id: String,
+    /// Program to run; defaults to the user's login shell.
+    pub shell: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Starting directory; defaults to the home directory.
+    pub cwd: Option<String>,
+}
 
+/// Register a local terminal tab. The shell is spawned when the frontend
+/// sends StartPty for `connection_id`, exactly as for SSH.
+#[tauri::command]
+pub async fn local_connect(
+    request: LocalConnectRequest,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<CommandResponse, String> {
+    let config = LocalShellConfig {
+        shell: request.shell,
+        args: request.args,
+        cwd: request.cwd,
+    };
     match state
-        .create_sftp_connection(request.connection_id.clone(), config)
+        .create_local_connection(request.connection_id.clone(), config)
         .await
     {
         Ok(_) => Ok(CommandResponse {
             success: true,
-            output: Some(format!("SFTP connected: {}", request.connection_id)),
+            output: Some(format!("Local terminal ready: {}", request.connection_id)),
             error: None,
         }),
-        Err(e) => Err(format!("SFTP connection failed: {}", e)),
+        Err(e) => Err(format!("Local terminal failed: {}", e)),
     }
 }
 
 #[tauri::command]
-pub async fn sftp_standalone_disconnect(
+pub async fn local_disconnect(
     connection_id: String,
     state: State<'_, Arc<ConnectionManager>>,
 ) -> Result<CommandResponse, String> {
-    match state.close_sftp_connection(&connection_id).await {
+    match state.close_local_connection(&connection_id).await {
         Ok(_) => Ok(CommandResponse {
             success: true,
-            output: Some("SFTP disconnected".to_string()),
+            output: Some("Local terminal closed".to_string()),
             error: None,
         }),
         Err(e) => Ok(CommandResponse {
@@ -2232,73 +2518,64 @@ pub async fn sftp_standalone_disconnect(
     }
 }
 
-// ========== FTP Connection ==========
+// ========== Command Execution ==========
 
-#[derive(Debug, Deserialize)]
-pub struct FtpConnectRequest {
-    pub connection_id: String,
-    pub host: String,
-    pub port: u16,
-    pub username: String,
-    pub password: Option<String>,
-    pub ftps_enabled: bool,
-    pub anonymous: bool,
+/// Run a command and return stdout, stderr and the exit status separately.
+/// Unlike `ssh_execute_command`, a non-zero exit is not an error.
+#[tauri::command]
+pub async fn ssh_exec(
+    connection_id: String,
+    command: String,
+    options: Option<ExecOptions>,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<ExecOutput, String> {
+    let connection = state
+        .get_connection(&connection_id)
+        .await
+        .ok_or("Connection not found")?;
+    let client = connection.read().await;
+    client
+        .exec(&command, &options.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
 }
 
+/// Start a command whose output is pushed as `ssh-exec-event` events tagged
+/// with `exec_id`, ending with an `exit` or `error` event.
 #[tauri::command]
-pub async fn ftp_connect(
-    request: FtpConnectRequest,
+pub async fn ssh_exec_stream(
+    exec_id: String,
+    connection_id: String,
+    command: String,
+    options: Option<ExecOptions>,
     state: State<'_, Arc<ConnectionManager>>,
 ) -> Result<CommandResponse, String> {
-    tracing::info!(
-        "ftp_connect: id={}, host={}:{}, user={}, ftps={}, anon={}",
-        request.connection_id,
-        request.host,
-        request.port,
-        request.username,
-        request.ftps_enabled,
-        request.anonymous
-    );
-
-    let config = FtpConfig {
-        host: request.host,
-        port: request.port,
-        username: if request.anonymous {
-            "anonymous".to_string()
-        } else {
-            request.username
-        },
-        password: if request.anonymous {
-            "anonymous@".to_string()
-        } else {
-            request.password.unwrap_or_default()
-        },
-        ftps_enabled: request.ftps_enabled,
-        anonymous: request.anonymous,
-    };
-
     match state
-        .create_ftp_connection(request.connection_id.clone(), config)
+        .start_exec(exec_id, &connection_id, &command, options.unwrap_or_default())
         .await
     {
         Ok(_) => Ok(CommandResponse {
             success: true,
-            output: Some(format!("FTP connected: {}", request.connection_id)),
+            output: None,
             error: None,
         }),
-        Err(e) => Err(format!("FTP connection failed: {}", e)),
+        Err(e) => Ok(CommandResponse {
+            success: false,
+            output: None,
+            error: Some(e.to_string()),
+        }),
     }
 }
 
 #[tauri::command]
-pub async fn ftp_disconnect(
-    connection_id: String,
+pub async fn ssh_exec_cancel(
+    exec_id: String,
     state: State<'_, Arc<ConnectionManager>>,
 ) -> Result<CommandResponse, String> {
-    match state.close_ftp_connection(&connection_id).await {
+    match state.cancel_exec(&exec_id).await {
         Ok(_) => Ok(CommandResponse {
             success: true,
-            output: Some("FTP disconnected".to_string()),
+            output: Some("Command cancelled".to_string()),
             error: None,
         }),
         Err(e) => Ok(CommandResponse {
@@ -2309,21 +2586,297 @@ pub async fn ftp_disconnect(
     }
 }
 
-// ========== Unified File Operations ==========
+#[derive(Debug, Deserialize)]
+pub struct BroadcastExecRequest {
+    /// Chosen by the caller to match up `ssh-broadcast-event` events.
+    pub broadcast_id: String,
+    #[serde(default)]
+    pub connection_ids: Vec<String>,
+    /// Also run on every open connection with this tag.
+    pub tag: Option<String>,
+    pub command: String,
+    pub options: Option<ExecOptions>,
+    /// Hosts running the command at once (default 8).
+    pub max_parallel: Option<usize>,
+}
 
+/// Run one command on several connections, returning a result per host in
+/// the order the connections were resolved.
 #[tauri::command]
-pub async fn list_remote_files(
-    connection_id: String,
-    path: String,
+pub async fn ssh_broadcast_exec(
+    request: BroadcastExecRequest,
     state: State<'_, Arc<ConnectionManager>>,
-) -> Result<Vec<FileEntry>, String> {
-    let conn_type = state
-        .get_connection_type(&connection_id)
+) -> Result<Vec<HostExecResult>, String> {
+    let target = BroadcastTarget {
+        connection_ids: request.connection_ids,
+        tag: request.tag,
+    };
+    state
+        .broadcast_exec(
+            &request.broadcast_id,
+            &target,
+            &request.command,
+            &request.options.unwrap_or_default(),
+            request
+                .max_parallel
+                .unwrap_or(broadcast::DEFAULT_PARALLELISM),
+        )
         .await
-        .ok_or_else(|| format!("No file connection found for '{}'", connection_id))?;
+        .map_err(|e| e.to_string())
+}
 
-    match conn_type.as_str() {
-        "SFTP" => {
+// ========== Telnet Connection ==========
+
+#[derive(Debug, Deserialize)]
+pub struct TelnetConnectRequest {
+    pub connection_id: String,
+    pub host: String,
+    pub port: Option<u16>,
+    /// Typed automatically at the login prompt.
+    pub username: Option<String>,
+    /// Typed automatically at the password prompt.
+    pub password: Option<String>,
+    pub login_prompt: Option<String>,
+    pub password_prompt: Option<String>,
+    pub terminal_type: Option<String>,
+}
+
+#[tauri::command]
+pub async fn telnet_connect(
+    request: TelnetConnectRequest,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<CommandResponse, String> {
+    let config = TelnetConfig {
+        host: request.host,
+        port: request.port.unwrap_or(23),
+        username: request.username.filter(|u| !u.is_empty()),
+        password: request.password.filter(|p| !p.is_empty()),
+        login_prompt: request.login_prompt.filter(|p| !p.is_empty()),
+        password_prompt: request.password_prompt.filter(|p| !p.is_empty()),
+        terminal_type: request
+            .terminal_type
+            .unwrap_or_else(|| "xterm-256color".to_string()),
+    };
+    match state
+        .create_telnet_connection(request.connection_id.clone(), config)
+        .await
+    {
+        Ok(_) => Ok(CommandResponse {
+            success: true,
+            output: Some(format!("Telnet connected: {}", request.connection_id)),
+            error: None,
+        }),
+        Err(e) => Err(format!("Telnet connection failed: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn telnet_disconnect(
+    connection_id: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<CommandResponse, String> {
+    match state.close_telnet_connection(&connection_id).await {
+        Ok(_) => Ok(CommandResponse {
+            success: true,
+            output: Some("Telnet disconnected".to_string()),
+            error: None,
+        }),
+        Err(e) => Ok(CommandResponse {
+            success: false,
+            output: None,
+            error: Some(e.to_string()),
+        }),
+    }
+}
+
+// ========== Standalone SFTP Connection ==========
+
+#[derive(Debug, Deserialize)]
+pub struct SftpConnectRequest {
+    pub connection_id: String,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub auth_method: String,
+    pub password: Option<String>,
+    pub key_path: Option<String>,
+    pub passphrase: Option<String>,
+    /// OpenSSH certificate for `key_path`; `<key_path>-cert.pub` is used
+    /// when omitted and present.
+    pub certificate_path: Option<String>,
+    /// Methods tried after `auth_method`; they share the credential fields.
+    pub additional_auth_methods: Option<Vec<String>>,
+    /// Bastions to hop through, outermost first.
+    pub jump_hosts: Option<Vec<JumpHost>>,
+}
+
+fn build_sftp_auth_method(
+    method: &str,
+    request: &SftpConnectRequest,
+) -> Result<SftpAuthMethod, String> {
+    match method {
+        "password" => Ok(SftpAuthMethod::Password {
+            password: request.password.clone().unwrap_or_default(),
+        }),
+        "publickey" => Ok(SftpAuthMethod::PublicKey {
+            key_path: request.key_path.clone().ok_or("Key path required for SFTP")?,
+            passphrase: request.passphrase.clone(),
+            certificate_path: request.certificate_path.clone(),
+        }),
+        "agent" => Ok(SftpAuthMethod::Agent),
+        "keyboard-interactive" => Ok(SftpAuthMethod::KeyboardInteractive {
+            password: request.password.clone(),
+        }),
+        _ => Err("Invalid SFTP auth method".to_string()),
+    }
+}
+
+#[tauri::command]
+pub async fn sftp_connect(
+    request: SftpConnectRequest,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<CommandResponse, String> {
+    let auth = build_sftp_auth_method(&request.auth_method, &request)?;
+    let additional_auth_methods = request
+        .additional_auth_methods
+        .iter()
+        .flatten()
+        .map(|method| build_sftp_auth_method(method, &request))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let config = SftpConfig {
+        host: request.host,
+        port: request.port,
+        username: request.username,
+        auth_method: auth,
+        additional_auth_methods,
+        jump_hosts: request.jump_hosts.unwrap_or_default(),
+    };
+
+    match state
+        .create_sftp_connection(request.connection_id.clone(), config)
+        .await
+    {
+        Ok(_) => Ok(CommandResponse {
+            success: true,
+            output: Some(format!("SFTP connected: {}", request.connection_id)),
+            error: None,
+        }),
+        Err(e) => Err(format!("SFTP connection failed: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn sftp_standalone_disconnect(
+    connection_id: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<CommandResponse, String> {
+    match state.close_sftp_connection(&connection_id).await {
+        Ok(_) => Ok(CommandResponse {
+            success: true,
+            output: Some("SFTP disconnected".to_string()),
+            error: None,
+        }),
+        Err(e) => Ok(CommandResponse {
+            success: false,
+            output: None,
+            error: Some(e.to_string()),
+        }),
+    }
+}
+
+// ========== FTP Connection ==========
+
+#[derive(Debug, Deserialize)]
+pub struct FtpConnectRequest {
+    pub connection_id: String,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: Option<String>,
+    pub ftps_enabled: bool,
+    pub anonymous: bool,
+}
+
+#[tauri::command]
+pub async fn ftp_connect(
+    request: FtpConnectRequest,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<CommandResponse, String> {
+    tracing::info!(
+        "ftp_connect: id={}, host={}:{}, user={}, ftps={}, anon={}",
+        request.connection_id,
+        request.host,
+        request.port,
+        request.username,
+        request.ftps_enabled,
+        request.anonymous
+    );
+
+    let config = FtpConfig {
+        host: request.host,
+        port: request.port,
+        username: if request.anonymous {
+            "anonymous".to_string()
+        } else {
+            request.username
+        },
+        password: if request.anonymous {
+            "anonymous@".to_string()
+        } else {
+            request.password.unwrap_or_default()
+        },
+        ftps_enabled: request.ftps_enabled,
+        anonymous: request.anonymous,
+    };
+
+    match state
+        .create_ftp_connection(request.connection_id.clone(), config)
+        .await
+    {
+        Ok(_) => Ok(CommandResponse {
+            success: true,
+            output: Some(format!("FTP connected: {}", request.connection_id)),
+            error: None,
+        }),
+        Err(e) => Err(format!("FTP connection failed: {}", e)),
+    }
+}
+
+#[tauri::command]
+pub async fn ftp_disconnect(
+    connection_id: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<CommandResponse, String> {
+    match state.close_ftp_connection(&connection_id).await {
+        Ok(_) => Ok(CommandResponse {
+            success: true,
+            output: Some("FTP disconnected".to_string()),
+            error: None,
+        }),
+        Err(e) => Ok(CommandResponse {
+            success: false,
+            output: None,
+            error: Some(e.to_string()),
+        }),
+    }
+}
+
+// ========== Unified File Operations ==========
+
+#[tauri::command]
+pub async fn list_remote_files(
+    connection_id: String,
+    path: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<Vec<FileEntry>, String> {
+    let conn_type = state
+        .get_connection_type(&connection_id)
+        .await
+        .ok_or_else(|| format!("No file connection found for '{}'", connection_id))?;
+
+    match conn_type.as_str() {
+        "SFTP" => {
             let sftp_map = state.get_sftp_connection().await;
             let connections = sftp_map.read().await;
             let client = connections
@@ -2332,56 +2885,23 @@ pub async fn list_remote_files(
             client.list_dir(&path).await.map_err(|e| e.to_string())
         }
         "FTP" => {
-            let ftp_map = state.get_ftp_connection().await;
-            let mut connections = ftp_map.write().await;
-            let client = connections
-                .get_mut(&connection_id)
+            let client = state
+                .get_ftp_client(&connection_id)
+                .await
                 .ok_or("FTP connection not found")?;
+            let mut client = client.lock().await;
             client.list_dir(&path).await.map_err(|e| e.to_string())
         }
         _ => Err(format!("Unsupported protocol: {}", conn_type)),
     }
 }
 
-async fn download_remote_file_to_path(
-    connection_id: &str,
-    remote_path: &str,
-    local_path: &str,
+async fn transfer_remote_file(
+    spec: TransferSpec,
+    transfer_id: Option<String>,
     state: &Arc<ConnectionManager>,
 ) -> Result<FileTransferResponse, String> {
-    let conn_type = state.get_connection_type(connection_id).await;
-
-    let result = match conn_type.as_deref() {
-        Some("SFTP") => {
-            let sftp_map = state.get_sftp_connection().await;
-            let connections = sftp_map.read().await;
-            let client = connections
-                .get(connection_id)
-                .ok_or("SFTP connection not found".to_string())?;
-            client.download_file(remote_path, local_path).await
-        }
-        Some("FTP") => {
-            let ftp_map = state.get_ftp_connection().await;
-            let mut connections = ftp_map.write().await;
-            let client = connections
-                .get_mut(connection_id)
-                .ok_or("FTP connection not found".to_string())?;
-            client.download_file(remote_path, local_path).await
-        }
-        Some(other) => return Err(format!("Unsupported protocol: {}", other)),
-        None => {
-            // Fallback: try SSH connection (integrated file browser uses SSH connections
-            // which are not registered in connection_types)
-            let connection = state
-                .get_connection(connection_id)
-                .await
-                .ok_or_else(|| format!("No connection found for '{}'", connection_id))?;
-            let client = connection.read().await;
-            client.download_file(remote_path, local_path).await
-        }
-    };
-
-    match result {
+    match state.transfer_file(transfer_id, spec).await {
         Ok(bytes) => Ok(FileTransferResponse {
             success: true,
             bytes_transferred: Some(bytes),
@@ -2397,16 +2917,32 @@ async fn download_remote_file_to_path(
     }
 }
 
+/// Progress is reported as `transfer-progress` events under `transfer_id`,
+/// which `cancel_transfer` accepts. With `resume`, a partial local file is
+/// continued rather than replaced. With `verify`, both copies are hashed
+/// afterwards and a mismatch fails the transfer.
 #[tauri::command]
 pub async fn download_remote_file(
     connection_id: String,
     remote_path: String,
     local_path: String,
+    transfer_id: Option<String>,
+    resume: Option<bool>,
+    verify: Option<HashAlgorithm>,
     state: State<'_, Arc<ConnectionManager>>,
 ) -> Result<FileTransferResponse, String> {
-    download_remote_file_to_path(&connection_id, &remote_path, &local_path, state.inner()).await
+    let spec = TransferSpec {
+        connection_id,
+        direction: TransferDirection::Download,
+        remote_path,
+        local_path,
+        resume: resume.unwrap_or(false),
+        verify,
+    };
+    transfer_remote_file(spec, transfer_id, state.inner()).await
 }
 
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 pub async fn download_remote_file_confined(
     connection_id: String,
@@ -2414,9 +2950,12 @@ pub async fn download_remote_file_confined(
     destination_root: String,
     remote_relative_path: String,
     destination_relative_path: String,
+    transfer_id: Option<String>,
+    resume: Option<bool>,
+    verify: Option<HashAlgorithm>,
     state: State<'_, Arc<ConnectionManager>>,
 ) -> Result<FileTransferResponse, String> {
-    validate_remote_relative_path(&remote_relative_path)?;
+    validate_remote_relative_path(&remote_relative_path).map_err(|e| e.to_string())?;
     let local_path = resolve_confined_local_path(
         std::path::Path::new(&destination_root),
         &destination_relative_path,
@@ -2434,49 +2973,47 @@ pub async fn download_remote_file_confined(
         )
     };
 
-    download_remote_file_to_path(&connection_id, &remote_path, local_path, state.inner()).await
+    let spec = TransferSpec {
+        connection_id,
+        direction: TransferDirection::Download,
+        remote_path,
+        local_path: local_path.to_string(),
+        resume: resume.unwrap_or(false),
+        verify,
+    };
+    transfer_remote_file(spec, transfer_id, state.inner()).await
 }
 
+/// Like `download_remote_file`; with `resume`, a shorter remote file is
+/// continued rather than replaced.
 #[tauri::command]
 pub async fn upload_remote_file(
     connection_id: String,
     local_path: String,
     remote_path: String,
+    transfer_id: Option<String>,
+    resume: Option<bool>,
+    verify: Option<HashAlgorithm>,
     state: State<'_, Arc<ConnectionManager>>,
 ) -> Result<FileTransferResponse, String> {
-    let conn_type = state.get_connection_type(&connection_id).await;
-
-    let result = match conn_type.as_deref() {
-        Some("SFTP") => {
-            let sftp_map = state.get_sftp_connection().await;
-            let connections = sftp_map.read().await;
-            let client = connections
-                .get(&connection_id)
-                .ok_or("SFTP connection not found".to_string())?;
-            client.upload_file(&local_path, &remote_path).await
-        }
-        Some("FTP") => {
-            let ftp_map = state.get_ftp_connection().await;
-            let mut connections = ftp_map.write().await;
-            let client = connections
-                .get_mut(&connection_id)
-                .ok_or("FTP connection not found".to_string())?;
-            client.upload_file(&local_path, &remote_path).await
-        }
-        Some(other) => return Err(format!("Unsupported protocol: {}", other)),
-        None => {
-            // Fallback: try SSH connection (integrated file browser uses SSH connections
-            // which are not registered in connection_types)
-            let connection = state
-                .get_connection(&connection_id)
-                .await
-                .ok_or_else(|| format!("No connection found for '{}'", connection_id))?;
-            let client = connection.read().await;
-            client.upload_file(&local_path, &remote_path).await
-        }
+    let spec = TransferSpec {
+        connection_id,
+        direction: TransferDirection::Upload,
+        remote_path,
+        local_path,
+        resume: resume.unwrap_or(false),
+        verify,
     };
+    transfer_remote_file(spec, transfer_id, state.inner()).await
+}
 
-    match result {
+async fn transfer_remote_directory(
+    spec: TransferSpec,
+    transfer_id: Option<String>,
+    options: DirectoryTransferOptions,
+    state: &Arc<ConnectionManager>,
+) -> Result<FileTransferResponse, String> {
+    match state.transfer_directory(transfer_id, spec, options).await {
         Ok(bytes) => Ok(FileTransferResponse {
             success: true,
             bytes_transferred: Some(bytes),
@@ -2492,6 +3029,92 @@ pub async fn upload_remote_file(
     }
 }
 
+/// Download a whole directory tree. Progress for the tree as a whole is
+/// reported as `transfer-progress` events under `transfer_id`.
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+pub async fn download_remote_directory(
+    connection_id: String,
+    remote_path: String,
+    local_path: String,
+    transfer_id: Option<String>,
+    resume: Option<bool>,
+    verify: Option<HashAlgorithm>,
+    options: Option<DirectoryTransferOptions>,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<FileTransferResponse, String> {
+    let spec = TransferSpec {
+        connection_id,
+        direction: TransferDirection::Download,
+        remote_path,
+        local_path,
+        resume: resume.unwrap_or(false),
+        verify,
+    };
+    let options = options.unwrap_or_default();
+    transfer_remote_directory(spec, transfer_id, options, state.inner()).await
+}
+
+/// Upload a whole directory tree. Progress for the tree as a whole is
+/// reported as `transfer-progress` events under `transfer_id`.
+#[allow(clippy::too_many_arguments)]
+#[tauri::command]
+pub async fn upload_remote_directory(
+    connection_id: String,
+    local_path: String,
+    remote_path: String,
+    transfer_id: Option<String>,
+    resume: Option<bool>,
+    verify: Option<HashAlgorithm>,
+    options: Option<DirectoryTransferOptions>,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<FileTransferResponse, String> {
+    let spec = TransferSpec {
+        connection_id,
+        direction: TransferDirection::Upload,
+        remote_path,
+        local_path,
+        resume: resume.unwrap_or(false),
+        verify,
+    };
+    let options = options.unwrap_or_default();
+    transfer_remote_directory(spec, transfer_id, options, state.inner()).await
+}
+
+#[tauri::command]
+pub async fn cancel_transfer(
+    transfer_id: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<CommandResponse, String> {
+    match state.cancel_transfer(&transfer_id).await {
+        Ok(_) => Ok(CommandResponse {
+            success: true,
+            output: Some("Transfer cancelled".to_string()),
+            error: None,
+        }),
+        Err(e) => Ok(CommandResponse {
+            success: false,
+            output: None,
+            error: Some(e.to_string()),
+        }),
+    }
+}
+
+/// Digest of a remote file (SHA-256 unless another algorithm is given),
+/// computed on the server over SSH where possible.
+#[tauri::command]
+pub async fn checksum_remote_file(
+    connection_id: String,
+    path: String,
+    algorithm: Option<HashAlgorithm>,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<Checksum, String> {
+    state
+        .remote_checksum(&connection_id, &path, algorithm.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn delete_remote_item(
     connection_id: String,
@@ -2518,11 +3141,11 @@ pub async fn delete_remote_item(
             }
         }
         "FTP" => {
-            let ftp_map = state.get_ftp_connection().await;
-            let mut connections = ftp_map.write().await;
-            let client = connections
-                .get_mut(&connection_id)
+            let client = state
+                .get_ftp_client(&connection_id)
+                .await
                 .ok_or("FTP connection not found".to_string())?;
+            let mut client = client.lock().await;
             if is_directory {
                 client.delete_dir(&path).await
             } else {
@@ -2567,11 +3190,11 @@ pub async fn create_remote_directory(
             client.create_dir(&path).await
         }
         "FTP" => {
-            let ftp_map = state.get_ftp_connection().await;
-            let mut connections = ftp_map.write().await;
-            let client = connections
-                .get_mut(&connection_id)
+            let client = state
+                .get_ftp_client(&connection_id)
+                .await
                 .ok_or("FTP connection not found".to_string())?;
+            let mut client = client.lock().await;
             client.create_dir(&path).await
         }
         _ => return Err(format!("Unsupported protocol: {}", conn_type)),
@@ -2613,11 +3236,11 @@ pub async fn rename_remote_item(
             client.rename(&old_path, &new_path).await
         }
         "FTP" => {
-            let ftp_map = state.get_ftp_connection().await;
-            let mut connections = ftp_map.write().await;
-            let client = connections
-                .get_mut(&connection_id)
+            let client = state
+                .get_ftp_client(&connection_id)
+                .await
                 .ok_or("FTP connection not found".to_string())?;
+            let mut client = client.lock().await;
             client.rename(&old_path, &new_path).await
         }
         _ => return Err(format!("Unsupported protocol: {}", conn_type)),
@@ -2637,6 +3260,244 @@ pub async fn rename_remote_item(
     }
 }
 
+fn remote_item_response(result: anyhow::Result<()>, output: String) -> CommandResponse {
+    match result {
+        Ok(()) => CommandResponse {
+            success: true,
+            output: Some(output),
+            error: None,
+        },
+        Err(e) => CommandResponse {
+            success: false,
+            output: None,
+            error: Some(e.to_string()),
+        },
+    }
+}
+
+/// Change permissions to the octal `mode`, e.g. "755". Uses SETSTAT over
+/// SFTP and `SITE CHMOD` over FTP.
+#[tauri::command]
+pub async fn chmod_remote_item(
+    connection_id: String,
+    path: String,
+    mode: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<CommandResponse, String> {
+    let mode = match parse_mode(&mode) {
+        Ok(mode) => mode,
+        Err(e) => return Ok(remote_item_response(Err(e), String::new())),
+    };
+    let changes = AttributeChanges {
+        permissions: Some(mode),
+        ..Default::default()
+    };
+    let result = state
+        .change_remote_attributes(&connection_id, &path, &changes)
+        .await;
+    Ok(remote_item_response(
+        result,
+        format!("Changed permissions of '{}' to {:o}", path, mode),
+    ))
+}
+
+/// Change the numeric owner and/or group. SFTP only.
+#[tauri::command]
+pub async fn chown_remote_item(
+    connection_id: String,
+    path: String,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<CommandResponse, String> {
+    let changes = AttributeChanges {
+        uid,
+        gid,
+        ..Default::default()
+    };
+    let result = state
+        .change_remote_attributes(&connection_id, &path, &changes)
+        .await;
+    Ok(remote_item_response(
+        result,
+        format!("Changed ownership of '{}'", path),
+    ))
+}
+
+/// Set the modification and/or access time (Unix seconds). FTP can only set
+/// the modification time, with `MFMT`.
+#[tauri::command]
+pub async fn set_remote_item_times(
+    connection_id: String,
+    path: String,
+    modified: Option<u64>,
+    accessed: Option<u64>,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<CommandResponse, String> {
+    let changes = AttributeChanges {
+        modified,
+        accessed,
+        ..Default::default()
+    };
+    let result = state
+        .change_remote_attributes(&connection_id, &path, &changes)
+        .await;
+    Ok(remote_item_response(
+        result,
+        format!("Updated timestamps of '{}'", path),
+    ))
+}
+
+/// Create a symlink at `link_path` pointing to `target`. SFTP only.
+#[tauri::command]
+pub async fn create_remote_symlink(
+    connection_id: String,
+    link_path: String,
+    target: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<CommandResponse, String> {
+    let result = state
+        .create_remote_symlink(&connection_id, &link_path, &target)
+        .await;
+    Ok(remote_item_response(
+        result,
+        format!("Created symlink '{}' -> '{}'", link_path, target),
+    ))
+}
+
+/// Where the symlink at `path` points. SFTP only.
+#[tauri::command]
+pub async fn read_remote_link(
+    connection_id: String,
+    path: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<String, String> {
+    state
+        .read_remote_link(&connection_id, &path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Full attributes of one remote path without following a symlink. FTP
+/// fills in what its listing, SIZE and MDTM report.
+#[tauri::command]
+pub async fn stat_remote_item(
+    connection_id: String,
+    path: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<RemoteFileStat, String> {
+    state
+        .stat_remote_path(&connection_id, &path)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// ========== Transfer Queue ==========
+
+#[derive(Debug, Deserialize)]
+pub struct QueueTransferRequest {
+    #[serde(flatten)]
+    pub spec: TransferSpec,
+    /// Copy the whole directory tree rather than one file.
+    #[serde(default)]
+    pub directory: bool,
+    #[serde(default)]
+    pub options: DirectoryTransferOptions,
+}
+
+#[tauri::command]
+pub async fn transfer_queue_add(
+    jobs: Vec<QueueTransferRequest>,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<Vec<TransferJob>, String> {
+    let jobs = jobs
+        .into_iter()
+        .map(|job| (job.spec, job.directory, job.options))
+        .collect();
+    state.enqueue_transfers(jobs).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn transfer_queue_list(
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<Vec<TransferJob>, String> {
+    Ok(state.transfer_jobs())
+}
+
+#[tauri::command]
+pub async fn transfer_queue_pause(
+    job_id: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<TransferJob, String> {
+    state
+        .pause_transfer_job(&job_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn transfer_queue_resume(
+    job_id: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<TransferJob, String> {
+    state.resume_transfer_job(&job_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn transfer_queue_retry(
+    job_id: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<TransferJob, String> {
+    state.retry_transfer_job(&job_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn transfer_queue_cancel(
+    job_id: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<TransferJob, String> {
+    state
+        .cancel_transfer_job(&job_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn transfer_queue_move(
+    job_id: String,
+    index: usize,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<Vec<String>, String> {
+    state
+        .move_transfer_job(&job_id, index)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn transfer_queue_remove(
+    job_id: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<(), String> {
+    state.remove_transfer_job(&job_id).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn transfer_queue_get_limits(
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<QueueLimits, String> {
+    Ok(state.transfer_queue_limits())
+}
+
+#[tauri::command]
+pub async fn transfer_queue_set_limits(
+    limits: QueueLimits,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<(), String> {
+    state
+        .set_transfer_queue_limits(limits)
+        .map_err(|e| e.to_string())
+}
+
 // ========== Local Filesystem Commands ==========
 
 #[tauri::command]
@@ -2841,39 +3702,6 @@ pub async fn rename_local_item(old_path: String, new_path: String) -> Result<(),
         .map_err(|e| format!("Failed to rename '{}' to '{}': {}", old_path, new_path, e))
 }
 
-fn validate_remote_relative_path(remote_relative_path: &str) -> Result<(), String> {
-    if remote_relative_path.is_empty()
-        || remote_relative_path.starts_with('/')
-        || remote_relative_path.starts_with('\\')
-        || remote_relative_path.contains('\\')
-    {
-        return Err(format!(
-            "Unsafe remote relative path: {}",
-            remote_relative_path
-        ));
-    }
-
-    for (index, component) in remote_relative_path.split('/').enumerate() {
-        let has_windows_prefix = index == 0
-            && component
-                .as_bytes()
-                .get(1)
-                .is_some_and(|separator| *separator == b':');
-        if component.is_empty()
-            || component == "."
-            || component == ".."
-            || component.contains('\0')
-            || has_windows_prefix
-        {
-            return Err(format!(
-                "Unsafe remote relative path: {}",
-                remote_relative_path
-            ));
-        }
-    }
-    Ok(())
-}
-
 fn resolve_confined_local_path(
     destination_root: &std::path::Path,
     remote_relative_path: &str,
@@ -2881,7 +3709,7 @@ fn resolve_confined_local_path(
     if !destination_root.is_absolute() {
         return Err("Destination root must be absolute".to_string());
     }
-    validate_remote_relative_path(remote_relative_path)?;
+    validate_remote_relative_path(remote_relative_path).map_err(|e| e.to_string())?;
 
     let mut resolved = destination_root.to_path_buf();
     for component in remote_relative_path.split('/') {
@@ -2968,6 +3796,23 @@ pub async fn stat_local_path(path: String) -> Result<LocalPathStat, String> {
     })
 }
 
+/// Digest of a local file (SHA-256 unless another algorithm is given).
+#[tauri::command]
+pub async fn checksum_local_file(
+    path: String,
+    algorithm: Option<HashAlgorithm>,
+) -> Result<Checksum, String> {
+    let algorithm = algorithm.unwrap_or_default();
+    let hash = crate::checksum::local_checksum(std::path::Path::new(&path), algorithm)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(Checksum {
+        algorithm,
+        hash,
+        computed_remotely: false,
+    })
+}
+
 // ========== Directory Synchronization ==========
 
 /// A file entry with a relative path (used for recursive listing comparisons).
@@ -3150,11 +3995,11 @@ pub async fn list_remote_files_recursive(
             walk_sftp(sftp, &path, &path, &exclude_patterns, &mut results).await?;
         }
         Some("FTP") => {
-            let ftp_map = state.get_ftp_connection().await;
-            let mut connections = ftp_map.write().await;
-            let client = connections
-                .get_mut(&connection_id)
+            let client = state
+                .get_ftp_client(&connection_id)
+                .await
                 .ok_or("FTP connection not found")?;
+            let mut client = client.lock().await;
 
             // FTP recursive walk — iterative with a queue since we need &mut
             let mut dirs_to_visit: Vec<String> = vec![path.clone()];
@@ -3220,6 +4065,96 @@ pub async fn list_remote_files_recursive(
     Ok(results)
 }
 
+/// Compare a local and a remote directory and return the sync plan without
+/// changing anything (dry run).
+#[tauri::command]
+pub async fn sync_plan(
+    request: SyncRequest,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<SyncPlan, String> {
+    state.plan_sync(&request).await.map_err(|e| e.to_string())
+}
+
+/// Apply a sync. Pass the `plan` from `sync_plan` to carry out exactly what
+/// was reviewed; it is refused if an item's path leaves the trees or its
+/// action doesn't fit the request's mode. Without one a fresh plan is made.
+/// Copy progress is reported as `transfer-progress` events under `sync_id`.
+#[tauri::command]
+pub async fn sync_apply(
+    request: SyncRequest,
+    plan: Option<SyncPlan>,
+    sync_id: Option<String>,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<SyncReport, String> {
+    let plan = match plan {
+        Some(plan) => {
+            plan.check(&request.options).map_err(|e| e.to_string())?;
+            plan
+        }
+        None => state.plan_sync(&request).await.map_err(|e| e.to_string())?,
+    };
+    state
+        .apply_sync(sync_id, &request, &plan)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// ========== Remote Editing ==========
+
+/// Download a remote file into a private temp directory and open it with the
+/// system's default editor. Each save is uploaded back and reported as a
+/// `remote-edit-event`; if the server copy changed meanwhile the save is held
+/// back until `save_remote_edit` is called with `overwrite`.
+#[tauri::command]
+pub async fn edit_remote_file(
+    connection_id: String,
+    path: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<RemoteEditSession, String> {
+    let session = state
+        .start_remote_edit(&connection_id, &path)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Err(e) = open_in_os(session.local_path.clone()).await {
+        let _ = state.close_remote_edit(&session.edit_id).await;
+        return Err(e);
+    }
+    Ok(session)
+}
+
+/// Upload the local copy now. `overwrite` replaces the server copy even if it
+/// changed since it was opened.
+#[tauri::command]
+pub async fn save_remote_edit(
+    edit_id: String,
+    overwrite: Option<bool>,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<RemoteEditSession, String> {
+    state
+        .save_remote_edit(&edit_id, overwrite.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Stop watching the local copy and delete it.
+#[tauri::command]
+pub async fn close_remote_edit(
+    edit_id: String,
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<(), String> {
+    state
+        .close_remote_edit(&edit_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_remote_edits(
+    state: State<'_, Arc<ConnectionManager>>,
+) -> Result<Vec<RemoteEditSession>, String> {
+    Ok(state.remote_edits().await)
+}
+
 /// Simple glob-like pattern matching for exclude filter.
 fn matches_exclude(name: &str, patterns: &[String]) -> bool {
     for pat in patterns {
@@ -3352,7 +4287,7 @@ pub async fn desktop_resize(
         .get_desktop_connection(&connection_id)
         .await
         .ok_or_else(|| format!("Desktop connection not found: {}", connection_id))?;
-    let mut c = client.write().await;
+    let c = client.read().await;
     c.resize(width, height).await.map_err(|e| e.to_string())
 }
 
@@ -3608,6 +4543,7 @@ mod proxy_config_tests {
     fn request(proxy_type: Option<&str>) -> ConnectRequest {
         ConnectRequest {
             connection_id: "c1".to_string(),
+            ssh_config_host: None,
             host: "example.com".to_string(),
             port: 22,
             username: "root".to_string(),
@@ -3615,6 +4551,8 @@ mod proxy_config_tests {
             password: Some("pw".to_string()),
             key_path: None,
             passphrase: None,
+            certificate_path: None,
+            additional_auth_methods: None,
             compression: None,
             keepalive_enabled: None,
             keepalive_interval: None,
@@ -3624,6 +4562,11 @@ mod proxy_config_tests {
             proxy_port: None,
             proxy_username: None,
             proxy_password: None,
+            proxy_command: None,
+            jump_hosts: None,
+            agent_forwarding: None,
+            reconnect: None,
+            tags: None,
         }
     }
 
@@ -3651,6 +4594,16 @@ mod proxy_config_tests {
         assert_eq!(socks4.proxy_type, ProxyType::Socks4);
     }
 
+    #[test]
+    fn maps_proxy_command() {
+        let mut req = request(Some("command"));
+        assert!(build_proxy(&req).unwrap_err().contains("Proxy command is required"));
+        req.proxy_command = Some("cloudflared access ssh --hostname %h".to_string());
+        let proxy = build_proxy(&req).unwrap().unwrap();
+        assert_eq!(proxy.proxy_type, ProxyType::Command);
+        assert_eq!(proxy.command.as_deref(), Some("cloudflared access ssh --hostname %h"));
+    }
+
     #[test]
     fn requires_proxy_host() {
         let err = build_proxy(&request(Some("http"))).unwrap_err();
@@ -1,15 +1,45 @@
+use crate::auth_prompt::AuthPrompter;
+use crate::broadcast::{BroadcastEvent, BroadcastTarget, HostExecResult};
+use crate::checksum::{local_checksum, Checksum, HashAlgorithm, Hasher};
 use crate::desktop_protocol::{DesktopConnectRequest, DesktopProtocol, FrameUpdate};
+use crate::dir_transfer::{
+    local_attributes, matches_exclude, set_local_attributes, validate_remote_relative_path,
+    walk_local, DirectoryTransferOptions, TreeEntry, TreeProgress,
+};
 use crate::ftp_client::FtpClient;
+use crate::known_hosts::HostKeyVerifier;
+use crate::local_shell::{spawn_local_shell, LocalShellConfig};
 use crate::os_detect::OsInfoCache;
+use crate::playback::{PlaybackCommand, PlaybackHandle};
 use crate::rdp_client::RdpClient;
-use crate::sftp_client::StandaloneSftpClient;
-use crate::ssh::{PtySession, SshClient, SshConfig};
+use crate::recording::{Recording, RecordingInfo};
+use crate::remote_edit::{
+    self, local_stamp, remote_changed, EditState, RemoteEditEvent, RemoteEditSession, SaveWatcher,
+};
+use crate::remote_fs::{unsupported, AttributeChanges, RemoteFileOp, RemoteFileStat};
+use crate::sftp_client::{
+    list_sftp_dir, parse_listing_timestamp, sftp_change_attributes, sftp_lstat, sftp_read_chunks,
+    sftp_read_link, sftp_set_attributes, sftp_stat, sftp_symlink, FileEntry, FileEntryType,
+    RemoteAttributes, StandaloneSftpClient,
+};
+use crate::ssh::{
+    ConnectionState, ConnectionStatus, ExecEvent, ExecOptions, ExecOutput, ExecStatus, ForwardInfo,
+    ForwardSpec, PortForward, PtySession, SshClient, SshConfig,
+};
+use crate::sync::{self, SyncAction, SyncFailure, SyncPlan, SyncReport, SyncRequest};
+use crate::telnet_client::{spawn_telnet_session, TelnetConfig};
+use crate::transfer::{
+    join_remote_path, ProgressSink, Transfer, TransferDirection, TransferProgress, TransferSpec,
+};
+use crate::transfer_queue::{JobState, QueueEvent, QueueLimits, TransferJob, TransferQueue};
 use crate::vnc_client::VncClient;
 use anyhow::Result;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::mpsc;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 use tokio_util::sync::CancellationToken;
 
 pub struct ConnectionManager {
@@ -22,13 +52,80 @@ pub struct ConnectionManager {
     /// Standalone SFTP connections (no PTY)
     sftp_connections: Arc<RwLock<HashMap<String, StandaloneSftpClient>>>,
     /// FTP/FTPS connections
-    ftp_connections: Arc<RwLock<HashMap<String, FtpClient>>>,
+    ftp_connections: Arc<RwLock<HashMap<String, Arc<Mutex<FtpClient>>>>>,
     /// Remote desktop (RDP/VNC) connections
     desktop_connections: Arc<RwLock<HashMap<String, Arc<RwLock<Box<dyn DesktopProtocol>>>>>>,
-    /// Track protocol type per connection ID ("SSH", "SFTP", "FTP", "RDP", "VNC")
+    /// Frame loop running for each desktop connection, with a stream id so a
+    /// finished loop doesn't unregister its replacement
+    desktop_streams: Arc<RwLock<HashMap<String, (u64, CancellationToken)>>>,
+    next_desktop_stream_id: AtomicU64,
+    /// Track protocol type per connection ID ("SSH", "SFTP", "FTP", "RDP", "VNC", "LOCAL", "TELNET")
     connection_types: Arc<RwLock<HashMap<String, String>>>,
     /// Cached OS info per SSH connection (auto-detected on first monitoring call)
     os_info_cache: OsInfoCache,
+    /// known_hosts verifier shared by SSH and standalone SFTP connections
+    host_key_verifier: Arc<HostKeyVerifier>,
+    /// Keyboard-interactive prompt relay shared by SSH and standalone SFTP
+    auth_prompter: Arc<AuthPrompter>,
+    /// Port forwards on SSH connections, in creation order
+    port_forwards: Arc<RwLock<Vec<PortForward>>>,
+    next_forward_id: AtomicU64,
+    /// Config each SSH connection was opened with, reused to reconnect
+    ssh_configs: Arc<RwLock<HashMap<String, SshConfig>>>,
+    /// Last known PTY size per connection, restored after reconnecting
+    pty_sizes: Arc<RwLock<HashMap<String, (u32, u32)>>>,
+    status_handler: std::sync::RwLock<Option<StatusHandler>>,
+    /// Streamed commands still running, keyed by exec_id
+    execs: Arc<RwLock<HashMap<String, CancellationToken>>>,
+    exec_handler: std::sync::RwLock<Option<ExecHandler>>,
+    /// Tags given at connect time, for broadcasting to a group
+    connection_tags: Arc<RwLock<HashMap<String, Vec<String>>>>,
+    broadcast_handler: std::sync::RwLock<Option<BroadcastHandler>>,
+    /// File transfers in progress, keyed by transfer_id
+    transfers: Arc<RwLock<HashMap<String, CancellationToken>>>,
+    next_transfer_id: AtomicU64,
+    transfer_handler: std::sync::RwLock<Option<ProgressSink>>,
+    /// Queued file and directory transfers, saved across restarts
+    transfer_queue: std::sync::Mutex<TransferQueue>,
+    queue_handler: std::sync::RwLock<Option<QueueHandler>>,
+    /// Remote files open in a local editor, keyed by edit_id
+    remote_edits: Arc<RwLock<HashMap<String, RemoteEdit>>>,
+    next_edit_id: AtomicU64,
+    remote_edit_handler: std::sync::RwLock<Option<RemoteEditHandler>>,
+    /// Local terminals, started on StartPty like SSH shells
+    local_shells: Arc<RwLock<HashMap<String, LocalShellConfig>>>,
+    /// Telnet terminals, started on StartPty like SSH shells
+    telnet_connections: Arc<RwLock<HashMap<String, TelnetConnection>>>,
+    /// Active asciicast recordings per connection
+    recordings: Arc<RwLock<HashMap<String, Arc<Recording>>>>,
+    /// Recordings being replayed, keyed by the terminal's connection_id
+    playbacks: Arc<RwLock<HashMap<String, PlaybackHandle>>>,
+}
+
+type StatusHandler = Arc<dyn Fn(ConnectionStatus) + Send + Sync>;
+type ExecHandler = Arc<dyn Fn(ExecEvent) + Send + Sync>;
+type BroadcastHandler = Arc<dyn Fn(BroadcastEvent) + Send + Sync>;
+type QueueHandler = Arc<dyn Fn(QueueEvent) + Send + Sync>;
+type RemoteEditHandler = Arc<dyn Fn(RemoteEditEvent) + Send + Sync>;
+
+/// Where a connection's PTY comes from.
+enum PtyBackend {
+    Ssh(Arc<RwLock<SshClient>>),
+    Local(LocalShellConfig),
+    /// Telnet has no channels: the socket opened by `telnet_connect` serves
+    /// the first PTY and later ones reconnect.
+    Telnet(TelnetConfig, Option<tokio::net::TcpStream>),
+}
+
+struct TelnetConnection {
+    config: TelnetConfig,
+    stream: Option<tokio::net::TcpStream>,
+}
+
+struct RemoteEdit {
+    session: RemoteEditSession,
+    /// Stops the task watching the local copy.
+    stop: CancellationToken,
 }
 
 impl ConnectionManager {
@@ -41,13 +138,41 @@ impl ConnectionManager {
             sftp_connections: Arc::new(RwLock::new(HashMap::new())),
             ftp_connections: Arc::new(RwLock::new(HashMap::new())),
             desktop_connections: Arc::new(RwLock::new(HashMap::new())),
+            desktop_streams: Arc::new(RwLock::new(HashMap::new())),
+            next_desktop_stream_id: AtomicU64::new(1),
             connection_types: Arc::new(RwLock::new(HashMap::new())),
             os_info_cache: OsInfoCache::new(),
+            host_key_verifier: Arc::new(HostKeyVerifier::from_default_location()),
+            auth_prompter: Arc::new(AuthPrompter::new()),
+            port_forwards: Arc::new(RwLock::new(Vec::new())),
+            next_forward_id: AtomicU64::new(1),
+            ssh_configs: Arc::new(RwLock::new(HashMap::new())),
+            pty_sizes: Arc::new(RwLock::new(HashMap::new())),
+            status_handler: std::sync::RwLock::new(None),
+            execs: Arc::new(RwLock::new(HashMap::new())),
+            exec_handler: std::sync::RwLock::new(None),
+            connection_tags: Arc::new(RwLock::new(HashMap::new())),
+            broadcast_handler: std::sync::RwLock::new(None),
+            transfers: Arc::new(RwLock::new(HashMap::new())),
+            next_transfer_id: AtomicU64::new(1),
+            transfer_handler: std::sync::RwLock::new(None),
+            transfer_queue: std::sync::Mutex::new(TransferQueue::load(
+                TransferQueue::default_path(),
+            )),
+            queue_handler: std::sync::RwLock::new(None),
+            remote_edits: Arc::new(RwLock::new(HashMap::new())),
+            next_edit_id: AtomicU64::new(1),
+            remote_edit_handler: std::sync::RwLock::new(None),
+            local_shells: Arc::new(RwLock::new(HashMap::new())),
+            telnet_connections: Arc::new(RwLock::new(HashMap::new())),
+            recordings: Arc::new(RwLock::new(HashMap::new())),
+            playbacks: Arc::new(RwLock::new(HashMap::new())),
         }
     }
 
     pub async fn create_connection(&self, connection_id: String, config: SshConfig) -> Result<()> {
-        let mut client = SshClient::new();
+        let mut client = SshClient::with_host_key_verifier(self.host_key_verifier.clone())
+            .with_auth_prompter(self.auth_prompter.clone());
         let cancel_token = self.register_pending_connection(&connection_id).await;
 
         let connect_result = tokio::select! {
@@ -59,6 +184,10 @@ impl ConnectionManager {
 
         connect_result?;
 
+        self.ssh_configs
+            .write()
+            .await
+            .insert(connection_id.clone(), config);
         let mut connections = self.connections.write().await;
         connections.insert(connection_id, Arc::new(RwLock::new(client)));
 
@@ -93,8 +222,20 @@ impl ConnectionManager {
     }
 
     pub async fn close_connection(&self, connection_id: &str) -> Result<()> {
+        // Stops a reconnect in progress.
+        self.cancel_pending_connection(connection_id).await;
+        self.ssh_configs.write().await.remove(connection_id);
+        self.pty_sizes.write().await.remove(connection_id);
+        self.connection_tags.write().await.remove(connection_id);
+        if self.recordings.read().await.contains_key(connection_id) {
+            if let Err(e) = self.stop_recording(connection_id).await {
+                tracing::warn!("Failed to finish recording for {}: {}", connection_id, e);
+            }
+        }
+        self.close_remote_edits_for(connection_id).await;
         let mut connections = self.connections.write().await;
         if let Some(client) = connections.remove(connection_id) {
+            self.stop_port_forwards(connection_id, &client).await;
             let mut client = client.write().await;
             client.disconnect().await?;
         }
@@ -103,6 +244,78 @@ impl ConnectionManager {
         Ok(())
     }
 
+    // ===== Port Forwarding =====
+
+    /// Start a local, remote or dynamic forward on an SSH connection.
+    pub async fn add_port_forward(
+        &self,
+        connection_id: &str,
+        spec: ForwardSpec,
+    ) -> Result<ForwardInfo> {
+        let client = self
+            .get_connection(connection_id)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Connection not found"))?;
+        let id = format!(
+            "fwd-{}",
+            self.next_forward_id.fetch_add(1, Ordering::Relaxed)
+        );
+        let forward =
+            crate::ssh::start_forward(client, id, connection_id.to_string(), spec).await?;
+        let info = forward.info();
+        self.port_forwards.write().await.push(forward);
+        Ok(info)
+    }
+
+    /// Forwards with live counters, optionally limited to one connection.
+    pub async fn list_port_forwards(&self, connection_id: Option<&str>) -> Vec<ForwardInfo> {
+        let forwards = self.port_forwards.read().await;
+        forwards
+            .iter()
+            .filter(|f| connection_id.is_none_or(|id| f.connection_id() == id))
+            .map(PortForward::info)
+            .collect()
+    }
+
+    pub async fn remove_port_forward(&self, forward_id: &str) -> Result<()> {
+        let forward = {
+            let mut forwards = self.port_forwards.write().await;
+            let index = forwards
+                .iter()
+                .position(|f| f.id() == forward_id)
+                .ok_or_else(|| anyhow::anyhow!("Port forward not found"))?;
+            forwards.remove(index)
+        };
+        if let Some(client) = self.get_connection(forward.connection_id()).await {
+            forward.stop(&client).await;
+        }
+        Ok(())
+    }
+
+    async fn stop_port_forwards(&self, connection_id: &str, client: &RwLock<SshClient>) {
+        let stopped: Vec<PortForward> = {
+            let mut forwards = self.port_forwards.write().await;
+            let (stopped, kept) = forwards
+                .drain(..)
+                .partition(|f| f.connection_id() == connection_id);
+            *forwards = kept;
+            stopped
+        };
+        for forward in stopped {
+            forward.stop(client).await;
+        }
+    }
+
+    /// Host-key verifier used for every SSH transport.
+    pub fn host_key_verifier(&self) -> &Arc<HostKeyVerifier> {
+        &self.host_key_verifier
+    }
+
+    /// Keyboard-interactive prompt relay used for every SSH transport.
+    pub fn auth_prompter(&self) -> &Arc<AuthPrompter> {
+        &self.auth_prompter
+    }
+
     /// Access the OS info cache (for distro-aware monitoring commands).
     pub fn os_info_cache(&self) -> &OsInfoCache {
         &self.os_info_cache
@@ -123,13 +336,7 @@ impl ConnectionManager {
         cols: u32,
         rows: u32,
     ) -> Result<u64> {
-        // Get the SSH client
-        let connections = self.connections.read().await;
-        let client = connections
-            .get(connection_id)
-            .ok_or_else(|| anyhow::anyhow!("Connection not found"))?;
-
-        let client = client.read().await;
+        let backend = self.pty_backend(connection_id).await?;
 
         // Cancel and remove any existing PTY session for this connection first.
         // This ensures the old SSH channel and reader task are torn down before
@@ -143,7 +350,30 @@ impl ConnectionManager {
         }
 
         // Create PTY session
-        let pty = client.create_pty_session(cols, rows).await?;
+        let pty = match backend {
+            PtyBackend::Ssh(client) => client.read().await.create_pty_session(cols, rows).await?,
+            PtyBackend::Local(config) => spawn_local_shell(&config, cols, rows)?,
+            PtyBackend::Telnet(config, stream) => {
+                let stream = match stream {
+                    Some(stream) => stream,
+                    None => crate::telnet_client::connect(&config).await?,
+                };
+                spawn_telnet_session(stream, &config, cols, rows)
+            }
+        };
+        let reattach = self
+            .ssh_configs
+            .read()
+            .await
+            .get(connection_id)
+            .and_then(|config| config.reconnect.reattach.clone());
+        if let Some(reattach) = reattach {
+            pty.input_tx.send(reattach.attach_command()).await?;
+        }
+        self.pty_sizes
+            .write()
+            .await
+            .insert(connection_id.to_string(), (cols, rows));
 
         // Bump generation so any in-flight Close for the old session is ignored
         let mut generations = self.pty_generations.write().await;
@@ -159,6 +389,22 @@ impl ConnectionManager {
         Ok(current_gen)
     }
 
+    async fn pty_backend(&self, connection_id: &str) -> Result<PtyBackend> {
+        if let Some(config) = self.local_shells.read().await.get(connection_id) {
+            return Ok(PtyBackend::Local(config.clone()));
+        }
+        if let Some(telnet) = self.telnet_connections.write().await.get_mut(connection_id) {
+            return Ok(PtyBackend::Telnet(
+                telnet.config.clone(),
+                telnet.stream.take(),
+            ));
+        }
+        self.get_connection(connection_id)
+            .await
+            .map(PtyBackend::Ssh)
+            .ok_or_else(|| anyhow::anyhow!("Connection not found"))
+    }
+
     /// Send data to PTY (user input)
     /// Uses try_send for better performance (non-blocking)
     pub async fn write_to_pty(&self, connection_id: &str, data: Vec<u8>) -> Result<()> {
@@ -167,6 +413,10 @@ impl ConnectionManager {
             .get(connection_id)
             .ok_or_else(|| anyhow::anyhow!("PTY connection not found"))?;
 
+        if let Some(recording) = self.recordings.read().await.get(connection_id) {
+            recording.input(&data);
+        }
+
         // Use try_send for better performance (like ttyd's immediate send)
         match pty.input_tx.try_send(data) {
             Ok(_) => Ok(()),
@@ -196,7 +446,10 @@ impl ConnectionManager {
 
         // Try immediate read first (non-blocking)
         match rx.try_recv() {
-            Ok(data) => return Ok(data),
+            Ok(data) => {
+                self.record_output(connection_id, &data).await;
+                return Ok(data);
+            }
             Err(tokio::sync::mpsc::error::TryRecvError::Empty) => {
                 // No immediate data, use short timeout
             }
@@ -207,7 +460,10 @@ impl ConnectionManager {
 
         // Fall back to short timeout wait (1ms for ultra-low latency)
         match tokio::time::timeout(tokio::time::Duration::from_millis(1), rx.recv()).await {
-            Ok(Some(data)) => Ok(data),
+            Ok(Some(data)) => {
+                self.record_output(connection_id, &data).await;
+                Ok(data)
+            }
             Ok(None) => Err(anyhow::anyhow!("PTY connection closed")),
             Err(_) => Ok(Vec::new()), // Timeout - no data available
         }
@@ -258,7 +514,494 @@ impl ConnectionManager {
         pty.resize_tx
             .send((cols, rows))
             .await
-            .map_err(|_| anyhow::anyhow!("PTY resize channel closed"))
+            .map_err(|_| anyhow::anyhow!("PTY resize channel closed"))?;
+        self.pty_sizes
+            .write()
+            .await
+            .insert(connection_id.to_string(), (cols, rows));
+        if let Some(recording) = self.recordings.read().await.get(connection_id) {
+            recording.resize(cols, rows);
+        }
+        Ok(())
+    }
+
+    // ===== Session Recording =====
+
+    /// Start recording a PTY session to `path` as asciicast v2. Input is only
+    /// written when `include_input` is set, since it may contain passwords.
+    pub async fn start_recording(
+        &self,
+        connection_id: &str,
+        path: std::path::PathBuf,
+        title: Option<String>,
+        include_input: bool,
+    ) -> Result<RecordingInfo> {
+        if !self.pty_sessions.read().await.contains_key(connection_id) {
+            anyhow::bail!("PTY connection not found");
+        }
+        let mut recordings = self.recordings.write().await;
+        if recordings.contains_key(connection_id) {
+            anyhow::bail!("Connection {} is already being recorded", connection_id);
+        }
+        let (cols, rows) = self
+            .pty_sizes
+            .read()
+            .await
+            .get(connection_id)
+            .copied()
+            .unwrap_or((80, 24));
+        let recording = Recording::create(connection_id, path, cols, rows, title, include_input)?;
+        let info = recording.info();
+        recordings.insert(connection_id.to_string(), Arc::new(recording));
+        Ok(info)
+    }
+
+    pub async fn stop_recording(&self, connection_id: &str) -> Result<RecordingInfo> {
+        let recording = self
+            .recordings
+            .write()
+            .await
+            .remove(connection_id)
+            .ok_or_else(|| anyhow::anyhow!("Connection {} is not being recorded", connection_id))?;
+        recording.finish()
+    }
+
+    pub async fn list_active_recordings(&self) -> Vec<RecordingInfo> {
+        let recordings = self.recordings.read().await;
+        recordings.values().map(|r| r.info()).collect()
+    }
+
+    // ===== Playback =====
+
+    /// Track a player for `connection_id`, stopping any previous one.
+    pub async fn register_playback(&self, connection_id: &str, handle: PlaybackHandle) {
+        let old = self
+            .playbacks
+            .write()
+            .await
+            .insert(connection_id.to_string(), handle);
+        if let Some(old) = old {
+            let _ = old.send(PlaybackCommand::Stop).await;
+        }
+    }
+
+    pub async fn control_playback(
+        &self,
+        connection_id: &str,
+        command: PlaybackCommand,
+    ) -> Result<()> {
+        let handle = self
+            .playbacks
+            .read()
+            .await
+            .get(connection_id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No playback for {}", connection_id))?;
+        if matches!(command, PlaybackCommand::Stop) {
+            self.finish_playback(connection_id, &handle).await;
+        }
+        handle.send(command).await
+    }
+
+    /// Forget `handle` once its player is done, unless it was replaced.
+    pub async fn finish_playback(&self, connection_id: &str, handle: &PlaybackHandle) {
+        let mut playbacks = self.playbacks.write().await;
+        if playbacks
+            .get(connection_id)
+            .is_some_and(|current| current.same_player(handle))
+        {
+            playbacks.remove(connection_id);
+        }
+    }
+
+    async fn record_output(&self, connection_id: &str, data: &[u8]) {
+        if let Some(recording) = self.recordings.read().await.get(connection_id) {
+            recording.output(data);
+        }
+    }
+
+    // ===== Command Execution =====
+
+    /// Install the callback that streams command output to the UI.
+    pub fn set_exec_handler(&self, handler: impl Fn(ExecEvent) + Send + Sync + 'static) {
+        if let Ok(mut slot) = self.exec_handler.write() {
+            *slot = Some(Arc::new(handler));
+        }
+    }
+
+    /// Run `command` on an SSH connection in the background, reporting its
+    /// output and exit through the exec handler under `exec_id`. The id is
+    /// chosen by the caller so it can listen before the first chunk arrives.
+    pub async fn start_exec(
+        &self,
+        exec_id: String,
+        connection_id: &str,
+        command: &str,
+        options: ExecOptions,
+    ) -> Result<()> {
+        let client = self
+            .get_connection(connection_id)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Connection not found"))?;
+        let cancel = CancellationToken::new();
+        {
+            let mut execs = self.execs.write().await;
+            if execs.contains_key(&exec_id) {
+                return Err(anyhow::anyhow!("Command {} is already running", exec_id));
+            }
+            execs.insert(exec_id.clone(), cancel.clone());
+        }
+        let channel = match client.read().await.open_exec(command).await {
+            Ok(channel) => channel,
+            Err(e) => {
+                self.execs.write().await.remove(&exec_id);
+                return Err(e);
+            }
+        };
+
+        let handler = self.exec_handler.read().ok().and_then(|slot| slot.clone());
+        let execs = self.execs.clone();
+        tokio::spawn(async move {
+            let emit = |event: ExecEvent| {
+                if let Some(handler) = &handler {
+                    handler(event);
+                }
+            };
+            let result = crate::ssh::run_exec(channel, &options, &cancel, |stream, data| {
+                emit(ExecEvent::Output {
+                    exec_id: exec_id.clone(),
+                    stream,
+                    data,
+                })
+            })
+            .await;
+            execs.write().await.remove(&exec_id);
+            emit(match result {
+                Ok(status) => ExecEvent::Exit { exec_id, status },
+                Err(e) => ExecEvent::Error {
+                    exec_id,
+                    error: e.to_string(),
+                },
+            });
+        });
+        Ok(())
+    }
+
+    /// Stop a streamed command by closing its channel.
+    pub async fn cancel_exec(&self, exec_id: &str) -> Result<()> {
+        let execs = self.execs.read().await;
+        let cancel = execs
+            .get(exec_id)
+            .ok_or_else(|| anyhow::anyhow!("Command not running"))?;
+        cancel.cancel();
+        Ok(())
+    }
+
+    pub async fn set_connection_tags(&self, connection_id: &str, tags: Vec<String>) {
+        let mut connection_tags = self.connection_tags.write().await;
+        if tags.is_empty() {
+            connection_tags.remove(connection_id);
+        } else {
+            connection_tags.insert(connection_id.to_string(), tags);
+        }
+    }
+
+    /// Install the callback that reports broadcast progress to the UI.
+    pub fn set_broadcast_handler(&self, handler: impl Fn(BroadcastEvent) + Send + Sync + 'static) {
+        if let Ok(mut slot) = self.broadcast_handler.write() {
+            *slot = Some(Arc::new(handler));
+        }
+    }
+
+    /// Run `command` on every connection in `target`, at most `parallelism`
+    /// hosts at a time. A host that can't run it gets an `error` result
+    /// rather than failing the whole broadcast.
+    pub async fn broadcast_exec(
+        &self,
+        broadcast_id: &str,
+        target: &BroadcastTarget,
+        command: &str,
+        options: &ExecOptions,
+        parallelism: usize,
+    ) -> Result<Vec<HostExecResult>> {
+        let connection_ids = {
+            let tags = self.connection_tags.read().await;
+            crate::broadcast::resolve_targets(target, &tags)
+        };
+        if connection_ids.is_empty() {
+            return Err(anyhow::anyhow!("No connections match the broadcast target"));
+        }
+        let handler = self
+            .broadcast_handler
+            .read()
+            .ok()
+            .and_then(|slot| slot.clone());
+        let results = crate::broadcast::run_broadcast(
+            broadcast_id,
+            connection_ids,
+            parallelism,
+            |connection_id| self.exec_on_host(connection_id, command, options),
+            |event| {
+                if let Some(handler) = &handler {
+                    handler(event);
+                }
+            },
+        )
+        .await;
+        Ok(results)
+    }
+
+    async fn exec_on_host(
+        &self,
+        connection_id: String,
+        command: &str,
+        options: &ExecOptions,
+    ) -> HostExecResult {
+        let started = std::time::Instant::now();
+        let output = match self.get_connection(&connection_id).await {
+            // Only opening the channel needs the client; reconnects aren't
+            // held up while the command runs.
+            Some(client) => {
+                let channel = client.read().await.open_exec(command).await;
+                match channel {
+                    Ok(channel) => crate::ssh::collect_exec(channel, options).await,
+                    Err(e) => Err(e),
+                }
+            }
+            None => Err(anyhow::anyhow!("Connection not found")),
+        };
+        match output {
+            Ok(output) => HostExecResult {
+                connection_id,
+                output,
+                error: None,
+            },
+            Err(e) => HostExecResult {
+                connection_id,
+                output: ExecOutput {
+                    status: ExecStatus {
+                        duration_ms: started.elapsed().as_millis() as u64,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                error: Some(e.to_string()),
+            },
+        }
+    }
+
+    // ===== Automatic Reconnect =====
+
+    /// Install the callback that reports reconnect progress to the UI.
+    pub fn set_status_handler(&self, handler: impl Fn(ConnectionStatus) + Send + Sync + 'static) {
+        if let Ok(mut slot) = self.status_handler.write() {
+            *slot = Some(Arc::new(handler));
+        }
+    }
+
+    fn emit_status(&self, status: ConnectionStatus) {
+        let handler = self
+            .status_handler
+            .read()
+            .ok()
+            .and_then(|slot| slot.clone());
+        if let Some(handler) = handler {
+            handler(status);
+        }
+    }
+
+    /// Called when a PTY's output stream ends. If the SSH transport itself was
+    /// lost, reconnect with backoff using the stored config and start a new
+    /// PTY for `connection_id`, returning its generation. Returns `None` when
+    /// the shell simply exited, reconnect is disabled, or the connection was
+    /// closed meanwhile; `cancel` (the old PTY's token) aborts the attempt.
+    ///
+    /// Local and dynamic forwards keep working on the new transport; remote
+    /// forwards are not re-requested.
+    pub async fn reconnect_pty(
+        &self,
+        connection_id: &str,
+        cancel: &CancellationToken,
+    ) -> Result<Option<u64>> {
+        let Some(config) = self.ssh_configs.read().await.get(connection_id).cloned() else {
+            return Ok(None);
+        };
+        let Some(client) = self.get_connection(connection_id).await else {
+            return Ok(None);
+        };
+        if !config.reconnect.enabled || client.read().await.check_alive().await {
+            return Ok(None);
+        }
+
+        let policy = &config.reconnect;
+        let status =
+            |state, attempt, retry_in: Option<std::time::Duration>, error| ConnectionStatus {
+                connection_id: connection_id.to_string(),
+                state,
+                attempt,
+                max_attempts: policy.max_attempts,
+                retry_in_ms: retry_in.map(|d| d.as_millis() as u64),
+                error,
+            };
+        tracing::warn!("SSH transport lost for {}, reconnecting", connection_id);
+        self.emit_status(status(ConnectionState::Disconnected, 0, None, None));
+
+        // Registered as pending so `cancel_ssh_connection` can stop it too.
+        let pending = self.register_pending_connection(connection_id).await;
+        let mut attempt = 0;
+        let mut last_error = String::new();
+        let connected = loop {
+            attempt += 1;
+            if !policy.allows_attempt(attempt) {
+                break None;
+            }
+            let delay = policy.delay(attempt);
+            self.emit_status(status(
+                ConnectionState::Reconnecting,
+                attempt,
+                Some(delay),
+                (!last_error.is_empty()).then(|| last_error.clone()),
+            ));
+
+            let mut new_client = SshClient::with_host_key_verifier(self.host_key_verifier.clone())
+                .with_auth_prompter(self.auth_prompter.clone());
+            let result = tokio::select! {
+                _ = cancel.cancelled() => break None,
+                _ = pending.cancelled() => break None,
+                res = async {
+                    tokio::time::sleep(delay).await;
+                    new_client.connect(&config).await
+                } => res,
+            };
+            match result {
+                Ok(()) => break Some(new_client),
+                Err(e) => {
+                    tracing::warn!(
+                        "Reconnect attempt {} for {} failed: {}",
+                        attempt,
+                        connection_id,
+                        e
+                    );
+                    last_error = e.to_string();
+                }
+            }
+        };
+        self.clear_pending_connection(connection_id).await;
+
+        let Some(mut new_client) = connected else {
+            if cancel.is_cancelled() || pending.is_cancelled() {
+                return Ok(None);
+            }
+            self.emit_status(status(
+                ConnectionState::Failed,
+                attempt - 1,
+                None,
+                Some(last_error.clone()),
+            ));
+            anyhow::bail!(
+                "Reconnect failed after {} attempts: {}",
+                attempt - 1,
+                last_error
+            );
+        };
+
+        // Swap the transport in place so forwards and monitoring holding the
+        // client keep working, unless the connection was closed meanwhile.
+        let Some(client) = self.get_connection(connection_id).await else {
+            let _ = new_client.disconnect().await;
+            return Ok(None);
+        };
+        let mut old_client = std::mem::replace(&mut *client.write().await, new_client);
+        let _ = old_client.disconnect().await;
+
+        let (cols, rows) = self
+            .pty_sizes
+            .read()
+            .await
+            .get(connection_id)
+            .copied()
+            .unwrap_or((80, 24));
+        match self.start_pty_connection(connection_id, cols, rows).await {
+            Ok(generation) => {
+                tracing::info!(
+                    "Reconnected {} (PTY generation {})",
+                    connection_id,
+                    generation
+                );
+                self.emit_status(status(ConnectionState::Reconnected, attempt, None, None));
+                Ok(Some(generation))
+            }
+            Err(e) => {
+                self.emit_status(status(
+                    ConnectionState::Failed,
+                    attempt,
+                    None,
+                    Some(e.to_string()),
+                ));
+                Err(e)
+            }
+        }
+    }
+
+    // ===== Local Terminal Management =====
+
+    /// Register a local terminal; the shell starts with the first StartPty.
+    pub async fn create_local_connection(
+        &self,
+        connection_id: String,
+        config: LocalShellConfig,
+    ) -> Result<()> {
+        self.local_shells
+            .write()
+            .await
+            .insert(connection_id.clone(), config);
+        let mut types = self.connection_types.write().await;
+        types.insert(connection_id, "LOCAL".to_string());
+        Ok(())
+    }
+
+    pub async fn close_local_connection(&self, connection_id: &str) -> Result<()> {
+        self.close_pty_connection(connection_id, None).await?;
+        self.local_shells.write().await.remove(connection_id);
+        self.pty_sizes.write().await.remove(connection_id);
+        if self.recordings.read().await.contains_key(connection_id) {
+            self.stop_recording(connection_id).await?;
+        }
+        let mut types = self.connection_types.write().await;
+        types.remove(connection_id);
+        Ok(())
+    }
+
+    // ===== Telnet Connection Management =====
+
+    pub async fn create_telnet_connection(
+        &self,
+        connection_id: String,
+        config: TelnetConfig,
+    ) -> Result<()> {
+        let stream = crate::telnet_client::connect(&config).await?;
+        self.telnet_connections.write().await.insert(
+            connection_id.clone(),
+            TelnetConnection {
+                config,
+                stream: Some(stream),
+            },
+        );
+        let mut types = self.connection_types.write().await;
+        types.insert(connection_id, "TELNET".to_string());
+        Ok(())
+    }
+
+    pub async fn close_telnet_connection(&self, connection_id: &str) -> Result<()> {
+        self.close_pty_connection(connection_id, None).await?;
+        self.telnet_connections.write().await.remove(connection_id);
+        self.pty_sizes.write().await.remove(connection_id);
+        if self.recordings.read().await.contains_key(connection_id) {
+            self.stop_recording(connection_id).await?;
+        }
+        let mut types = self.connection_types.write().await;
+        types.remove(connection_id);
+        Ok(())
     }
 
     // ===== Standalone SFTP Connection Management =====
@@ -268,7 +1011,12 @@ impl ConnectionManager {
         connection_id: String,
         config: crate::sftp_client::SftpConfig,
     ) -> Result<()> {
-        let client = StandaloneSftpClient::connect(&config).await?;
+        let client = StandaloneSftpClient::connect(
+            &config,
+            self.host_key_verifier.clone(),
+            &self.auth_prompter,
+        )
+        .await?;
         let mut sftp_connections = self.sftp_connections.write().await;
         sftp_connections.insert(connection_id.clone(), client);
         let mut types = self.connection_types.write().await;
@@ -281,6 +1029,7 @@ impl ConnectionManager {
     }
 
     pub async fn close_sftp_connection(&self, connection_id: &str) -> Result<()> {
+        self.close_remote_edits_for(connection_id).await;
         let mut sftp_connections = self.sftp_connections.write().await;
         if let Some(mut client) = sftp_connections.remove(connection_id) {
             client.disconnect().await?;
@@ -299,20 +1048,24 @@ impl ConnectionManager {
     ) -> Result<()> {
         let client = FtpClient::connect(&config).await?;
         let mut ftp_connections = self.ftp_connections.write().await;
-        ftp_connections.insert(connection_id.clone(), client);
+        ftp_connections.insert(connection_id.clone(), Arc::new(Mutex::new(client)));
         let mut types = self.connection_types.write().await;
         types.insert(connection_id, "FTP".to_string());
         Ok(())
     }
 
-    pub async fn get_ftp_connection(&self) -> Arc<RwLock<HashMap<String, FtpClient>>> {
-        self.ftp_connections.clone()
+    /// The map lock is released on return; callers then hold only this
+    /// client's lock, so a long FTP command doesn't stall other connections.
+    pub async fn get_ftp_client(&self, connection_id: &str) -> Option<Arc<Mutex<FtpClient>>> {
+        let connections = self.ftp_connections.read().await;
+        connections.get(connection_id).cloned()
     }
 
     pub async fn close_ftp_connection(&self, connection_id: &str) -> Result<()> {
-        let mut ftp_connections = self.ftp_connections.write().await;
-        if let Some(mut client) = ftp_connections.remove(connection_id) {
-            client.disconnect().await?;
+        self.close_remote_edits_for(connection_id).await;
+        let client = self.ftp_connections.write().await.remove(connection_id);
+        if let Some(client) = client {
+            client.lock().await.disconnect().await?;
         }
         let mut types = self.connection_types.write().await;
         types.remove(connection_id);
@@ -325,6 +1078,1305 @@ impl ConnectionManager {
         types.get(connection_id).cloned()
     }
 
+    // ===== File Transfers =====
+
+    /// Install the callback that reports transfer progress to the UI.
+    pub fn set_transfer_handler(&self, handler: impl Fn(TransferProgress) + Send + Sync + 'static) {
+        if let Ok(mut slot) = self.transfer_handler.write() {
+            *slot = Some(Arc::new(handler));
+        }
+    }
+
+    /// Copy one file as described by `spec`, reporting progress under
+    /// `transfer_id` (generated when `None`) until it finishes or is stopped
+    /// with `cancel_transfer`. Returns the bytes copied.
+    pub async fn transfer_file(
+        &self,
+        transfer_id: Option<String>,
+        spec: TransferSpec,
+    ) -> Result<u64> {
+        let (transfer_id, cancel) = self.register_transfer(transfer_id).await?;
+        self.run_registered_file(transfer_id, spec, cancel).await
+    }
+
+    async fn run_registered_file(
+        &self,
+        transfer_id: String,
+        spec: TransferSpec,
+        cancel: CancellationToken,
+    ) -> Result<u64> {
+        let mut transfer = Transfer::new(transfer_id.clone(), spec, cancel, self.transfer_sink());
+        let result = self.run_transfer(&mut transfer).await;
+        self.transfers.write().await.remove(&transfer_id);
+        result
+    }
+
+    /// Copy the tree under `spec`'s paths file by file, recreating its
+    /// directories on the destination side. Progress for the whole tree is
+    /// reported under `transfer_id`, and `cancel_transfer` stops the copy.
+    /// Returns the bytes copied.
+    pub async fn transfer_directory(
+        &self,
+        transfer_id: Option<String>,
+        spec: TransferSpec,
+        options: DirectoryTransferOptions,
+    ) -> Result<u64> {
+        let (transfer_id, cancel) = self.register_transfer(transfer_id).await?;
+        self.run_registered_directory(transfer_id, spec, options, cancel)
+            .await
+    }
+
+    async fn run_registered_directory(
+        &self,
+        transfer_id: String,
+        spec: TransferSpec,
+        options: DirectoryTransferOptions,
+        cancel: CancellationToken,
+    ) -> Result<u64> {
+        let result = self.copy_tree(&transfer_id, &spec, &options, &cancel).await;
+        self.transfers.write().await.remove(&transfer_id);
+        result
+    }
+
+    async fn register_transfer(
+        &self,
+        transfer_id: Option<String>,
+    ) -> Result<(String, CancellationToken)> {
+        let transfer_id = transfer_id.unwrap_or_else(|| {
+            format!(
+                "transfer-{}",
+                self.next_transfer_id.fetch_add(1, Ordering::Relaxed)
+            )
+        });
+        let cancel = CancellationToken::new();
+        let mut transfers = self.transfers.write().await;
+        if transfers.contains_key(&transfer_id) {
+            return Err(anyhow::anyhow!(
+                "Transfer {} is already running",
+                transfer_id
+            ));
+        }
+        transfers.insert(transfer_id.clone(), cancel.clone());
+        Ok((transfer_id, cancel))
+    }
+
+    fn transfer_sink(&self) -> Option<ProgressSink> {
+        self.transfer_handler
+            .read()
+            .ok()
+            .and_then(|slot| slot.clone())
+    }
+
+    async fn copy_tree(
+        &self,
+        transfer_id: &str,
+        spec: &TransferSpec,
+        options: &DirectoryTransferOptions,
+        cancel: &CancellationToken,
+    ) -> Result<u64> {
+        let upload = spec.direction == TransferDirection::Upload;
+        let local_root = PathBuf::from(&spec.local_path);
+        let entries = if upload {
+            walk_local(&local_root, &options.exclude_patterns).await?
+        } else {
+            self.walk_remote(
+                &spec.connection_id,
+                &spec.remote_path,
+                &options.exclude_patterns,
+            )
+            .await?
+        };
+        let progress = TreeProgress::new(transfer_id, spec, &entries, self.transfer_sink());
+
+        if upload {
+            self.ensure_remote_dir(&spec.connection_id, &spec.remote_path)
+                .await?;
+        } else {
+            tokio::fs::create_dir_all(&local_root).await?;
+        }
+        let mut copied = 0;
+        for entry in &entries {
+            if cancel.is_cancelled() {
+                return Err(anyhow::anyhow!("Transfer cancelled"));
+            }
+            let file_spec = TransferSpec {
+                remote_path: join_remote_path(&spec.remote_path, &entry.relative_path),
+                local_path: local_root
+                    .join(&entry.relative_path)
+                    .to_string_lossy()
+                    .into_owned(),
+                ..spec.clone()
+            };
+            if entry.is_dir {
+                if upload {
+                    self.ensure_remote_dir(&spec.connection_id, &file_spec.remote_path)
+                        .await?;
+                } else {
+                    tokio::fs::create_dir_all(&file_spec.local_path).await?;
+                }
+                continue;
+            }
+            let mut transfer = Transfer::new(
+                transfer_id.to_string(),
+                file_spec.clone(),
+                cancel.clone(),
+                progress.file_sink(),
+            );
+            let bytes = self.run_transfer(&mut transfer).await?;
+            progress.file_done(entry.size, bytes);
+            copied += bytes;
+            self.preserve_attributes(&file_spec, options).await;
+        }
+
+        // Writing into a directory bumps its mtime, so directories get
+        // theirs last, deepest first.
+        if options.preserve_times || options.preserve_permissions {
+            for entry in entries.iter().rev().filter(|entry| entry.is_dir) {
+                let dir_spec = TransferSpec {
+                    remote_path: join_remote_path(&spec.remote_path, &entry.relative_path),
+                    local_path: local_root
+                        .join(&entry.relative_path)
+                        .to_string_lossy()
+                        .into_owned(),
+                    ..spec.clone()
+                };
+                self.preserve_attributes(&dir_spec, options).await;
+            }
+            self.preserve_attributes(spec, options).await;
+        }
+        progress.finish();
+        Ok(copied)
+    }
+
+    /// Copy the source's mtime and/or permissions onto the destination. Not
+    /// every backend can, so failures are logged rather than failing the
+    /// transfer.
+    async fn preserve_attributes(&self, spec: &TransferSpec, options: &DirectoryTransferOptions) {
+        if !options.preserve_times && !options.preserve_permissions {
+            return;
+        }
+        let result = if spec.direction == TransferDirection::Upload {
+            match local_attributes(Path::new(&spec.local_path)) {
+                Ok(attrs) => {
+                    let attrs = RemoteAttributes {
+                        size: None,
+                        modified: attrs.modified.filter(|_| options.preserve_times),
+                        permissions: attrs.permissions.filter(|_| options.preserve_permissions),
+                    };
+                    self.set_remote_attributes(&spec.connection_id, &spec.remote_path, &attrs)
+                        .await
+                }
+                Err(e) => Err(e),
+            }
+        } else {
+            match self
+                .remote_attributes(&spec.connection_id, &spec.remote_path)
+                .await
+            {
+                Ok(attrs) => set_local_attributes(
+                    Path::new(&spec.local_path),
+                    attrs.modified.filter(|_| options.preserve_times),
+                    attrs.permissions.filter(|_| options.preserve_permissions),
+                ),
+                Err(e) => Err(e),
+            }
+        };
+        if let Err(e) = result {
+            tracing::warn!(
+                "Could not preserve attributes of {}: {}",
+                spec.remote_path,
+                e
+            );
+        }
+    }
+
+    /// Entries under a remote directory, parents before their contents.
+    /// Fails on a listed name that would reach outside the tree once joined
+    /// to a local root.
+    async fn walk_remote(
+        &self,
+        connection_id: &str,
+        root: &str,
+        exclude_patterns: &[String],
+    ) -> Result<Vec<TreeEntry>> {
+        let mut entries = Vec::new();
+        let mut pending = vec![String::new()];
+        while let Some(relative_dir) = pending.pop() {
+            let dir = if relative_dir.is_empty() {
+                root.to_string()
+            } else {
+                join_remote_path(root, &relative_dir)
+            };
+            for item in self.list_remote_dir(connection_id, &dir).await? {
+                let relative_path = if relative_dir.is_empty() {
+                    item.name
+                } else {
+                    format!("{}/{}", relative_dir, item.name)
+                };
+                validate_remote_relative_path(&relative_path)?;
+                if matches_exclude(&relative_path, exclude_patterns) {
+                    continue;
+                }
+                let is_dir = item.file_type == FileEntryType::Directory;
+                if is_dir {
+                    pending.push(relative_path.clone());
+                }
+                entries.push(TreeEntry {
+                    relative_path,
+                    is_dir,
+                    size: if is_dir { 0 } else { item.size },
+                    modified: item.modified.as_deref().and_then(parse_listing_timestamp),
+                });
+            }
+        }
+        entries.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        Ok(entries)
+    }
+
+    async fn remote_attributes(&self, connection_id: &str, path: &str) -> Result<RemoteAttributes> {
+        match self.get_connection_type(connection_id).await.as_deref() {
+            Some("SFTP") => {
+                let connections = self.sftp_connections.read().await;
+                let client = connections
+                    .get(connection_id)
+                    .ok_or_else(|| anyhow::anyhow!("SFTP connection not found"))?;
+                sftp_stat(client.sftp_session()?, path).await
+            }
+            Some("FTP") => {
+                let client = self
+                    .get_ftp_client(connection_id)
+                    .await
+                    .ok_or_else(|| anyhow::anyhow!("FTP connection not found"))?;
+                let mut client = client.lock().await;
+                client.attributes(path).await
+            }
+            Some(other) => Err(anyhow::anyhow!("Unsupported protocol: {}", other)),
+            None => {
+                let client = self.get_connection(connection_id).await.ok_or_else(|| {
+                    anyhow::anyhow!("No connection found for '{}'", connection_id)
+                })?;
+                let sftp = client.read().await.open_sftp_session().await?;
+                sftp_stat(&sftp, path).await
+            }
+        }
+    }
+
+    async fn set_remote_attributes(
+        &self,
+        connection_id: &str,
+        path: &str,
+        attrs: &RemoteAttributes,
+    ) -> Result<()> {
+        match self.get_connection_type(connection_id).await.as_deref() {
+            Some("SFTP") => {
+                let connections = self.sftp_connections.read().await;
+                let client = connections
+                    .get(connection_id)
+                    .ok_or_else(|| anyhow::anyhow!("SFTP connection not found"))?;
+                sftp_set_attributes(client.sftp_session()?, path, attrs).await
+            }
+            Some("FTP") => {
+                let client = self
+                    .get_ftp_client(connection_id)
+                    .await
+                    .ok_or_else(|| anyhow::anyhow!("FTP connection not found"))?;
+                let mut client = client.lock().await;
+                client.set_attributes(path, attrs).await
+            }
+            Some(other) => Err(anyhow::anyhow!("Unsupported protocol: {}", other)),
+            None => {
+                let client = self.get_connection(connection_id).await.ok_or_else(|| {
+                    anyhow::anyhow!("No connection found for '{}'", connection_id)
+                })?;
+                let sftp = client.read().await.open_sftp_session().await?;
+                sftp_set_attributes(&sftp, path, attrs).await
+            }
+        }
+    }
+
+    async fn list_remote_dir(&self, connection_id: &str, path: &str) -> Result<Vec<FileEntry>> {
+        match self.get_connection_type(connection_id).await.as_deref() {
+            Some("SFTP") => {
+                let connections = self.sftp_connections.read().await;
+                let client = connections
+                    .get(connection_id)
+                    .ok_or_else(|| anyhow::anyhow!("SFTP connection not found"))?;
+                client.list_dir(path).await
+            }
+            Some("FTP") => {
+                let client = self
+                    .get_ftp_client(connection_id)
+                    .await
+                    .ok_or_else(|| anyhow::anyhow!("FTP connection not found"))?;
+                let mut client = client.lock().await;
+                client.list_dir(path).await
+            }
+            Some(other) => Err(anyhow::anyhow!("Unsupported protocol: {}", other)),
+            None => {
+                let client = self.get_connection(connection_id).await.ok_or_else(|| {
+                    anyhow::anyhow!("No connection found for '{}'", connection_id)
+                })?;
+                let sftp = client.read().await.open_sftp_session().await?;
+                list_sftp_dir(&sftp, path).await
+            }
+        }
+    }
+
+    /// Create a remote directory unless it already exists.
+    async fn ensure_remote_dir(&self, connection_id: &str, path: &str) -> Result<()> {
+        let created = match self.get_connection_type(connection_id).await.as_deref() {
+            Some("SFTP") => {
+                let connections = self.sftp_connections.read().await;
+                let client = connections
+                    .get(connection_id)
+                    .ok_or_else(|| anyhow::anyhow!("SFTP connection not found"))?;
+                client.create_dir(path).await
+            }
+            Some("FTP") => {
+                let client = self
+                    .get_ftp_client(connection_id)
+                    .await
+                    .ok_or_else(|| anyhow::anyhow!("FTP connection not found"))?;
+                let mut client = client.lock().await;
+                client.create_dir(path).await
+            }
+            Some(other) => Err(anyhow::anyhow!("Unsupported protocol: {}", other)),
+            None => {
+                let client = self.get_connection(connection_id).await.ok_or_else(|| {
+                    anyhow::anyhow!("No connection found for '{}'", connection_id)
+                })?;
+                let sftp = client.read().await.open_sftp_session().await?;
+                sftp.create_dir(path)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Failed to create directory '{}': {}", path, e))
+            }
+        };
+        match created {
+            Ok(()) => Ok(()),
+            // Servers disagree on how to report an existing directory, so
+            // check by listing it.
+            Err(e) => match self.list_remote_dir(connection_id, path).await {
+                Ok(_) => Ok(()),
+                Err(_) => Err(e),
+            },
+        }
+    }
+
+    /// Copy one file, then compare hashes when the spec asks to verify.
+    async fn run_transfer(&self, transfer: &mut Transfer) -> Result<u64> {
+        let copied = self.copy_file(transfer).await?;
+        if let Some(algorithm) = transfer.spec().verify {
+            self.verify_copy(transfer.spec(), algorithm).await?;
+        }
+        Ok(copied)
+    }
+
+    async fn verify_copy(&self, spec: &TransferSpec, algorithm: HashAlgorithm) -> Result<()> {
+        let local = local_checksum(Path::new(&spec.local_path), algorithm).await?;
+        let remote = self
+            .remote_checksum(&spec.connection_id, &spec.remote_path, algorithm)
+            .await?;
+        if local != remote.hash {
+            return Err(anyhow::anyhow!(
+                "Checksum mismatch for '{}': local {} {}, remote {}",
+                spec.remote_path,
+                algorithm.name(),
+                local,
+                remote.hash
+            ));
+        }
+        Ok(())
+    }
+
+    async fn copy_file(&self, transfer: &mut Transfer) -> Result<u64> {
+        let spec = transfer.spec().clone();
+        let (remote, local) = (spec.remote_path.as_str(), spec.local_path.as_str());
+        let upload = spec.direction == TransferDirection::Upload;
+        match self
+            .get_connection_type(&spec.connection_id)
+            .await
+            .as_deref()
+        {
+            Some("SFTP") => {
+                let connections = self.sftp_connections.read().await;
+                let client = connections
+                    .get(&spec.connection_id)
+                    .ok_or_else(|| anyhow::anyhow!("SFTP connection not found"))?;
+                if upload {
+                    client.upload(local, remote, transfer).await
+                } else {
+                    client.download(remote, local, transfer).await
+                }
+            }
+            Some("FTP") => {
+                let client = self
+                    .get_ftp_client(&spec.connection_id)
+                    .await
+                    .ok_or_else(|| anyhow::anyhow!("FTP connection not found"))?;
+                let mut client = client.lock().await;
+                if upload {
+                    client.upload(local, remote, transfer).await
+                } else {
+                    client.download(remote, local, transfer).await
+                }
+            }
+            Some(other) => Err(anyhow::anyhow!("Unsupported protocol: {}", other)),
+            None => {
+                // Fallback: try SSH connection (integrated file browser uses SSH connections
+                // which are not registered in connection_types)
+                let client = self
+                    .get_connection(&spec.connection_id)
+                    .await
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("No connection found for '{}'", spec.connection_id)
+                    })?;
+                let sftp = client.read().await.open_sftp_session().await?;
+                if upload {
+                    crate::sftp_client::sftp_upload(&sftp, local, remote, transfer).await
+                } else {
+                    crate::sftp_client::sftp_download(&sftp, remote, local, transfer).await
+                }
+            }
+        }
+    }
+
+    /// Stop a running transfer. The partial file is left in place so the
+    /// transfer can be resumed.
+    pub async fn cancel_transfer(&self, transfer_id: &str) -> Result<()> {
+        let transfers = self.transfers.read().await;
+        let cancel = transfers
+            .get(transfer_id)
+            .ok_or_else(|| anyhow::anyhow!("Transfer not running"))?;
+        cancel.cancel();
+        Ok(())
+    }
+
+    // ===== Transfer Queue =====
+
+    /// Install the callback that reports queue changes to the UI.
+    pub fn set_queue_handler(&self, handler: impl Fn(QueueEvent) + Send + Sync + 'static) {
+        if let Ok(mut slot) = self.queue_handler.write() {
+            *slot = Some(Arc::new(handler));
+        }
+    }
+
+    fn emit_queue_event(&self, event: QueueEvent) {
+        if let Some(handler) = self.queue_handler.read().ok().and_then(|slot| slot.clone()) {
+            handler(event);
+        }
+    }
+
+    /// Apply `change` to the queue and save it.
+    fn update_queue<T>(&self, change: impl FnOnce(&mut TransferQueue) -> Result<T>) -> Result<T> {
+        let mut queue = self
+            .transfer_queue
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Transfer queue is unavailable"))?;
+        let value = change(&mut queue)?;
+        if let Err(e) = queue.save() {
+            tracing::warn!("Failed to save transfer queue: {}", e);
+        }
+        Ok(value)
+    }
+
+    pub fn transfer_jobs(&self) -> Vec<TransferJob> {
+        self.transfer_queue
+            .lock()
+            .map(|queue| queue.jobs().to_vec())
+            .unwrap_or_default()
+    }
+
+    pub fn transfer_queue_limits(&self) -> QueueLimits {
+        self.transfer_queue
+            .lock()
+            .map(|queue| queue.limits())
+            .unwrap_or_default()
+    }
+
+    /// Queue transfers of files or, with `directory`, whole trees, and start
+    /// as many as the limits allow.
+    pub fn enqueue_transfers(
+        self: &Arc<Self>,
+        jobs: Vec<(TransferSpec, bool, DirectoryTransferOptions)>,
+    ) -> Result<Vec<TransferJob>> {
+        let added = self.update_queue(|queue| {
+            Ok(jobs
+                .into_iter()
+                .map(|(spec, directory, options)| queue.add(spec, directory, options))
+                .collect::<Vec<_>>())
+        })?;
+        for job in &added {
+            self.emit_queue_event(QueueEvent::Updated { job: job.clone() });
+        }
+        self.pump_transfer_queue();
+        Ok(added)
+    }
+
+    pub fn set_transfer_queue_limits(self: &Arc<Self>, limits: QueueLimits) -> Result<()> {
+        self.update_queue(|queue| {
+            queue.set_limits(limits);
+            Ok(())
+        })?;
+        self.pump_transfer_queue();
+        Ok(())
+    }
+
+    /// Hold a job; a running one is stopped and keeps its partial file.
+    pub async fn pause_transfer_job(&self, job_id: &str) -> Result<TransferJob> {
+        let (previous, job) = self.update_queue(|queue| queue.pause(job_id))?;
+        if let (JobState::Running, Some(transfer_id)) = (previous, &job.transfer_id) {
+            let _ = self.cancel_transfer(transfer_id).await;
+        }
+        self.emit_queue_event(QueueEvent::Updated { job: job.clone() });
+        Ok(job)
+    }
+
+    pub async fn cancel_transfer_job(&self, job_id: &str) -> Result<TransferJob> {
+        let (previous, job) = self.update_queue(|queue| queue.cancel(job_id))?;
+        if let (JobState::Running, Some(transfer_id)) = (previous, &job.transfer_id) {
+            let _ = self.cancel_transfer(transfer_id).await;
+        }
+        self.emit_queue_event(QueueEvent::Updated { job: job.clone() });
+        Ok(job)
+    }
+
+    pub fn resume_transfer_job(self: &Arc<Self>, job_id: &str) -> Result<TransferJob> {
+        let (_, job) = self.update_queue(|queue| queue.resume(job_id))?;
+        self.emit_queue_event(QueueEvent::Updated { job: job.clone() });
+        self.pump_transfer_queue();
+        Ok(job)
+    }
+
+    pub fn retry_transfer_job(self: &Arc<Self>, job_id: &str) -> Result<TransferJob> {
+        let (_, job) = self.update_queue(|queue| queue.retry(job_id))?;
+        self.emit_queue_event(QueueEvent::Updated { job: job.clone() });
+        self.pump_transfer_queue();
+        Ok(job)
+    }
+
+    /// Move a job to `index`; queued jobs start in queue order.
+    pub fn move_transfer_job(&self, job_id: &str, index: usize) -> Result<Vec<String>> {
+        let job_ids = self.update_queue(|queue| queue.move_job(job_id, index))?;
+        self.emit_queue_event(QueueEvent::Reordered {
+            job_ids: job_ids.clone(),
+        });
+        Ok(job_ids)
+    }
+
+    pub fn remove_transfer_job(&self, job_id: &str) -> Result<()> {
+        self.update_queue(|queue| queue.remove(job_id))?;
+        self.emit_queue_event(QueueEvent::Removed {
+            job_id: job_id.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Start every queued job the limits allow, each in its own task.
+    fn pump_transfer_queue(self: &Arc<Self>) {
+        let started = match self.update_queue(|queue| Ok(queue.start_runnable())) {
+            Ok(started) => started,
+            Err(e) => {
+                tracing::warn!("Failed to start queued transfers: {}", e);
+                return;
+            }
+        };
+        for job in started {
+            self.emit_queue_event(QueueEvent::Updated { job: job.clone() });
+            let manager = self.clone();
+            tokio::spawn(async move {
+                let job_id = job.id.clone();
+                let transfer_id = job.transfer_id.clone().unwrap_or_else(|| job.id.clone());
+                let result = manager.run_job(job, transfer_id.clone()).await;
+                let finished = manager.update_queue(|queue| {
+                    Ok(queue.finish(&job_id, &transfer_id, result.map_err(|e| e.to_string())))
+                });
+                if let Ok(Some(job)) = finished {
+                    manager.emit_queue_event(QueueEvent::Updated { job });
+                }
+                manager.pump_transfer_queue();
+            });
+        }
+    }
+
+    /// Run one start of a queued job. The job is checked again once its
+    /// transfer is registered, so a pause or cancel that came before the
+    /// registration stops it too.
+    async fn run_job(&self, job: TransferJob, transfer_id: String) -> Result<u64> {
+        let (transfer_id, cancel) = self.register_transfer(Some(transfer_id)).await?;
+        let still_running = self.transfer_jobs().iter().any(|current| {
+            current.id == job.id
+                && current.state == JobState::Running
+                && current.transfer_id.as_deref() == Some(transfer_id.as_str())
+        });
+        if !still_running {
+            cancel.cancel();
+        }
+        if job.directory {
+            self.run_registered_directory(transfer_id, job.spec, job.options, cancel)
+                .await
+        } else {
+            self.run_registered_file(transfer_id, job.spec, cancel)
+                .await
+        }
+    }
+
+    // ===== Directory Synchronization =====
+
+    /// Compare the two trees of `request` and return what applying the sync
+    /// would do, without changing anything.
+    pub async fn plan_sync(&self, request: &SyncRequest) -> Result<SyncPlan> {
+        let options = &request.options;
+        let local = walk_local(Path::new(&request.local_path), &options.exclude_patterns).await?;
+        let mut remote = self
+            .walk_remote(
+                &request.connection_id,
+                &request.remote_path,
+                &options.exclude_patterns,
+            )
+            .await?;
+
+        // FTP listings show minutes at best, so ask MDTM for files both
+        // sides have.
+        if self
+            .get_connection_type(&request.connection_id)
+            .await
+            .as_deref()
+            == Some("FTP")
+        {
+            let local_files: HashSet<&str> = local
+                .iter()
+                .filter(|entry| !entry.is_dir)
+                .map(|entry| entry.relative_path.as_str())
+                .collect();
+            for entry in remote.iter_mut() {
+                if entry.is_dir || !local_files.contains(entry.relative_path.as_str()) {
+                    continue;
+                }
+                let path = join_remote_path(&request.remote_path, &entry.relative_path);
+                if let Ok(attrs) = self.remote_attributes(&request.connection_id, &path).await {
+                    entry.modified = attrs.modified.or(entry.modified);
+                }
+            }
+        }
+
+        let mut identical = HashSet::new();
+        for relative_path in sync::checksum_candidates(&local, &remote, options) {
+            let local_hash = local_checksum(
+                &Path::new(&request.local_path).join(&relative_path),
+                HashAlgorithm::Sha256,
+            )
+            .await?;
+            let remote = self
+                .remote_checksum(
+                    &request.connection_id,
+                    &join_remote_path(&request.remote_path, &relative_path),
+                    HashAlgorithm::Sha256,
+                )
+                .await?;
+            if local_hash == remote.hash {
+                identical.insert(relative_path);
+            }
+        }
+        Ok(sync::plan(&local, &remote, options, &identical))
+    }
+
+    /// Carry out `plan`, reporting copy progress for the whole sync under
+    /// `sync_id`; `cancel_transfer` stops it. A failed item doesn't stop the
+    /// rest and is listed in the report. Conflicts are skipped.
+    pub async fn apply_sync(
+        &self,
+        sync_id: Option<String>,
+        request: &SyncRequest,
+        plan: &SyncPlan,
+    ) -> Result<SyncReport> {
+        let (sync_id, cancel) = self.register_transfer(sync_id).await?;
+        let result = self.run_sync(&sync_id, request, plan, &cancel).await;
+        self.transfers.write().await.remove(&sync_id);
+        result
+    }
+
+    async fn run_sync(
+        &self,
+        sync_id: &str,
+        request: &SyncRequest,
+        plan: &SyncPlan,
+        cancel: &CancellationToken,
+    ) -> Result<SyncReport> {
+        let root = TransferSpec {
+            connection_id: request.connection_id.clone(),
+            direction: TransferDirection::Upload,
+            remote_path: request.remote_path.clone(),
+            local_path: request.local_path.clone(),
+            resume: false,
+            verify: None,
+        };
+        let copies: Vec<TreeEntry> = plan
+            .items
+            .iter()
+            .filter(|item| matches!(item.action, SyncAction::Upload | SyncAction::Download))
+            .map(|item| TreeEntry {
+                relative_path: item.relative_path.clone(),
+                is_dir: false,
+                size: item.size,
+                modified: None,
+            })
+            .collect();
+        let progress = TreeProgress::new(sync_id, &root, &copies, self.transfer_sink());
+        // Copies keep the source's time so the next comparison sees them
+        // as unchanged.
+        let keep_times = DirectoryTransferOptions {
+            preserve_times: true,
+            ..Default::default()
+        };
+
+        let mut report = SyncReport::default();
+        for item in &plan.items {
+            if cancel.is_cancelled() {
+                return Err(anyhow::anyhow!("Transfer cancelled"));
+            }
+            let spec = TransferSpec {
+                direction: if item.action == SyncAction::Download {
+                    TransferDirection::Download
+                } else {
+                    TransferDirection::Upload
+                },
+                remote_path: join_remote_path(&request.remote_path, &item.relative_path),
+                local_path: Path::new(&request.local_path)
+                    .join(&item.relative_path)
+                    .to_string_lossy()
+                    .into_owned(),
+                ..root.clone()
+            };
+            let result = match item.action {
+                SyncAction::Conflict => {
+                    report.conflicts += 1;
+                    continue;
+                }
+                SyncAction::Upload | SyncAction::Download => {
+                    let mut transfer = Transfer::new(
+                        sync_id.to_string(),
+                        spec.clone(),
+                        cancel.clone(),
+                        progress.file_sink(),
+                    );
+                    let copied = self.run_transfer(&mut transfer).await;
+                    if let Ok(bytes) = copied {
+                        progress.file_done(item.size, bytes);
+                        self.preserve_attributes(&spec, &keep_times).await;
+                    }
+                    copied
+                }
+                SyncAction::CreateLocalDir => tokio::fs::create_dir_all(&spec.local_path)
+                    .await
+                    .map(|_| 0)
+                    .map_err(Into::into),
+                SyncAction::CreateRemoteDir => self
+                    .ensure_remote_dir(&request.connection_id, &spec.remote_path)
+                    .await
+                    .map(|_| 0),
+                SyncAction::DeleteLocal if item.is_dir => tokio::fs::remove_dir(&spec.local_path)
+                    .await
+                    .map(|_| 0)
+                    .map_err(Into::into),
+                SyncAction::DeleteLocal => tokio::fs::remove_file(&spec.local_path)
+                    .await
+                    .map(|_| 0)
+                    .map_err(Into::into),
+                SyncAction::DeleteRemote => self
+                    .delete_remote_path(&request.connection_id, &spec.remote_path, item.is_dir)
+                    .await
+                    .map(|_| 0),
+            };
+            match result {
+                Ok(bytes) => {
+                    report.applied += 1;
+                    report.bytes_transferred += bytes;
+                }
+                Err(e) if cancel.is_cancelled() => return Err(e),
+                Err(e) => report.failures.push(SyncFailure {
+                    relative_path: item.relative_path.clone(),
+                    action: item.action,
+                    error: e.to_string(),
+                }),
+            }
+        }
+        progress.finish();
+        Ok(report)
+    }
+
+    /// Digest of a remote file. On SSH connections the server hashes it
+    /// with `sha256sum`, `shasum` or the like; when that isn't available,
+    /// and on SFTP and FTP connections, the file is streamed here and hashed.
+    pub async fn remote_checksum(
+        &self,
+        connection_id: &str,
+        path: &str,
+        algorithm: HashAlgorithm,
+    ) -> Result<Checksum> {
+        let mut hasher = Hasher::new(algorithm);
+        let update = |chunk: &[u8]| hasher.update(chunk);
+        match self.get_connection_type(connection_id).await.as_deref() {
+            Some("SFTP") => {
+                let connections = self.sftp_connections.read().await;
+                let client = connections
+                    .get(connection_id)
+                    .ok_or_else(|| anyhow::anyhow!("SFTP connection not found"))?;
+                sftp_read_chunks(client.sftp_session()?, path, update).await?;
+            }
+            Some("FTP") => {
+                let client = self
+                    .get_ftp_client(connection_id)
+                    .await
+                    .ok_or_else(|| anyhow::anyhow!("FTP connection not found"))?;
+                let mut client = client.lock().await;
+                client.read_chunks(path, update).await?;
+            }
+            Some(other) => return Err(anyhow::anyhow!("Unsupported protocol: {}", other)),
+            None => {
+                let client = self.get_connection(connection_id).await.ok_or_else(|| {
+                    anyhow::anyhow!("No connection found for '{}'", connection_id)
+                })?;
+                let client = client.read().await;
+                let output = client
+                    .exec(&algorithm.remote_command(path), &ExecOptions::default())
+                    .await;
+                let hashed = output.ok().and_then(|output| {
+                    (output.status.exit_code == Some(0))
+                        .then(|| algorithm.parse_remote_output(&output.stdout))
+                        .flatten()
+                });
+                if let Some(hash) = hashed {
+                    return Ok(Checksum {
+                        algorithm,
+                        hash,
+                        computed_remotely: true,
+                    });
+                }
+                let sftp = client.open_sftp_session().await?;
+                sftp_read_chunks(&sftp, path, update).await?;
+            }
+        }
+        Ok(Checksum {
+            algorithm,
+            hash: hasher.finish_hex(),
+            computed_remotely: false,
+        })
+    }
+
+    /// Delete a remote file, or a directory that is already empty.
+    async fn delete_remote_path(
+        &self,
+        connection_id: &str,
+        path: &str,
+        is_dir: bool,
+    ) -> Result<()> {
+        match self.get_connection_type(connection_id).await.as_deref() {
+            Some("SFTP") => {
+                let connections = self.sftp_connections.read().await;
+                let client = connections
+                    .get(connection_id)
+                    .ok_or_else(|| anyhow::anyhow!("SFTP connection not found"))?;
+                if is_dir {
+                    client.delete_dir(path).await
+                } else {
+                    client.delete_file(path).await
+                }
+            }
+            Some("FTP") => {
+                let client = self
+                    .get_ftp_client(connection_id)
+                    .await
+                    .ok_or_else(|| anyhow::anyhow!("FTP connection not found"))?;
+                let mut client = client.lock().await;
+                if is_dir {
+                    client.delete_dir(path).await
+                } else {
+                    client.delete_file(path).await
+                }
+            }
+            Some(other) => Err(anyhow::anyhow!("Unsupported protocol: {}", other)),
+            None => {
+                let client = self.get_connection(connection_id).await.ok_or_else(|| {
+                    anyhow::anyhow!("No connection found for '{}'", connection_id)
+                })?;
+                let sftp = client.read().await.open_sftp_session().await?;
+                let deleted = if is_dir {
+                    sftp.remove_dir(path).await
+                } else {
+                    sftp.remove_file(path).await
+                };
+                deleted.map_err(|e| anyhow::anyhow!("Failed to delete '{}': {}", path, e))
+            }
+        }
+    }
+
+    // ===== Remote Editing =====
+
+    /// Install the callback that reports remote edit saves and conflicts to
+    /// the UI.
+    pub fn set_remote_edit_handler(
+        &self,
+        handler: impl Fn(RemoteEditEvent) + Send + Sync + 'static,
+    ) {
+        if let Ok(mut slot) = self.remote_edit_handler.write() {
+            *slot = Some(Arc::new(handler));
+        }
+    }
+
+    fn emit_remote_edit_event(&self, event: RemoteEditEvent) {
+        let handler = self
+            .remote_edit_handler
+            .read()
+            .ok()
+            .and_then(|slot| slot.clone());
+        if let Some(handler) = handler {
+            handler(event);
+        }
+    }
+
+    /// Download `remote_path` into a private temp directory and upload the
+    /// copy back each time it is saved, until `close_remote_edit`.
+    pub async fn start_remote_edit(
+        self: &Arc<Self>,
+        connection_id: &str,
+        remote_path: &str,
+    ) -> Result<RemoteEditSession> {
+        let edit_id = format!("edit-{}", self.next_edit_id.fetch_add(1, Ordering::Relaxed));
+        let dir = remote_edit::create_edit_dir(&edit_id)?;
+        let local_path = dir.join(remote_edit::local_file_name(remote_path));
+        let spec = TransferSpec {
+            connection_id: connection_id.to_string(),
+            direction: TransferDirection::Download,
+            remote_path: remote_path.to_string(),
+            local_path: local_path.to_string_lossy().into_owned(),
+            resume: false,
+            verify: None,
+        };
+        let downloaded = async {
+            self.transfer_file(None, spec).await?;
+            self.remote_attributes(connection_id, remote_path).await
+        };
+        let remote = match downloaded.await {
+            Ok(remote) => remote,
+            Err(e) => {
+                let _ = tokio::fs::remove_dir_all(&dir).await;
+                return Err(e);
+            }
+        };
+
+        let session = RemoteEditSession {
+            edit_id: edit_id.clone(),
+            connection_id: connection_id.to_string(),
+            remote_path: remote_path.to_string(),
+            local_path: local_path.to_string_lossy().into_owned(),
+            remote,
+            state: EditState::Watching,
+            uploads: 0,
+        };
+        let stop = CancellationToken::new();
+        self.remote_edits.write().await.insert(
+            edit_id.clone(),
+            RemoteEdit {
+                session: session.clone(),
+                stop: stop.clone(),
+            },
+        );
+
+        let manager = self.clone();
+        let mut watcher = SaveWatcher::new(local_stamp(&local_path).await);
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = stop.cancelled() => break,
+                    _ = tokio::time::sleep(remote_edit::POLL_INTERVAL) => {}
+                }
+                if watcher.observe(local_stamp(&local_path).await) {
+                    // The outcome is reported through the event handler.
+                    let _ = manager.save_remote_edit(&edit_id, false).await;
+                }
+            }
+        });
+        Ok(session)
+    }
+
+    /// Upload the local copy of an edit. Unless `overwrite` is set, refuses
+    /// when the server copy changed since it was last downloaded or uploaded.
+    pub async fn save_remote_edit(
+        &self,
+        edit_id: &str,
+        overwrite: bool,
+    ) -> Result<RemoteEditSession> {
+        let result = self.upload_remote_edit(edit_id, overwrite).await;
+        if let Err(e) = &result {
+            let conflict = self
+                .remote_edits
+                .read()
+                .await
+                .get(edit_id)
+                .is_some_and(|edit| edit.session.state == EditState::Conflict);
+            if !conflict {
+                self.emit_remote_edit_event(RemoteEditEvent::Failed {
+                    edit_id: edit_id.to_string(),
+                    error: e.to_string(),
+                });
+            }
+        }
+        result
+    }
+
+    async fn upload_remote_edit(
+        &self,
+        edit_id: &str,
+        overwrite: bool,
+    ) -> Result<RemoteEditSession> {
+        let session = self
+            .remote_edits
+            .read()
+            .await
+            .get(edit_id)
+            .map(|edit| edit.session.clone())
+            .ok_or_else(|| anyhow::anyhow!("Remote edit {} not found", edit_id))?;
+        let connection_id = &session.connection_id;
+        let remote_path = &session.remote_path;
+
+        if !overwrite {
+            let current = self.remote_attributes(connection_id, remote_path).await?;
+            if remote_changed(&session.remote, &current) {
+                let session = self
+                    .update_remote_edit(edit_id, |session| session.state = EditState::Conflict)
+                    .await?;
+                self.emit_remote_edit_event(RemoteEditEvent::Conflict { session, current });
+                return Err(anyhow::anyhow!(
+                    "'{}' changed on the server; save with overwrite to replace it",
+                    remote_path
+                ));
+            }
+        }
+
+        let spec = TransferSpec {
+            connection_id: connection_id.clone(),
+            direction: TransferDirection::Upload,
+            remote_path: remote_path.clone(),
+            local_path: session.local_path.clone(),
+            resume: false,
+            verify: None,
+        };
+        self.transfer_file(None, spec).await?;
+        let remote = self.remote_attributes(connection_id, remote_path).await?;
+        let session = self
+            .update_remote_edit(edit_id, |session| {
+                session.remote = remote;
+                session.state = EditState::Watching;
+                session.uploads += 1;
+            })
+            .await?;
+        self.emit_remote_edit_event(RemoteEditEvent::Saved {
+            session: session.clone(),
+        });
+        Ok(session)
+    }
+
+    async fn update_remote_edit(
+        &self,
+        edit_id: &str,
+        change: impl FnOnce(&mut RemoteEditSession),
+    ) -> Result<RemoteEditSession> {
+        let mut edits = self.remote_edits.write().await;
+        let edit = edits
+            .get_mut(edit_id)
+            .ok_or_else(|| anyhow::anyhow!("Remote edit {} not found", edit_id))?;
+        change(&mut edit.session);
+        Ok(edit.session.clone())
+    }
+
+    /// Stop watching an edit and delete its local copy. Unsaved changes are
+    /// not uploaded.
+    pub async fn close_remote_edit(&self, edit_id: &str) -> Result<()> {
+        let edit = self
+            .remote_edits
+            .write()
+            .await
+            .remove(edit_id)
+            .ok_or_else(|| anyhow::anyhow!("Remote edit {} not found", edit_id))?;
+        edit.stop.cancel();
+        if let Some(dir) = Path::new(&edit.session.local_path).parent() {
+            if let Err(e) = tokio::fs::remove_dir_all(dir).await {
+                tracing::warn!("Failed to remove '{}': {}", dir.display(), e);
+            }
+        }
+        self.emit_remote_edit_event(RemoteEditEvent::Closed {
+            edit_id: edit_id.to_string(),
+        });
+        Ok(())
+    }
+
+    /// Close every edit opened through `connection_id`, which is going away.
+    async fn close_remote_edits_for(&self, connection_id: &str) {
+        let edit_ids: Vec<String> = self
+            .remote_edits
+            .read()
+            .await
+            .values()
+            .filter(|edit| edit.session.connection_id == connection_id)
+            .map(|edit| edit.session.edit_id.clone())
+            .collect();
+        for edit_id in edit_ids {
+            // Already closed by the user in the meantime.
+            let _ = self.close_remote_edit(&edit_id).await;
+        }
+    }
+
+    pub async fn remote_edits(&self) -> Vec<RemoteEditSession> {
+        let mut sessions: Vec<_> = self
+            .remote_edits
+            .read()
+            .await
+            .values()
+            .map(|edit| edit.session.clone())
+            .collect();
+        sessions.sort_by(|a, b| a.edit_id.cmp(&b.edit_id));
+        sessions
+    }
+
+    // ===== Remote File Attributes =====
+
+    /// Attributes of `path` itself; a symlink is described, not followed.
+    pub async fn stat_remote_path(
+        &self,
+        connection_id: &str,
+        path: &str,
+    ) -> Result<RemoteFileStat> {
+        match self.get_connection_type(connection_id).await.as_deref() {
+            Some("SFTP") => {
+                let connections = self.sftp_connections.read().await;
+                let client = connections
+                    .get(connection_id)
+                    .ok_or_else(|| anyhow::anyhow!("SFTP connection not found"))?;
+                sftp_lstat(client.sftp_session()?, path).await
+            }
+            Some("FTP") => {
+                let client = self
+                    .get_ftp_client(connection_id)
+                    .await
+                    .ok_or_else(|| anyhow::anyhow!("FTP connection not found"))?;
+                let mut client = client.lock().await;
+                client.stat(path).await
+            }
+            Some(other) => Err(unsupported(RemoteFileOp::Stat, other)),
+            None => {
+                let client = self.get_connection(connection_id).await.ok_or_else(|| {
+                    anyhow::anyhow!("No connection found for '{}'", connection_id)
+                })?;
+                let sftp = client.read().await.open_sftp_session().await?;
+                sftp_lstat(&sftp, path).await
+            }
+        }
+    }
+
+    /// Change the permissions, ownership or timestamps of `path`. Over FTP
+    /// only permissions (`SITE CHMOD`) and the modification time (`MFMT`)
+    /// can change; nothing is changed if any requested part is unsupported.
+    pub async fn change_remote_attributes(
+        &self,
+        connection_id: &str,
+        path: &str,
+        changes: &AttributeChanges,
+    ) -> Result<()> {
+        if changes.is_empty() {
+            return Err(anyhow::anyhow!("No attributes to change for '{}'", path));
+        }
+        match self.get_connection_type(connection_id).await.as_deref() {
+            Some("SFTP") => {
+                let connections = self.sftp_connections.read().await;
+                let client = connections
+                    .get(connection_id)
+                    .ok_or_else(|| anyhow::anyhow!("SFTP connection not found"))?;
+                sftp_change_attributes(client.sftp_session()?, path, changes).await
+            }
+            Some("FTP") => {
+                let missing = changes.operations().into_iter().find(|op| {
+                    matches!(op, RemoteFileOp::ChangeOwner | RemoteFileOp::SetAccessTime)
+                });
+                if let Some(op) = missing {
+                    return Err(unsupported(op, "FTP"));
+                }
+                let client = self
+                    .get_ftp_client(connection_id)
+                    .await
+                    .ok_or_else(|| anyhow::anyhow!("FTP connection not found"))?;
+                let mut client = client.lock().await;
+                let attrs = RemoteAttributes {
+                    size: None,
+                    modified: changes.modified,
+                    permissions: changes.permissions,
+                };
+                client.set_attributes(path, &attrs).await
+            }
+            Some(other) => {
+                let op = changes
+                    .operations()
+                    .first()
+                    .copied()
+                    .unwrap_or(RemoteFileOp::ChangePermissions);
+                Err(unsupported(op, other))
+            }
+            None => {
+                let client = self.get_connection(connection_id).await.ok_or_else(|| {
+                    anyhow::anyhow!("No connection found for '{}'", connection_id)
+                })?;
+                let sftp = client.read().await.open_sftp_session().await?;
+                sftp_change_attributes(&sftp, path, changes).await
+            }
+        }
+    }
+
+    /// Create `link_path` pointing at `target`. SFTP only.
+    pub async fn create_remote_symlink(
+        &self,
+        connection_id: &str,
+        link_path: &str,
+        target: &str,
+    ) -> Result<()> {
+        match self.get_connection_type(connection_id).await.as_deref() {
+            Some("SFTP") => {
+                let connections = self.sftp_connections.read().await;
+                let client = connections
+                    .get(connection_id)
+                    .ok_or_else(|| anyhow::anyhow!("SFTP connection not found"))?;
+                sftp_symlink(client.sftp_session()?, link_path, target).await
+            }
+            Some(other) => Err(unsupported(RemoteFileOp::CreateSymlink, other)),
+            None => {
+                let client = self.get_connection(connection_id).await.ok_or_else(|| {
+                    anyhow::anyhow!("No connection found for '{}'", connection_id)
+                })?;
+                let sftp = client.read().await.open_sftp_session().await?;
+                sftp_symlink(&sftp, link_path, target).await
+            }
+        }
+    }
+
+    /// Where the symlink at `path` points. SFTP only.
+    pub async fn read_remote_link(&self, connection_id: &str, path: &str) -> Result<String> {
+        match self.get_connection_type(connection_id).await.as_deref() {
+            Some("SFTP") => {
+                let connections = self.sftp_connections.read().await;
+                let client = connections
+                    .get(connection_id)
+                    .ok_or_else(|| anyhow::anyhow!("SFTP connection not found"))?;
+                sftp_read_link(client.sftp_session()?, path).await
+            }
+            Some(other) => Err(unsupported(RemoteFileOp::ReadLink, other)),
+            None => {
+                let client = self.get_connection(connection_id).await.ok_or_else(|| {
+                    anyhow::anyhow!("No connection found for '{}'", connection_id)
+                })?;
+                let sftp = client.read().await.open_sftp_session().await?;
+                sftp_read_link(&sftp, path).await
+            }
+        }
+    }
+
     // ===== Desktop (RDP/VNC) Connection Management =====
 
     /// Create a desktop connection (RDP or VNC) based on the request.
@@ -368,29 +2420,52 @@ impl ConnectionManager {
 
     /// Close and remove a desktop connection.
     pub async fn close_desktop_connection(&self, connection_id: &str) -> Result<()> {
-        let mut desktop = self.desktop_connections.write().await;
-        if let Some(client) = desktop.remove(connection_id) {
-            let mut client = client.write().await;
-            client.disconnect().await?;
+        let client = self.desktop_connections.write().await.remove(connection_id);
+        if let Some((_, stream)) = self.desktop_streams.write().await.remove(connection_id) {
+            stream.cancel();
+        }
+        if let Some(client) = client {
+            client.read().await.disconnect().await?;
         }
         let mut types = self.connection_types.write().await;
         types.remove(connection_id);
         Ok(())
     }
 
-    /// Start the frame update loop for a desktop connection.
+    /// Run the frame update loop for a desktop connection until `cancel`
+    /// fires, the connection is closed or another stream replaces it.
     pub async fn start_desktop_stream(
         &self,
         connection_id: &str,
         frame_tx: mpsc::UnboundedSender<FrameUpdate>,
         cancel: CancellationToken,
     ) -> Result<()> {
-        let desktop = self.desktop_connections.read().await;
-        let client = desktop
-            .get(connection_id)
+        let client = self
+            .get_desktop_connection(connection_id)
+            .await
             .ok_or_else(|| anyhow::anyhow!("Desktop connection not found: {}", connection_id))?;
-        let client = client.read().await;
-        client.start_frame_loop(frame_tx, cancel).await
+        let stream_id = self.next_desktop_stream_id.fetch_add(1, Ordering::Relaxed);
+        let previous = self
+            .desktop_streams
+            .write()
+            .await
+            .insert(connection_id.to_string(), (stream_id, cancel.clone()));
+        if let Some((_, previous)) = previous {
+            previous.cancel();
+        }
+
+        // Only a shared lock on this client is held while the loop runs; the
+        // other desktop methods take `&self` and don't wait for it.
+        let result = client.read().await.start_frame_loop(frame_tx, cancel).await;
+
+        let mut streams = self.desktop_streams.write().await;
+        if streams
+            .get(connection_id)
+            .is_some_and(|(id, _)| *id == stream_id)
+        {
+            streams.remove(connection_id);
+        }
+        result
     }
 }
 
@@ -467,6 +2542,47 @@ mod tests {
         assert!(mgr.get_connection_type("ftp-close").await.is_none());
     }
 
+    #[tokio::test]
+    async fn test_close_sftp_closes_its_remote_edits() {
+        let mgr = ConnectionManager::new();
+        let mut edits = Vec::new();
+        for (edit_id, connection_id) in [("edit-a", "sftp-edit"), ("edit-b", "other")] {
+            let dir = remote_edit::create_edit_dir(edit_id).unwrap();
+            let stop = CancellationToken::new();
+            let session = RemoteEditSession {
+                edit_id: edit_id.to_string(),
+                connection_id: connection_id.to_string(),
+                remote_path: "/etc/motd".to_string(),
+                local_path: dir.join("motd").to_string_lossy().into_owned(),
+                remote: RemoteAttributes::default(),
+                state: EditState::Watching,
+                uploads: 0,
+            };
+            mgr.remote_edits.write().await.insert(
+                edit_id.to_string(),
+                RemoteEdit {
+                    session,
+                    stop: stop.clone(),
+                },
+            );
+            edits.push((dir, stop));
+        }
+
+        mgr.close_sftp_connection("sftp-edit").await.unwrap();
+
+        let open: Vec<String> = mgr
+            .remote_edits()
+            .await
+            .into_iter()
+            .map(|s| s.edit_id)
+            .collect();
+        assert_eq!(open, ["edit-b"]);
+        assert!(edits[0].1.is_cancelled());
+        assert!(!edits[0].0.exists());
+        assert!(!edits[1].1.is_cancelled());
+        mgr.close_remote_edit("edit-b").await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_cancel_nonexistent_pending_connection() {
         let mgr = ConnectionManager::new();
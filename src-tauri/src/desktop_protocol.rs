@@ -47,10 +47,13 @@ pub trait DesktopProtocol: Send + Sync {
     /// Request the remote desktop to resize to the given dimensions.
     /// For RDP: sends a display resize request to the server.
     /// For VNC: no-op (VNC does not support server-side resize; client-side scaling is used).
-    async fn resize(&mut self, width: u16, height: u16) -> Result<()>;
+    ///
+    /// Takes `&self` like the other methods so it can run while a frame loop
+    /// is active.
+    async fn resize(&self, width: u16, height: u16) -> Result<()>;
 
-    /// Disconnect and release resources.
-    async fn disconnect(&mut self) -> Result<()>;
+    /// Disconnect and release resources. Stops a running frame loop.
+    async fn disconnect(&self) -> Result<()>;
 }
 
 // ---------------------------------------------------------------------------
@@ -0,0 +1,494 @@
+//! Whole-directory transfers: exclude globs, walking the local side of a
+//! tree, and folding per-file progress into one report for the tree.
+
+use crate::sftp_client::RemoteAttributes;
+use crate::transfer::{ProgressSink, TransferProgress, TransferSpec, PROGRESS_INTERVAL};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DirectoryTransferOptions {
+    /// Give copied files and directories the source's modification time.
+    pub preserve_times: bool,
+    /// Give copied files and directories the source's permission bits.
+    pub preserve_permissions: bool,
+    /// Globs (`*`, `?`) matched against each entry's name, or against its
+    /// path relative to the tree root when the pattern contains a `/`.
+    /// Excluded directories are skipped with everything under them.
+    pub exclude_patterns: Vec<String>,
+}
+
+/// A file or directory inside a tree, relative to the tree root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    /// `/`-separated, without a leading slash.
+    pub relative_path: String,
+    pub is_dir: bool,
+    pub size: u64,
+    /// Unix seconds, when known.
+    pub modified: Option<u64>,
+}
+
+/// Whether `relative_path` is excluded by any of `patterns`.
+pub fn matches_exclude(relative_path: &str, patterns: &[String]) -> bool {
+    let name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+    patterns.iter().any(|pattern| {
+        if pattern.contains('/') {
+            glob_match(pattern.trim_start_matches('/'), relative_path)
+        } else {
+            glob_match(pattern, name)
+        }
+    })
+}
+
+/// Reject a relative path that could reach outside the directory it is
+/// joined to: empty, absolute, with `.` or `..` components, backslashes,
+/// NULs, or a drive prefix. Paths built from server listings go through
+/// this before touching the local disk.
+pub fn validate_remote_relative_path(relative_path: &str) -> Result<()> {
+    let unsafe_path = || anyhow::anyhow!("Unsafe remote relative path: {}", relative_path);
+    if relative_path.is_empty() || relative_path.starts_with('/') || relative_path.contains('\\') {
+        return Err(unsafe_path());
+    }
+    for (index, component) in relative_path.split('/').enumerate() {
+        let has_windows_prefix = index == 0 && component.as_bytes().get(1) == Some(&b':');
+        if component.is_empty()
+            || component == "."
+            || component == ".."
+            || component.contains('\0')
+            || has_windows_prefix
+        {
+            return Err(unsafe_path());
+        }
+    }
+    Ok(())
+}
+
+/// `*` matches any run of characters (including `/`), `?` any single one.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was and the text position it is currently covering.
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Files and directories under `root`, parents before their contents.
+/// Symlinks to files count as files; symlinks to directories and dangling
+/// links are skipped so a link loop can't recurse forever.
+pub async fn walk_local(root: &Path, exclude_patterns: &[String]) -> Result<Vec<TreeEntry>> {
+    let mut entries = Vec::new();
+    let mut pending = vec![String::new()];
+    while let Some(relative_dir) = pending.pop() {
+        let dir = root.join(&relative_dir);
+        let mut read_dir = tokio::fs::read_dir(&dir)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read '{}': {}", dir.display(), e))?;
+        while let Some(item) = read_dir.next_entry().await? {
+            let name = item.file_name().to_string_lossy().into_owned();
+            let relative_path = if relative_dir.is_empty() {
+                name
+            } else {
+                format!("{}/{}", relative_dir, name)
+            };
+            if matches_exclude(&relative_path, exclude_patterns) {
+                continue;
+            }
+            let file_type = item.file_type().await?;
+            let metadata = if file_type.is_symlink() {
+                match tokio::fs::metadata(item.path()).await {
+                    Ok(metadata) if metadata.is_file() => metadata,
+                    _ => continue,
+                }
+            } else {
+                item.metadata().await?
+            };
+            if metadata.is_dir() {
+                pending.push(relative_path.clone());
+            } else if !metadata.is_file() {
+                continue;
+            }
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|since| since.as_secs());
+            entries.push(TreeEntry {
+                relative_path,
+                is_dir: metadata.is_dir(),
+                size: if metadata.is_dir() { 0 } else { metadata.len() },
+                modified,
+            });
+        }
+    }
+    entries.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+    Ok(entries)
+}
+
+/// Modification time and, on Unix, permission bits of a local path, in the
+/// shape used for remote paths.
+pub fn local_attributes(path: &Path) -> Result<RemoteAttributes> {
+    let metadata = std::fs::metadata(path)?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| since.as_secs());
+    #[cfg(unix)]
+    let permissions = {
+        use std::os::unix::fs::PermissionsExt;
+        Some(metadata.permissions().mode() & 0o7777)
+    };
+    #[cfg(not(unix))]
+    let permissions = None;
+    Ok(RemoteAttributes {
+        size: Some(metadata.len()),
+        modified,
+        permissions,
+    })
+}
+
+/// Set the modification time and/or permission bits of a local path.
+/// Permissions are ignored off Unix.
+pub fn set_local_attributes(
+    path: &Path,
+    modified: Option<u64>,
+    permissions: Option<u32>,
+) -> Result<()> {
+    if let Some(modified) = modified {
+        let file = std::fs::File::open(path)?;
+        file.set_modified(UNIX_EPOCH + Duration::from_secs(modified))?;
+    }
+    #[cfg(unix)]
+    if let Some(mode) = permissions {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(unix))]
+    let _ = permissions;
+    Ok(())
+}
+
+/// Folds the progress of each file in a directory transfer into one report
+/// for the whole tree, sent under the directory's transfer_id.
+#[derive(Clone)]
+pub struct TreeProgress {
+    sink: Option<ProgressSink>,
+    totals: Arc<Mutex<TreeTotals>>,
+}
+
+struct TreeTotals {
+    /// Identifies the transfer and names the tree roots.
+    root: TransferProgress,
+    started: Instant,
+    last_report: Option<Instant>,
+    files_total: u64,
+    files_done: u64,
+    total_bytes: u64,
+    /// Size of the files already finished.
+    done_bytes: u64,
+    /// Bytes copied by this run, leaving out resumed prefixes.
+    copied: u64,
+}
+
+impl TreeProgress {
+    pub fn new(
+        transfer_id: &str,
+        spec: &TransferSpec,
+        entries: &[TreeEntry],
+        sink: Option<ProgressSink>,
+    ) -> Self {
+        let files = entries.iter().filter(|entry| !entry.is_dir);
+        let root = TransferProgress {
+            transfer_id: transfer_id.to_string(),
+            connection_id: spec.connection_id.clone(),
+            direction: spec.direction,
+            remote_path: spec.remote_path.clone(),
+            local_path: spec.local_path.clone(),
+            bytes_transferred: 0,
+            total_bytes: None,
+            resumed_from: 0,
+            bytes_per_second: 0.0,
+            eta_seconds: None,
+            finished: false,
+            files_done: None,
+            files_total: None,
+        };
+        Self {
+            sink,
+            totals: Arc::new(Mutex::new(TreeTotals {
+                root,
+                started: Instant::now(),
+                last_report: None,
+                files_total: files.clone().count() as u64,
+                files_done: 0,
+                total_bytes: files.map(|entry| entry.size).sum(),
+                done_bytes: 0,
+                copied: 0,
+            })),
+        }
+    }
+
+    /// Progress sink for the `Transfer` of one file in the tree.
+    pub fn file_sink(&self) -> Option<ProgressSink> {
+        self.sink.as_ref()?;
+        let tree = self.clone();
+        Some(Arc::new(move |file: TransferProgress| {
+            if !file.finished {
+                tree.report(Some(&file), false);
+            }
+        }))
+    }
+
+    /// Count a finished file of `size` bytes, `copied` of them by this run.
+    pub fn file_done(&self, size: u64, copied: u64) {
+        if let Ok(mut totals) = self.totals.lock() {
+            totals.files_done += 1;
+            totals.done_bytes += size;
+            totals.copied += copied;
+        }
+    }
+
+    /// Report the whole tree as done.
+    pub fn finish(&self) {
+        self.report(None, true);
+    }
+
+    fn report(&self, current: Option<&TransferProgress>, finished: bool) {
+        let Some(sink) = &self.sink else {
+            return;
+        };
+        let progress = {
+            let Ok(mut totals) = self.totals.lock() else {
+                return;
+            };
+            let due = totals
+                .last_report
+                .is_none_or(|last| last.elapsed() >= PROGRESS_INTERVAL);
+            if !finished && !due {
+                return;
+            }
+            totals.last_report = Some(Instant::now());
+            totals.progress(current, finished)
+        };
+        sink(progress);
+    }
+}
+
+impl TreeTotals {
+    fn progress(&self, current: Option<&TransferProgress>, finished: bool) -> TransferProgress {
+        let (in_flight, copied_in_flight) = current
+            .map(|file| {
+                (
+                    file.bytes_transferred,
+                    file.bytes_transferred - file.resumed_from,
+                )
+            })
+            .unwrap_or_default();
+        let done = self.done_bytes + in_flight;
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let bytes_per_second = if elapsed > 0.0 {
+            (self.copied + copied_in_flight) as f64 / elapsed
+        } else {
+            0.0
+        };
+        let eta_seconds = if finished {
+            Some(0.0)
+        } else if bytes_per_second > 0.0 {
+            Some(self.total_bytes.saturating_sub(done) as f64 / bytes_per_second)
+        } else {
+            None
+        };
+        let mut progress = self.root.clone();
+        if let Some(file) = current {
+            progress.direction = file.direction;
+            progress.remote_path = file.remote_path.clone();
+            progress.local_path = file.local_path.clone();
+        }
+        TransferProgress {
+            bytes_transferred: done,
+            total_bytes: Some(self.total_bytes),
+            bytes_per_second,
+            eta_seconds,
+            finished,
+            files_done: Some(self.files_done),
+            files_total: Some(self.files_total),
+            ..progress
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::TransferDirection;
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn excludes_by_name_glob_or_relative_path() {
+        let excludes = patterns(&["*.log", "node_modules", "build/*.o", "tmp?"]);
+        assert!(matches_exclude("app.log", &excludes));
+        assert!(matches_exclude("src/debug.log", &excludes));
+        assert!(matches_exclude("web/node_modules", &excludes));
+        assert!(matches_exclude("build/main.o", &excludes));
+        assert!(!matches_exclude("src/main.o", &excludes));
+        assert!(matches_exclude("tmp1", &excludes));
+        assert!(!matches_exclude("tmp12", &excludes));
+        assert!(!matches_exclude("logs", &excludes));
+        assert!(glob_match("a*b*c", "a-b-b-c"));
+        assert!(!glob_match("a*b*c", "a-b-b-d"));
+    }
+
+    #[test]
+    fn rejects_relative_paths_that_leave_the_tree() {
+        for path in [
+            "",
+            "/etc/passwd",
+            "../up",
+            "a/../../b",
+            "a//b",
+            "./a",
+            "a\\b",
+            "C:x",
+        ] {
+            assert!(validate_remote_relative_path(path).is_err(), "{:?}", path);
+        }
+        for path in ["a", "src/main.rs", "..hidden/x", "c/d:e"] {
+            assert!(validate_remote_relative_path(path).is_ok(), "{:?}", path);
+        }
+    }
+
+    #[tokio::test]
+    async fn walks_local_tree_skipping_excluded_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("src/nested")).unwrap();
+        std::fs::create_dir_all(root.join("target/debug")).unwrap();
+        std::fs::write(root.join("README.md"), "hello").unwrap();
+        std::fs::write(root.join("src/nested/lib.rs"), "fn main() {}").unwrap();
+        std::fs::write(root.join("src/out.log"), "x").unwrap();
+        std::fs::write(root.join("target/debug/app"), "bin").unwrap();
+
+        let entries = walk_local(root, &patterns(&["target", "*.log"]))
+            .await
+            .unwrap();
+        let paths: Vec<(&str, bool, u64)> = entries
+            .iter()
+            .map(|e| (e.relative_path.as_str(), e.is_dir, e.size))
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                ("README.md", false, 5),
+                ("src", true, 0),
+                ("src/nested", true, 0),
+                ("src/nested/lib.rs", false, 12),
+            ]
+        );
+    }
+
+    #[test]
+    fn sets_local_times_and_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+        std::fs::write(&path, "data").unwrap();
+        set_local_attributes(&path, Some(1_000_000_000), Some(0o640)).unwrap();
+        let attrs = local_attributes(&path).unwrap();
+        assert_eq!(attrs.modified, Some(1_000_000_000));
+        assert_eq!(attrs.size, Some(4));
+        #[cfg(unix)]
+        assert_eq!(attrs.permissions, Some(0o640));
+    }
+
+    #[test]
+    fn aggregates_file_progress_over_the_tree() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink_events = events.clone();
+        let sink: ProgressSink = Arc::new(move |p| sink_events.lock().unwrap().push(p));
+        let spec = TransferSpec {
+            connection_id: "conn".into(),
+            direction: TransferDirection::Upload,
+            remote_path: "/srv/site".into(),
+            local_path: "/home/me/site".into(),
+            resume: false,
+            verify: None,
+        };
+        let entries = [
+            TreeEntry {
+                relative_path: "a".into(),
+                is_dir: false,
+                size: 100,
+                modified: None,
+            },
+            TreeEntry {
+                relative_path: "d".into(),
+                is_dir: true,
+                size: 0,
+                modified: None,
+            },
+            TreeEntry {
+                relative_path: "d/b".into(),
+                is_dir: false,
+                size: 300,
+                modified: None,
+            },
+        ];
+        let tree = TreeProgress::new("dir-1", &spec, &entries, Some(sink));
+        let file_sink = tree.file_sink().unwrap();
+
+        tree.file_done(100, 100);
+        let mut file = tree.totals.lock().unwrap().root.clone();
+        file.remote_path = "/srv/site/d/b".into();
+        file.bytes_transferred = 150;
+        file_sink(file.clone());
+        // Throttled: arrives right after the previous report.
+        file.bytes_transferred = 200;
+        file_sink(file);
+        tree.file_done(300, 300);
+        tree.finish();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].transfer_id, "dir-1");
+        assert_eq!(events[0].remote_path, "/srv/site/d/b");
+        assert_eq!(events[0].bytes_transferred, 250);
+        assert_eq!(events[0].total_bytes, Some(400));
+        assert_eq!(
+            (events[0].files_done, events[0].files_total),
+            (Some(1), Some(2))
+        );
+        let last = &events[1];
+        assert!(last.finished);
+        assert_eq!(last.remote_path, "/srv/site");
+        assert_eq!(last.bytes_transferred, 400);
+        assert_eq!(last.files_done, Some(2));
+    }
+}
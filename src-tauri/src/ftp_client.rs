@@ -1,9 +1,15 @@
 use anyhow::Result;
-use async_std::io::ReadExt;
+use async_std::io::{ReadExt, WriteExt};
 use serde::Deserialize;
+use std::io::SeekFrom;
 use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
-use crate::sftp_client::{FileEntry, FileEntryType};
+use crate::remote_fs::{parse_permission_string, RemoteFileStat};
+use crate::sftp_client::{
+    days_to_ymd, parse_listing_timestamp, FileEntry, FileEntryType, RemoteAttributes,
+};
+use crate::transfer::{resume_offset, Transfer, CHUNK_SIZE};
 
 /// Configuration for an FTP/FTPS connection.
 #[derive(Debug, Clone, Deserialize)]
@@ -193,42 +199,84 @@ impl FtpClient {
         Ok(result)
     }
 
-    /// Download a remote file to a local path. Returns bytes downloaded.
-    pub async fn download_file(&mut self, remote_path: &str, local_path: &str) -> Result<u64> {
-        let data: Vec<u8> = ftp_stream!(self, s => {
+    /// Download with progress and cancellation through `transfer`. Resuming
+    /// skips the bytes already on disk with `REST`; servers without it start over.
+    pub async fn download(
+        &mut self,
+        remote_path: &str,
+        local_path: &str,
+        transfer: &mut Transfer,
+    ) -> Result<u64> {
+        let existing = if transfer.resume_requested() {
+            tokio::fs::metadata(local_path).await.ok().map(|m| m.len())
+        } else {
+            None
+        };
+
+        ftp_stream!(self, s => {
+            let total = s.size(remote_path).await.ok().map(|size| size as u64);
+            let mut offset = resume_offset(existing, total);
+            if offset > 0 && s.resume_transfer(offset as usize).await.is_err() {
+                offset = 0;
+            }
+            let mut local_file = if offset > 0 {
+                tokio::fs::OpenOptions::new().append(true).open(local_path).await?
+            } else {
+                tokio::fs::File::create(local_path).await?
+            };
             let mut data_stream = s.retr_as_stream(remote_path).await.map_err(|e| {
                 anyhow::anyhow!("Failed to download file '{}': {}", remote_path, e)
             })?;
-            let mut buf = Vec::new();
-            data_stream.read_to_end(&mut buf).await.map_err(|e| {
-                anyhow::anyhow!("Failed to read download stream: {}", e)
-            })?;
+            transfer.start(offset, total);
+            if let Err(e) = copy_from_data_stream(&mut data_stream, &mut local_file, transfer).await {
+                let _ = s.abort(data_stream).await;
+                return Err(e);
+            }
             s.finalize_retr_stream(data_stream).await.map_err(|e| {
                 anyhow::anyhow!("Failed to finalize download: {}", e)
             })?;
-            buf
         });
 
-        let total_bytes = data.len() as u64;
-        tokio::fs::write(local_path, data).await?;
-        Ok(total_bytes)
+        Ok(transfer.finish())
     }
 
-    /// Upload a local file to a remote path. Returns bytes uploaded.
-    pub async fn upload_file(&mut self, local_path: &str, remote_path: &str) -> Result<u64> {
-        let data = tokio::fs::read(local_path)
+    /// Upload with progress and cancellation through `transfer`. Resuming
+    /// appends the rest of the file (`APPE`) to a shorter remote copy.
+    pub async fn upload(
+        &mut self,
+        local_path: &str,
+        remote_path: &str,
+        transfer: &mut Transfer,
+    ) -> Result<u64> {
+        let mut local_file = tokio::fs::File::open(local_path)
             .await
             .map_err(|e| anyhow::anyhow!("Failed to read local file '{}': {}", local_path, e))?;
-        let total_bytes = data.len() as u64;
+        let total = local_file.metadata().await?.len();
 
         ftp_stream!(self, s => {
-            let mut reader = async_std::io::Cursor::new(data);
-            s.put_file(remote_path, &mut reader).await.map_err(|e| {
+            let existing = if transfer.resume_requested() {
+                s.size(remote_path).await.ok().map(|size| size as u64)
+            } else {
+                None
+            };
+            let offset = resume_offset(existing, Some(total));
+            let mut data_stream = if offset > 0 {
+                local_file.seek(SeekFrom::Start(offset)).await?;
+                s.append_with_stream(remote_path).await
+            } else {
+                s.put_with_stream(remote_path).await
+            }
+            .map_err(|e| anyhow::anyhow!("Failed to upload file '{}': {}", remote_path, e))?;
+            transfer.start(offset, Some(total));
+            let copied = copy_to_data_stream(&mut local_file, &mut data_stream, transfer).await;
+            let finalized = s.finalize_put_stream(data_stream).await;
+            copied?;
+            finalized.map_err(|e| {
                 anyhow::anyhow!("Failed to upload file '{}': {}", remote_path, e)
-            })?
+            })?;
         });
 
-        Ok(total_bytes)
+        Ok(transfer.finish())
     }
 
     /// Create a directory on the remote server.
@@ -270,6 +318,157 @@ impl FtpClient {
         });
         Ok(())
     }
+
+    /// Read a remote file front to back, handing each chunk to `on_chunk`.
+    /// Returns the bytes read.
+    pub async fn read_chunks(
+        &mut self,
+        remote_path: &str,
+        mut on_chunk: impl FnMut(&[u8]),
+    ) -> Result<u64> {
+        ftp_stream!(self, s => {
+            let mut data_stream = s.retr_as_stream(remote_path).await.map_err(|e| {
+                anyhow::anyhow!("Failed to read file '{}': {}", remote_path, e)
+            })?;
+            let mut buf = vec![0u8; CHUNK_SIZE];
+            let mut total = 0;
+            loop {
+                let n = match data_stream.read(&mut buf).await {
+                    Ok(n) => n,
+                    Err(e) => {
+                        let _ = s.abort(data_stream).await;
+                        return Err(anyhow::anyhow!("Failed to read download stream: {}", e));
+                    }
+                };
+                if n == 0 {
+                    break;
+                }
+                on_chunk(&buf[..n]);
+                total += n as u64;
+            }
+            s.finalize_retr_stream(data_stream).await.map_err(|e| {
+                anyhow::anyhow!("Failed to finalize download: {}", e)
+            })?;
+            Ok(total)
+        })
+    }
+
+    /// Size and modification time of a file via SIZE and MDTM. FTP has no
+    /// portable way to read permissions, so those are left out.
+    pub async fn attributes(&mut self, path: &str) -> Result<RemoteAttributes> {
+        ftp_stream!(self, s => {
+            let size = s.size(path).await.ok().map(|size| size as u64);
+            let modified = s
+                .mdtm(path)
+                .await
+                .ok()
+                .map(|time| time.and_utc().timestamp().max(0) as u64);
+            if size.is_none() && modified.is_none() {
+                return Err(anyhow::anyhow!(
+                    "Failed to stat '{}': the server answered neither SIZE nor MDTM",
+                    path
+                ));
+            }
+            Ok(RemoteAttributes {
+                size,
+                modified,
+                permissions: None,
+            })
+        })
+    }
+
+    /// Type, permissions and owner of `path` from its parent's listing, with
+    /// the exact size and modification time from SIZE and MDTM where the
+    /// server answers them. FTP doesn't report symlink targets or access
+    /// times.
+    pub async fn stat(&mut self, path: &str) -> Result<RemoteFileStat> {
+        let trimmed = path.trim_end_matches('/');
+        let (parent, name) = match trimmed.rsplit_once('/') {
+            Some(("", name)) => ("/", name),
+            Some((parent, name)) => (parent, name),
+            None => (".", trimmed),
+        };
+        let mut stat = RemoteFileStat {
+            path: path.to_string(),
+            file_type: FileEntryType::Directory,
+            size: None,
+            permissions: None,
+            uid: None,
+            gid: None,
+            owner: None,
+            group: None,
+            accessed: None,
+            modified: None,
+            link_target: None,
+        };
+        // The root has no parent to list it.
+        if name.is_empty() {
+            return Ok(stat);
+        }
+
+        let entry = match self.list_dir(parent).await {
+            Ok(entries) => entries.into_iter().find(|entry| entry.name == name),
+            Err(_) => None,
+        };
+        let attributes = self.attributes(path).await;
+        match (entry, attributes) {
+            (Some(entry), attributes) => {
+                let attributes = attributes.unwrap_or_default();
+                stat.file_type = entry.file_type;
+                stat.size = attributes.size.or(Some(entry.size));
+                stat.permissions = entry.permissions.as_deref().and_then(parse_permission_string);
+                stat.owner = entry.owner;
+                stat.group = entry.group;
+                stat.modified = attributes
+                    .modified
+                    .or_else(|| entry.modified.as_deref().and_then(parse_listing_timestamp));
+            }
+            // Not in the listing, but SIZE or MDTM only answer for files.
+            (None, Ok(attributes)) => {
+                stat.file_type = FileEntryType::File;
+                stat.size = attributes.size;
+                stat.modified = attributes.modified;
+            }
+            (None, Err(_)) => {
+                return Err(anyhow::anyhow!("Failed to stat '{}': no such file or directory", path))
+            }
+        }
+        Ok(stat)
+    }
+
+    /// Apply permissions with `SITE CHMOD` and the modification time with
+    /// `MFMT`. Both are extensions that not every server implements.
+    pub async fn set_attributes(&mut self, path: &str, attrs: &RemoteAttributes) -> Result<()> {
+        ftp_stream!(self, s => {
+            if let Some(mode) = attrs.permissions {
+                s.site(format!("CHMOD {:o} {}", mode, path)).await.map_err(|e| {
+                    anyhow::anyhow!("Failed to change permissions of '{}': {}", path, e)
+                })?;
+            }
+            if let Some(modified) = attrs.modified {
+                let command = format!("MFMT {} {}", mfmt_timestamp(modified), path);
+                s.custom_command(command, &[suppaftp::Status::File]).await.map_err(|e| {
+                    anyhow::anyhow!("Failed to set modification time of '{}': {}", path, e)
+                })?;
+            }
+        });
+        Ok(())
+    }
+}
+
+/// `YYYYMMDDHHMMSS` in UTC, as MDTM reports and MFMT expects.
+fn mfmt_timestamp(secs: u64) -> String {
+    let (year, month, day) = days_to_ymd((secs / 86400) as i64);
+    let time = secs % 86400;
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        (time % 3600) / 60,
+        time % 60
+    )
 }
 
 /// Parse a single line from the FTP LIST command (Unix `ls -l` format).
@@ -481,9 +680,52 @@ fn is_leap_year(y: i64) -> bool {
 // The tests are gated behind the FTP_TEST_HOST env var so they are skipped
 // in CI / normal `cargo test` runs.
 // =============================================================================
+/// Copy an FTP data stream into a local file, counting against `transfer`.
+async fn copy_from_data_stream<R: async_std::io::Read + Unpin>(
+    stream: &mut R,
+    file: &mut tokio::fs::File,
+    transfer: &mut Transfer,
+) -> Result<()> {
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        transfer.ensure_active()?;
+        let n = stream
+            .read(&mut buf)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to read download stream: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        file.write_all(&buf[..n]).await?;
+        transfer.advance(n)?;
+    }
+    file.flush().await?;
+    Ok(())
+}
+
+/// Copy a local file into an FTP data stream, counting against `transfer`.
+async fn copy_to_data_stream<W: async_std::io::Write + Unpin>(
+    file: &mut tokio::fs::File,
+    stream: &mut W,
+    transfer: &mut Transfer,
+) -> Result<()> {
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        transfer.ensure_active()?;
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        stream.write_all(&buf[..n]).await?;
+        transfer.advance(n)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::transfer::TransferDirection;
 
     /// Helper – read env vars or skip the test.
     fn test_config() -> Option<FtpConfig> {
@@ -605,10 +847,13 @@ mod tests {
             .await
             .expect("write temp file");
 
+        let upload_path = tmp_upload.to_str().unwrap();
+        let mut transfer =
+            Transfer::untracked(TransferDirection::Upload, &test_file_remote, upload_path);
         let uploaded_bytes = client
-            .upload_file(tmp_upload.to_str().unwrap(), &test_file_remote)
+            .upload(upload_path, &test_file_remote, &mut transfer)
             .await
-            .expect("upload_file should succeed");
+            .expect("upload should succeed");
         assert_eq!(uploaded_bytes, upload_content.len() as u64);
         eprintln!("Uploaded {} bytes to {}", uploaded_bytes, test_file_remote);
 
@@ -624,10 +869,13 @@ mod tests {
 
         // 4d. Download the file and verify contents
         let tmp_download = std::env::temp_dir().join("rshell_e2e_download.txt");
+        let download_path = tmp_download.to_str().unwrap();
+        let mut transfer =
+            Transfer::untracked(TransferDirection::Download, &test_file_remote, download_path);
         let downloaded_bytes = client
-            .download_file(&test_file_remote, tmp_download.to_str().unwrap())
+            .download(&test_file_remote, download_path, &mut transfer)
             .await
-            .expect("download_file should succeed");
+            .expect("download should succeed");
         assert_eq!(downloaded_bytes, upload_content.len() as u64);
 
         let downloaded_data = tokio::fs::read(&tmp_download)
@@ -952,4 +1200,10 @@ mod tests {
         let entry = parse_ftp_list_line(line).expect("should parse");
         assert_eq!(entry.size, 0);
     }
+
+    #[test]
+    fn test_mfmt_timestamp() {
+        assert_eq!(mfmt_timestamp(0), "19700101000000");
+        assert_eq!(mfmt_timestamp(1_709_164_799), "20240228235959");
+    }
 }
@@ -0,0 +1,124 @@
+//! Synchronized input: terminals joined to the same broadcast group receive
+//! whatever is typed into any one of them.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupMember {
+    pub connection_id: String,
+    /// Stays in the group but neither sends nor receives broadcast input.
+    pub excluded: bool,
+}
+
+#[derive(Debug)]
+struct Membership {
+    group: String,
+    excluded: bool,
+}
+
+/// Broadcast group membership of PTY sessions. A session is in at most one
+/// group at a time.
+#[derive(Debug, Default)]
+pub struct BroadcastGroups {
+    members: HashMap<String, Membership>,
+}
+
+impl BroadcastGroups {
+    /// Add `connection_id` to `group`, leaving any group it was in.
+    pub fn join(&mut self, connection_id: &str, group: &str) {
+        self.members.insert(
+            connection_id.to_string(),
+            Membership {
+                group: group.to_string(),
+                excluded: false,
+            },
+        );
+    }
+
+    /// Remove `connection_id` from its group, returning the group's name.
+    pub fn leave(&mut self, connection_id: &str) -> Option<String> {
+        self.members
+            .remove(connection_id)
+            .map(|membership| membership.group)
+    }
+
+    /// Exclude or re-include a member, returning its group's name.
+    pub fn set_excluded(&mut self, connection_id: &str, excluded: bool) -> Option<String> {
+        let membership = self.members.get_mut(connection_id)?;
+        membership.excluded = excluded;
+        Some(membership.group.clone())
+    }
+
+    pub fn members(&self, group: &str) -> Vec<GroupMember> {
+        let mut members: Vec<GroupMember> = self
+            .members
+            .iter()
+            .filter(|(_, membership)| membership.group == group)
+            .map(|(connection_id, membership)| GroupMember {
+                connection_id: connection_id.clone(),
+                excluded: membership.excluded,
+            })
+            .collect();
+        members.sort_by(|a, b| a.connection_id.cmp(&b.connection_id));
+        members
+    }
+
+    /// Other sessions that input typed into `connection_id` is copied to.
+    pub fn fan_out_targets(&self, connection_id: &str) -> Vec<String> {
+        let Some(source) = self.members.get(connection_id) else {
+            return Vec::new();
+        };
+        if source.excluded {
+            return Vec::new();
+        }
+        self.members(&source.group)
+            .into_iter()
+            .filter(|member| !member.excluded && member.connection_id != connection_id)
+            .map(|member| member.connection_id)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_fans_out_to_other_included_members() {
+        let mut groups = BroadcastGroups::default();
+        groups.join("a", "web");
+        groups.join("b", "web");
+        groups.join("c", "web");
+        groups.join("d", "db");
+
+        assert_eq!(groups.fan_out_targets("a"), ["b", "c"]);
+        assert!(groups.fan_out_targets("d").is_empty());
+        assert!(groups.fan_out_targets("unknown").is_empty());
+
+        assert_eq!(groups.set_excluded("b", true).as_deref(), Some("web"));
+        assert_eq!(groups.fan_out_targets("a"), ["c"]);
+        assert!(groups.fan_out_targets("b").is_empty());
+        assert!(groups.set_excluded("unknown", true).is_none());
+    }
+
+    #[test]
+    fn joining_another_group_leaves_the_first() {
+        let mut groups = BroadcastGroups::default();
+        groups.join("a", "web");
+        groups.join("b", "web");
+        groups.join("a", "db");
+        assert!(groups.fan_out_targets("b").is_empty());
+        assert_eq!(
+            groups.members("db"),
+            [GroupMember {
+                connection_id: "a".into(),
+                excluded: false,
+            }]
+        );
+
+        assert_eq!(groups.leave("a").as_deref(), Some("db"));
+        assert_eq!(groups.leave("a"), None);
+        assert!(groups.members("db").is_empty());
+    }
+}
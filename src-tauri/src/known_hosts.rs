@@ -0,0 +1,598 @@
+use crate::prompt::{PendingPrompts, Unanswered};
+use anyhow::{bail, Result};
+use base64::Engine as _;
+use hmac::{Hmac, Mac};
+use russh_keys::key::PublicKey;
+use serde::Serialize;
+use sha1::Sha1;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long a trust-on-first-use prompt waits for the user before the
+/// connection is refused.
+pub const HOST_KEY_PROMPT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Marker that may prefix a known_hosts line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Marker {
+    /// The key is revoked and must never be accepted.
+    Revoked,
+    /// The key is a CA that signs host certificates for matching hosts.
+    /// Only plain host keys are negotiated, so these lines never trust a
+    /// server; they are parsed so the CA key is not mistaken for a host key.
+    CertAuthority,
+}
+
+/// A single parsed known_hosts line.
+#[derive(Debug, Clone)]
+pub struct KnownHostEntry {
+    pub marker: Option<Marker>,
+    /// Comma-separated host patterns exactly as written in the file.
+    pub hosts: String,
+    pub key: PublicKey,
+    /// 1-based line number, for error messages.
+    pub line: usize,
+}
+
+/// Outcome of looking up a server key in known_hosts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostKeyStatus {
+    /// A matching entry records exactly this key.
+    Trusted,
+    /// No entry records a key of this type for the host.
+    Unknown,
+    /// An entry records a different key of the same type for the host.
+    Changed { line: usize },
+    /// The key is listed under `@revoked`.
+    Revoked { line: usize },
+}
+
+/// Parse the contents of a known_hosts file. Blank lines, comments and lines
+/// whose key cannot be decoded are skipped, matching OpenSSH's tolerance.
+pub fn parse_known_hosts(content: &str) -> Vec<KnownHostEntry> {
+    content
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| parse_line(line, index + 1))
+        .collect()
+}
+
+fn parse_line(line: &str, line_number: usize) -> Option<KnownHostEntry> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let mut fields = line.split_whitespace();
+    let mut first = fields.next()?;
+    let marker = match first {
+        "@revoked" => Some(Marker::Revoked),
+        "@cert-authority" => Some(Marker::CertAuthority),
+        other if other.starts_with('@') => return None,
+        _ => None,
+    };
+    if marker.is_some() {
+        first = fields.next()?;
+    }
+
+    // The key type column is implied by the decoded blob.
+    fields.next()?;
+    let key = russh_keys::parse_public_key_base64(fields.next()?).ok()?;
+    Some(KnownHostEntry {
+        marker,
+        hosts: first.to_string(),
+        key,
+        line: line_number,
+    })
+}
+
+/// The name OpenSSH looks up for a host: `host` on port 22, `[host]:port`
+/// otherwise.
+pub fn lookup_name(host: &str, port: u16) -> String {
+    let host = host.to_lowercase();
+    if port == 22 {
+        host
+    } else {
+        format!("[{host}]:{port}")
+    }
+}
+
+/// True when `name` matches the comma-separated pattern list. A list matches
+/// when at least one positive pattern matches and no negated (`!`) pattern does.
+pub fn hosts_match(patterns: &str, name: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns.split(',') {
+        if let Some(negated) = pattern.strip_prefix('!') {
+            if pattern_matches(negated, name) {
+                return false;
+            }
+        } else if pattern_matches(pattern, name) {
+            matched = true;
+        }
+    }
+    matched
+}
+
+fn pattern_matches(pattern: &str, name: &str) -> bool {
+    if let Some(hashed) = pattern.strip_prefix("|1|") {
+        return hashed_matches(hashed, name);
+    }
+    wildcard_match(&pattern.to_lowercase(), name)
+}
+
+/// Check a `|1|salt|hash` entry: the hash is HMAC-SHA1 of the lookup name
+/// keyed with the salt, both base64-encoded.
+fn hashed_matches(hashed: &str, name: &str) -> bool {
+    let engine = base64::engine::general_purpose::STANDARD;
+    let Some((salt, hash)) = hashed.split_once('|') else {
+        return false;
+    };
+    let (Ok(salt), Ok(hash)) = (engine.decode(salt), engine.decode(hash)) else {
+        return false;
+    };
+    let Ok(mut mac) = Hmac::<Sha1>::new_from_slice(&salt) else {
+        return false;
+    };
+    mac.update(name.as_bytes());
+    mac.verify_slice(&hash).is_ok()
+}
+
+/// Glob matching with `*` (any run) and `?` (any single character).
+pub(crate) fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Key family used to decide whether two keys "compete" for the same slot.
+/// RSA keys are one family regardless of the negotiated signature hash.
+fn key_family(key: &PublicKey) -> &'static str {
+    match key {
+        PublicKey::RSA { .. } => "ssh-rsa",
+        other => other.name(),
+    }
+}
+
+/// SHA-256 fingerprint in the `SHA256:...` form printed by `ssh-keygen -l`.
+pub fn fingerprint(key: &PublicKey) -> String {
+    format!("SHA256:{}", key.fingerprint())
+}
+
+/// Look up `key` for `host:port` among parsed entries.
+pub fn check_entries(
+    entries: &[KnownHostEntry],
+    host: &str,
+    port: u16,
+    key: &PublicKey,
+) -> HostKeyStatus {
+    let name = lookup_name(host, port);
+    let matching = entries.iter().filter(|e| hosts_match(&e.hosts, &name));
+
+    let mut status = HostKeyStatus::Unknown;
+    for entry in matching {
+        match entry.marker {
+            Some(Marker::Revoked) if entry.key == *key => {
+                return HostKeyStatus::Revoked { line: entry.line };
+            }
+            Some(_) => {}
+            None if entry.key == *key => status = HostKeyStatus::Trusted,
+            None if key_family(&entry.key) == key_family(key)
+                && status == HostKeyStatus::Unknown =>
+            {
+                status = HostKeyStatus::Changed { line: entry.line };
+            }
+            None => {}
+        }
+    }
+    status
+}
+
+/// A trust-on-first-use question sent to the frontend.
+#[derive(Debug, Clone, Serialize)]
+pub struct HostKeyPrompt {
+    pub prompt_id: String,
+    pub host: String,
+    pub port: u16,
+    pub key_type: String,
+    pub fingerprint: String,
+}
+
+/// Verifies server host keys against a known_hosts file.
+///
+/// Shared by every SSH transport (PTY sessions, integrated SFTP and the
+/// standalone SFTP client). Unknown hosts are refused unless a prompt handler
+/// has been installed, in which case the user is asked to trust the key and
+/// accepted keys are appended to the file.
+pub struct HostKeyVerifier {
+    known_hosts_path: Option<PathBuf>,
+    prompts: PendingPrompts<HostKeyPrompt, bool>,
+}
+
+impl HostKeyVerifier {
+    pub fn new(known_hosts_path: Option<PathBuf>) -> Self {
+        Self {
+            known_hosts_path,
+            prompts: PendingPrompts::new("host-key", HOST_KEY_PROMPT_TIMEOUT),
+        }
+    }
+
+    /// Verifier backed by the user's `~/.ssh/known_hosts`.
+    pub fn from_default_location() -> Self {
+        Self::new(dirs::home_dir().map(|home| home.join(".ssh").join("known_hosts")))
+    }
+
+    /// Install the callback used to ask the user about unknown host keys.
+    pub fn set_prompt_handler(&self, handler: impl Fn(HostKeyPrompt) + Send + Sync + 'static) {
+        self.prompts.set_handler(handler);
+    }
+
+    /// Read and parse the known_hosts file. A missing file has no entries.
+    pub fn entries(&self) -> Result<Vec<KnownHostEntry>> {
+        let Some(path) = &self.known_hosts_path else {
+            return Ok(Vec::new());
+        };
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(parse_known_hosts(&content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => bail!("Failed to read {}: {}", path.display(), e),
+        }
+    }
+
+    pub fn check(&self, host: &str, port: u16, key: &PublicKey) -> Result<HostKeyStatus> {
+        Ok(check_entries(&self.entries()?, host, port, key))
+    }
+
+    /// Verify a server key, prompting the user for unknown hosts.
+    ///
+    /// Returns an error with a user-facing explanation whenever the key must
+    /// not be accepted.
+    pub async fn verify(&self, host: &str, port: u16, key: &PublicKey) -> Result<()> {
+        let fingerprint = fingerprint(key);
+        match self.check(host, port, key)? {
+            HostKeyStatus::Trusted => Ok(()),
+            HostKeyStatus::Revoked { line } => bail!(
+                "The {} host key for {} ({}) is marked as revoked in {} (line {}). Refusing to connect.",
+                key.name(),
+                lookup_name(host, port),
+                fingerprint,
+                self.path_display(),
+                line
+            ),
+            HostKeyStatus::Changed { line } => bail!(
+                "REMOTE HOST IDENTIFICATION HAS CHANGED for {}! The server presented {} key {}, \
+                 which does not match the key recorded in {} (line {}). Someone could be \
+                 eavesdropping on you, or the host key has just been changed. Remove the old \
+                 entry to connect.",
+                lookup_name(host, port),
+                key.name(),
+                fingerprint,
+                self.path_display(),
+                line
+            ),
+            HostKeyStatus::Unknown => {
+                if !self.ask_user(host, port, key).await? {
+                    bail!(
+                        "Host key for {} ({}) was not trusted.",
+                        lookup_name(host, port),
+                        fingerprint
+                    );
+                }
+                if let Err(e) = self.trust(host, port, key) {
+                    tracing::warn!("Failed to record host key for {}: {}", host, e);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    async fn ask_user(&self, host: &str, port: u16, key: &PublicKey) -> Result<bool> {
+        let answer = self
+            .prompts
+            .ask(|prompt_id| HostKeyPrompt {
+                prompt_id,
+                host: host.to_string(),
+                port,
+                key_type: key.name().to_string(),
+                fingerprint: fingerprint(key),
+            })
+            .await;
+        match answer {
+            Ok(accept) => Ok(accept),
+            Err(Unanswered::Cancelled) => Ok(false),
+            Err(Unanswered::NoHandler) => bail!(
+                "The authenticity of host {} can't be established ({} key {}) and it is not in {}.",
+                lookup_name(host, port),
+                key.name(),
+                fingerprint(key),
+                self.path_display()
+            ),
+            Err(Unanswered::TimedOut) => bail!(
+                "Timed out waiting for confirmation of the host key for {}.",
+                lookup_name(host, port)
+            ),
+        }
+    }
+
+    /// Deliver the user's answer to a pending prompt. Returns `false` when no
+    /// prompt with that ID is waiting (already answered or timed out).
+    pub fn respond(&self, prompt_id: &str, accept: bool) -> bool {
+        self.prompts.respond(prompt_id, accept)
+    }
+
+    /// Append `key` for `host:port` to the known_hosts file.
+    pub fn trust(&self, host: &str, port: u16, key: &PublicKey) -> Result<()> {
+        let Some(path) = &self.known_hosts_path else {
+            bail!("No known_hosts file is configured");
+        };
+        russh_keys::learn_known_hosts_path(&host.to_lowercase(), port, key, path)
+            .map_err(|e| anyhow::anyhow!("Failed to update {}: {}", path.display(), e))
+    }
+
+    fn path_display(&self) -> String {
+        self.known_hosts_path
+            .as_deref()
+            .map(Path::display)
+            .map(|p| p.to_string())
+            .unwrap_or_else(|| "known_hosts".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use russh_keys::key::KeyPair;
+    use russh_keys::PublicKeyBase64;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn ed25519() -> PublicKey {
+        KeyPair::generate_ed25519()
+            .unwrap()
+            .clone_public_key()
+            .unwrap()
+    }
+
+    fn line(hosts: &str, key: &PublicKey) -> String {
+        format!("{} {} {}", hosts, key.name(), key.public_key_base64())
+    }
+
+    fn hashed(name: &str) -> String {
+        let engine = base64::engine::general_purpose::STANDARD;
+        let salt = b"0123456789abcdefghij";
+        let mut mac = Hmac::<Sha1>::new_from_slice(salt).unwrap();
+        mac.update(name.as_bytes());
+        let hash = mac.finalize().into_bytes();
+        format!("|1|{}|{}", engine.encode(salt), engine.encode(hash))
+    }
+
+    #[test]
+    fn lookup_name_brackets_non_default_ports() {
+        assert_eq!(lookup_name("Example.COM", 22), "example.com");
+        assert_eq!(lookup_name("example.com", 2222), "[example.com]:2222");
+    }
+
+    #[test]
+    fn plain_entry_is_trusted() {
+        let key = ed25519();
+        let entries = parse_known_hosts(&line("example.com,10.0.0.1", &key));
+        assert_eq!(
+            check_entries(&entries, "10.0.0.1", 22, &key),
+            HostKeyStatus::Trusted
+        );
+        assert_eq!(
+            check_entries(&entries, "other.com", 22, &key),
+            HostKeyStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn bracketed_port_entry_only_matches_that_port() {
+        let key = ed25519();
+        let entries = parse_known_hosts(&line("[example.com]:2222", &key));
+        assert_eq!(
+            check_entries(&entries, "example.com", 2222, &key),
+            HostKeyStatus::Trusted
+        );
+        assert_eq!(
+            check_entries(&entries, "example.com", 22, &key),
+            HostKeyStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn hashed_entry_matches_lookup_name() {
+        let key = ed25519();
+        let content = format!(
+            "{}\n{}",
+            line(&hashed("example.com"), &key),
+            line(&hashed("[example.com]:2200"), &key)
+        );
+        let entries = parse_known_hosts(&content);
+        assert_eq!(
+            check_entries(&entries, "example.com", 22, &key),
+            HostKeyStatus::Trusted
+        );
+        assert_eq!(
+            check_entries(&entries, "example.com", 2200, &key),
+            HostKeyStatus::Trusted
+        );
+        assert_eq!(
+            check_entries(&entries, "example.org", 22, &key),
+            HostKeyStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn wildcards_and_negation() {
+        let key = ed25519();
+        let entries = parse_known_hosts(&line("*.example.com,!bad.example.com,db?", &key));
+        assert_eq!(
+            check_entries(&entries, "web.example.com", 22, &key),
+            HostKeyStatus::Trusted
+        );
+        assert_eq!(
+            check_entries(&entries, "db1", 22, &key),
+            HostKeyStatus::Trusted
+        );
+        assert_eq!(
+            check_entries(&entries, "bad.example.com", 22, &key),
+            HostKeyStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn different_key_of_same_type_is_a_change() {
+        let recorded = ed25519();
+        let presented = ed25519();
+        let content = format!("# comment\n\n{}", line("example.com", &recorded));
+        let entries = parse_known_hosts(&content);
+        assert_eq!(
+            check_entries(&entries, "example.com", 22, &presented),
+            HostKeyStatus::Changed { line: 3 }
+        );
+    }
+
+    #[test]
+    fn any_matching_entry_wins_over_a_stale_one() {
+        let old = ed25519();
+        let current = ed25519();
+        let content = format!(
+            "{}\n{}",
+            line("example.com", &old),
+            line("example.com", &current)
+        );
+        let entries = parse_known_hosts(&content);
+        assert_eq!(
+            check_entries(&entries, "example.com", 22, &current),
+            HostKeyStatus::Trusted
+        );
+    }
+
+    #[test]
+    fn revoked_key_is_rejected_even_if_also_trusted() {
+        let key = ed25519();
+        let content = format!(
+            "{}\n@revoked {}",
+            line("example.com", &key),
+            line("*", &key)
+        );
+        let entries = parse_known_hosts(&content);
+        assert_eq!(
+            check_entries(&entries, "example.com", 22, &key),
+            HostKeyStatus::Revoked { line: 2 }
+        );
+    }
+
+    #[test]
+    fn cert_authority_lines_do_not_trust_plain_keys() {
+        let ca = ed25519();
+        let entries = parse_known_hosts(&format!("@cert-authority {}", line("*", &ca)));
+        assert_eq!(entries[0].marker, Some(Marker::CertAuthority));
+        assert_eq!(
+            check_entries(&entries, "example.com", 22, &ca),
+            HostKeyStatus::Unknown
+        );
+    }
+
+    #[test]
+    fn malformed_lines_are_skipped() {
+        let key = ed25519();
+        let content = format!(
+            "garbage\n@unknown-marker {}\nhost ssh-ed25519 not-base64!!\n{}",
+            line("x", &key),
+            line("example.com", &key)
+        );
+        let entries = parse_known_hosts(&content);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].line, 4);
+    }
+
+    #[tokio::test]
+    async fn unknown_host_without_prompt_handler_is_refused() {
+        let dir = TempDir::new().unwrap();
+        let verifier = HostKeyVerifier::new(Some(dir.path().join("known_hosts")));
+        let err = verifier
+            .verify("example.com", 22, &ed25519())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("can't be established"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn accepted_prompt_records_the_key() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("known_hosts");
+        let verifier = Arc::new(HostKeyVerifier::new(Some(path.clone())));
+        let responder = Arc::downgrade(&verifier);
+        verifier.set_prompt_handler(move |prompt| {
+            assert!(prompt.fingerprint.starts_with("SHA256:"));
+            assert_eq!(prompt.port, 2222);
+            if let Some(v) = responder.upgrade() {
+                v.respond(&prompt.prompt_id, true);
+            }
+        });
+
+        let key = ed25519();
+        verifier.verify("example.com", 2222, &key).await.unwrap();
+        assert_eq!(
+            verifier.check("example.com", 2222, &key).unwrap(),
+            HostKeyStatus::Trusted
+        );
+        let content = std::fs::read_to_string(path).unwrap();
+        assert!(content.contains("[example.com]:2222 ssh-ed25519 "));
+    }
+
+    #[tokio::test]
+    async fn rejected_prompt_refuses_the_connection() {
+        let dir = TempDir::new().unwrap();
+        let verifier = Arc::new(HostKeyVerifier::new(Some(dir.path().join("known_hosts"))));
+        let responder = Arc::downgrade(&verifier);
+        verifier.set_prompt_handler(move |prompt| {
+            if let Some(v) = responder.upgrade() {
+                v.respond(&prompt.prompt_id, false);
+            }
+        });
+        let err = verifier
+            .verify("example.com", 22, &ed25519())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not trusted"), "{err}");
+        assert!(!dir.path().join("known_hosts").exists());
+    }
+
+    #[tokio::test]
+    async fn changed_key_is_a_hard_failure() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("known_hosts");
+        std::fs::write(&path, line("example.com", &ed25519())).unwrap();
+        let verifier = HostKeyVerifier::new(Some(path));
+        let err = verifier
+            .verify("example.com", 22, &ed25519())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("HAS CHANGED"), "{err}");
+    }
+
+    #[test]
+    fn respond_to_unknown_prompt_returns_false() {
+        let verifier = HostKeyVerifier::new(None);
+        assert!(!verifier.respond("host-key-42", true));
+    }
+}
@@ -1,13 +1,33 @@
+mod auth_prompt;
+mod broadcast;
+mod checksum;
 mod commands;
 mod connection_manager;
 mod desktop_protocol;
+mod dir_transfer;
 mod ftp_client;
+mod input_broadcast;
+mod known_hosts;
+mod local_shell;
 mod ls_parser;
 mod os_detect;
+mod playback;
+mod prompt;
 mod proxy;
 mod rdp_client;
+mod rdp_gfx;
+mod recording;
+mod remote_edit;
+mod remote_fs;
 mod sftp_client;
+mod shell;
 mod ssh;
+mod ssh_config;
+mod sync;
+mod telnet_client;
+mod transfer;
+mod transfer_queue;
+mod utf8;
 mod vnc_client;
 mod websocket_server;
 
@@ -274,6 +294,50 @@ pub fn run() {
                     }
                 }
 
+                // Relay unknown host-key prompts to the frontend; the answer comes
+                // back through `ssh_respond_host_key`.
+                let app_handle = app.handle().clone();
+                connection_manager_clone
+                    .host_key_verifier()
+                    .set_prompt_handler(move |prompt| {
+                        let _ = app_handle.emit("ssh-host-key-prompt", prompt);
+                    });
+
+                // Keyboard-interactive prompts (PAM, OTP) are answered through
+                // `ssh_respond_auth_prompt`.
+                let app_handle = app.handle().clone();
+                connection_manager_clone
+                    .auth_prompter()
+                    .set_prompt_handler(move |prompt| {
+                        let _ = app_handle.emit("ssh-auth-prompt", prompt);
+                    });
+
+                // Reconnect progress after a dropped transport.
+                let app_handle = app.handle().clone();
+                connection_manager_clone.set_status_handler(move |status| {
+                    let _ = app_handle.emit("ssh-connection-status", status);
+                });
+                let app_handle = app.handle().clone();
+                connection_manager_clone.set_exec_handler(move |event| {
+                    let _ = app_handle.emit("ssh-exec-event", event);
+                });
+                let app_handle = app.handle().clone();
+                connection_manager_clone.set_broadcast_handler(move |event| {
+                    let _ = app_handle.emit("ssh-broadcast-event", event);
+                });
+                let app_handle = app.handle().clone();
+                connection_manager_clone.set_transfer_handler(move |progress| {
+                    let _ = app_handle.emit("transfer-progress", progress);
+                });
+                let app_handle = app.handle().clone();
+                connection_manager_clone.set_queue_handler(move |event| {
+                    let _ = app_handle.emit("transfer-queue-event", event);
+                });
+                let app_handle = app.handle().clone();
+                connection_manager_clone.set_remote_edit_handler(move |event| {
+                    let _ = app_handle.emit("remote-edit-event", event);
+                });
+
                 // Start WebSocket server for terminal I/O
                 // Try ports 9001-9010 to avoid conflicts with other instances
                 let ws_server = Arc::new(WebSocketServer::new(connection_manager_clone));
@@ -294,6 +358,8 @@ pub fn run() {
             commands::ssh_connect,
             commands::ssh_cancel_connect,
             commands::ssh_disconnect,
+            commands::ssh_respond_host_key,
+            commands::ssh_respond_auth_prompt,
             commands::ssh_execute_command,
             commands::ssh_tab_complete,
             commands::get_system_stats,
@@ -323,6 +389,28 @@ pub fn run() {
             commands::detect_gpu,
             commands::get_gpu_stats,
             commands::get_websocket_port,
+            // SSH config commands
+            commands::ssh_config_list_hosts,
+            commands::ssh_config_resolve,
+            // Port forwarding commands
+            commands::ssh_add_port_forward,
+            commands::ssh_list_port_forwards,
+            commands::ssh_remove_port_forward,
+            // Session recording commands
+            commands::start_recording,
+            commands::stop_recording,
+            commands::list_recordings,
+            // Local terminal commands
+            commands::local_connect,
+            commands::local_disconnect,
+            // Command execution commands
+            commands::ssh_exec,
+            commands::ssh_exec_stream,
+            commands::ssh_exec_cancel,
+            commands::ssh_broadcast_exec,
+            // Telnet commands
+            commands::telnet_connect,
+            commands::telnet_disconnect,
             // Standalone SFTP/FTP commands
             commands::sftp_connect,
             commands::sftp_standalone_disconnect,
@@ -333,9 +421,30 @@ pub fn run() {
             commands::download_remote_file,
             commands::download_remote_file_confined,
             commands::upload_remote_file,
+            commands::download_remote_directory,
+            commands::upload_remote_directory,
+            commands::cancel_transfer,
+            commands::checksum_remote_file,
             commands::delete_remote_item,
             commands::create_remote_directory,
             commands::rename_remote_item,
+            commands::chmod_remote_item,
+            commands::chown_remote_item,
+            commands::set_remote_item_times,
+            commands::create_remote_symlink,
+            commands::read_remote_link,
+            commands::stat_remote_item,
+            // Transfer queue commands
+            commands::transfer_queue_add,
+            commands::transfer_queue_list,
+            commands::transfer_queue_pause,
+            commands::transfer_queue_resume,
+            commands::transfer_queue_retry,
+            commands::transfer_queue_cancel,
+            commands::transfer_queue_move,
+            commands::transfer_queue_remove,
+            commands::transfer_queue_get_limits,
+            commands::transfer_queue_set_limits,
             // Local filesystem commands
             commands::list_local_files,
             commands::get_home_directory,
@@ -345,9 +454,17 @@ pub fn run() {
             commands::create_local_directory_confined,
             commands::open_in_os,
             commands::stat_local_path,
+            commands::checksum_local_file,
             // Directory synchronization commands
             commands::list_local_files_recursive,
             commands::list_remote_files_recursive,
+            commands::sync_plan,
+            commands::sync_apply,
+            // Remote editing commands
+            commands::edit_remote_file,
+            commands::save_remote_edit,
+            commands::close_remote_edit,
+            commands::list_remote_edits,
             // Desktop (RDP/VNC) commands
             commands::desktop_connect,
             commands::desktop_disconnect,
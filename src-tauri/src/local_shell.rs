@@ -0,0 +1,191 @@
+//! Local terminal tabs: the user's login shell in a local pseudo-terminal,
+//! exposed as a `PtySession` so it shares the SSH terminal's WebSocket path.
+
+use crate::ssh::PtySession;
+use anyhow::{anyhow, Result};
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+/// What to run in a local terminal.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LocalShellConfig {
+    /// Program to run instead of the user's login shell.
+    #[serde(default)]
+    pub shell: Option<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Starting directory; defaults to the home directory.
+    #[serde(default)]
+    pub cwd: Option<String>,
+}
+
+impl LocalShellConfig {
+    fn command(&self) -> CommandBuilder {
+        let mut command = match &self.shell {
+            Some(shell) => {
+                let mut command = CommandBuilder::new(crate::ssh::expand_tilde(shell));
+                command.args(&self.args);
+                command
+            }
+            // $SHELL (or the passwd entry) started as a login shell; cmd.exe
+            // / %ComSpec% on Windows.
+            None => CommandBuilder::new_default_prog(),
+        };
+        let cwd = self
+            .cwd
+            .as_deref()
+            .map(|dir| crate::ssh::expand_tilde(dir).into())
+            .or_else(dirs::home_dir);
+        if let Some(cwd) = cwd {
+            command.cwd(cwd);
+        }
+        command.env("TERM", "xterm-256color");
+        command.env("COLORTERM", "truecolor");
+        command
+    }
+}
+
+/// Start `config` in a new pseudo-terminal of `cols`x`rows`. The process is
+/// killed when the session is cancelled or dropped.
+pub fn spawn_local_shell(config: &LocalShellConfig, cols: u32, rows: u32) -> Result<PtySession> {
+    let pair = native_pty_system()
+        .openpty(pty_size(cols, rows))
+        .map_err(|e| anyhow!("Failed to open a pseudo-terminal: {}", e))?;
+    let mut child = pair
+        .slave
+        .spawn_command(config.command())
+        .map_err(|e| anyhow!("Failed to start local shell: {}", e))?;
+    // Only the child keeps the slave side open, so reads end when it exits.
+    drop(pair.slave);
+    let master = pair.master;
+    let mut reader = master.try_clone_reader()?;
+    let mut writer = master.take_writer()?;
+
+    let (input_tx, mut input_rx) = mpsc::channel::<Vec<u8>>(1000);
+    let (output_tx, output_rx) = mpsc::channel::<Vec<u8>>(128);
+    let (resize_tx, mut resize_rx) = mpsc::channel::<(u32, u32)>(16);
+    let cancel = CancellationToken::new();
+
+    // PTY reads and writes block, so they get their own threads. A full
+    // output channel blocks the reader, which stops the shell's output.
+    std::thread::spawn(move || {
+        let mut buf = [0u8; 16 * 1024];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    if output_tx.blocking_send(buf[..n].to_vec()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        tracing::info!("Local shell output closed");
+    });
+    std::thread::spawn(move || {
+        while let Some(data) = input_rx.blocking_recv() {
+            if writer
+                .write_all(&data)
+                .and_then(|_| writer.flush())
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    let session_cancel = cancel.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = session_cancel.cancelled() => break,
+                resize = resize_rx.recv() => match resize {
+                    Some((cols, rows)) => {
+                        if let Err(e) = master.resize(pty_size(cols, rows)) {
+                            tracing::warn!("Failed to resize local terminal: {}", e);
+                        }
+                    }
+                    // Session dropped.
+                    None => break,
+                },
+            }
+        }
+        let _ = child.kill();
+        drop(master);
+        let _ = tokio::task::spawn_blocking(move || child.wait()).await;
+    });
+
+    Ok(PtySession {
+        input_tx,
+        output_rx: Arc::new(tokio::sync::Mutex::new(output_rx)),
+        channel_id: None,
+        resize_tx,
+        cancel,
+    })
+}
+
+fn pty_size(cols: u32, rows: u32) -> PtySize {
+    PtySize {
+        rows: rows.clamp(1, u16::MAX as u32) as u16,
+        cols: cols.clamp(1, u16::MAX as u32) as u16,
+        pixel_width: 0,
+        pixel_height: 0,
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    async fn read_until(session: &PtySession, needle: &str) -> String {
+        let mut seen = Vec::new();
+        let mut rx = session.output_rx.lock().await;
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+        while !String::from_utf8_lossy(&seen).contains(needle) {
+            match tokio::time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(data)) => seen.extend(data),
+                _ => break,
+            }
+        }
+        String::from_utf8_lossy(&seen).into_owned()
+    }
+
+    #[tokio::test]
+    async fn runs_command_and_reports_resize() {
+        let config = LocalShellConfig {
+            shell: Some("/bin/sh".into()),
+            args: Vec::new(),
+            cwd: Some("/".into()),
+        };
+        let session = spawn_local_shell(&config, 100, 30).unwrap();
+        session
+            .input_tx
+            .send(b"echo \"$TERM:$(pwd):$(stty size)\"\n".to_vec())
+            .await
+            .unwrap();
+        let output = read_until(&session, "xterm-256color:/:30 100").await;
+        assert!(output.contains("xterm-256color:/:30 100"), "{output}");
+
+        session.resize_tx.send((120, 40)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        session
+            .input_tx
+            .send(b"stty size\n".to_vec())
+            .await
+            .unwrap();
+        let output = read_until(&session, "40 120").await;
+        assert!(output.contains("40 120"), "{output}");
+
+        session.input_tx.send(b"exit\n".to_vec()).await.unwrap();
+        let closed = tokio::time::timeout(Duration::from_secs(10), async {
+            while session.output_rx.lock().await.recv().await.is_some() {}
+        })
+        .await;
+        assert!(closed.is_ok(), "output should end when the shell exits");
+    }
+}
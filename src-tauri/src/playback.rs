@@ -0,0 +1,594 @@
+//! Replay of recorded terminal sessions — asciicast v2 files and
+//! `script`/`scriptreplay` typescripts with their timing file — as terminal
+//! output, so the regular xterm view can play them back.
+
+use crate::recording::AsciicastHeader;
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+/// Sent before replaying from the start after a seek (RIS: full reset).
+const TERMINAL_RESET: &[u8] = b"\x1bc";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TerminalEvent {
+    Output(Vec<u8>),
+    Resize { cols: u32, rows: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimedEvent {
+    /// Seconds since the start of the recording.
+    pub time: f64,
+    pub event: TerminalEvent,
+}
+
+/// A recording loaded into memory, events in time order.
+#[derive(Debug, Clone)]
+pub struct Timeline {
+    pub width: u32,
+    pub height: u32,
+    /// Idle limit suggested by the recording itself (asciicast header).
+    pub idle_time_limit: Option<f64>,
+    events: Vec<TimedEvent>,
+}
+
+impl Timeline {
+    /// Load `path` as asciicast v2, or as a `script` typescript when a
+    /// `timing_path` is given.
+    pub fn load(path: &Path, timing_path: Option<&Path>) -> Result<Self> {
+        match timing_path {
+            Some(timing_path) => {
+                let timing = std::fs::read_to_string(timing_path)
+                    .with_context(|| format!("Failed to read {}", timing_path.display()))?;
+                let typescript = std::fs::read(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                Self::from_script(&timing, &typescript)
+            }
+            None => {
+                let content = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                Self::from_asciicast(&content)
+            }
+        }
+    }
+
+    pub fn from_asciicast(content: &str) -> Result<Self> {
+        let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+        let header: AsciicastHeader = lines
+            .next()
+            .ok_or_else(|| anyhow!("Recording is empty"))
+            .and_then(|line| {
+                serde_json::from_str(line).map_err(|e| anyhow!("Not an asciicast file: {}", e))
+            })?;
+        if header.version != 2 {
+            bail!("Unsupported asciicast version {}", header.version);
+        }
+
+        let mut events = Vec::new();
+        for (number, line) in lines.enumerate() {
+            let (time, code, data): (f64, String, String) = serde_json::from_str(line)
+                .map_err(|e| anyhow!("Invalid event on line {}: {}", number + 2, e))?;
+            let event = match code.as_str() {
+                "o" => TerminalEvent::Output(data.into_bytes()),
+                "r" => match parse_size(&data) {
+                    Some((cols, rows)) => TerminalEvent::Resize { cols, rows },
+                    None => continue,
+                },
+                // Input ("i") and markers ("m") are not shown.
+                _ => continue,
+            };
+            events.push(TimedEvent { time, event });
+        }
+        Ok(Self::new(
+            header.width,
+            header.height,
+            header.idle_time_limit,
+            events,
+        ))
+    }
+
+    /// Parse a typescript and its timing file, in either the classic
+    /// `<delay> <bytes>` format or `script --log-timing`'s advanced
+    /// `<type> <delay> <data>` format.
+    pub fn from_script(timing: &str, typescript: &[u8]) -> Result<Self> {
+        let mut output = skip_script_header(typescript);
+        let (mut width, mut height) = (80, 24);
+        let mut time = 0.0;
+        let mut events = Vec::new();
+
+        for (number, line) in timing.lines().enumerate() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.is_empty() {
+                continue;
+            }
+            let invalid = || anyhow!("Invalid timing entry on line {}: {:?}", number + 1, line);
+            let (kind, delay, rest) = match fields[0].parse::<f64>() {
+                Ok(delay) => ("O", delay, &fields[1..]),
+                Err(_) if fields.len() >= 2 => (
+                    fields[0],
+                    fields[1].parse::<f64>().map_err(|_| invalid())?,
+                    &fields[2..],
+                ),
+                Err(_) => return Err(invalid()),
+            };
+            time += delay.max(0.0);
+
+            match kind {
+                "O" => {
+                    let count: usize = rest
+                        .first()
+                        .and_then(|n| n.parse().ok())
+                        .ok_or_else(invalid)?;
+                    let count = count.min(output.len());
+                    let (chunk, remaining) = output.split_at(count);
+                    output = remaining;
+                    events.push(TimedEvent {
+                        time,
+                        event: TerminalEvent::Output(chunk.to_vec()),
+                    });
+                }
+                "H" => match rest {
+                    ["COLUMNS", value, ..] => width = value.parse().unwrap_or(width),
+                    ["LINES", value, ..] => height = value.parse().unwrap_or(height),
+                    _ => {}
+                },
+                "S" if rest.first() == Some(&"SIGWINCH") => {
+                    let value = |key: &str| {
+                        rest.iter()
+                            .find_map(|field| field.strip_prefix(key))
+                            .and_then(|v| v.parse().ok())
+                    };
+                    if let (Some(cols), Some(rows)) = (value("COLS="), value("ROWS=")) {
+                        events.push(TimedEvent {
+                            time,
+                            event: TerminalEvent::Resize { cols, rows },
+                        });
+                    }
+                }
+                // Input lives in a separate log; it only advances the clock.
+                _ => {}
+            }
+        }
+        Ok(Self::new(width, height, None, events))
+    }
+
+    fn new(
+        width: u32,
+        height: u32,
+        idle_time_limit: Option<f64>,
+        mut events: Vec<TimedEvent>,
+    ) -> Self {
+        events.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self {
+            width,
+            height,
+            idle_time_limit,
+            events,
+        }
+    }
+
+    /// Shorten every gap between events to at most `limit` seconds.
+    pub fn compress_idle(&mut self, limit: f64) {
+        if limit <= 0.0 {
+            return;
+        }
+        let mut previous = 0.0;
+        let mut shift = 0.0;
+        for event in &mut self.events {
+            let gap = event.time - previous;
+            previous = event.time;
+            if gap > limit {
+                shift += gap - limit;
+            }
+            event.time -= shift;
+        }
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.events.last().map(|e| e.time).unwrap_or(0.0)
+    }
+
+    /// Index of the first event after `position`.
+    fn index_after(&self, position: f64) -> usize {
+        self.events.partition_point(|e| e.time <= position)
+    }
+
+    /// Everything shown up to `position`: the concatenated output and the
+    /// last terminal size.
+    fn state_at(&self, position: f64) -> (Vec<u8>, (u32, u32)) {
+        let mut output = TERMINAL_RESET.to_vec();
+        let mut size = (self.width, self.height);
+        for event in &self.events[..self.index_after(position)] {
+            match &event.event {
+                TerminalEvent::Output(data) => output.extend_from_slice(data),
+                TerminalEvent::Resize { cols, rows } => size = (*cols, *rows),
+            }
+        }
+        (output, size)
+    }
+}
+
+fn parse_size(value: &str) -> Option<(u32, u32)> {
+    let (cols, rows) = value.split_once('x')?;
+    Some((cols.trim().parse().ok()?, rows.trim().parse().ok()?))
+}
+
+/// `script` starts its typescript with a "Script started on ..." line that
+/// is not part of the session.
+fn skip_script_header(typescript: &[u8]) -> &[u8] {
+    if typescript.starts_with(b"Script started") {
+        match typescript.iter().position(|&b| b == b'\n') {
+            Some(end) => &typescript[end + 1..],
+            None => &[],
+        }
+    } else {
+        typescript
+    }
+}
+
+/// Control messages for a running player.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action")]
+pub enum PlaybackCommand {
+    Play,
+    Pause,
+    /// Jump to `position` seconds (after idle compression).
+    Seek {
+        position: f64,
+    },
+    Speed {
+        speed: f64,
+    },
+    Stop,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlaybackStatus {
+    pub position: f64,
+    pub duration: f64,
+    pub speed: f64,
+    pub paused: bool,
+    pub finished: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlayerEvent {
+    Output(Vec<u8>),
+    Resize { cols: u32, rows: u32 },
+    Status(PlaybackStatus),
+}
+
+/// Handle to a running player.
+#[derive(Clone)]
+pub struct PlaybackHandle {
+    commands: mpsc::Sender<PlaybackCommand>,
+    /// Cancelled when playback stops; the task forwarding its output should
+    /// select on this.
+    pub cancel: CancellationToken,
+}
+
+impl PlaybackHandle {
+    pub async fn send(&self, command: PlaybackCommand) -> Result<()> {
+        if matches!(command, PlaybackCommand::Stop) {
+            self.cancel.cancel();
+        }
+        self.commands
+            .send(command)
+            .await
+            .map_err(|_| anyhow!("Playback has ended"))
+    }
+
+    pub fn same_player(&self, other: &PlaybackHandle) -> bool {
+        self.commands.same_channel(&other.commands)
+    }
+}
+
+/// Start playing `timeline` at `speed`. Events arrive on the returned
+/// receiver; the player stops when it is dropped or on `Stop`.
+pub fn spawn_player(
+    timeline: Timeline,
+    speed: f64,
+) -> (PlaybackHandle, mpsc::Receiver<PlayerEvent>) {
+    let (command_tx, command_rx) = mpsc::channel(16);
+    let (event_tx, event_rx) = mpsc::channel(64);
+    let speed = if speed > 0.0 && speed.is_finite() {
+        speed
+    } else {
+        1.0
+    };
+    tokio::spawn(run_player(timeline, speed, command_rx, event_tx));
+    (
+        PlaybackHandle {
+            commands: command_tx,
+            cancel: CancellationToken::new(),
+        },
+        event_rx,
+    )
+}
+
+async fn run_player(
+    timeline: Timeline,
+    mut speed: f64,
+    mut commands: mpsc::Receiver<PlaybackCommand>,
+    events: mpsc::Sender<PlayerEvent>,
+) {
+    let duration = timeline.duration();
+    let mut position = 0.0;
+    let mut index = 0;
+    let mut paused = false;
+
+    'player: loop {
+        let finished = index >= timeline.events.len();
+        let status = PlayerEvent::Status(PlaybackStatus {
+            position,
+            duration,
+            speed,
+            paused,
+            finished,
+        });
+        if events.send(status).await.is_err() {
+            return;
+        }
+
+        // Play events until a command arrives.
+        let command = loop {
+            if paused || index >= timeline.events.len() {
+                tokio::select! {
+                    command = commands.recv() => break command,
+                    _ = events.closed() => return,
+                }
+            }
+            let next = timeline.events[index].time;
+            // A tiny speed can stretch the wait past what a `Duration` holds.
+            let wait = Duration::try_from_secs_f64(((next - position) / speed).max(0.0))
+                .unwrap_or(Duration::MAX);
+            let started = Instant::now();
+            tokio::select! {
+                _ = tokio::time::sleep(wait) => {
+                    position = next;
+                    if !emit_until(&timeline, &mut index, next, &events).await {
+                        return;
+                    }
+                    if index >= timeline.events.len() {
+                        // Report the end, then wait for a command.
+                        continue 'player;
+                    }
+                }
+                command = commands.recv() => {
+                    position = (position + started.elapsed().as_secs_f64() * speed).min(next);
+                    break command;
+                }
+            }
+        };
+
+        let Some(command) = command else {
+            return;
+        };
+        match command {
+            PlaybackCommand::Stop => return,
+            PlaybackCommand::Play => {
+                if index >= timeline.events.len() {
+                    position = 0.0;
+                    index = 0;
+                    if events
+                        .send(PlayerEvent::Output(TERMINAL_RESET.to_vec()))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+                paused = false;
+            }
+            PlaybackCommand::Pause => paused = true,
+            PlaybackCommand::Speed { speed: new_speed } => {
+                if new_speed > 0.0 && new_speed.is_finite() {
+                    speed = new_speed;
+                }
+            }
+            PlaybackCommand::Seek { position: target } => {
+                position = target.clamp(0.0, duration);
+                index = timeline.index_after(position);
+                let (output, (cols, rows)) = timeline.state_at(position);
+                if events
+                    .send(PlayerEvent::Resize { cols, rows })
+                    .await
+                    .is_err()
+                    || events.send(PlayerEvent::Output(output)).await.is_err()
+                {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Send every event up to `time`, merging adjacent output into one chunk.
+async fn emit_until(
+    timeline: &Timeline,
+    index: &mut usize,
+    time: f64,
+    events: &mpsc::Sender<PlayerEvent>,
+) -> bool {
+    let mut output = Vec::new();
+    while let Some(event) = timeline.events.get(*index).filter(|e| e.time <= time) {
+        *index += 1;
+        match &event.event {
+            TerminalEvent::Output(data) => output.extend_from_slice(data),
+            TerminalEvent::Resize { cols, rows } => {
+                if !output.is_empty()
+                    && events
+                        .send(PlayerEvent::Output(std::mem::take(&mut output)))
+                        .await
+                        .is_err()
+                {
+                    return false;
+                }
+                let resize = PlayerEvent::Resize {
+                    cols: *cols,
+                    rows: *rows,
+                };
+                if events.send(resize).await.is_err() {
+                    return false;
+                }
+            }
+        }
+    }
+    output.is_empty() || events.send(PlayerEvent::Output(output)).await.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAST: &str = r#"{"version": 2, "width": 80, "height": 24, "idle_time_limit": 2.0}
+[0.5, "o", "$ "]
+[1.0, "i", "ls\r"]
+[1.2, "o", "ls\r\n"]
+[10.0, "r", "100x30"]
+[10.5, "o", "done\r\n"]
+"#;
+
+    fn output(event: &TimedEvent) -> &[u8] {
+        match &event.event {
+            TerminalEvent::Output(data) => data,
+            other => panic!("expected output, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parses_asciicast_output_and_resizes() {
+        let timeline = Timeline::from_asciicast(CAST).unwrap();
+        assert_eq!((timeline.width, timeline.height), (80, 24));
+        assert_eq!(timeline.idle_time_limit, Some(2.0));
+        assert_eq!(timeline.events.len(), 4);
+        assert_eq!(output(&timeline.events[1]), b"ls\r\n");
+        assert_eq!(
+            timeline.events[2].event,
+            TerminalEvent::Resize {
+                cols: 100,
+                rows: 30
+            }
+        );
+        assert_eq!(timeline.duration(), 10.5);
+
+        assert!(Timeline::from_asciicast(r#"{"version": 1, "width": 80, "height": 24}"#).is_err());
+    }
+
+    #[test]
+    fn compresses_idle_gaps() {
+        let mut timeline = Timeline::from_asciicast(CAST).unwrap();
+        timeline.compress_idle(2.0);
+        let millis: Vec<i64> = timeline
+            .events
+            .iter()
+            .map(|e| (e.time * 1000.0).round() as i64)
+            .collect();
+        assert_eq!(millis, [500, 1200, 3200, 3700]);
+    }
+
+    #[test]
+    fn state_at_replays_output_and_size() {
+        let timeline = Timeline::from_asciicast(CAST).unwrap();
+        let (shown, size) = timeline.state_at(1.2);
+        assert_eq!(shown, b"\x1bc$ ls\r\n");
+        assert_eq!(size, (80, 24));
+        assert_eq!(timeline.state_at(20.0).1, (100, 30));
+        assert_eq!(timeline.index_after(1.2), 2);
+    }
+
+    #[test]
+    fn parses_classic_script_timing() {
+        let typescript = b"Script started on 2024-01-01 10:00:00\nhello world";
+        let timeline = Timeline::from_script("0.5 6\n1.25 5\n", typescript).unwrap();
+        assert_eq!(timeline.events.len(), 2);
+        assert_eq!(output(&timeline.events[0]), b"hello ");
+        assert_eq!(output(&timeline.events[1]), b"world");
+        assert_eq!(timeline.duration(), 1.75);
+    }
+
+    #[test]
+    fn parses_advanced_script_timing() {
+        let timing = "H 0.000000 COLUMNS 120\nH 0.000000 LINES 40\n\
+                      O 0.100000 3\nI 0.500000 1\nS 0.250000 SIGWINCH ROWS=50 COLS=132\n\
+                      O 0.150000 2\n";
+        let timeline = Timeline::from_script(timing, b"abcde").unwrap();
+        assert_eq!((timeline.width, timeline.height), (120, 40));
+        assert_eq!(output(&timeline.events[0]), b"abc");
+        assert_eq!(
+            timeline.events[1],
+            TimedEvent {
+                time: 0.85,
+                event: TerminalEvent::Resize {
+                    cols: 132,
+                    rows: 50
+                }
+            }
+        );
+        assert_eq!(output(&timeline.events[2]), b"de");
+        assert!(Timeline::from_script("O nope 3", b"abc").is_err());
+    }
+
+    async fn next_status(events: &mut mpsc::Receiver<PlayerEvent>) -> PlaybackStatus {
+        loop {
+            if let PlayerEvent::Status(status) = events.recv().await.unwrap() {
+                return status;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn player_plays_to_the_end_and_seeks() {
+        let timeline = Timeline::from_asciicast(CAST).unwrap();
+        let (handle, mut events) = spawn_player(timeline, 1000.0);
+
+        let mut played = Vec::new();
+        let finished = loop {
+            match events.recv().await.unwrap() {
+                PlayerEvent::Output(data) => played.extend(data),
+                PlayerEvent::Status(status) if status.finished => break status,
+                _ => {}
+            }
+        };
+        assert_eq!(played, b"$ ls\r\ndone\r\n");
+        assert_eq!(finished.position, 10.5);
+
+        handle.send(PlaybackCommand::Pause).await.unwrap();
+        assert!(next_status(&mut events).await.paused);
+        handle
+            .send(PlaybackCommand::Seek { position: 1.0 })
+            .await
+            .unwrap();
+        assert_eq!(
+            events.recv().await.unwrap(),
+            PlayerEvent::Resize { cols: 80, rows: 24 }
+        );
+        assert_eq!(
+            events.recv().await.unwrap(),
+            PlayerEvent::Output(b"\x1bc$ ".to_vec())
+        );
+        let status = next_status(&mut events).await;
+        assert_eq!((status.position, status.paused), (1.0, true));
+
+        handle.send(PlaybackCommand::Stop).await.unwrap();
+        assert!(handle.cancel.is_cancelled());
+        assert!(events.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn player_survives_a_vanishingly_small_speed() {
+        let timeline = Timeline::from_asciicast(CAST).unwrap();
+        let (handle, mut events) = spawn_player(timeline, 1e-300);
+        assert_eq!(next_status(&mut events).await.speed, 1e-300);
+
+        handle.send(PlaybackCommand::Pause).await.unwrap();
+        let status = next_status(&mut events).await;
+        assert!(status.paused && !status.finished);
+        handle.send(PlaybackCommand::Stop).await.unwrap();
+    }
+}
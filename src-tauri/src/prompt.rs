@@ -0,0 +1,118 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+type PromptHandler<P> = Arc<dyn Fn(P) + Send + Sync>;
+
+/// Why a prompt ended without an answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unanswered {
+    /// No handler is installed, so the question could not be shown.
+    NoHandler,
+    /// The prompt was dropped without a reply.
+    Cancelled,
+    TimedOut,
+}
+
+/// Questions of one kind shown to the user through an installed handler,
+/// each waiting for [`PendingPrompts::respond`] with the matching ID.
+///
+/// Used by the host key verifier and the keyboard-interactive prompter; each
+/// wraps the outcome in its own error messages.
+pub struct PendingPrompts<P, A> {
+    id_prefix: &'static str,
+    timeout: Duration,
+    handler: RwLock<Option<PromptHandler<P>>>,
+    pending: Mutex<HashMap<String, oneshot::Sender<A>>>,
+    next_id: AtomicU64,
+}
+
+impl<P, A> PendingPrompts<P, A> {
+    /// Prompt IDs are `<id_prefix>-<n>`.
+    pub fn new(id_prefix: &'static str, timeout: Duration) -> Self {
+        Self {
+            id_prefix,
+            timeout,
+            handler: RwLock::new(None),
+            pending: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Install the callback used to show prompts to the user.
+    pub fn set_handler(&self, handler: impl Fn(P) + Send + Sync + 'static) {
+        if let Ok(mut slot) = self.handler.write() {
+            *slot = Some(Arc::new(handler));
+        }
+    }
+
+    /// Show the prompt `build` makes for a fresh ID and wait for the answer.
+    pub async fn ask(&self, build: impl FnOnce(String) -> P) -> Result<A, Unanswered> {
+        let handler = self
+            .handler
+            .read()
+            .ok()
+            .and_then(|slot| slot.clone())
+            .ok_or(Unanswered::NoHandler)?;
+
+        let prompt_id = format!(
+            "{}-{}",
+            self.id_prefix,
+            self.next_id.fetch_add(1, Ordering::Relaxed)
+        );
+        let (tx, rx) = oneshot::channel();
+        self.pending_map().insert(prompt_id.clone(), tx);
+
+        handler(build(prompt_id.clone()));
+
+        let answer = tokio::time::timeout(self.timeout, rx).await;
+        self.pending_map().remove(&prompt_id);
+        match answer {
+            Ok(Ok(answer)) => Ok(answer),
+            Ok(Err(_)) => Err(Unanswered::Cancelled),
+            Err(_) => Err(Unanswered::TimedOut),
+        }
+    }
+
+    /// Deliver the user's answer to a pending prompt. Returns `false` when no
+    /// prompt with that ID is waiting (already answered or timed out).
+    pub fn respond(&self, prompt_id: &str, answer: A) -> bool {
+        match self.pending_map().remove(prompt_id) {
+            Some(tx) => tx.send(answer).is_ok(),
+            None => false,
+        }
+    }
+
+    fn pending_map(&self) -> std::sync::MutexGuard<'_, HashMap<String, oneshot::Sender<A>>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn prompts_time_out_and_ignore_late_answers() {
+        let prompts = Arc::new(PendingPrompts::<String, bool>::new(
+            "test",
+            Duration::from_millis(20),
+        ));
+        assert_eq!(
+            prompts.ask(|id| id).await.unwrap_err(),
+            Unanswered::NoHandler
+        );
+
+        let shown = Arc::new(Mutex::new(Vec::new()));
+        let sink = shown.clone();
+        prompts.set_handler(move |id| sink.lock().unwrap().push(id));
+        assert_eq!(
+            prompts.ask(|id| id).await.unwrap_err(),
+            Unanswered::TimedOut
+        );
+        assert_eq!(*shown.lock().unwrap(), vec!["test-1".to_string()]);
+        assert!(!prompts.respond("test-1", true));
+    }
+}
@@ -3,10 +3,17 @@ use base64::Engine as _;
 use serde::{Deserialize, Serialize};
 use std::io;
 use std::pin::Pin;
+use std::process::Stdio;
 use std::task::{Context, Poll};
 use std::time::Duration;
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
 use tokio::net::TcpStream;
+use tokio::process::{Child, ChildStderr, ChildStdin, ChildStdout};
+
+/// Handshake budget when tunnelling through `ProxyType::Command`. Tools such
+/// as `cloudflared access ssh` or `aws ssm start-session` take a few seconds
+/// before the server's banner comes through.
+pub const PROXY_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// Proxy protocol used to tunnel the SSH connection.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -14,6 +21,9 @@ pub enum ProxyType {
     Http,
     Socks4,
     Socks5,
+    /// Run a local command (OpenSSH `ProxyCommand`) and speak SSH over its
+    /// stdin/stdout.
+    Command,
 }
 
 /// Proxy server configuration applied when establishing the SSH connection.
@@ -24,6 +34,21 @@ pub struct ProxyConfig {
     pub port: u16,
     pub username: Option<String>,
     pub password: Option<String>,
+    /// Command line for `ProxyType::Command`. `%h`, `%p` and `%r` are
+    /// replaced by the target host, port and remote user; `%%` is a literal %.
+    #[serde(default)]
+    pub command: Option<String>,
+}
+
+impl ProxyConfig {
+    /// How long the SSH handshake through this proxy may take, given the
+    /// timeout used for direct connections.
+    pub fn handshake_timeout(&self, timeout: Duration) -> Duration {
+        match self.proxy_type {
+            ProxyType::Command => timeout.max(PROXY_COMMAND_TIMEOUT),
+            _ => timeout,
+        }
+    }
 }
 
 /// A TCP stream that may already hold bytes read past the proxy handshake.
@@ -32,10 +57,22 @@ pub struct ProxyConfig {
 /// server's SSH banner arriving early). Those bytes are parked in `pending` and
 /// replayed on the first read so russh sees the full stream.
 pub struct Tunnel {
-    stream: TcpStream,
+    stream: Transport,
     pending: Vec<u8>,
 }
 
+/// What a [`Tunnel`] reads from and writes to.
+enum Transport {
+    Tcp(TcpStream),
+    /// The stdin/stdout of a proxy command. The child is killed when the
+    /// tunnel is dropped.
+    Command {
+        _child: Child,
+        stdin: ChildStdin,
+        stdout: ChildStdout,
+    },
+}
+
 impl AsyncRead for Tunnel {
     fn poll_read(
         mut self: Pin<&mut Self>,
@@ -48,7 +85,10 @@ impl AsyncRead for Tunnel {
             self.pending.drain(..n);
             return Poll::Ready(Ok(()));
         }
-        Pin::new(&mut self.stream).poll_read(cx, buf)
+        match &mut self.stream {
+            Transport::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            Transport::Command { stdout, .. } => Pin::new(stdout).poll_read(cx, buf),
+        }
     }
 }
 
@@ -58,15 +98,24 @@ impl AsyncWrite for Tunnel {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<io::Result<usize>> {
-        Pin::new(&mut self.stream).poll_write(cx, buf)
+        match &mut self.stream {
+            Transport::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            Transport::Command { stdin, .. } => Pin::new(stdin).poll_write(cx, buf),
+        }
     }
 
     fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.stream).poll_flush(cx)
+        match &mut self.stream {
+            Transport::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            Transport::Command { stdin, .. } => Pin::new(stdin).poll_flush(cx),
+        }
     }
 
     fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
-        Pin::new(&mut self.stream).poll_shutdown(cx)
+        match &mut self.stream {
+            Transport::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            Transport::Command { stdin, .. } => Pin::new(stdin).poll_shutdown(cx),
+        }
     }
 }
 
@@ -74,13 +123,19 @@ impl AsyncWrite for Tunnel {
 ///
 /// The returned stream is connected to the target via the proxy's CONNECT
 /// handshake and can be handed to russh's `connect_stream` so the SSH handshake
-/// runs over the tunnel.
+/// runs over the tunnel. For `ProxyType::Command` the proxy command is started
+/// instead; `username` is substituted for `%r` in its command line.
 pub async fn connect_via_proxy(
     proxy: &ProxyConfig,
     host: &str,
     port: u16,
+    username: &str,
     timeout: Duration,
 ) -> Result<Tunnel> {
+    if proxy.proxy_type == ProxyType::Command {
+        return spawn_proxy_command(proxy, host, port, username);
+    }
+
     let mut stream = tokio::time::timeout(
         timeout,
         TcpStream::connect((proxy.host.as_str(), proxy.port)),
@@ -130,6 +185,7 @@ pub async fn connect_via_proxy(
                 .await?;
                 Ok(Vec::new())
             }
+            ProxyType::Command => unreachable!("handled above"),
         }
     })
     .await
@@ -140,7 +196,120 @@ pub async fn connect_via_proxy(
         )
     })??;
 
-    Ok(Tunnel { stream, pending })
+    Ok(Tunnel {
+        stream: Transport::Tcp(stream),
+        pending,
+    })
+}
+
+/// Whether `value` can be pasted into a shell command line without changing
+/// how it is parsed. `sh` and `cmd` quote differently, so host and user names
+/// outside this set are refused rather than quoted, as OpenSSH does.
+fn is_shell_safe(value: &str) -> bool {
+    value.chars().all(|c| {
+        c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | ':' | '@' | '[' | ']')
+    })
+}
+
+/// A host or user name about to be pasted into a proxy command.
+fn shell_safe<'a>(what: &str, value: &'a str) -> Result<&'a str> {
+    if !is_shell_safe(value) {
+        return Err(anyhow::anyhow!(
+            "The {} {:?} contains characters that are not allowed in a proxy command",
+            what,
+            value
+        ));
+    }
+    Ok(value)
+}
+
+/// Expand the `%h`, `%p`, `%r` and `%%` tokens of a proxy command. Host and
+/// user names are only checked when their token is used.
+fn expand_proxy_command(template: &str, host: &str, port: u16, username: &str) -> Result<String> {
+    let mut command = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            command.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('h') => command.push_str(shell_safe("host name", host)?),
+            Some('p') => command.push_str(&port.to_string()),
+            Some('r') => command.push_str(shell_safe("user name", username)?),
+            Some('%') => command.push('%'),
+            Some(other) => {
+                command.push('%');
+                command.push(other);
+            }
+            None => command.push('%'),
+        }
+    }
+    Ok(command)
+}
+
+/// Start the proxy command through the platform shell, as OpenSSH does, and
+/// wrap its stdin/stdout as the tunnel. A command that never produces the
+/// server's banner is caught by the SSH handshake timeout.
+fn spawn_proxy_command(
+    proxy: &ProxyConfig,
+    host: &str,
+    port: u16,
+    username: &str,
+) -> Result<Tunnel> {
+    let template = proxy
+        .command
+        .as_deref()
+        .filter(|c| !c.trim().is_empty())
+        .ok_or_else(|| anyhow::anyhow!("Proxy command is empty"))?;
+    let command = expand_proxy_command(template, host, port, username)?;
+
+    #[cfg(windows)]
+    let mut process = {
+        let mut process = tokio::process::Command::new("cmd");
+        process.arg("/C").arg(&command);
+        // CREATE_NO_WINDOW: don't flash a console window from the GUI app.
+        process.creation_flags(0x0800_0000);
+        process
+    };
+    #[cfg(not(windows))]
+    let mut process = {
+        let mut process = tokio::process::Command::new("/bin/sh");
+        process.arg("-c").arg(format!("exec {command}"));
+        process
+    };
+
+    let mut child = process
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| anyhow::anyhow!("Failed to start proxy command `{}`: {}", command, e))?;
+
+    let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        bail!("Proxy command `{}` has no stdin/stdout", command);
+    };
+    if let Some(stderr) = child.stderr.take() {
+        tokio::spawn(log_proxy_stderr(stderr));
+    }
+
+    Ok(Tunnel {
+        stream: Transport::Command {
+            _child: child,
+            stdin,
+            stdout,
+        },
+        pending: Vec::new(),
+    })
+}
+
+/// Forward the proxy command's diagnostics (login prompts, errors) to the log.
+async fn log_proxy_stderr(stderr: ChildStderr) {
+    let mut lines = tokio::io::BufReader::new(stderr).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        tracing::warn!("proxy command: {}", line);
+    }
 }
 
 /// Perform an HTTP CONNECT handshake so the stream is tunneled to host:port.
@@ -368,6 +537,7 @@ mod tests {
             port: 0, // filled in by each test
             username: None,
             password: None,
+            command: None,
         }
     }
 
@@ -412,7 +582,7 @@ mod tests {
         proxy.username = Some("user".to_string());
         proxy.password = Some("pass".to_string());
 
-        let result = connect_via_proxy(&proxy, "example.com", 443, TIMEOUT).await;
+        let result = connect_via_proxy(&proxy, "example.com", 443, "user", TIMEOUT).await;
         assert!(
             result.is_ok(),
             "HTTP CONNECT should succeed: {:?}",
@@ -438,7 +608,7 @@ mod tests {
         let mut proxy = test_proxy(ProxyType::Http);
         proxy.port = addr.port();
 
-        let mut tunnel = connect_via_proxy(&proxy, "example.com", 443, TIMEOUT)
+        let mut tunnel = connect_via_proxy(&proxy, "example.com", 443, "user", TIMEOUT)
             .await
             .expect("HTTP CONNECT should succeed");
         let mut banner = String::new();
@@ -469,7 +639,7 @@ mod tests {
         let mut proxy = test_proxy(ProxyType::Http);
         proxy.port = addr.port();
 
-        let err = match connect_via_proxy(&proxy, "example.com", 443, TIMEOUT).await {
+        let err = match connect_via_proxy(&proxy, "example.com", 443, "user", TIMEOUT).await {
             Ok(_) => panic!("expected a proxy failure"),
             Err(e) => e,
         };
@@ -495,7 +665,7 @@ mod tests {
         let mut proxy = test_proxy(ProxyType::Http);
         proxy.port = addr.port();
 
-        let result = connect_via_proxy(&proxy, "example.com", 443, TIMEOUT).await;
+        let result = connect_via_proxy(&proxy, "example.com", 443, "user", TIMEOUT).await;
         assert!(
             result.is_ok(),
             "HTTP CONNECT should succeed: {:?}",
@@ -520,8 +690,14 @@ mod tests {
         let mut proxy = test_proxy(ProxyType::Http);
         proxy.port = addr.port();
 
-        let err = match connect_via_proxy(&proxy, "example.com", 443, Duration::from_millis(100))
-            .await
+        let err = match connect_via_proxy(
+            &proxy,
+            "example.com",
+            443,
+            "user",
+            Duration::from_millis(100),
+        )
+        .await
         {
             Ok(_) => panic!("expected the handshake to time out"),
             Err(e) => e,
@@ -567,7 +743,7 @@ mod tests {
         let mut proxy = test_proxy(ProxyType::Socks5);
         proxy.port = addr.port();
 
-        let result = connect_via_proxy(&proxy, "example.com", 443, TIMEOUT).await;
+        let result = connect_via_proxy(&proxy, "example.com", 443, "user", TIMEOUT).await;
         assert!(result.is_ok(), "SOCKS5 should succeed: {:?}", result.err());
         server.await.unwrap();
     }
@@ -614,7 +790,7 @@ mod tests {
         proxy.username = Some("user".to_string());
         proxy.password = Some("pass".to_string());
 
-        let result = connect_via_proxy(&proxy, "example.com", 443, TIMEOUT).await;
+        let result = connect_via_proxy(&proxy, "example.com", 443, "user", TIMEOUT).await;
         assert!(
             result.is_ok(),
             "SOCKS5 auth should succeed: {:?}",
@@ -643,7 +819,7 @@ mod tests {
         let mut proxy = test_proxy(ProxyType::Socks5);
         proxy.port = addr.port();
 
-        let err = match connect_via_proxy(&proxy, "example.com", 443, TIMEOUT).await {
+        let err = match connect_via_proxy(&proxy, "example.com", 443, "user", TIMEOUT).await {
             Ok(_) => panic!("expected a proxy failure"),
             Err(e) => e,
         };
@@ -678,7 +854,7 @@ mod tests {
         proxy.port = addr.port();
 
         // Use a literal IPv4 host so lookup_host resolves without DNS.
-        let result = connect_via_proxy(&proxy, "127.0.0.1", 22, TIMEOUT).await;
+        let result = connect_via_proxy(&proxy, "127.0.0.1", 22, "user", TIMEOUT).await;
         assert!(result.is_ok(), "SOCKS4 should succeed: {:?}", result.err());
         server.await.unwrap();
     }
@@ -699,7 +875,7 @@ mod tests {
         let mut proxy = test_proxy(ProxyType::Socks4);
         proxy.port = addr.port();
 
-        let err = match connect_via_proxy(&proxy, "127.0.0.1", 22, TIMEOUT).await {
+        let err = match connect_via_proxy(&proxy, "127.0.0.1", 22, "user", TIMEOUT).await {
             Ok(_) => panic!("expected a proxy failure"),
             Err(e) => e,
         };
@@ -709,4 +885,86 @@ mod tests {
         );
         server.await.unwrap();
     }
+
+    #[test]
+    fn proxy_command_tokens_are_expanded() {
+        assert_eq!(
+            expand_proxy_command(
+                "cloudflared access ssh --hostname %h:%p # %r 100%%",
+                "db",
+                22,
+                "ops"
+            )
+            .unwrap(),
+            "cloudflared access ssh --hostname db:22 # ops 100%"
+        );
+        assert_eq!(
+            expand_proxy_command("nc %x %", "h", 1, "u").unwrap(),
+            "nc %x %"
+        );
+        assert_eq!(
+            expand_proxy_command("nc %h %p", "[fe80::1]", 22, "").unwrap(),
+            "nc [fe80::1] 22"
+        );
+    }
+
+    #[test]
+    fn proxy_command_refuses_shell_metacharacters() {
+        for host in ["db; rm -rf ~", "$(id)", "a`b`", "a b", "x|y", "'q'"] {
+            assert!(expand_proxy_command("nc %h %p", host, 22, "ops").is_err(), "{host}");
+        }
+        assert!(expand_proxy_command("ssh -W %h:%p %r@bastion", "db", 22, "o&p").is_err());
+        // Values whose token is absent never reach the shell.
+        assert_eq!(
+            expand_proxy_command("nc %h %p # %%r", "db", 22, "first last").unwrap(),
+            "nc db 22 # %r"
+        );
+        assert!(expand_proxy_command("cloudflared access ssh", "a b", 22, "o&p").is_ok());
+    }
+
+    #[test]
+    fn command_proxies_get_a_longer_handshake_budget() {
+        let short = Duration::from_secs(3);
+        assert_eq!(test_proxy(ProxyType::Socks5).handshake_timeout(short), short);
+        assert_eq!(
+            test_proxy(ProxyType::Command).handshake_timeout(short),
+            PROXY_COMMAND_TIMEOUT
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn proxy_command_stdio_is_the_tunnel() {
+        let mut proxy = test_proxy(ProxyType::Command);
+        // `exec` replaces the shell, so wrap the two commands in their own.
+        proxy.command = Some("sh -c 'printf \"SSH-2.0-%h:%p\\n\"; exec cat'".to_string());
+
+        let mut tunnel = connect_via_proxy(&proxy, "example.com", 2222, "user", TIMEOUT)
+            .await
+            .expect("proxy command should start");
+        let mut banner = vec![0u8; 24];
+        tokio::time::timeout(TIMEOUT, tunnel.read_exact(&mut banner))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(banner, b"SSH-2.0-example.com:2222");
+
+        tunnel.write_all(b"echoed").await.unwrap();
+        let mut echoed = vec![0u8; 7];
+        tokio::time::timeout(TIMEOUT, tunnel.read_exact(&mut echoed))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(echoed, b"\nechoed");
+    }
+
+    #[tokio::test]
+    async fn proxy_command_must_not_be_empty() {
+        let proxy = test_proxy(ProxyType::Command);
+        let err = match connect_via_proxy(&proxy, "example.com", 22, "user", TIMEOUT).await {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => e,
+        };
+        assert!(err.to_string().contains("empty"), "{err}");
+    }
 }
@@ -1,29 +1,140 @@
 use crate::desktop_protocol::{DesktopProtocol, FrameUpdate, RdpConfig};
-use anyhow::Result;
+use crate::rdp_gfx::GfxCompositor;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
-use tokio::sync::mpsc;
+use ironrdp::cliprdr::backend::CliprdrBackend;
+use ironrdp::cliprdr::pdu::{
+    ClipboardFormat, ClipboardFormatId, ClipboardGeneralCapabilityFlags, FileContentsRequest,
+    FileContentsResponse, FormatDataRequest, FormatDataResponse, LockDataId,
+    OwnedFormatDataResponse,
+};
+use ironrdp::cliprdr::{Cliprdr, CliprdrClient};
+use ironrdp::connector::connection_activation::ConnectionActivationState;
+use ironrdp::connector::sspi::generator::NetworkRequest;
+use ironrdp::connector::Sequence;
+use ironrdp::connector::{ClientConnector, ConnectorResult, Credentials, DesktopSize, ServerName};
+use ironrdp::core::{impl_as_any, WriteBuf};
+use ironrdp::displaycontrol::client::DisplayControlClient;
+use ironrdp::displaycontrol::pdu::MonitorLayoutEntry;
+use ironrdp::dvc::DrdynvcClient;
+use ironrdp::graphics::image_processing::PixelFormat;
+use ironrdp::input::{Database, MouseButton, MousePosition, Operation, Scancode, WheelRotations};
+use ironrdp::pdu::geometry::InclusiveRectangle;
+use ironrdp::pdu::rdp::capability_sets::{client_codecs_capabilities, MajorPlatformType};
+use ironrdp::pdu::rdp::client_info::{PerformanceFlags, TimezoneInfo};
+use ironrdp::pdu::rdp::headers::ShareDataPdu;
+use ironrdp::pdu::rdp::refresh_rectangle::RefreshRectanglePdu;
+use ironrdp::pdu::Action;
+use ironrdp::session::image::DecodedImage;
+use ironrdp::session::{fast_path, ActiveStage, ActiveStageBuilder, ActiveStageOutput};
+use ironrdp_egfx::client::GraphicsPipelineClient;
+use ironrdp_tokio::bytes::BytesMut;
+use ironrdp_tokio::{NetworkClient, TokioFramed};
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Mutex};
 use tokio_util::sync::CancellationToken;
 
-/// RDP remote desktop client.
+/// Timeout for the TCP connection to the RDP server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Timeout for the whole RDP handshake (X.224, TLS, CredSSP and capability exchange).
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often CLIPRDR lock timeouts are driven while the frame loop runs.
+const CLIPBOARD_TIMEOUT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Client name advertised to the server (shows up in the session list).
+const CLIENT_NAME: &str = "r-shell";
+
+/// `button_mask` bits as sent by the frontend (`MouseEvent.buttons`, plus the
+/// VNC-style wheel bits used by the wheel handler).
+const BUTTON_LEFT: u8 = 0x01;
+const BUTTON_RIGHT: u8 = 0x02;
+const BUTTON_MIDDLE: u8 = 0x04;
+const WHEEL_UP: u8 = 0x08;
+const WHEEL_DOWN: u8 = 0x10;
+
+/// One wheel notch, in Windows `WHEEL_DELTA` units.
+const WHEEL_DELTA: i16 = 120;
+
+trait AsyncReadWrite: AsyncRead + AsyncWrite {}
+
+impl<T> AsyncReadWrite for T where T: AsyncRead + AsyncWrite {}
+
+type UpgradedStream = Box<dyn AsyncReadWrite + Unpin + Send + Sync>;
+type RdpWriter = WriteHalf<UpgradedStream>;
+
+/// Splits the server byte stream into whole X.224 / fast-path PDUs.
 ///
-/// Uses the RDP protocol to connect to Windows hosts.  The actual protocol
-/// wire-work (TLS, NLA, graphics pipeline) will be implemented in a follow-up
-/// using the `ironrdp` crate ecosystem.  For now the struct compiles and
-/// exposes the full `DesktopProtocol` interface so that the rest of the
-/// application (commands, WebSocket, frontend) can be wired end-to-end.
+/// `ironrdp_tokio::Framed` is not used past the handshake because its read
+/// futures cannot be proven `Send` inside the `async_trait` frame loop.
+struct PduReader {
+    stream: ReadHalf<UpgradedStream>,
+    buf: BytesMut,
+}
+
+impl PduReader {
+    /// Read the next PDU.  Cancel safe: partial data stays in the buffer.
+    async fn read_pdu(&mut self) -> Result<(Action, BytesMut)> {
+        loop {
+            if let Some(info) = ironrdp::pdu::find_size(&self.buf)
+                .map_err(|e| anyhow::anyhow!("Invalid RDP PDU header: {}", e))?
+            {
+                if self.buf.len() >= info.length {
+                    return Ok((info.action, self.buf.split_to(info.length)));
+                }
+            }
+            if self.stream.read_buf(&mut self.buf).await? == 0 {
+                return Err(anyhow::anyhow!("RDP server closed the connection"));
+            }
+        }
+    }
+}
+
+/// RDP remote desktop client built on the `ironrdp` crates.
+///
+/// The connection goes through X.224 negotiation, a TLS upgrade and
+/// CredSSP/NLA.  Bitmap updates (interleaved, planar and RemoteFX surface
+/// commands) are decoded into a local RGBA framebuffer and dirty regions are
+/// forwarded as `FrameUpdate`s.  The graphics pipeline dynamic channel is
+/// registered as well; when the server opens it, its surfaces are composed
+/// by `GfxCompositor` and forwarded the same way.  Keyboard and pointer input are sent as
+/// fast-path scancode/mouse events, clipboard text goes over CLIPRDR and
+/// `resize` uses the display-control dynamic channel.
 pub struct RdpClient {
     config: RdpConfig,
-    desktop_width: u16,
-    desktop_height: u16,
-    connected: bool,
+    /// Updated by `resize` while the frame loop may be running.
+    desktop_width: AtomicU16,
+    desktop_height: AtomicU16,
+    connected: AtomicBool,
+    /// Read half of the session; owned by the frame loop while it runs.
+    reader: Mutex<Option<PduReader>>,
+    /// Everything needed to encode outgoing PDUs.
+    session: Mutex<Option<RdpSession>>,
+    shutdown: CancellationToken,
+}
+
+/// Active-stage state shared between the frame loop and the input methods.
+struct RdpSession {
+    writer: RdpWriter,
+    active_stage: ActiveStage,
+    image: DecodedImage,
+    activation_factory: ironrdp::connector::connection_activation::ConnectionActivationFactory,
+    input: Database,
+    clipboard_events: mpsc::UnboundedReceiver<ClipboardEvent>,
+    /// Frames finished on the graphics pipeline channel.
+    gfx_updates: mpsc::UnboundedReceiver<FrameUpdate>,
+    /// Whether the CLIPRDR channel finished its initialization handshake.
+    clipboard_ready: bool,
+    /// Last text handed to `set_clipboard`, served on format data requests.
+    clipboard_text: Option<String>,
 }
 
 impl RdpClient {
-    /// Attempt to establish an RDP connection.
-    ///
-    /// Currently returns an error because the actual RDP protocol implementation
-    /// (ironrdp integration) is pending.  The function validates the config so
-    /// callers get a meaningful message.
+    /// Connect to an RDP server and authenticate with NLA.
     pub async fn connect(config: &RdpConfig) -> Result<Self> {
         if config.host.is_empty() {
             return Err(anyhow::anyhow!("RDP host cannot be empty"));
@@ -34,98 +145,760 @@ impl RdpClient {
             ));
         }
 
-        // TODO: implement actual RDP connection using ironrdp crate
-        // Steps would be:
-        // 1. TCP connect to host:port
-        // 2. TLS upgrade
-        // 3. NLA authentication (username, password, domain)
-        // 4. Negotiate display resolution
-        // 5. Start graphics pipeline
-        Err(anyhow::anyhow!(
-            "RDP protocol support is not yet implemented. \
-             Connection to {}:{} cannot be established.",
-            config.host,
-            config.port
-        ))
+        let addr = format!("{}:{}", config.host, config.port);
+        let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(&addr))
+            .await
+            .map_err(|_| anyhow::anyhow!("Connection to {} timed out", addr))?
+            .with_context(|| format!("Failed to connect to {}", addr))?;
+        stream.set_nodelay(true)?;
+
+        let (clipboard_tx, clipboard_events) = mpsc::unbounded_channel();
+        let (gfx_tx, gfx_updates) = mpsc::unbounded_channel();
+        let (connection_result, framed) = tokio::time::timeout(
+            HANDSHAKE_TIMEOUT,
+            Self::handshake(config, stream, clipboard_tx, gfx_tx),
+        )
+        .await
+        .map_err(|_| anyhow::anyhow!("RDP handshake with {} timed out", addr))??;
+
+        let desktop_size = connection_result.desktop_size;
+        let active_stage = ActiveStageBuilder {
+            static_channels: connection_result.static_channels,
+            user_channel_id: connection_result.user_channel_id,
+            io_channel_id: connection_result.io_channel_id,
+            message_channel_id: connection_result.message_channel_id,
+            share_id: connection_result.share_id,
+            compression_type: connection_result.compression_type,
+            enable_server_pointer: connection_result.enable_server_pointer,
+            pointer_software_rendering: connection_result.pointer_software_rendering,
+        }
+        .build();
+        let (stream, leftover) = framed.into_inner();
+        let (stream, writer) = tokio::io::split(stream);
+        let reader = PduReader {
+            stream,
+            buf: leftover,
+        };
+
+        tracing::info!(
+            "RDP connected to {} ({}x{})",
+            addr,
+            desktop_size.width,
+            desktop_size.height
+        );
+
+        Ok(Self {
+            config: config.clone(),
+            desktop_width: AtomicU16::new(desktop_size.width),
+            desktop_height: AtomicU16::new(desktop_size.height),
+            connected: AtomicBool::new(true),
+            reader: Mutex::new(Some(reader)),
+            session: Mutex::new(Some(RdpSession {
+                writer,
+                active_stage,
+                image: DecodedImage::new(
+                    PixelFormat::RgbA32,
+                    desktop_size.width,
+                    desktop_size.height,
+                ),
+                activation_factory: connection_result.activation_factory,
+                input: Database::new(),
+                clipboard_events,
+                gfx_updates,
+                clipboard_ready: false,
+                clipboard_text: None,
+            })),
+            shutdown: CancellationToken::new(),
+        })
+    }
+
+    /// Run the connection sequence up to the active stage.
+    async fn handshake(
+        config: &RdpConfig,
+        stream: TcpStream,
+        clipboard_tx: mpsc::UnboundedSender<ClipboardEvent>,
+        gfx_tx: mpsc::UnboundedSender<FrameUpdate>,
+    ) -> Result<(
+        ironrdp::connector::ConnectionResult,
+        TokioFramed<UpgradedStream>,
+    )> {
+        let client_addr = stream.local_addr()?;
+        let mut framed = TokioFramed::new(stream);
+
+        let drdynvc = DrdynvcClient::new()
+            .with_dynamic_channel(DisplayControlClient::new(|_| Ok(Vec::new())))
+            .with_dynamic_channel(GraphicsPipelineClient::new(
+                Box::new(GfxCompositor::new(gfx_tx)),
+                None,
+            ));
+        let mut connector = ClientConnector::new(build_connector_config(config)?, client_addr)
+            .with_static_channel(drdynvc);
+        connector.attach_static_channel(Cliprdr::new(Box::new(TextClipboardBackend {
+            events: clipboard_tx,
+        })));
+
+        let should_upgrade = ironrdp_tokio::connect_begin(&mut framed, &mut connector)
+            .await
+            .map_err(|e| anyhow::anyhow!("RDP negotiation failed: {}", e))?;
+
+        let (stream, leftover) = framed.into_inner();
+        let (tls_stream, tls_cert) = ironrdp_tls::upgrade(stream, &config.host)
+            .await
+            .context("TLS upgrade failed")?;
+        let upgraded = ironrdp_tokio::mark_as_upgraded(should_upgrade, &mut connector);
+
+        let server_public_key = ironrdp_tls::extract_tls_server_public_key(&tls_cert)
+            .ok_or_else(|| anyhow::anyhow!("Unable to extract the server TLS public key"))?
+            .to_owned();
+        let stream: UpgradedStream = Box::new(tls_stream);
+        let mut framed = TokioFramed::new_with_leftover(stream, leftover);
+
+        let connection_result = ironrdp_tokio::connect_finalize(
+            upgraded,
+            connector,
+            &mut framed,
+            &mut NtlmOnlyNetworkClient,
+            ServerName::new(config.host.clone()),
+            server_public_key,
+            None,
+        )
+        .await
+        .map_err(|e| anyhow::anyhow!("RDP authentication failed: {}", e))?;
+
+        Ok((connection_result, framed))
     }
 
     /// Create a client instance in disconnected state (for testing).
     #[allow(dead_code)]
     fn new_disconnected(config: RdpConfig) -> Self {
         Self {
-            desktop_width: config.width,
-            desktop_height: config.height,
+            desktop_width: AtomicU16::new(config.width),
+            desktop_height: AtomicU16::new(config.height),
             config,
-            connected: false,
+            connected: AtomicBool::new(false),
+            reader: Mutex::new(None),
+            session: Mutex::new(None),
+            shutdown: CancellationToken::new(),
+        }
+    }
+
+    /// Feed input operations through the session's input database and send
+    /// the resulting fast-path events.
+    async fn send_input(&self, operations: Vec<Operation>) -> Result<()> {
+        let mut slot = self.session.lock().await;
+        let session = slot
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("RDP client is not connected"))?;
+        let events = session.input.apply(operations);
+        let outputs = session
+            .active_stage
+            .process_fastpath_input(&mut session.image, &events)
+            .map_err(|e| anyhow::anyhow!("Failed to encode RDP input: {}", e))?;
+        session.write_responses(outputs).await
+    }
+
+    /// Process one server PDU.  Returns `false` once the session has ended.
+    async fn handle_pdu(
+        &self,
+        reader: &mut PduReader,
+        action: Action,
+        payload: &[u8],
+        frame_tx: &mpsc::UnboundedSender<FrameUpdate>,
+    ) -> Result<bool> {
+        let mut slot = self.session.lock().await;
+        let session = slot
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("RDP client is not connected"))?;
+        let outputs = session
+            .active_stage
+            .process(&mut session.image, action, payload)
+            .map_err(|e| anyhow::anyhow!("Failed to process RDP PDU: {}", e))?;
+
+        for output in outputs {
+            match output {
+                ActiveStageOutput::ResponseFrame(frame) => session.write(&frame).await?,
+                ActiveStageOutput::GraphicsUpdate(region) => {
+                    if let Some(update) = crop_region(
+                        session.image.data(),
+                        session.image.width(),
+                        session.image.height(),
+                        &region,
+                    ) {
+                        let _ = frame_tx.send(update);
+                    }
+                }
+                ActiveStageOutput::DeactivateAll => session.reactivate(reader).await?,
+                ActiveStageOutput::Terminate(reason) => {
+                    tracing::info!(
+                        "RDP session with {}:{} ended: {}",
+                        self.config.host,
+                        self.config.port,
+                        reason.description()
+                    );
+                    return Ok(false);
+                }
+                _ => {}
+            }
+        }
+        while let Ok(update) = session.gfx_updates.try_recv() {
+            let _ = frame_tx.send(update);
+        }
+
+        let frame = session.drain_clipboard_events()?;
+        session.write(&frame).await?;
+        Ok(true)
+    }
+}
+
+impl RdpSession {
+    async fn write(&mut self, frame: &[u8]) -> Result<()> {
+        if frame.is_empty() {
+            return Ok(());
+        }
+        self.writer
+            .write_all(frame)
+            .await
+            .context("Failed to write to RDP server")
+    }
+
+    /// Write out the response frames of an active-stage call.
+    async fn write_responses(&mut self, outputs: Vec<ActiveStageOutput>) -> Result<()> {
+        for output in outputs {
+            if let ActiveStageOutput::ResponseFrame(frame) = output {
+                self.write(&frame).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the Deactivation-Reactivation Sequence, which the server uses to
+    /// apply a new desktop size.
+    async fn reactivate(&mut self, reader: &mut PduReader) -> Result<()> {
+        let mut activation = self.activation_factory.create();
+        let mut buf = WriteBuf::new();
+        loop {
+            buf.clear();
+            let written = if activation.next_pdu_hint().is_some() {
+                let (_, payload) = reader.read_pdu().await?;
+                activation.step(&payload, &mut buf)
+            } else {
+                activation.step_no_input(&mut buf)
+            }
+            .map_err(|e| anyhow::anyhow!("RDP reactivation failed: {}", e))?;
+            if written.size().is_some() {
+                self.write(buf.filled()).await?;
+            }
+            if let ConnectionActivationState::Finalized {
+                desktop_size,
+                share_id,
+                enable_server_pointer,
+                pointer_software_rendering,
+            } = activation.connection_activation_state()
+            {
+                tracing::debug!(
+                    "RDP desktop reactivated at {}x{}",
+                    desktop_size.width,
+                    desktop_size.height
+                );
+                self.image =
+                    DecodedImage::new(PixelFormat::RgbA32, desktop_size.width, desktop_size.height);
+                self.active_stage.set_fastpath_processor(
+                    fast_path::ProcessorBuilder {
+                        io_channel_id: activation.io_channel_id(),
+                        user_channel_id: activation.user_channel_id(),
+                        share_id,
+                        enable_server_pointer,
+                        pointer_software_rendering,
+                        bulk_decompressor: None,
+                    }
+                    .build(),
+                );
+                self.active_stage.set_share_id(share_id);
+                self.active_stage
+                    .set_enable_server_pointer(enable_server_pointer);
+                return Ok(());
+            }
         }
     }
+
+    /// Answer the CLIPRDR requests queued by the backend during the last
+    /// `process` call.  Returns the encoded frame to send (may be empty).
+    fn drain_clipboard_events(&mut self) -> Result<Vec<u8>> {
+        let mut frame = Vec::new();
+        while let Ok(event) = self.clipboard_events.try_recv() {
+            let Some(cliprdr) = self.active_stage.get_svc_processor_mut::<CliprdrClient>() else {
+                continue;
+            };
+            let messages = match event {
+                ClipboardEvent::FormatListRequested => {
+                    self.clipboard_ready = true;
+                    cliprdr.initiate_copy(&text_formats(self.clipboard_text.is_some()))
+                }
+                ClipboardEvent::DataRequested(format) => cliprdr.submit_format_data(
+                    format_data_response(self.clipboard_text.as_deref(), format),
+                ),
+            }
+            .map_err(|e| anyhow::anyhow!("CLIPRDR error: {}", e))?;
+            frame.extend(
+                self.active_stage
+                    .process_svc_processor_messages(messages)
+                    .map_err(|e| anyhow::anyhow!("CLIPRDR error: {}", e))?,
+            );
+        }
+        Ok(frame)
+    }
+
+    fn drive_clipboard_timeouts(&mut self) -> Result<Vec<u8>> {
+        let Some(cliprdr) = self.active_stage.get_svc_processor_mut::<CliprdrClient>() else {
+            return Ok(Vec::new());
+        };
+        let messages = cliprdr
+            .drive_timeouts()
+            .map_err(|e| anyhow::anyhow!("CLIPRDR error: {}", e))?;
+        self.active_stage
+            .process_svc_processor_messages(messages)
+            .map_err(|e| anyhow::anyhow!("CLIPRDR error: {}", e))
+    }
+}
+
+/// Build the `ironrdp` connector configuration from an `RdpConfig`.
+fn build_connector_config(config: &RdpConfig) -> Result<ironrdp::connector::Config> {
+    let codecs = client_codecs_capabilities(&[])
+        .map_err(|e| anyhow::anyhow!("Invalid RDP codec configuration: {}", e))?;
+
+    Ok(ironrdp::connector::Config {
+        desktop_size: DesktopSize {
+            width: config.width,
+            height: config.height,
+        },
+        desktop_scale_factor: 0,
+        enable_tls: true,
+        enable_credssp: true,
+        credentials: Credentials::UsernamePassword {
+            username: config.username.clone(),
+            password: config.password.clone(),
+        },
+        domain: config.domain.clone().filter(|d| !d.is_empty()),
+        client_build: 0,
+        client_name: CLIENT_NAME.to_string(),
+        keyboard_type: ironrdp::pdu::gcc::KeyboardType::IbmEnhanced,
+        keyboard_subtype: 0,
+        keyboard_functional_keys_count: 12,
+        keyboard_layout: 0,
+        ime_file_name: String::new(),
+        bitmap: Some(ironrdp::connector::BitmapConfig {
+            lossy_compression: true,
+            color_depth: 32,
+            codecs,
+        }),
+        dig_product_id: String::new(),
+        client_dir: String::new(),
+        alternate_shell: String::new(),
+        work_dir: String::new(),
+        platform: MajorPlatformType::UNSPECIFIED,
+        hardware_id: None,
+        request_data: None,
+        autologon: false,
+        enable_audio_playback: false,
+        performance_flags: PerformanceFlags::default(),
+        license_cache: None,
+        timezone_info: TimezoneInfo::default(),
+        compression_type: None,
+        // The frontend draws its own cursor on top of the canvas.
+        enable_server_pointer: false,
+        pointer_software_rendering: false,
+        multitransport_flags: None,
+    })
+}
+
+/// CredSSP network client for NTLM.  Only Kerberos needs to reach a KDC, and
+/// Kerberos is not configured, so every request is refused.
+struct NtlmOnlyNetworkClient;
+
+impl NetworkClient for NtlmOnlyNetworkClient {
+    async fn send(&mut self, request: &NetworkRequest) -> ConnectorResult<Vec<u8>> {
+        tracing::debug!("Refusing CredSSP network request to {}", request.url);
+        Err(ironrdp::connector::general_err!(
+            "KDC requests are not supported (only NTLM authentication is available)"
+        ))
+    }
+}
+
+/// CLIPRDR notifications that need a response from the session.
+#[derive(Debug)]
+enum ClipboardEvent {
+    /// The server is ready and wants our initial format list.
+    FormatListRequested,
+    /// The server wants the clipboard content in the given format.
+    DataRequested(ClipboardFormatId),
+}
+
+/// Text-only CLIPRDR backend.  Callbacks run inside `ActiveStage::process`,
+/// so they only queue events that `RdpSession` answers afterwards.
+#[derive(Debug)]
+struct TextClipboardBackend {
+    events: mpsc::UnboundedSender<ClipboardEvent>,
+}
+
+impl_as_any!(TextClipboardBackend);
+
+impl CliprdrBackend for TextClipboardBackend {
+    fn temporary_directory(&self) -> &str {
+        ".cliprdr"
+    }
+
+    fn client_capabilities(&self) -> ClipboardGeneralCapabilityFlags {
+        ClipboardGeneralCapabilityFlags::empty()
+    }
+
+    fn on_ready(&mut self) {}
+
+    fn on_request_format_list(&mut self) {
+        let _ = self.events.send(ClipboardEvent::FormatListRequested);
+    }
+
+    fn on_process_negotiated_capabilities(&mut self, _: ClipboardGeneralCapabilityFlags) {}
+
+    fn on_remote_copy(&mut self, available_formats: &[ClipboardFormat]) {
+        tracing::debug!(
+            "RDP server clipboard changed ({} formats)",
+            available_formats.len()
+        );
+    }
+
+    fn on_format_data_request(&mut self, request: FormatDataRequest) {
+        let _ = self
+            .events
+            .send(ClipboardEvent::DataRequested(request.format));
+    }
+
+    fn on_format_data_response(&mut self, _: FormatDataResponse<'_>) {}
+
+    fn on_file_contents_request(&mut self, _: FileContentsRequest) {}
+
+    fn on_file_contents_response(&mut self, _: FileContentsResponse<'_>) {}
+
+    fn on_lock(&mut self, _: LockDataId) {}
+
+    fn on_unlock(&mut self, _: LockDataId) {}
+}
+
+/// Formats advertised in our CLIPRDR format list.
+fn text_formats(has_text: bool) -> Vec<ClipboardFormat> {
+    if has_text {
+        vec![
+            ClipboardFormat::new(ClipboardFormatId::CF_UNICODETEXT),
+            ClipboardFormat::new(ClipboardFormatId::CF_TEXT),
+        ]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Encode the clipboard text in the requested format, or an error response
+/// when there is no text or the format is not a text format.
+fn format_data_response(text: Option<&str>, format: ClipboardFormatId) -> OwnedFormatDataResponse {
+    match text {
+        Some(text) if format == ClipboardFormatId::CF_UNICODETEXT => {
+            OwnedFormatDataResponse::new_unicode_string(text)
+        }
+        Some(text) if format == ClipboardFormatId::CF_TEXT => {
+            OwnedFormatDataResponse::new_string(text)
+        }
+        _ => OwnedFormatDataResponse::new_error(),
+    }
+}
+
+/// Copy a dirty region out of the RGBA framebuffer.
+fn crop_region(
+    data: &[u8],
+    image_width: u16,
+    image_height: u16,
+    region: &InclusiveRectangle,
+) -> Option<FrameUpdate> {
+    let right = region.right.min(image_width.checked_sub(1)?);
+    let bottom = region.bottom.min(image_height.checked_sub(1)?);
+    if region.left > right || region.top > bottom {
+        return None;
+    }
+    let width = right - region.left + 1;
+    let height = bottom - region.top + 1;
+    let stride = usize::from(image_width) * 4;
+
+    let mut rgba_data = Vec::with_capacity(usize::from(width) * usize::from(height) * 4);
+    for row in region.top..=bottom {
+        let start = usize::from(row) * stride + usize::from(region.left) * 4;
+        rgba_data.extend_from_slice(&data[start..start + usize::from(width) * 4]);
+    }
+    // The decoder leaves the alpha channel undefined; the canvas needs it opaque.
+    for pixel in rgba_data.chunks_exact_mut(4) {
+        pixel[3] = 0xff;
+    }
+
+    Some(FrameUpdate {
+        x: region.left,
+        y: region.top,
+        width,
+        height,
+        rgba_data,
+    })
+}
+
+/// Translate a JavaScript `keyCode` (as sent by the frontend) into a PC/AT
+/// set 1 scancode.
+fn scancode_for_key_code(key_code: u32) -> Option<Scancode> {
+    let (extended, code) = match key_code {
+        8 => (false, 0x0e),                               // Backspace
+        9 => (false, 0x0f),                               // Tab
+        13 => (false, 0x1c),                              // Enter
+        16 => (false, 0x2a),                              // Shift
+        17 => (false, 0x1d),                              // Control
+        18 => (false, 0x38),                              // Alt
+        20 => (false, 0x3a),                              // CapsLock
+        27 => (false, 0x01),                              // Escape
+        32 => (false, 0x39),                              // Space
+        33 => (true, 0x49),                               // PageUp
+        34 => (true, 0x51),                               // PageDown
+        35 => (true, 0x4f),                               // End
+        36 => (true, 0x47),                               // Home
+        37 => (true, 0x4b),                               // ArrowLeft
+        38 => (true, 0x48),                               // ArrowUp
+        39 => (true, 0x4d),                               // ArrowRight
+        40 => (true, 0x50),                               // ArrowDown
+        44 => (true, 0x37),                               // PrintScreen
+        45 => (true, 0x52),                               // Insert
+        46 => (true, 0x53),                               // Delete
+        48 => (false, 0x0b),                              // 0
+        49..=57 => (false, (key_code - 49 + 0x02) as u8), // 1-9
+        65..=90 => (false, LETTER_SCANCODES[(key_code - 65) as usize]),
+        91 => (true, 0x5b),                                  // Meta (left)
+        92 => (true, 0x5c),                                  // Meta (right)
+        93 => (true, 0x5d),                                  // ContextMenu
+        96 => (false, 0x52),                                 // Numpad 0
+        97 => (false, 0x4f),                                 // Numpad 1
+        98 => (false, 0x50),                                 // Numpad 2
+        99 => (false, 0x51),                                 // Numpad 3
+        100 => (false, 0x4b),                                // Numpad 4
+        101 => (false, 0x4c),                                // Numpad 5
+        102 => (false, 0x4d),                                // Numpad 6
+        103 => (false, 0x47),                                // Numpad 7
+        104 => (false, 0x48),                                // Numpad 8
+        105 => (false, 0x49),                                // Numpad 9
+        106 => (false, 0x37),                                // Numpad *
+        107 => (false, 0x4e),                                // Numpad +
+        109 => (false, 0x4a),                                // Numpad -
+        110 => (false, 0x53),                                // Numpad .
+        111 => (true, 0x35),                                 // Numpad /
+        112..=121 => (false, (key_code - 112 + 0x3b) as u8), // F1-F10
+        122 => (false, 0x57),                                // F11
+        123 => (false, 0x58),                                // F12
+        144 => (false, 0x45),                                // NumLock
+        145 => (false, 0x46),                                // ScrollLock
+        59 | 186 => (false, 0x27),                           // ;
+        61 | 187 => (false, 0x0d),                           // =
+        188 => (false, 0x33),                                // ,
+        173 | 189 => (false, 0x0c),                          // -
+        190 => (false, 0x34),                                // .
+        191 => (false, 0x35),                                // /
+        192 => (false, 0x29),                                // `
+        219 => (false, 0x1a),                                // [
+        220 => (false, 0x2b),                                // \
+        221 => (false, 0x1b),                                // ]
+        222 => (false, 0x28),                                // '
+        _ => return None,
+    };
+    Some(Scancode::from_u8(extended, code))
+}
+
+/// Set 1 scancodes for A-Z.
+const LETTER_SCANCODES: [u8; 26] = [
+    0x1e, 0x30, 0x2e, 0x20, 0x12, 0x21, 0x22, 0x23, 0x17, 0x24, 0x25, 0x26, 0x32, 0x31, 0x18, 0x19,
+    0x10, 0x13, 0x1f, 0x14, 0x16, 0x2f, 0x11, 0x2d, 0x15, 0x2c,
+];
+
+/// Translate a frontend pointer event into input-database operations.
+///
+/// Wheel events carry only the wheel bit, so they leave the button state alone.
+fn pointer_operations(x: u16, y: u16, button_mask: u8) -> Vec<Operation> {
+    let mut operations = vec![Operation::MouseMove(MousePosition { x, y })];
+    if button_mask & (WHEEL_UP | WHEEL_DOWN) != 0 {
+        let rotation_units = if button_mask & WHEEL_UP != 0 {
+            WHEEL_DELTA
+        } else {
+            -WHEEL_DELTA
+        };
+        operations.push(Operation::WheelRotations(WheelRotations {
+            is_vertical: true,
+            rotation_units,
+        }));
+        return operations;
+    }
+    for (bit, button) in [
+        (BUTTON_LEFT, MouseButton::Left),
+        (BUTTON_RIGHT, MouseButton::Right),
+        (BUTTON_MIDDLE, MouseButton::Middle),
+    ] {
+        operations.push(if button_mask & bit != 0 {
+            Operation::MouseButtonPressed(button)
+        } else {
+            Operation::MouseButtonReleased(button)
+        });
+    }
+    operations
 }
 
 #[async_trait]
 impl DesktopProtocol for RdpClient {
     async fn start_frame_loop(
         &self,
-        _frame_tx: mpsc::UnboundedSender<FrameUpdate>,
-        _cancel: CancellationToken,
+        frame_tx: mpsc::UnboundedSender<FrameUpdate>,
+        cancel: CancellationToken,
     ) -> Result<()> {
-        if !self.connected {
+        if !self.connected.load(Ordering::SeqCst) {
             return Err(anyhow::anyhow!("RDP client is not connected"));
         }
-        // TODO: decode incoming RDP framebuffer updates and send as FrameUpdate
-        Ok(())
-    }
+        let mut slot = self
+            .reader
+            .try_lock()
+            .map_err(|_| anyhow::anyhow!("RDP frame loop is already running"))?;
+        let reader = slot
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("RDP client is not connected"))?;
 
-    async fn send_key(&self, _key_code: u32, _down: bool) -> Result<()> {
-        if !self.connected {
-            return Err(anyhow::anyhow!("RDP client is not connected"));
+        self.request_full_frame().await?;
+
+        let mut clipboard_timer = tokio::time::interval(CLIPBOARD_TIMEOUT_INTERVAL);
+        loop {
+            // `read_pdu` buffers partial PDUs internally, so cancelling it
+            // leaves the stream consistent for the next frame loop.
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = self.shutdown.cancelled() => break,
+                _ = clipboard_timer.tick() => {
+                    let mut slot = self.session.lock().await;
+                    if let Some(session) = slot.as_mut() {
+                        let frame = session.drive_clipboard_timeouts()?;
+                        session.write(&frame).await?;
+                    }
+                }
+                pdu = reader.read_pdu() => {
+                    let (action, payload) = pdu?;
+                    if !self.handle_pdu(reader, action, &payload, &frame_tx).await? {
+                        break;
+                    }
+                }
+            }
         }
-        // TODO: forward as RDP scancode input event
         Ok(())
     }
 
-    async fn send_pointer(&self, _x: u16, _y: u16, _button_mask: u8) -> Result<()> {
-        if !self.connected {
-            return Err(anyhow::anyhow!("RDP client is not connected"));
-        }
-        // TODO: forward as RDP pointer input event
-        Ok(())
+    async fn send_key(&self, key_code: u32, down: bool) -> Result<()> {
+        let Some(scancode) = scancode_for_key_code(key_code) else {
+            tracing::debug!("No RDP scancode for key code {}", key_code);
+            return Ok(());
+        };
+        self.send_input(vec![if down {
+            Operation::KeyPressed(scancode)
+        } else {
+            Operation::KeyReleased(scancode)
+        }])
+        .await
+    }
+
+    async fn send_pointer(&self, x: u16, y: u16, button_mask: u8) -> Result<()> {
+        self.send_input(pointer_operations(x, y, button_mask)).await
     }
 
     async fn request_full_frame(&self) -> Result<()> {
-        if !self.connected {
-            return Err(anyhow::anyhow!("RDP client is not connected"));
-        }
-        // TODO: request full framebuffer refresh
-        Ok(())
+        let mut slot = self.session.lock().await;
+        let session = slot
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("RDP client is not connected"))?;
+        let area = InclusiveRectangle {
+            left: 0,
+            top: 0,
+            right: session.image.width().saturating_sub(1),
+            bottom: session.image.height().saturating_sub(1),
+        };
+        let mut buf = WriteBuf::new();
+        session
+            .active_stage
+            .encode_static(
+                &mut buf,
+                ShareDataPdu::RefreshRectangle(RefreshRectanglePdu {
+                    areas_to_refresh: vec![area],
+                }),
+            )
+            .map_err(|e| anyhow::anyhow!("Failed to encode refresh request: {}", e))?;
+        session.write(buf.filled()).await
     }
 
-    async fn set_clipboard(&self, _text: String) -> Result<()> {
-        if !self.connected {
-            return Err(anyhow::anyhow!("RDP client is not connected"));
+    async fn set_clipboard(&self, text: String) -> Result<()> {
+        let mut slot = self.session.lock().await;
+        let session = slot
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("RDP client is not connected"))?;
+        session.clipboard_text = Some(text);
+        // Before the channel is ready the text is advertised in response to
+        // the server's format list request instead.
+        if !session.clipboard_ready {
+            return Ok(());
         }
-        // TODO: send via CLIPRDR virtual channel
-        Ok(())
+        let cliprdr = session
+            .active_stage
+            .get_svc_processor_mut::<CliprdrClient>()
+            .ok_or_else(|| anyhow::anyhow!("RDP clipboard channel is not available"))?;
+        let messages = cliprdr
+            .initiate_copy(&text_formats(true))
+            .map_err(|e| anyhow::anyhow!("CLIPRDR error: {}", e))?;
+        let frame = session
+            .active_stage
+            .process_svc_processor_messages(messages)
+            .map_err(|e| anyhow::anyhow!("CLIPRDR error: {}", e))?;
+        session.write(&frame).await
     }
 
     fn desktop_size(&self) -> (u16, u16) {
-        (self.desktop_width, self.desktop_height)
+        (
+            self.desktop_width.load(Ordering::SeqCst),
+            self.desktop_height.load(Ordering::SeqCst),
+        )
     }
 
-    async fn resize(&mut self, width: u16, height: u16) -> Result<()> {
-        if !self.connected {
-            return Err(anyhow::anyhow!("RDP client is not connected"));
-        }
-        // TODO: send RDP display resize PDU to server
-        // If server rejects, retain current resolution
-        self.desktop_width = width;
-        self.desktop_height = height;
+    async fn resize(&self, width: u16, height: u16) -> Result<()> {
+        let mut slot = self.session.lock().await;
+        let session = slot
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("RDP client is not connected"))?;
+        let (width, height) =
+            MonitorLayoutEntry::adjust_display_size(u32::from(width), u32::from(height));
+        // The server answers with a Deactivation-Reactivation Sequence that the
+        // frame loop handles; without the channel the resolution is unchanged.
+        let frame = session
+            .active_stage
+            .encode_resize(width, height, None, None)
+            .ok_or_else(|| {
+                anyhow::anyhow!("RDP server does not support dynamic resolution changes")
+            })?
+            .map_err(|e| anyhow::anyhow!("Failed to encode resize request: {}", e))?;
+        session.write(&frame).await?;
+
+        // adjust_display_size clamps to 200..=8192, so this always fits.
+        self.desktop_width.store(width as u16, Ordering::SeqCst);
+        self.desktop_height.store(height as u16, Ordering::SeqCst);
         Ok(())
     }
 
-    async fn disconnect(&mut self) -> Result<()> {
-        if self.connected {
-            // TODO: send graceful RDP disconnect request
-            self.connected = false;
+    async fn disconnect(&self) -> Result<()> {
+        if self.connected.swap(false, Ordering::SeqCst) {
+            self.shutdown.cancel();
+            if let Some(mut session) = self.session.lock().await.take() {
+                if let Ok(outputs) = session.active_stage.graceful_shutdown() {
+                    let _ = session.write_responses(outputs).await;
+                }
+                let _ = session.writer.shutdown().await;
+            }
+            self.reader.lock().await.take();
             tracing::info!(
                 "RDP disconnected from {}:{}",
                 self.config.host,
@@ -135,3 +908,208 @@ impl DesktopProtocol for RdpClient {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ironrdp::pdu::input::fast_path::FastPathInputEvent;
+    use ironrdp::pdu::input::mouse::PointerFlags;
+
+    fn config() -> RdpConfig {
+        RdpConfig {
+            host: "127.0.0.1".to_string(),
+            port: 3389,
+            username: "alice".to_string(),
+            password: "secret".to_string(),
+            domain: Some("CORP".to_string()),
+            width: 1280,
+            height: 720,
+        }
+    }
+
+    #[test]
+    fn test_connector_config_uses_credentials_and_size() {
+        let cfg = build_connector_config(&config()).unwrap();
+        assert!(cfg.enable_tls);
+        assert!(cfg.enable_credssp);
+        assert_eq!(cfg.domain.as_deref(), Some("CORP"));
+        assert_eq!(
+            (cfg.desktop_size.width, cfg.desktop_size.height),
+            (1280, 720)
+        );
+        match cfg.credentials {
+            Credentials::UsernamePassword { username, password } => {
+                assert_eq!(username, "alice");
+                assert_eq!(password, "secret");
+            }
+            _ => panic!("expected username/password credentials"),
+        }
+        assert_eq!(cfg.bitmap.unwrap().color_depth, 32);
+    }
+
+    #[test]
+    fn test_connector_config_drops_empty_domain() {
+        let mut c = config();
+        c.domain = Some(String::new());
+        assert!(build_connector_config(&c).unwrap().domain.is_none());
+    }
+
+    #[test]
+    fn test_scancode_mapping() {
+        assert_eq!(scancode_for_key_code(65).unwrap().as_u8(), (false, 0x1e)); // A
+        assert_eq!(scancode_for_key_code(90).unwrap().as_u8(), (false, 0x2c)); // Z
+        assert_eq!(scancode_for_key_code(49).unwrap().as_u8(), (false, 0x02)); // 1
+        assert_eq!(scancode_for_key_code(48).unwrap().as_u8(), (false, 0x0b)); // 0
+        assert_eq!(scancode_for_key_code(112).unwrap().as_u8(), (false, 0x3b)); // F1
+        assert_eq!(scancode_for_key_code(123).unwrap().as_u8(), (false, 0x58)); // F12
+        assert_eq!(scancode_for_key_code(46).unwrap().as_u8(), (true, 0x53)); // Delete
+        assert_eq!(scancode_for_key_code(37).unwrap().as_u8(), (true, 0x4b)); // ArrowLeft
+        assert!(scancode_for_key_code(0).is_none());
+        assert!(scancode_for_key_code(0xffff).is_none());
+    }
+
+    fn mouse_flags(events: &[FastPathInputEvent]) -> Vec<PointerFlags> {
+        events
+            .iter()
+            .filter_map(|e| match e {
+                FastPathInputEvent::MouseEvent(pdu) => Some(pdu.flags),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pointer_press_move_release() {
+        let mut db = Database::new();
+
+        let events = db.apply(pointer_operations(10, 20, BUTTON_LEFT));
+        assert_eq!(
+            mouse_flags(&events),
+            vec![
+                PointerFlags::MOVE,
+                PointerFlags::DOWN | PointerFlags::LEFT_BUTTON
+            ]
+        );
+
+        // Dragging only moves; the button is already down.
+        let events = db.apply(pointer_operations(11, 20, BUTTON_LEFT));
+        assert_eq!(mouse_flags(&events), vec![PointerFlags::MOVE]);
+
+        let events = db.apply(pointer_operations(11, 20, 0));
+        assert_eq!(mouse_flags(&events), vec![PointerFlags::LEFT_BUTTON]);
+    }
+
+    #[test]
+    fn test_pointer_right_and_middle_buttons() {
+        let mut db = Database::new();
+        let events = db.apply(pointer_operations(0, 0, BUTTON_RIGHT | BUTTON_MIDDLE));
+        assert_eq!(
+            mouse_flags(&events),
+            vec![
+                PointerFlags::DOWN | PointerFlags::RIGHT_BUTTON,
+                PointerFlags::DOWN | PointerFlags::MIDDLE_BUTTON_OR_WHEEL,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pointer_wheel_keeps_buttons() {
+        let mut db = Database::new();
+        db.apply(pointer_operations(5, 5, BUTTON_LEFT));
+
+        let events = db.apply(pointer_operations(5, 5, WHEEL_DOWN));
+        assert_eq!(events.len(), 1);
+        match &events[0] {
+            FastPathInputEvent::MouseEvent(pdu) => {
+                assert_eq!(pdu.flags, PointerFlags::VERTICAL_WHEEL);
+                assert_eq!(pdu.number_of_wheel_rotation_units, -WHEEL_DELTA);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(db.is_mouse_button_pressed(MouseButton::Left));
+    }
+
+    #[test]
+    fn test_crop_region_copies_rows_and_sets_alpha() {
+        // 3x2 image, each pixel tagged with its index in the red channel.
+        let data: Vec<u8> = (0..6u8).flat_map(|i| [i, 0, 0, 0]).collect();
+        let region = InclusiveRectangle {
+            left: 1,
+            top: 0,
+            right: 2,
+            bottom: 1,
+        };
+        let update = crop_region(&data, 3, 2, &region).unwrap();
+        assert_eq!(
+            (update.x, update.y, update.width, update.height),
+            (1, 0, 2, 2)
+        );
+        assert_eq!(
+            update.rgba_data,
+            vec![1, 0, 0, 255, 2, 0, 0, 255, 4, 0, 0, 255, 5, 0, 0, 255]
+        );
+    }
+
+    #[test]
+    fn test_crop_region_clamps_to_image() {
+        let data = vec![0u8; 4 * 4 * 4];
+        let region = InclusiveRectangle {
+            left: 2,
+            top: 2,
+            right: 100,
+            bottom: 100,
+        };
+        let update = crop_region(&data, 4, 4, &region).unwrap();
+        assert_eq!((update.width, update.height), (2, 2));
+        assert_eq!(update.rgba_data.len(), 2 * 2 * 4);
+
+        let outside = InclusiveRectangle {
+            left: 10,
+            top: 0,
+            right: 12,
+            bottom: 1,
+        };
+        assert!(crop_region(&data, 4, 4, &outside).is_none());
+    }
+
+    #[test]
+    fn test_format_data_response() {
+        let unicode =
+            format_data_response(Some("hi"), ClipboardFormatId::CF_UNICODETEXT).to_unicode_string();
+        assert_eq!(unicode.unwrap(), "hi");
+        let ansi = format_data_response(Some("hi"), ClipboardFormatId::CF_TEXT).to_string();
+        assert_eq!(ansi.unwrap(), "hi");
+        assert!(format_data_response(None, ClipboardFormatId::CF_UNICODETEXT).is_error());
+        assert!(format_data_response(Some("hi"), ClipboardFormatId::new(0xc000)).is_error());
+        assert!(text_formats(false).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_connect_validates_config() {
+        let mut c = config();
+        c.host.clear();
+        assert!(RdpClient::connect(&c).await.is_err());
+
+        let mut c = config();
+        c.username.clear();
+        let err = RdpClient::connect(&c).await.err().unwrap();
+        assert!(err.to_string().contains("username"));
+    }
+
+    #[tokio::test]
+    async fn test_disconnected_client_rejects_input() {
+        let client = RdpClient::new_disconnected(config());
+        assert_eq!(client.desktop_size(), (1280, 720));
+        assert!(client.send_key(65, true).await.is_err());
+        assert!(client.send_pointer(1, 1, 0).await.is_err());
+        assert!(client.set_clipboard("x".to_string()).await.is_err());
+        assert!(client.request_full_frame().await.is_err());
+        assert!(client.resize(800, 600).await.is_err());
+        let (tx, _rx) = mpsc::unbounded_channel();
+        assert!(client
+            .start_frame_loop(tx, CancellationToken::new())
+            .await
+            .is_err());
+        assert!(client.disconnect().await.is_ok());
+    }
+}
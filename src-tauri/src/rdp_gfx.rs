@@ -0,0 +1,398 @@
+//! Graphics pipeline (MS-RDPEGFX) output for `RdpClient`.
+//!
+//! With the graphics pipeline the server draws into off-screen surfaces and
+//! maps them onto the desktop.  `GfxCompositor` keeps an RGBA copy of every
+//! surface, applies the drawing commands to it and, when a frame ends,
+//! reports the redrawn part of each mapped surface as a `FrameUpdate` in
+//! desktop coordinates.
+
+use crate::desktop_protocol::FrameUpdate;
+use ironrdp::graphics::clearcodec::ClearCodecDecoder;
+use ironrdp::graphics::rdp6::BitmapStreamDecoder;
+use ironrdp::pdu::geometry::ExclusiveRectangle;
+use ironrdp_egfx::client::{BitmapUpdate, GraphicsPipelineHandler, Surface};
+use ironrdp_egfx::pdu::{
+    CacheToSurfacePdu, Codec1Type, EvictCacheEntryPdu, GfxPdu, SolidFillPdu, SurfaceToCachePdu,
+    SurfaceToSurfacePdu, WireToSurface1Pdu,
+};
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+
+/// One server surface, as RGBA pixels.
+struct GfxSurface {
+    width: u16,
+    height: u16,
+    data: Vec<u8>,
+    /// Desktop position, once the surface is mapped to the output.
+    origin: Option<(u16, u16)>,
+    /// Area drawn since the last frame ended.
+    dirty: Option<ExclusiveRectangle>,
+}
+
+/// Pixels saved with `SurfaceToCache`.
+struct CachedTile {
+    width: u16,
+    height: u16,
+    data: Vec<u8>,
+}
+
+impl GfxSurface {
+    fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0; usize::from(width) * usize::from(height) * 4],
+            origin: None,
+            dirty: None,
+        }
+    }
+
+    /// The part of `rect` inside the surface, if any.
+    fn clip(&self, rect: &ExclusiveRectangle) -> Option<ExclusiveRectangle> {
+        let clipped = ExclusiveRectangle {
+            left: rect.left,
+            top: rect.top,
+            right: rect.right.min(self.width),
+            bottom: rect.bottom.min(self.height),
+        };
+        (clipped.left < clipped.right && clipped.top < clipped.bottom).then_some(clipped)
+    }
+
+    fn mark_dirty(&mut self, rect: ExclusiveRectangle) {
+        self.dirty = Some(match self.dirty.take() {
+            Some(dirty) => ExclusiveRectangle {
+                left: dirty.left.min(rect.left),
+                top: dirty.top.min(rect.top),
+                right: dirty.right.max(rect.right),
+                bottom: dirty.bottom.max(rect.bottom),
+            },
+            None => rect,
+        });
+    }
+
+    /// Copy a `width`x`height` RGBA block to (`x`, `y`), cropping whatever
+    /// falls outside the surface.
+    fn blit(&mut self, x: u16, y: u16, width: u16, height: u16, rgba: &[u8]) {
+        let target = ExclusiveRectangle {
+            left: x,
+            top: y,
+            right: x.saturating_add(width),
+            bottom: y.saturating_add(height),
+        };
+        let Some(target) = self.clip(&target) else {
+            return;
+        };
+        let row_len = usize::from(target.right - target.left) * 4;
+        let src_stride = usize::from(width) * 4;
+        let dst_stride = usize::from(self.width) * 4;
+        for row in 0..usize::from(target.bottom - target.top) {
+            let src = row * src_stride;
+            let Some(src_row) = rgba.get(src..src + row_len) else {
+                break;
+            };
+            let dst = (usize::from(target.top) + row) * dst_stride + usize::from(target.left) * 4;
+            self.data[dst..dst + row_len].copy_from_slice(src_row);
+        }
+        self.mark_dirty(target);
+    }
+
+    fn fill(&mut self, rect: &ExclusiveRectangle, pixel: [u8; 4]) {
+        let Some(target) = self.clip(rect) else {
+            return;
+        };
+        let stride = usize::from(self.width) * 4;
+        for row in target.top..target.bottom {
+            let start = usize::from(row) * stride + usize::from(target.left) * 4;
+            let end = start + usize::from(target.right - target.left) * 4;
+            for dst in self.data[start..end].chunks_exact_mut(4) {
+                dst.copy_from_slice(&pixel);
+            }
+        }
+        self.mark_dirty(target);
+    }
+
+    /// The pixels under `rect`, cropped to the surface.
+    fn read(&self, rect: &ExclusiveRectangle) -> Option<CachedTile> {
+        let source = self.clip(rect)?;
+        let stride = usize::from(self.width) * 4;
+        let row_len = usize::from(source.right - source.left) * 4;
+        let mut data = Vec::with_capacity(row_len * usize::from(source.bottom - source.top));
+        for row in source.top..source.bottom {
+            let start = usize::from(row) * stride + usize::from(source.left) * 4;
+            data.extend_from_slice(&self.data[start..start + row_len]);
+        }
+        Some(CachedTile {
+            width: source.right - source.left,
+            height: source.bottom - source.top,
+            data,
+        })
+    }
+
+    /// The dirty area as a desktop update, clearing it.
+    fn take_update(&mut self) -> Option<FrameUpdate> {
+        let (origin_x, origin_y) = self.origin?;
+        let dirty = self.dirty.take()?;
+        let tile = self.read(&dirty)?;
+        Some(FrameUpdate {
+            x: origin_x.saturating_add(dirty.left),
+            y: origin_y.saturating_add(dirty.top),
+            width: tile.width,
+            height: tile.height,
+            rgba_data: tile.data,
+        })
+    }
+}
+
+/// Composes graphics pipeline output and sends finished frames to
+/// `updates`.  Uncompressed, planar and ClearCodec bitmaps are decoded;
+/// other codecs (RemoteFX, progressive, H.264) are skipped, which is why the
+/// channel only advertises the non-AVC capability sets.
+pub struct GfxCompositor {
+    surfaces: HashMap<u16, GfxSurface>,
+    cache: HashMap<u16, CachedTile>,
+    clear_codec: ClearCodecDecoder,
+    planar: BitmapStreamDecoder,
+    updates: mpsc::UnboundedSender<FrameUpdate>,
+}
+
+impl GfxCompositor {
+    pub fn new(updates: mpsc::UnboundedSender<FrameUpdate>) -> Self {
+        Self {
+            surfaces: HashMap::new(),
+            cache: HashMap::new(),
+            clear_codec: ClearCodecDecoder::new(),
+            planar: BitmapStreamDecoder::default(),
+            updates,
+        }
+    }
+
+    /// Decode a bitmap the egfx client leaves to the handler, as RGBA.
+    fn decode(&mut self, pdu: &WireToSurface1Pdu, width: u16, height: u16) -> Option<Vec<u8>> {
+        match pdu.codec_id {
+            Codec1Type::Planar => {
+                let mut rgb = Vec::new();
+                if let Err(e) = self.planar.decode_bitmap_stream_to_rgb24(
+                    &pdu.bitmap_data,
+                    &mut rgb,
+                    usize::from(width),
+                    usize::from(height),
+                ) {
+                    tracing::warn!("Invalid planar bitmap: {}", e);
+                    return None;
+                }
+                Some(
+                    rgb.chunks_exact(3)
+                        .flat_map(|p| [p[0], p[1], p[2], 0xff])
+                        .collect(),
+                )
+            }
+            Codec1Type::ClearCodec => {
+                let mut bgra = match self.clear_codec.decode(&pdu.bitmap_data, width, height) {
+                    Ok(bgra) => bgra,
+                    Err(e) => {
+                        tracing::warn!("Invalid ClearCodec bitmap: {}", e);
+                        return None;
+                    }
+                };
+                for pixel in bgra.chunks_exact_mut(4) {
+                    pixel.swap(0, 2);
+                }
+                Some(bgra)
+            }
+            codec => {
+                tracing::debug!("Skipping graphics pipeline bitmap in {:?}", codec);
+                None
+            }
+        }
+    }
+}
+
+impl GraphicsPipelineHandler for GfxCompositor {
+    fn on_reset_graphics(&mut self, _width: u32, _height: u32) {
+        self.surfaces.clear();
+    }
+
+    fn on_surface_created(&mut self, surface: &Surface) {
+        self.surfaces
+            .insert(surface.id, GfxSurface::new(surface.width, surface.height));
+    }
+
+    fn on_surface_deleted(&mut self, surface_id: u16) {
+        self.surfaces.remove(&surface_id);
+    }
+
+    fn on_surface_mapped(&mut self, surface_id: u16, origin_x: u32, origin_y: u32) {
+        let Some(surface) = self.surfaces.get_mut(&surface_id) else {
+            return;
+        };
+        let (Ok(x), Ok(y)) = (u16::try_from(origin_x), u16::try_from(origin_y)) else {
+            return;
+        };
+        surface.origin = Some((x, y));
+        surface.mark_dirty(ExclusiveRectangle {
+            left: 0,
+            top: 0,
+            right: surface.width,
+            bottom: surface.height,
+        });
+    }
+
+    fn on_bitmap_updated(&mut self, update: &BitmapUpdate) {
+        if update.data.is_empty() {
+            return;
+        }
+        if let Some(surface) = self.surfaces.get_mut(&update.surface_id) {
+            let rect = &update.destination_rectangle;
+            surface.blit(
+                rect.left,
+                rect.top,
+                update.width,
+                update.height,
+                &update.data,
+            );
+        }
+    }
+
+    fn on_unhandled_pdu(&mut self, pdu: &GfxPdu) {
+        let GfxPdu::WireToSurface1(pdu) = pdu else {
+            return;
+        };
+        let rect = &pdu.destination_rectangle;
+        let width = rect.right.saturating_sub(rect.left);
+        let height = rect.bottom.saturating_sub(rect.top);
+        if !self.surfaces.contains_key(&pdu.surface_id) || width == 0 || height == 0 {
+            return;
+        }
+        if let Some(rgba) = self.decode(pdu, width, height) {
+            if let Some(surface) = self.surfaces.get_mut(&pdu.surface_id) {
+                surface.blit(rect.left, rect.top, width, height, &rgba);
+            }
+        }
+    }
+
+    fn on_solid_fill(&mut self, pdu: &SolidFillPdu) {
+        let Some(surface) = self.surfaces.get_mut(&pdu.surface_id) else {
+            return;
+        };
+        let color = &pdu.fill_pixel;
+        for rect in &pdu.rectangles {
+            surface.fill(rect, [color.r, color.g, color.b, 0xff]);
+        }
+    }
+
+    fn on_surface_to_surface(&mut self, pdu: &SurfaceToSurfacePdu) {
+        let Some(tile) = self
+            .surfaces
+            .get(&pdu.source_surface_id)
+            .and_then(|surface| surface.read(&pdu.source_rectangle))
+        else {
+            return;
+        };
+        if let Some(surface) = self.surfaces.get_mut(&pdu.destination_surface_id) {
+            for point in &pdu.destination_points {
+                surface.blit(point.x, point.y, tile.width, tile.height, &tile.data);
+            }
+        }
+    }
+
+    fn on_surface_to_cache(&mut self, pdu: &SurfaceToCachePdu) {
+        if let Some(tile) = self
+            .surfaces
+            .get(&pdu.surface_id)
+            .and_then(|surface| surface.read(&pdu.source_rectangle))
+        {
+            self.cache.insert(pdu.cache_slot, tile);
+        }
+    }
+
+    fn on_cache_to_surface(&mut self, pdu: &CacheToSurfacePdu) {
+        let (Some(tile), Some(surface)) = (
+            self.cache.get(&pdu.cache_slot),
+            self.surfaces.get_mut(&pdu.surface_id),
+        ) else {
+            return;
+        };
+        for point in &pdu.destination_points {
+            surface.blit(point.x, point.y, tile.width, tile.height, &tile.data);
+        }
+    }
+
+    fn on_evict_cache_entry(&mut self, pdu: &EvictCacheEntryPdu) {
+        self.cache.remove(&pdu.cache_slot);
+    }
+
+    fn on_frame_complete(&mut self, _frame_id: u32) {
+        for surface in self.surfaces.values_mut() {
+            if let Some(update) = surface.take_update() {
+                let _ = self.updates.send(update);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(left: u16, top: u16, right: u16, bottom: u16) -> ExclusiveRectangle {
+        ExclusiveRectangle {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    #[test]
+    fn test_blit_crops_to_surface_and_tracks_dirty_area() {
+        let mut surface = GfxSurface::new(4, 3);
+        let block: Vec<u8> = (0..3 * 2 * 4).map(|i| i as u8).collect();
+        surface.blit(2, 1, 3, 2, &block);
+
+        // Only the first two columns fit.
+        assert_eq!(&surface.data[(4 + 2) * 4..(4 + 4) * 4], &block[..8]);
+        assert_eq!(&surface.data[(8 + 2) * 4..(8 + 4) * 4], &block[12..20]);
+        assert_eq!(surface.dirty, Some(rect(2, 1, 4, 3)));
+
+        surface.fill(&rect(0, 0, 1, 1), [1, 2, 3, 0xff]);
+        assert_eq!(&surface.data[..4], &[1, 2, 3, 0xff]);
+        assert_eq!(surface.dirty, Some(rect(0, 0, 4, 3)));
+    }
+
+    #[test]
+    fn test_frames_report_mapped_surfaces_in_desktop_coordinates() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let mut compositor = GfxCompositor::new(tx);
+        compositor.surfaces.insert(1, GfxSurface::new(8, 8));
+        compositor.surfaces.insert(2, GfxSurface::new(8, 8));
+        compositor.on_surface_mapped(1, 100, 50);
+        compositor.on_frame_complete(0);
+        assert_eq!(rx.try_recv().unwrap().width, 8);
+
+        compositor.on_solid_fill(&SolidFillPdu {
+            surface_id: 1,
+            fill_pixel: ironrdp_egfx::pdu::Color {
+                b: 0,
+                g: 0,
+                r: 0xff,
+                xa: 0,
+            },
+            rectangles: vec![rect(2, 3, 4, 4)],
+        });
+        // Unmapped surfaces are drawn but never shown.
+        compositor
+            .surfaces
+            .get_mut(&2)
+            .unwrap()
+            .fill(&rect(0, 0, 8, 8), [0; 4]);
+        compositor.on_frame_complete(1);
+
+        let update = rx.try_recv().unwrap();
+        assert_eq!(
+            (update.x, update.y, update.width, update.height),
+            (102, 53, 2, 1)
+        );
+        assert_eq!(update.rgba_data, vec![0xff, 0, 0, 0xff, 0xff, 0, 0, 0xff]);
+        assert!(rx.try_recv().is_err());
+    }
+}
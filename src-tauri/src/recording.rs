@@ -0,0 +1,340 @@
+//! Terminal session recording in asciicast v2 format
+//! (<https://docs.asciinema.org/manual/asciicast/v2/>): a JSON header line
+//! followed by one `[seconds, code, data]` line per event.
+
+use crate::utf8::take_utf8;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+/// First line of a `.cast` file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AsciicastHeader {
+    pub version: u32,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Longest pause (seconds) a player should show; longer gaps are cut.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub idle_time_limit: Option<f64>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+}
+
+/// A recording in progress or just finished.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingInfo {
+    pub connection_id: String,
+    pub path: String,
+    /// Unix time the recording started.
+    pub started_at: u64,
+    pub include_input: bool,
+    /// Seconds recorded so far.
+    pub duration: f64,
+}
+
+/// A `.cast` file found in the recordings directory.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordingFile {
+    pub name: String,
+    pub path: String,
+    pub size: u64,
+    pub width: u32,
+    pub height: u32,
+    pub timestamp: Option<u64>,
+    pub title: Option<String>,
+}
+
+/// Writes one connection's terminal events to a `.cast` file.
+pub struct Recording {
+    connection_id: String,
+    path: PathBuf,
+    started: Instant,
+    started_at: u64,
+    include_input: bool,
+    state: Mutex<RecorderState>,
+}
+
+struct RecorderState {
+    /// Unbuffered, so an interrupted session still leaves a usable file.
+    file: File,
+    /// Bytes of a UTF-8 sequence split across reads, per event code.
+    pending_output: Vec<u8>,
+    pending_input: Vec<u8>,
+}
+
+impl Recording {
+    /// Create `path` and write the header for a `width`x`height` terminal.
+    pub fn create(
+        connection_id: &str,
+        path: PathBuf,
+        width: u32,
+        height: u32,
+        title: Option<String>,
+        include_input: bool,
+    ) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let mut file = File::create(&path)
+            .with_context(|| format!("Failed to create recording {}", path.display()))?;
+        let started_at = unix_now();
+        let header = AsciicastHeader {
+            version: 2,
+            width,
+            height,
+            timestamp: Some(started_at),
+            title,
+            idle_time_limit: None,
+            env: HashMap::from([("TERM".to_string(), "xterm-256color".to_string())]),
+        };
+        file.write_all(format!("{}\n", serde_json::to_string(&header)?).as_bytes())?;
+
+        Ok(Self {
+            connection_id: connection_id.to_string(),
+            path,
+            started: Instant::now(),
+            started_at,
+            include_input,
+            state: Mutex::new(RecorderState {
+                file,
+                pending_output: Vec::new(),
+                pending_input: Vec::new(),
+            }),
+        })
+    }
+
+    /// Record terminal output.
+    pub fn output(&self, data: &[u8]) {
+        self.record_bytes("o", data);
+    }
+
+    /// Record user input, if this recording includes it.
+    pub fn input(&self, data: &[u8]) {
+        if self.include_input {
+            self.record_bytes("i", data);
+        }
+    }
+
+    /// Record a terminal resize.
+    pub fn resize(&self, cols: u32, rows: u32) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let line = event_line(self.elapsed(), "r", &format!("{}x{}", cols, rows));
+        self.write_line(&mut state, &line);
+    }
+
+    /// Flush what has been written and describe the recording.
+    pub fn finish(&self) -> Result<RecordingInfo> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| anyhow!("Recording state poisoned"))?;
+        // Emit any incomplete UTF-8 sequence left over from the last read.
+        let leftovers = [
+            ("o", std::mem::take(&mut state.pending_output)),
+            ("i", std::mem::take(&mut state.pending_input)),
+        ];
+        for (code, pending) in leftovers {
+            if !pending.is_empty() {
+                let line = event_line(self.elapsed(), code, &String::from_utf8_lossy(&pending));
+                self.write_line(&mut state, &line);
+            }
+        }
+        state.file.sync_data()?;
+        Ok(self.info())
+    }
+
+    pub fn info(&self) -> RecordingInfo {
+        RecordingInfo {
+            connection_id: self.connection_id.clone(),
+            path: self.path.to_string_lossy().to_string(),
+            started_at: self.started_at,
+            include_input: self.include_input,
+            duration: self.elapsed(),
+        }
+    }
+
+    fn elapsed(&self) -> f64 {
+        self.started.elapsed().as_secs_f64()
+    }
+
+    fn record_bytes(&self, code: &str, data: &[u8]) {
+        let Ok(mut state) = self.state.lock() else {
+            return;
+        };
+        let pending = if code == "o" {
+            &mut state.pending_output
+        } else {
+            &mut state.pending_input
+        };
+        let text = take_utf8(pending, data);
+        if text.is_empty() {
+            return;
+        }
+        let line = event_line(self.elapsed(), code, &text);
+        self.write_line(&mut state, &line);
+    }
+
+    fn write_line(&self, state: &mut RecorderState, line: &str) {
+        if let Err(e) = state.file.write_all(line.as_bytes()) {
+            tracing::warn!("Failed to write recording {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+fn event_line(time: f64, code: &str, data: &str) -> String {
+    let data = serde_json::to_string(data).unwrap_or_else(|_| "\"\"".to_string());
+    format!("[{:.6}, \"{}\", {}]\n", time, code, data)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Where recordings go unless the caller picks a directory.
+pub fn default_recordings_dir() -> Result<PathBuf> {
+    dirs::data_dir()
+        .map(|dir| dir.join("r-shell").join("recordings"))
+        .ok_or_else(|| anyhow!("Could not determine the application data directory"))
+}
+
+/// File name for a new recording of `connection_id`.
+pub fn recording_file_name(connection_id: &str) -> String {
+    let safe: String = connection_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}-{}.cast", safe, unix_now())
+}
+
+/// List the `.cast` files in `dir`, newest first. Files whose header cannot
+/// be read are skipped.
+pub fn list_recordings(dir: &Path) -> Result<Vec<RecordingFile>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("cast") {
+            continue;
+        }
+        match read_header(&path) {
+            Ok(header) => files.push(RecordingFile {
+                name: path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                path: path.to_string_lossy().to_string(),
+                size: std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0),
+                width: header.width,
+                height: header.height,
+                timestamp: header.timestamp,
+                title: header.title,
+            }),
+            Err(e) => tracing::debug!("Skipping {}: {}", path.display(), e),
+        }
+    }
+    files.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then(a.name.cmp(&b.name)));
+    Ok(files)
+}
+
+pub fn read_header(path: &Path) -> Result<AsciicastHeader> {
+    let mut line = String::new();
+    BufReader::new(File::open(path)?).read_line(&mut line)?;
+    let header: AsciicastHeader =
+        serde_json::from_str(&line).map_err(|e| anyhow!("Not an asciicast file: {}", e))?;
+    if header.version != 2 {
+        return Err(anyhow!("Unsupported asciicast version {}", header.version));
+    }
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_header_and_events() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("nested").join("s.cast");
+        let recording =
+            Recording::create("conn", path.clone(), 80, 24, Some("demo".into()), false).unwrap();
+        recording.output(b"hello \x1b[1mworld\x1b[0m\r\n");
+        recording.input(b"ignored");
+        recording.resize(100, 30);
+        let info = recording.finish().unwrap();
+        assert_eq!(info.path, path.to_string_lossy());
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 3);
+        let header: AsciicastHeader = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!((header.version, header.width, header.height), (2, 80, 24));
+        assert_eq!(header.title.as_deref(), Some("demo"));
+
+        let output: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(output[1], "o");
+        assert_eq!(output[2], "hello \u{1b}[1mworld\u{1b}[0m\r\n");
+        let resize: serde_json::Value = serde_json::from_str(lines[2]).unwrap();
+        assert_eq!(resize[1], "r");
+        assert_eq!(resize[2], "100x30");
+    }
+
+    #[test]
+    fn input_is_recorded_when_enabled() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("s.cast");
+        let recording = Recording::create("conn", path.clone(), 80, 24, None, true).unwrap();
+        recording.input(b"ls\r");
+        recording.finish().unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.lines().nth(1).unwrap().contains(r#""i", "ls\r""#));
+    }
+
+    #[test]
+    fn lists_cast_files_only() {
+        let dir = tempfile::TempDir::new().unwrap();
+        Recording::create(
+            "a/b",
+            dir.path().join(recording_file_name("a/b")),
+            80,
+            24,
+            None,
+            false,
+        )
+        .unwrap()
+        .finish()
+        .unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "x").unwrap();
+        std::fs::write(dir.path().join("broken.cast"), "not json").unwrap();
+
+        let files = list_recordings(dir.path()).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].name.starts_with("a_b-"));
+        assert_eq!((files[0].width, files[0].height), (80, 24));
+        assert!(list_recordings(&dir.path().join("missing"))
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,214 @@
+//! Editing a remote file in a local editor: the file is downloaded into a
+//! private temp directory, and each save is uploaded back unless the server
+//! copy changed since it was last fetched.
+
+use crate::sftp_client::RemoteAttributes;
+use anyhow::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// How often the local copy is checked for saves.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EditState {
+    /// Saves are uploaded as they happen.
+    Watching,
+    /// The server copy changed; saves wait for an explicit overwrite.
+    Conflict,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteEditSession {
+    pub edit_id: String,
+    pub connection_id: String,
+    pub remote_path: String,
+    pub local_path: String,
+    /// The server copy as of the last download or upload.
+    pub remote: RemoteAttributes,
+    pub state: EditState,
+    /// Saves uploaded so far.
+    pub uploads: u64,
+}
+
+/// Reported through the `remote-edit-event` event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RemoteEditEvent {
+    Saved {
+        session: RemoteEditSession,
+    },
+    /// A save was held back because the server copy is now `current`.
+    Conflict {
+        session: RemoteEditSession,
+        current: RemoteAttributes,
+    },
+    Failed {
+        edit_id: String,
+        error: String,
+    },
+    Closed {
+        edit_id: String,
+    },
+}
+
+/// Whether the server copy differs from the one last seen. Sizes are always
+/// compared, modification times when both sides report one.
+pub fn remote_changed(known: &RemoteAttributes, current: &RemoteAttributes) -> bool {
+    let modified_changed = match (known.modified, current.modified) {
+        (Some(known), Some(current)) => known != current,
+        _ => false,
+    };
+    known.size != current.size || modified_changed
+}
+
+/// Size and mtime of the local copy, used to notice saves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalStamp {
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+}
+
+pub async fn local_stamp(path: &Path) -> Option<LocalStamp> {
+    let metadata = tokio::fs::metadata(path).await.ok()?;
+    Some(LocalStamp {
+        len: metadata.len(),
+        modified: metadata.modified().ok(),
+    })
+}
+
+/// Turns polled stamps into saves. A change counts once it has stayed the
+/// same for one poll, so a file still being written isn't uploaded half
+/// done, and editors that replace the file leave a gap without a stamp.
+pub struct SaveWatcher {
+    saved: Option<LocalStamp>,
+    previous: Option<LocalStamp>,
+}
+
+impl SaveWatcher {
+    pub fn new(initial: Option<LocalStamp>) -> Self {
+        Self {
+            saved: initial,
+            previous: initial,
+        }
+    }
+
+    /// Record the latest stamp; true when it is a new, settled save.
+    pub fn observe(&mut self, stamp: Option<LocalStamp>) -> bool {
+        let settled = stamp.is_some() && stamp == self.previous && stamp != self.saved;
+        self.previous = stamp;
+        if settled {
+            self.saved = stamp;
+        }
+        settled
+    }
+}
+
+/// Create a directory only the current user can read for one edit's copy.
+/// The name gets a random suffix, so neither another user nor a directory
+/// left behind by an earlier run can be in the way.
+pub fn create_edit_dir(edit_id: &str) -> Result<PathBuf> {
+    let prefix = format!("r-shell-{}-", edit_id);
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(&prefix);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        builder.permissions(std::fs::Permissions::from_mode(0o700));
+    }
+    let dir = builder
+        .tempdir()
+        .map_err(|e| anyhow::anyhow!("Failed to create the edit directory: {}", e))?;
+    Ok(dir.keep())
+}
+
+/// Local name for the copy of `remote_path`, keeping the extension so the
+/// editor picks the right syntax.
+pub fn local_file_name(remote_path: &str) -> String {
+    let name = remote_path
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or("");
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect();
+    if name.is_empty() || name == "." || name == ".." {
+        "untitled".to_string()
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attrs(size: u64, modified: Option<u64>) -> RemoteAttributes {
+        RemoteAttributes {
+            size: Some(size),
+            modified,
+            permissions: Some(0o644),
+        }
+    }
+
+    #[test]
+    fn detects_remote_changes_by_size_and_mtime() {
+        let known = attrs(10, Some(1_700_000_000));
+        assert!(!remote_changed(&known, &attrs(10, Some(1_700_000_000))));
+        assert!(remote_changed(&known, &attrs(11, Some(1_700_000_000))));
+        assert!(remote_changed(&known, &attrs(10, Some(1_700_000_060))));
+        // No mtime from the server: only the size can tell.
+        assert!(!remote_changed(&known, &attrs(10, None)));
+        assert!(remote_changed(&known, &attrs(12, None)));
+    }
+
+    #[test]
+    fn reports_each_save_once_it_settles() {
+        let stamp = |len| {
+            Some(LocalStamp {
+                len,
+                modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(len)),
+            })
+        };
+        let mut watcher = SaveWatcher::new(stamp(1));
+        assert!(!watcher.observe(stamp(1)));
+        // Still being written.
+        assert!(!watcher.observe(stamp(2)));
+        assert!(watcher.observe(stamp(2)));
+        assert!(!watcher.observe(stamp(2)));
+        // Replaced by the editor: briefly missing, then back with new content.
+        assert!(!watcher.observe(None));
+        assert!(!watcher.observe(stamp(3)));
+        assert!(watcher.observe(stamp(3)));
+    }
+
+    #[test]
+    fn names_the_local_copy_after_the_remote_file() {
+        assert_eq!(local_file_name("/etc/nginx/nginx.conf"), "nginx.conf");
+        assert_eq!(local_file_name("notes.md"), "notes.md");
+        assert_eq!(local_file_name("/srv/a:b?.txt"), "a_b_.txt");
+        assert_eq!(local_file_name("/"), "untitled");
+    }
+
+    #[test]
+    fn edit_dirs_are_private_and_never_collide() {
+        let first = create_edit_dir("edit-1").unwrap();
+        let second = create_edit_dir("edit-1").unwrap();
+        assert_ne!(first, second);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&first).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+        std::fs::remove_dir(first).unwrap();
+        std::fs::remove_dir(second).unwrap();
+    }
+}
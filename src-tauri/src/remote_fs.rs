@@ -0,0 +1,181 @@
+//! Attribute and link operations on a single remote path, and the error
+//! reported when a protocol can't perform one.
+
+use crate::sftp_client::FileEntryType;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+/// Operations not every file protocol can do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteFileOp {
+    ChangePermissions,
+    ChangeOwner,
+    SetModifiedTime,
+    SetAccessTime,
+    CreateSymlink,
+    ReadLink,
+    Stat,
+}
+
+impl RemoteFileOp {
+    fn describe(self) -> &'static str {
+        match self {
+            RemoteFileOp::ChangePermissions => "Changing permissions",
+            RemoteFileOp::ChangeOwner => "Changing ownership",
+            RemoteFileOp::SetModifiedTime => "Setting the modification time",
+            RemoteFileOp::SetAccessTime => "Setting the access time",
+            RemoteFileOp::CreateSymlink => "Creating symlinks",
+            RemoteFileOp::ReadLink => "Reading symlinks",
+            RemoteFileOp::Stat => "Reading file attributes",
+        }
+    }
+}
+
+/// The error for `op` on a connection whose protocol can't do it, worded the
+/// same for every backend.
+pub fn unsupported(op: RemoteFileOp, protocol: &str) -> anyhow::Error {
+    anyhow::anyhow!("{} is not supported over {}", op.describe(), protocol)
+}
+
+/// Everything the server reports about one path. Fields the protocol
+/// doesn't carry are `None`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RemoteFileStat {
+    pub path: String,
+    pub file_type: FileEntryType,
+    pub size: Option<u64>,
+    /// Permission bits, e.g. `0o644`.
+    pub permissions: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Owner and group names, where the listing has them.
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    /// Unix seconds.
+    pub accessed: Option<u64>,
+    pub modified: Option<u64>,
+    /// What a symlink points to.
+    pub link_target: Option<String>,
+}
+
+/// Attributes to change on one remote path; `None` leaves a field as is.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct AttributeChanges {
+    pub permissions: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Unix seconds.
+    pub accessed: Option<u64>,
+    pub modified: Option<u64>,
+}
+
+impl AttributeChanges {
+    pub fn is_empty(&self) -> bool {
+        self.permissions.is_none()
+            && self.uid.is_none()
+            && self.gid.is_none()
+            && self.accessed.is_none()
+            && self.modified.is_none()
+    }
+
+    /// The operations these changes need, to check them against what a
+    /// protocol supports.
+    pub fn operations(&self) -> Vec<RemoteFileOp> {
+        [
+            (self.permissions.is_some(), RemoteFileOp::ChangePermissions),
+            (
+                self.uid.is_some() || self.gid.is_some(),
+                RemoteFileOp::ChangeOwner,
+            ),
+            (self.modified.is_some(), RemoteFileOp::SetModifiedTime),
+            (self.accessed.is_some(), RemoteFileOp::SetAccessTime),
+        ]
+        .into_iter()
+        .filter_map(|(requested, op)| requested.then_some(op))
+        .collect()
+    }
+}
+
+/// Parse an octal mode as typed for `chmod`, e.g. `755` or `0644`.
+pub fn parse_mode(mode: &str) -> Result<u32> {
+    let digits = mode.trim();
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o7777)
+        .ok_or_else(|| anyhow::anyhow!("Invalid mode '{}': expected octal such as 755", mode))
+}
+
+/// Permission bits from an `ls -l` mode column such as `drwxr-sr-x` or
+/// `-rw-r--r--+`.
+pub fn parse_permission_string(text: &str) -> Option<u32> {
+    let chars: Vec<char> = text.trim_end_matches(['+', '.', '@']).chars().collect();
+    let bits = match chars.len() {
+        10 => &chars[1..],
+        9 => &chars[..],
+        _ => return None,
+    };
+    let mut mode = 0;
+    for (class, triple) in bits.chunks(3).enumerate() {
+        let shift = 6 - 3 * class as u32;
+        // setuid, setgid and sticky, in class order.
+        let special = 0o4000 >> class;
+        match triple[0] {
+            'r' => mode |= 0o4 << shift,
+            '-' => {}
+            _ => return None,
+        }
+        match triple[1] {
+            'w' => mode |= 0o2 << shift,
+            '-' => {}
+            _ => return None,
+        }
+        match triple[2] {
+            'x' => mode |= 0o1 << shift,
+            's' | 't' => mode |= (0o1 << shift) | special,
+            'S' | 'T' => mode |= special,
+            '-' => {}
+            _ => return None,
+        }
+    }
+    Some(mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_octal_modes() {
+        assert_eq!(parse_mode("755").unwrap(), 0o755);
+        assert_eq!(parse_mode(" 0644 ").unwrap(), 0o644);
+        assert_eq!(parse_mode("4755").unwrap(), 0o4755);
+        assert!(parse_mode("").is_err());
+        assert!(parse_mode("789").is_err());
+        assert!(parse_mode("17777").is_err());
+        assert!(parse_mode("u+x").is_err());
+    }
+
+    #[test]
+    fn parses_listing_permission_columns() {
+        assert_eq!(parse_permission_string("-rw-r--r--"), Some(0o644));
+        assert_eq!(parse_permission_string("drwxr-xr-x"), Some(0o755));
+        assert_eq!(parse_permission_string("rwx------"), Some(0o700));
+        assert_eq!(parse_permission_string("-rwsr-sr-x"), Some(0o6755));
+        assert_eq!(parse_permission_string("drwxrwxrwt"), Some(0o1777));
+        assert_eq!(parse_permission_string("-rwSr--r-T"), Some(0o5644));
+        assert_eq!(parse_permission_string("-rw-r--r--+"), Some(0o644));
+        assert_eq!(parse_permission_string("<DIR>"), None);
+    }
+
+    #[test]
+    fn words_missing_capabilities_the_same_way() {
+        assert_eq!(
+            unsupported(RemoteFileOp::ChangeOwner, "FTP").to_string(),
+            "Changing ownership is not supported over FTP"
+        );
+        assert_eq!(
+            unsupported(RemoteFileOp::ReadLink, "TELNET").to_string(),
+            "Reading symlinks is not supported over TELNET"
+        );
+    }
+}
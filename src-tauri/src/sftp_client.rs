@@ -1,13 +1,20 @@
 use anyhow::Result;
 use russh::*;
-use russh_keys::*;
 use russh_sftp::client::SftpSession;
+use russh_sftp::protocol::OpenFlags;
 use serde::{Deserialize, Serialize};
+use std::io::SeekFrom;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
-use crate::ssh::Client;
+use crate::auth_prompt::AuthPrompter;
+use crate::known_hosts::HostKeyVerifier;
+use crate::remote_fs::{AttributeChanges, RemoteFileStat};
+use crate::ssh::{
+    authenticate_methods, with_handshake_timeout, AuthMethod, Client, JumpChain, JumpHost,
+};
+use crate::transfer::{copy_with_progress, resume_offset, Transfer, CHUNK_SIZE};
 
 /// Configuration for a standalone SFTP connection (SSH transport, no PTY).
 #[derive(Debug, Clone, Deserialize)]
@@ -16,6 +23,13 @@ pub struct SftpConfig {
     pub port: u16,
     pub username: String,
     pub auth_method: SftpAuthMethod,
+    /// Further methods tried in order after `auth_method` when the server
+    /// requires more than one.
+    #[serde(default)]
+    pub additional_auth_methods: Vec<SftpAuthMethod>,
+    /// Bastions to hop through, outermost first (OpenSSH `-J a,b`).
+    #[serde(default)]
+    pub jump_hosts: Vec<JumpHost>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -27,7 +41,39 @@ pub enum SftpAuthMethod {
     PublicKey {
         key_path: String,
         passphrase: Option<String>,
+        /// OpenSSH certificate to present with the key. Defaults to
+        /// `<key_path>-cert.pub` when that file exists.
+        #[serde(default)]
+        certificate_path: Option<String>,
     },
+    /// Use the identities held by the local SSH agent (`SSH_AUTH_SOCK`).
+    Agent,
+    /// Answer server prompts (PAM, OTP) through the frontend.
+    KeyboardInteractive {
+        #[serde(default)]
+        password: Option<String>,
+    },
+}
+
+impl From<SftpAuthMethod> for AuthMethod {
+    fn from(method: SftpAuthMethod) -> Self {
+        match method {
+            SftpAuthMethod::Password { password } => AuthMethod::Password { password },
+            SftpAuthMethod::PublicKey {
+                key_path,
+                passphrase,
+                certificate_path,
+            } => AuthMethod::PublicKey {
+                key_path,
+                passphrase,
+                certificate_path,
+            },
+            SftpAuthMethod::Agent => AuthMethod::Agent,
+            SftpAuthMethod::KeyboardInteractive { password } => {
+                AuthMethod::KeyboardInteractive { password }
+            }
+        }
+    }
 }
 
 /// A single file/directory entry returned from directory listings.
@@ -56,6 +102,152 @@ pub enum FileEntryType {
     Symlink,
 }
 
+/// Attributes read from or applied to a single remote path. Fields the
+/// backend doesn't report, or that shouldn't change, are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RemoteAttributes {
+    pub size: Option<u64>,
+    /// Unix seconds.
+    pub modified: Option<u64>,
+    /// Permission bits, e.g. `0o644`.
+    pub permissions: Option<u32>,
+}
+
+pub(crate) async fn sftp_stat(sftp: &SftpSession, path: &str) -> Result<RemoteAttributes> {
+    let attrs = sftp
+        .metadata(path)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to stat '{}': {}", path, e))?;
+    Ok(RemoteAttributes {
+        size: attrs.size,
+        modified: attrs.mtime.map(u64::from),
+        permissions: attrs.permissions.map(|mode| mode & 0o7777),
+    })
+}
+
+/// SFTP version 3 sends times as 32-bit Unix seconds, which run out in 2106.
+fn sftp_time(secs: u64) -> Result<u32> {
+    u32::try_from(secs)
+        .map_err(|_| anyhow::anyhow!("Time {} is too late for SFTP, which stops at 2106", secs))
+}
+
+/// Apply the `Some` fields of `attrs` (except size) with SETSTAT.
+pub(crate) async fn sftp_set_attributes(
+    sftp: &SftpSession,
+    path: &str,
+    attrs: &RemoteAttributes,
+) -> Result<()> {
+    let mut update = russh_sftp::protocol::FileAttributes::empty();
+    update.permissions = attrs.permissions;
+    if let Some(modified) = attrs.modified {
+        // SFTP sets both times together.
+        let secs = sftp_time(modified)?;
+        update.atime = Some(secs);
+        update.mtime = Some(secs);
+    }
+    sftp.set_metadata(path, update)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to set attributes of '{}': {}", path, e))
+}
+
+/// Full attributes of `path` itself, not following a symlink, with the
+/// link's target when it is one.
+pub(crate) async fn sftp_lstat(sftp: &SftpSession, path: &str) -> Result<RemoteFileStat> {
+    let attrs = sftp
+        .symlink_metadata(path)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to stat '{}': {}", path, e))?;
+    let file_type = if attrs.is_dir() {
+        FileEntryType::Directory
+    } else if attrs.is_symlink() {
+        FileEntryType::Symlink
+    } else {
+        FileEntryType::File
+    };
+    let link_target = if file_type == FileEntryType::Symlink {
+        sftp.read_link(path).await.ok()
+    } else {
+        None
+    };
+    Ok(RemoteFileStat {
+        path: path.to_string(),
+        file_type,
+        size: attrs.size,
+        permissions: attrs.permissions.map(|mode| mode & 0o7777),
+        uid: attrs.uid,
+        gid: attrs.gid,
+        owner: attrs.user.clone(),
+        group: attrs.group.clone(),
+        accessed: attrs.atime.map(u64::from),
+        modified: attrs.mtime.map(u64::from),
+        link_target,
+    })
+}
+
+/// Apply `changes` with SETSTAT. The protocol sends uid with gid and atime
+/// with mtime, writing 0 for a missing half, so a half-given pair is
+/// completed from the current attributes.
+pub(crate) async fn sftp_change_attributes(
+    sftp: &SftpSession,
+    path: &str,
+    changes: &AttributeChanges,
+) -> Result<()> {
+    let mut update = russh_sftp::protocol::FileAttributes::empty();
+    update.permissions = changes.permissions;
+    update.uid = changes.uid;
+    update.gid = changes.gid;
+    update.atime = changes.accessed.map(sftp_time).transpose()?;
+    update.mtime = changes.modified.map(sftp_time).transpose()?;
+
+    let half_owner = update.uid.is_some() != update.gid.is_some();
+    let half_times = update.atime.is_some() != update.mtime.is_some();
+    if half_owner || half_times {
+        let current = sftp
+            .metadata(path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to stat '{}': {}", path, e))?;
+        if half_owner {
+            update.uid = update.uid.or(current.uid);
+            update.gid = update.gid.or(current.gid);
+        }
+        if half_times {
+            update.atime = update.atime.or(current.atime);
+            update.mtime = update.mtime.or(current.mtime);
+        }
+        if update.uid.is_some() != update.gid.is_some()
+            || update.atime.is_some() != update.mtime.is_some()
+        {
+            return Err(anyhow::anyhow!(
+                "Failed to set attributes of '{}': the server didn't report the current values",
+                path
+            ));
+        }
+    }
+    sftp.set_metadata(path, update)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to set attributes of '{}': {}", path, e))
+}
+
+/// Create `link_path` pointing at `target`.
+pub(crate) async fn sftp_symlink(sftp: &SftpSession, link_path: &str, target: &str) -> Result<()> {
+    // OpenSSH's sftp-server takes SYMLINK's arguments in the reverse of the
+    // draft's order, and clients follow it since it is what servers run.
+    sftp.symlink(target, link_path).await.map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to create symlink '{}' -> '{}': {}",
+            link_path,
+            target,
+            e
+        )
+    })
+}
+
+pub(crate) async fn sftp_read_link(sftp: &SftpSession, path: &str) -> Result<String> {
+    sftp.read_link(path)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to read link '{}': {}", path, e))
+}
+
 pub(crate) async fn list_sftp_dir(sftp: &SftpSession, path: &str) -> Result<Vec<RemoteFileEntry>> {
     let entries = sftp
         .read_dir(path)
@@ -102,11 +294,115 @@ pub(crate) async fn list_sftp_dir(sftp: &SftpSession, path: &str) -> Result<Vec<
     Ok(result)
 }
 
+/// Download `remote_path` to `local_path`, appending to a partial local file
+/// when the transfer asks to resume. Returns the bytes copied.
+pub(crate) async fn sftp_download(
+    sftp: &SftpSession,
+    remote_path: &str,
+    local_path: &str,
+    transfer: &mut Transfer,
+) -> Result<u64> {
+    let mut remote_file = sftp
+        .open(remote_path)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to open remote file '{}': {}", remote_path, e))?;
+    let total = remote_file
+        .metadata()
+        .await
+        .ok()
+        .and_then(|attrs| attrs.size);
+    let existing = if transfer.resume_requested() {
+        tokio::fs::metadata(local_path).await.ok().map(|m| m.len())
+    } else {
+        None
+    };
+    let offset = resume_offset(existing, total);
+    let mut local_file = if offset > 0 {
+        remote_file.seek(SeekFrom::Start(offset)).await?;
+        tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(local_path)
+            .await?
+    } else {
+        tokio::fs::File::create(local_path).await?
+    };
+
+    transfer.start(offset, total);
+    copy_with_progress(&mut remote_file, &mut local_file, transfer).await?;
+    Ok(transfer.finish())
+}
+
+/// Read `remote_path` front to back, handing each chunk to `on_chunk`.
+/// Returns the bytes read.
+pub(crate) async fn sftp_read_chunks(
+    sftp: &SftpSession,
+    remote_path: &str,
+    mut on_chunk: impl FnMut(&[u8]),
+) -> Result<u64> {
+    let mut remote_file = sftp
+        .open(remote_path)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to open remote file '{}': {}", remote_path, e))?;
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut total = 0;
+    loop {
+        let n = remote_file.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(total);
+        }
+        on_chunk(&buf[..n]);
+        total += n as u64;
+    }
+}
+
+/// Upload `local_path` to `remote_path`, continuing a shorter remote file
+/// when the transfer asks to resume. Returns the bytes copied.
+pub(crate) async fn sftp_upload(
+    sftp: &SftpSession,
+    local_path: &str,
+    remote_path: &str,
+    transfer: &mut Transfer,
+) -> Result<u64> {
+    let mut local_file = tokio::fs::File::open(local_path)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to read local file '{}': {}", local_path, e))?;
+    let total = local_file.metadata().await?.len();
+    let existing = if transfer.resume_requested() {
+        sftp.metadata(remote_path)
+            .await
+            .ok()
+            .and_then(|attrs| attrs.size)
+    } else {
+        None
+    };
+    let offset = resume_offset(existing, Some(total));
+    let mut remote_file = if offset > 0 {
+        let mut remote_file = sftp
+            .open_with_flags(remote_path, OpenFlags::WRITE)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to open remote file '{}': {}", remote_path, e))?;
+        remote_file.seek(SeekFrom::Start(offset)).await?;
+        local_file.seek(SeekFrom::Start(offset)).await?;
+        remote_file
+    } else {
+        sftp.create(remote_path)
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to create remote file '{}': {}", remote_path, e))?
+    };
+
+    transfer.start(offset, Some(total));
+    copy_with_progress(&mut local_file, &mut remote_file, transfer).await?;
+    remote_file.shutdown().await?;
+    Ok(transfer.finish())
+}
+
 /// Standalone SFTP client — opens an SSH connection and SFTP subsystem
 /// channel without allocating a PTY.
 pub struct StandaloneSftpClient {
     session: Option<Arc<client::Handle<Client>>>,
     sftp: Option<SftpSession>,
+    /// Bastion sessions carrying `session`, kept open for its lifetime.
+    jump_chain: Option<JumpChain>,
 }
 
 impl StandaloneSftpClient {
@@ -114,11 +410,19 @@ impl StandaloneSftpClient {
         Self {
             session: None,
             sftp: None,
+            jump_chain: None,
         }
     }
 
     /// Establish an SSH connection, authenticate, and open the SFTP subsystem.
-    pub async fn connect(config: &SftpConfig) -> Result<Self> {
+    /// The server's host key is checked with `host_key_verifier`, the same
+    /// verifier used by SSH terminal connections; keyboard-interactive prompts
+    /// are relayed through `auth_prompter`.
+    pub async fn connect(
+        config: &SftpConfig,
+        host_key_verifier: Arc<HostKeyVerifier>,
+        auth_prompter: &AuthPrompter,
+    ) -> Result<Self> {
         let ssh_config = client::Config {
             preferred: russh::Preferred {
                 key: std::borrow::Cow::Borrowed(crate::ssh::PREFERRED_HOST_KEY_ALGOS),
@@ -126,83 +430,78 @@ impl StandaloneSftpClient {
             },
             ..client::Config::default()
         };
+        let ssh_config = Arc::new(ssh_config);
         let connection_timeout = Duration::from_secs(10);
 
-        let mut ssh_session = tokio::time::timeout(
-            connection_timeout,
-            client::connect(
-                Arc::new(ssh_config),
-                (&config.host[..], config.port),
-                Client,
-            ),
-        )
-        .await
-        .map_err(|_| {
-            anyhow::anyhow!(
-                "SFTP connection timed out after 10 seconds. Please check the host and network."
-            )
-        })?
-        .map_err(|e| {
-            anyhow::anyhow!(
-                "Failed to connect to {}:{}: {}",
-                config.host,
-                config.port,
-                e
+        let jump_chain = if config.jump_hosts.is_empty() {
+            None
+        } else {
+            Some(
+                JumpChain::connect(
+                    &config.jump_hosts,
+                    None,
+                    ssh_config.clone(),
+                    &host_key_verifier,
+                    auth_prompter,
+                    connection_timeout,
+                )
+                .await?,
             )
-        })?;
+        };
 
-        // Authenticate
-        let authenticated = match &config.auth_method {
-            SftpAuthMethod::Password { password } => ssh_session
-                .authenticate_password(&config.username, password)
+        let handler = Client::new(&config.host, config.port, host_key_verifier);
+        let prompting = handler.prompt_flag();
+
+        let handshake = match &jump_chain {
+            Some(chain) => {
+                let tunnel = chain.open_tunnel(&config.host, config.port).await?;
+                with_handshake_timeout(
+                    client::connect_stream(ssh_config, tunnel, handler),
+                    connection_timeout,
+                    &prompting,
+                )
+                .await
+            }
+            None => {
+                with_handshake_timeout(
+                    client::connect(ssh_config, (&config.host[..], config.port), handler),
+                    connection_timeout,
+                    &prompting,
+                )
                 .await
-                .map_err(|e| anyhow::anyhow!("SFTP password authentication failed: {}", e))?,
-            SftpAuthMethod::PublicKey {
-                key_path,
-                passphrase,
-            } => {
-                let expanded_path = if key_path.starts_with("~/") {
-                    if let Ok(home) = std::env::var("HOME") {
-                        key_path.replacen("~", &home, 1)
-                    } else {
-                        key_path.clone()
-                    }
-                } else {
-                    key_path.clone()
-                };
-
-                if !std::path::Path::new(&expanded_path).exists() {
-                    return Err(anyhow::anyhow!(
-                        "SSH key file not found: {}. Please check the file path.",
-                        key_path
-                    ));
-                }
-
-                let key =
-                    decode_secret_key(&expanded_path, passphrase.as_deref()).map_err(|e| {
-                        if e.to_string().contains("encrypted")
-                            || e.to_string().contains("passphrase")
-                        {
-                            anyhow::anyhow!(
-                                "Failed to decrypt SSH key. Please provide the correct passphrase."
-                            )
-                        } else {
-                            anyhow::anyhow!("Failed to load SSH key from {}: {}.", key_path, e)
-                        }
-                    })?;
-
-                ssh_session
-                    .authenticate_publickey(&config.username, Arc::new(key))
-                    .await
-                    .map_err(|e| {
-                        anyhow::anyhow!(
-                            "SFTP public key authentication failed: {}. The key may not be authorized on the server.",
-                            e
-                        )
-                    })?
             }
         };
+        let mut ssh_session = handshake
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "SFTP connection timed out after 10 seconds. \
+                     Please check the host and network."
+                )
+            })?
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Failed to connect to {}:{}: {}",
+                    config.host,
+                    config.port,
+                    e
+                )
+            })?;
 
+        // Authenticate
+        let methods: Vec<AuthMethod> = std::iter::once(&config.auth_method)
+            .chain(&config.additional_auth_methods)
+            .cloned()
+            .map(AuthMethod::from)
+            .collect();
+        let authenticated = authenticate_methods(
+            &mut ssh_session,
+            auth_prompter,
+            &config.host,
+            config.port,
+            &config.username,
+            &methods.iter().collect::<Vec<_>>(),
+        )
+        .await?;
         if !authenticated {
             return Err(anyhow::anyhow!(
                 "SFTP authentication failed. Please check your credentials."
@@ -219,6 +518,7 @@ impl StandaloneSftpClient {
         Ok(Self {
             session: Some(session),
             sftp: Some(sftp),
+            jump_chain,
         })
     }
 
@@ -236,12 +536,16 @@ impl StandaloneSftpClient {
                     let _ = session
                         .disconnect(Disconnect::ByApplication, "", "English")
                         .await;
+                    if let Some(chain) = self.jump_chain.take() {
+                        chain.disconnect().await;
+                    }
                 }
                 Err(arc_session) => {
                     drop(arc_session);
                 }
             }
         }
+        self.jump_chain = None;
         Ok(())
     }
 
@@ -258,61 +562,26 @@ impl StandaloneSftpClient {
         list_sftp_dir(self.sftp_session()?, path).await
     }
 
-    /// Download a remote file to a local path. Returns bytes downloaded.
-    pub async fn download_file(&self, remote_path: &str, local_path: &str) -> Result<u64> {
-        let sftp = self
-            .sftp
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("SFTP session not connected"))?;
-
-        let mut remote_file = sftp
-            .open(remote_path)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to open remote file '{}': {}", remote_path, e))?;
-
-        let mut buffer = Vec::new();
-        let mut temp_buf = vec![0u8; 32768];
-        let mut total_bytes = 0u64;
-
-        loop {
-            let n = remote_file.read(&mut temp_buf).await?;
-            if n == 0 {
-                break;
-            }
-            buffer.extend_from_slice(&temp_buf[..n]);
-            total_bytes += n as u64;
-        }
-
-        tokio::fs::write(local_path, buffer).await?;
-        Ok(total_bytes)
+    /// Download a remote file to a local path with progress, cancellation
+    /// and resume through `transfer`. Returns bytes downloaded.
+    pub async fn download(
+        &self,
+        remote_path: &str,
+        local_path: &str,
+        transfer: &mut Transfer,
+    ) -> Result<u64> {
+        sftp_download(self.sftp_session()?, remote_path, local_path, transfer).await
     }
 
-    /// Upload a local file to a remote path. Returns bytes uploaded.
-    pub async fn upload_file(&self, local_path: &str, remote_path: &str) -> Result<u64> {
-        let sftp = self
-            .sftp
-            .as_ref()
-            .ok_or_else(|| anyhow::anyhow!("SFTP session not connected"))?;
-
-        let data = tokio::fs::read(local_path)
-            .await
-            .map_err(|e| anyhow::anyhow!("Failed to read local file '{}': {}", local_path, e))?;
-        let total_bytes = data.len() as u64;
-
-        let mut remote_file = sftp.create(remote_path).await.map_err(|e| {
-            anyhow::anyhow!("Failed to create remote file '{}': {}", remote_path, e)
-        })?;
-
-        let chunk_size = 32768;
-        let mut offset = 0;
-        while offset < data.len() {
-            let end = std::cmp::min(offset + chunk_size, data.len());
-            remote_file.write_all(&data[offset..end]).await?;
-            offset = end;
-        }
-        remote_file.flush().await?;
-
-        Ok(total_bytes)
+    /// Upload a local file to a remote path with progress, cancellation and
+    /// resume through `transfer`. Returns bytes uploaded.
+    pub async fn upload(
+        &self,
+        local_path: &str,
+        remote_path: &str,
+        transfer: &mut Transfer,
+    ) -> Result<u64> {
+        sftp_upload(self.sftp_session()?, local_path, remote_path, transfer).await
     }
 
     /// Create a directory on the remote server.
@@ -391,7 +660,30 @@ fn chrono_from_unix_timestamp(secs: u64) -> String {
     )
 }
 
-fn days_to_ymd(mut days: i64) -> (i64, u32, u32) {
+/// Read back a `YYYY-MM-DD HH:MM:SS` listing time (UTC) as a Unix
+/// timestamp.
+pub(crate) fn parse_listing_timestamp(text: &str) -> Option<u64> {
+    let (date, time) = text.trim().split_once(' ')?;
+    let mut date = date.splitn(3, '-').map(|part| part.parse::<i64>().ok());
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let mut time = time.splitn(3, ':').map(|part| part.parse::<i64>().ok());
+    let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next().flatten());
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Inverse of `days_to_ymd`.
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    let secs = days * 86400 + hours * 3600 + minutes * 60 + seconds.unwrap_or(0);
+    u64::try_from(secs).ok()
+}
+
+pub(crate) fn days_to_ymd(mut days: i64) -> (i64, u32, u32) {
     // Algorithm to convert days since 1970-01-01 to y/m/d
     days += 719468; // shift to 0000-03-01
     let era = if days >= 0 { days } else { days - 146096 } / 146097;
@@ -464,6 +756,13 @@ mod tests {
         assert_eq!(format_permissions(0o200), "-w-------");
     }
 
+    #[test]
+    fn test_sftp_time_rejects_times_past_2106() {
+        assert_eq!(sftp_time(1704067200).unwrap(), 1704067200);
+        assert_eq!(sftp_time(u64::from(u32::MAX)).unwrap(), u32::MAX);
+        assert!(sftp_time(u64::from(u32::MAX) + 1).is_err());
+    }
+
     #[test]
     fn test_chrono_from_unix_timestamp_epoch() {
         let result = chrono_from_unix_timestamp(0);
@@ -539,6 +838,19 @@ mod tests {
         assert!(json.contains("\"modified\":null"));
     }
 
+    #[test]
+    fn test_listing_timestamp_round_trip() {
+        for secs in [0, 951_782_400, 1_709_164_799, 4_102_444_800] {
+            let text = chrono_from_unix_timestamp(secs);
+            assert_eq!(parse_listing_timestamp(&text), Some(secs), "{text}");
+        }
+        assert_eq!(
+            parse_listing_timestamp("2024-02-28 23:59"),
+            Some(1_709_164_740)
+        );
+        assert_eq!(parse_listing_timestamp("not a date"), None);
+    }
+
     #[test]
     fn test_symlink_entry_serialization() {
         let entry = RemoteFileEntry {
@@ -576,7 +888,9 @@ mod tests {
             SftpAuthMethod::PublicKey {
                 key_path,
                 passphrase,
+                certificate_path,
             } => {
+                assert!(certificate_path.is_none());
                 assert_eq!(key_path, "/home/user/.ssh/id_rsa");
                 assert!(passphrase.is_none());
             }
@@ -584,6 +898,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_sftp_config_agent() {
+        let json =
+            r#"{"host":"server","port":22,"username":"deploy","auth_method":{"type":"Agent"}}"#;
+        let config: SftpConfig = serde_json::from_str(json).unwrap();
+        assert!(matches!(config.auth_method, SftpAuthMethod::Agent));
+    }
+
+    #[test]
+    fn test_sftp_auth_method_converts_to_ssh_auth_method() {
+        let method = AuthMethod::from(SftpAuthMethod::PublicKey {
+            key_path: "~/.ssh/id_ed25519".to_string(),
+            passphrase: Some("secret".to_string()),
+            certificate_path: Some("~/.ssh/id_ed25519-cert.pub".to_string()),
+        });
+        match method {
+            AuthMethod::PublicKey {
+                key_path,
+                passphrase,
+                certificate_path,
+            } => {
+                assert_eq!(key_path, "~/.ssh/id_ed25519");
+                assert_eq!(passphrase.as_deref(), Some("secret"));
+                assert_eq!(
+                    certificate_path.as_deref(),
+                    Some("~/.ssh/id_ed25519-cert.pub")
+                );
+            }
+            _ => panic!("Expected PublicKey auth method"),
+        }
+        assert!(matches!(
+            AuthMethod::from(SftpAuthMethod::KeyboardInteractive { password: None }),
+            AuthMethod::KeyboardInteractive { password: None }
+        ));
+    }
+
     #[tokio::test]
     async fn test_disconnect_on_new_client_is_ok() {
         let mut client = StandaloneSftpClient::new();
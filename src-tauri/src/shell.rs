@@ -0,0 +1,24 @@
+/// Escape a value for use inside a POSIX single-quoted shell argument.
+/// Single quotes cannot appear inside a single-quoted string, so we end the
+/// quote, emit the escaped quote, and reopen the quote: `'` → `'\''`.
+pub fn escape_single_quoted(value: &str) -> String {
+    value.replace('\'', "'\\''")
+}
+
+/// `value` as a single POSIX shell word.
+pub fn quote(value: &str) -> String {
+    format!("'{}'", escape_single_quoted(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_single_quotes_and_metacharacters() {
+        assert_eq!(quote("plain"), "'plain'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote("$(id); rm"), "'$(id); rm'");
+        assert_eq!(escape_single_quoted("a'b"), r"a'\''b");
+    }
+}
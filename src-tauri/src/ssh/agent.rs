@@ -0,0 +1,280 @@
+//! SSH agent support: authenticating with identities held by the local agent
+//! (`SSH_AUTH_SOCK`) and relaying forwarded agent channels back to it.
+
+use super::{Client, MethodUnavailable, SessionHandle};
+use anyhow::{anyhow, Result};
+use russh::client;
+use russh::{ChannelId, CryptoVec};
+use russh_keys::agent::client::AgentClient;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, Weak};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{mpsc, RwLock};
+
+#[cfg(unix)]
+type AgentStream = tokio::net::UnixStream;
+#[cfg(not(unix))]
+type AgentStream = tokio::net::TcpStream;
+
+/// Agent replies larger than this are treated as a protocol error rather than
+/// allocated (OpenSSH uses the same 256 KiB ceiling).
+const MAX_AGENT_MESSAGE: usize = 256 * 1024;
+
+async fn connect_agent_socket() -> Result<AgentStream> {
+    #[cfg(unix)]
+    {
+        let path = std::env::var_os("SSH_AUTH_SOCK").ok_or_else(|| {
+            anyhow!("SSH agent not available: SSH_AUTH_SOCK is not set. Start ssh-agent and add a key with ssh-add.")
+        })?;
+        AgentStream::connect(&path).await.map_err(|e| {
+            anyhow!(
+                "Failed to connect to SSH agent at {}: {}",
+                path.to_string_lossy(),
+                e
+            )
+        })
+    }
+    #[cfg(not(unix))]
+    {
+        Err(anyhow!(
+            "SSH agent authentication is only supported on Unix platforms"
+        ))
+    }
+}
+
+/// Try every identity offered by the local SSH agent until the server accepts
+/// one. Returns `Ok(false)` when none of them is authorized, and a
+/// [`MethodUnavailable`] error when the agent can't be used at all.
+pub(crate) async fn authenticate_with_agent(
+    session: &mut client::Handle<Client>,
+    username: &str,
+) -> Result<bool> {
+    let stream = connect_agent_socket()
+        .await
+        .map_err(|e| MethodUnavailable(e.to_string()))?;
+    let mut agent = AgentClient::connect(stream);
+    let identities = agent
+        .request_identities()
+        .await
+        .map_err(|e| MethodUnavailable(format!("Failed to list SSH agent identities: {}", e)))?;
+    if identities.is_empty() {
+        return Err(MethodUnavailable(
+            "The SSH agent has no identities. Add a key with ssh-add and try again.".to_string(),
+        )
+        .into());
+    }
+
+    for key in identities {
+        let fingerprint = key.fingerprint();
+        let (returned, result) = session.authenticate_future(username, key, agent).await;
+        agent = returned;
+        match result {
+            Ok(true) => return Ok(true),
+            Ok(false) => {
+                tracing::debug!("SSH agent key {} rejected by server", fingerprint);
+            }
+            // The agent may refuse to sign (e.g. a hardware key that was not
+            // touched); move on to the next identity.
+            Err(e) => {
+                tracing::debug!("SSH agent could not sign with key {}: {}", fingerprint, e);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Split the first complete length-prefixed agent message off `buf`,
+/// including its 4-byte length header.
+pub(crate) fn take_agent_message(buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+    if buf.len() < 4 {
+        return Ok(None);
+    }
+    let len = u32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+    if len > MAX_AGENT_MESSAGE {
+        return Err(anyhow!("SSH agent message too large ({} bytes)", len));
+    }
+    if buf.len() < 4 + len {
+        return Ok(None);
+    }
+    let rest = buf.split_off(4 + len);
+    Ok(Some(std::mem::replace(buf, rest)))
+}
+
+/// `SSH_AGENT_FAILURE`, sent for requests the local agent couldn't answer.
+const AGENT_FAILURE: [u8; 5] = [0, 0, 0, 1, 5];
+
+/// Send one request to the agent and read back its reply, length header
+/// included.
+async fn agent_exchange(stream: &mut AgentStream, request: &[u8]) -> Result<Vec<u8>> {
+    stream.write_all(request).await?;
+
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).await?;
+    let len = u32::from_be_bytes(header) as usize;
+    if len > MAX_AGENT_MESSAGE {
+        return Err(anyhow!("SSH agent reply too large ({} bytes)", len));
+    }
+    let mut reply = Vec::with_capacity(4 + len);
+    reply.extend_from_slice(&header);
+    reply.resize(4 + len, 0);
+    stream.read_exact(&mut reply[4..]).await?;
+    Ok(reply)
+}
+
+/// The connection's handle, for writing agent replies from outside the
+/// russh handler. Set once the session is established, and weak so the
+/// handler doesn't keep its own session alive.
+#[derive(Clone, Default)]
+pub(crate) struct AgentSession(Arc<OnceLock<Weak<RwLock<client::Handle<Client>>>>>);
+
+impl AgentSession {
+    pub(crate) fn attach(&self, session: &SessionHandle) {
+        let _ = self.0.set(Arc::downgrade(session));
+    }
+
+    fn get(&self) -> Option<SessionHandle> {
+        self.0.get().and_then(Weak::upgrade)
+    }
+}
+
+/// Relay one forwarded channel: data from the server arrives on `requests`,
+/// each complete request goes to the local agent, and the reply is written
+/// back through the session handle. Requests the agent can't answer get
+/// `SSH_AGENT_FAILURE` so the remote client doesn't hang.
+async fn relay_agent_channel(
+    channel: ChannelId,
+    mut requests: mpsc::UnboundedReceiver<Vec<u8>>,
+    session: AgentSession,
+) {
+    let mut agent = match connect_agent_socket().await {
+        Ok(stream) => Some(stream),
+        Err(e) => {
+            tracing::warn!("Agent forwarding unavailable: {}", e);
+            None
+        }
+    };
+    let mut pending = Vec::new();
+    while let Some(data) = requests.recv().await {
+        pending.extend_from_slice(&data);
+        loop {
+            let request = match take_agent_message(&mut pending) {
+                Ok(Some(request)) => request,
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::warn!("Forwarded agent channel failed: {}", e);
+                    return;
+                }
+            };
+            let reply = match agent.as_mut() {
+                Some(stream) => match agent_exchange(stream, &request).await {
+                    Ok(reply) => reply,
+                    Err(e) => {
+                        tracing::warn!("Forwarded agent channel failed: {}", e);
+                        agent = None;
+                        AGENT_FAILURE.to_vec()
+                    }
+                },
+                None => AGENT_FAILURE.to_vec(),
+            };
+            let Some(handle) = session.get() else {
+                return;
+            };
+            let sent = handle.read().await.data(channel, CryptoVec::from(reply)).await;
+            if sent.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Agent channels opened by the server (`auth-agent@openssh.com`). Each one
+/// is relayed by its own task with its own connection to the local agent,
+/// so the russh handler never waits on the agent.
+#[derive(Default)]
+pub(crate) struct AgentForwarder {
+    channels: HashMap<ChannelId, mpsc::UnboundedSender<Vec<u8>>>,
+    session: AgentSession,
+}
+
+impl AgentForwarder {
+    pub(crate) fn session(&self) -> AgentSession {
+        self.session.clone()
+    }
+
+    pub(crate) fn open(&mut self, channel: ChannelId) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(relay_agent_channel(channel, rx, self.session.clone()));
+        self.channels.insert(channel, tx);
+    }
+
+    pub(crate) fn handles(&self, channel: ChannelId) -> bool {
+        self.channels.contains_key(&channel)
+    }
+
+    /// Queue `data` from the server for the channel's relay task. False once
+    /// the task has given up on the channel.
+    pub(crate) fn relay(&mut self, channel: ChannelId, data: &[u8]) -> bool {
+        self.channels
+            .get(&channel)
+            .is_some_and(|tx| tx.send(data.to_vec()).is_ok())
+    }
+
+    pub(crate) fn close(&mut self, channel: ChannelId) {
+        self.channels.remove(&channel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn take_agent_message_waits_for_complete_frame() {
+        let mut buf = vec![0, 0, 0, 3, 11, 1];
+        assert!(take_agent_message(&mut buf).unwrap().is_none());
+        assert_eq!(buf.len(), 6);
+
+        buf.extend_from_slice(&[2, 0, 0]);
+        let message = take_agent_message(&mut buf).unwrap().unwrap();
+        assert_eq!(message, vec![0, 0, 0, 3, 11, 1, 2]);
+        assert_eq!(buf, vec![0, 0]);
+    }
+
+    #[test]
+    fn take_agent_message_splits_back_to_back_frames() {
+        let mut buf = vec![0, 0, 0, 1, 11, 0, 0, 0, 1, 13];
+        assert_eq!(
+            take_agent_message(&mut buf).unwrap().unwrap(),
+            vec![0, 0, 0, 1, 11]
+        );
+        assert_eq!(
+            take_agent_message(&mut buf).unwrap().unwrap(),
+            vec![0, 0, 0, 1, 13]
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn take_agent_message_rejects_oversized_length() {
+        let mut buf = vec![0xff, 0xff, 0xff, 0xff];
+        assert!(take_agent_message(&mut buf).is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn agent_exchange_reads_one_framed_reply() {
+        let (mut client, mut agent) = AgentStream::pair().unwrap();
+        let server = tokio::spawn(async move {
+            let mut request = [0u8; 5];
+            agent.read_exact(&mut request).await.unwrap();
+            assert_eq!(request, [0, 0, 0, 1, 11]);
+            agent.write_all(&[0, 0, 0, 2, 12, 0, 99]).await.unwrap();
+        });
+
+        let reply = agent_exchange(&mut client, &[0, 0, 0, 1, 11])
+            .await
+            .unwrap();
+        assert_eq!(reply, vec![0, 0, 0, 2, 12, 0]);
+        server.await.unwrap();
+    }
+}
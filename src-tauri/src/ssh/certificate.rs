@@ -0,0 +1,212 @@
+//! OpenSSH user certificates (`ssh-keygen -s`): find the `*-cert.pub` that
+//! belongs to a private key and check it before offering it to the server.
+
+use super::Client;
+use anyhow::{anyhow, bail, Result};
+use russh::client;
+use russh_keys::key::KeyPair;
+use russh_keys::PublicKeyBase64;
+use ssh_key::Certificate;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where OpenSSH looks for the certificate of `key_path`: `id_ed25519` →
+/// `id_ed25519-cert.pub`.
+pub(crate) fn default_certificate_path(key_path: &Path) -> PathBuf {
+    let mut path = key_path.as_os_str().to_owned();
+    path.push("-cert.pub");
+    PathBuf::from(path)
+}
+
+/// Parse an OpenSSH certificate and check that it is a user certificate for
+/// `key` that is valid at `now` (seconds since the Unix epoch).
+pub(crate) fn parse_user_certificate(
+    content: &str,
+    key: &KeyPair,
+    now: u64,
+) -> Result<Certificate> {
+    let cert = Certificate::from_openssh(content.trim())
+        .map_err(|e| anyhow!("Invalid OpenSSH certificate: {}", e))?;
+    if !cert.cert_type().is_user() {
+        bail!(
+            "Certificate {:?} is a host certificate, not a user certificate.",
+            cert.key_id()
+        );
+    }
+
+    let public_key = key
+        .clone_public_key()
+        .map_err(|e| anyhow!("Failed to read the public half of the key: {}", e))?;
+    let cert_key = ssh_key::PublicKey::from(cert.public_key().clone())
+        .to_bytes()
+        .map_err(|e| anyhow!("Invalid OpenSSH certificate: {}", e))?;
+    if cert_key != public_key.public_key_bytes() {
+        bail!(
+            "Certificate {:?} was not issued for this private key.",
+            cert.key_id()
+        );
+    }
+
+    if now < cert.valid_after() {
+        bail!("Certificate {:?} is not valid yet.", cert.key_id());
+    }
+    if now >= cert.valid_before() {
+        bail!("Certificate {:?} has expired.", cert.key_id());
+    }
+    Ok(cert)
+}
+
+/// Load the certificate to present with `key`. An explicit
+/// `certificate_path` must exist and be valid; otherwise `<key>-cert.pub` is
+/// used when present and silently skipped when it cannot be used, so the
+/// plain key is still offered.
+pub(crate) fn load_user_certificate(
+    key_path: &Path,
+    certificate_path: Option<&Path>,
+    key: &KeyPair,
+) -> Result<Option<Certificate>> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    if let Some(path) = certificate_path {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read SSH certificate {}: {}", path.display(), e))?;
+        return parse_user_certificate(&content, key, now)
+            .map(Some)
+            .map_err(|e| anyhow!("{} ({})", e, path.display()));
+    }
+
+    let path = default_certificate_path(key_path);
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    match parse_user_certificate(&content, key, now) {
+        Ok(cert) => Ok(Some(cert)),
+        Err(e) => {
+            tracing::warn!("Ignoring SSH certificate {}: {}", path.display(), e);
+            Ok(None)
+        }
+    }
+}
+
+/// Authenticate with `key`, offering its certificate first when there is one.
+/// Servers that do not trust the CA still get the plain key, as OpenSSH does.
+pub(crate) async fn authenticate_key(
+    session: &mut client::Handle<Client>,
+    username: &str,
+    key: KeyPair,
+    certificate: Option<Certificate>,
+) -> Result<bool, russh::Error> {
+    let key = Arc::new(key);
+    if let Some(cert) = certificate {
+        if session
+            .authenticate_openssh_cert(username, key.clone(), cert)
+            .await?
+        {
+            return Ok(true);
+        }
+        tracing::debug!(
+            "Certificate not accepted for {}, trying the plain key",
+            username
+        );
+    }
+    session.authenticate_publickey(username, key).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ssh_key::certificate::{Builder, CertType};
+    use ssh_key::private::{Ed25519Keypair, PrivateKey};
+
+    /// CA with a fixed seed so tests need no RNG.
+    fn test_ca() -> PrivateKey {
+        PrivateKey::from(Ed25519Keypair::from_seed(&[7; 32]))
+    }
+
+    /// Sign `public_key` (in SSH wire format) with `ca`.
+    fn sign(
+        ca: &PrivateKey,
+        public_key: &[u8],
+        cert_type: CertType,
+        principals: &[&str],
+        valid: std::ops::Range<u64>,
+    ) -> Certificate {
+        let key = ssh_key::PublicKey::from_bytes(public_key).unwrap();
+        let mut builder =
+            Builder::new([0u8; 16], key.key_data().clone(), valid.start, valid.end).unwrap();
+        builder.cert_type(cert_type).unwrap();
+        builder.key_id("test").unwrap();
+        for principal in principals {
+            builder.valid_principal(*principal).unwrap();
+        }
+        builder.sign(ca).unwrap()
+    }
+
+    fn user_cert(key: &KeyPair, valid: std::ops::Range<u64>) -> String {
+        let public = key.clone_public_key().unwrap().public_key_bytes();
+        sign(&test_ca(), &public, CertType::User, &["deploy"], valid)
+            .to_openssh()
+            .unwrap()
+    }
+
+    #[test]
+    fn default_path_appends_cert_suffix() {
+        assert_eq!(
+            default_certificate_path(Path::new("/home/u/.ssh/id_ed25519")),
+            PathBuf::from("/home/u/.ssh/id_ed25519-cert.pub")
+        );
+    }
+
+    #[test]
+    fn parse_accepts_matching_user_certificate() {
+        let key = KeyPair::generate_ed25519().unwrap();
+        let cert = parse_user_certificate(&user_cert(&key, 100..200), &key, 150).unwrap();
+        assert_eq!(cert.valid_principals(), ["deploy".to_string()]);
+    }
+
+    #[test]
+    fn parse_rejects_expired_foreign_and_host_certificates() {
+        let key = KeyPair::generate_ed25519().unwrap();
+        let err = parse_user_certificate(&user_cert(&key, 100..200), &key, 200).unwrap_err();
+        assert!(err.to_string().contains("expired"), "{err}");
+
+        let other = KeyPair::generate_ed25519().unwrap();
+        let err = parse_user_certificate(&user_cert(&other, 100..200), &key, 150).unwrap_err();
+        assert!(err.to_string().contains("not issued"), "{err}");
+
+        let public = key.clone_public_key().unwrap().public_key_bytes();
+        let host = sign(
+            &test_ca(),
+            &public,
+            CertType::Host,
+            &["example.com"],
+            100..200,
+        )
+        .to_openssh()
+        .unwrap();
+        let err = parse_user_certificate(&host, &key, 150).unwrap_err();
+        assert!(err.to_string().contains("host certificate"), "{err}");
+    }
+
+    #[test]
+    fn unusable_default_certificate_falls_back_to_plain_key() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let key_path = dir.path().join("id_ed25519");
+        let key = KeyPair::generate_ed25519().unwrap();
+        assert!(load_user_certificate(&key_path, None, &key)
+            .unwrap()
+            .is_none());
+
+        std::fs::write(default_certificate_path(&key_path), "garbage").unwrap();
+        assert!(load_user_certificate(&key_path, None, &key)
+            .unwrap()
+            .is_none());
+
+        let explicit = dir.path().join("missing-cert.pub");
+        assert!(load_user_certificate(&key_path, Some(&explicit), &key).is_err());
+    }
+}
@@ -0,0 +1,327 @@
+//! One-off commands on an exec channel: stdout and stderr kept apart, the
+//! exit code or signal, stdin, timeouts, and output streamed as it arrives.
+
+use crate::utf8::take_utf8;
+use anyhow::Result;
+use russh::{client, Channel, ChannelMsg, Sig};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// SSH_EXTENDED_DATA_STDERR
+const STDERR_EXT: u32 = 1;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ExecOptions {
+    /// Close the channel if the command is still running after this long.
+    pub timeout_ms: Option<u64>,
+    /// Written to the command's stdin before EOF.
+    pub stdin: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecStream {
+    Stdout,
+    Stderr,
+}
+
+/// How a command ended.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ExecStatus {
+    /// `None` if the command was killed by a signal, timed out, was
+    /// cancelled, or the server never reported a status.
+    pub exit_code: Option<u32>,
+    pub exit_signal: Option<String>,
+    pub timed_out: bool,
+    pub cancelled: bool,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExecOutput {
+    pub stdout: String,
+    pub stderr: String,
+    #[serde(flatten)]
+    pub status: ExecStatus,
+}
+
+/// Pushed to the frontend as a streamed command runs.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExecEvent {
+    Output {
+        exec_id: String,
+        stream: ExecStream,
+        data: String,
+    },
+    Exit {
+        exec_id: String,
+        #[serde(flatten)]
+        status: ExecStatus,
+    },
+    Error {
+        exec_id: String,
+        error: String,
+    },
+}
+
+/// Feed the command's stdin, then pass its output to `on_output` until it
+/// exits, `timeout_ms` elapses or `cancel` fires. The channel is closed
+/// before returning either way.
+pub(crate) async fn run(
+    mut channel: Channel<client::Msg>,
+    options: &ExecOptions,
+    cancel: &CancellationToken,
+    mut on_output: impl FnMut(ExecStream, String),
+) -> Result<ExecStatus> {
+    let started = Instant::now();
+    let deadline = options
+        .timeout_ms
+        .map(|ms| tokio::time::Instant::now() + Duration::from_millis(ms));
+    let timeout = async {
+        match deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::pin!(timeout);
+
+    if let Some(stdin) = &options.stdin {
+        channel.data(stdin.as_bytes()).await?;
+    }
+    // Nothing else will be written, so commands reading stdin don't hang.
+    channel.eof().await?;
+
+    let mut state = ExecState::default();
+    let server_closed = loop {
+        let msg = tokio::select! {
+            msg = channel.wait() => msg,
+            _ = cancel.cancelled() => {
+                state.status.cancelled = true;
+                break false;
+            }
+            _ = &mut timeout => {
+                state.status.timed_out = true;
+                break false;
+            }
+        };
+        match state.handle(msg, &mut on_output) {
+            Step::Continue => {}
+            Step::Finished => break false,
+            Step::Closed => break true,
+        }
+    };
+    state.flush(&mut on_output);
+
+    // Send SSH_MSG_CHANNEL_CLOSE if the server hasn't already closed the channel.
+    // Without this, russh's session keeps the channel in its internal map until
+    // the session is torn down, causing per-poll memory growth.
+    if !server_closed {
+        let _ = channel.close().await;
+    }
+
+    let mut status = state.status;
+    status.duration_ms = started.elapsed().as_millis() as u64;
+    Ok(status)
+}
+
+/// `run` with the output collected instead of streamed.
+pub(crate) async fn collect(
+    channel: Channel<client::Msg>,
+    options: &ExecOptions,
+) -> Result<ExecOutput> {
+    let (mut stdout, mut stderr) = (String::new(), String::new());
+    let status = run(
+        channel,
+        options,
+        &CancellationToken::new(),
+        |stream, text| match stream {
+            ExecStream::Stdout => stdout.push_str(&text),
+            ExecStream::Stderr => stderr.push_str(&text),
+        },
+    )
+    .await?;
+    Ok(ExecOutput {
+        stdout,
+        stderr,
+        status,
+    })
+}
+
+#[derive(Debug, PartialEq)]
+enum Step {
+    Continue,
+    /// Output ended and the exit status arrived.
+    Finished,
+    /// The server closed the channel.
+    Closed,
+}
+
+#[derive(Default)]
+struct ExecState {
+    status: ExecStatus,
+    eof: bool,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl ExecState {
+    fn handle(
+        &mut self,
+        msg: Option<ChannelMsg>,
+        on_output: &mut impl FnMut(ExecStream, String),
+    ) -> Step {
+        match msg {
+            Some(ChannelMsg::Data { data }) => {
+                let text = take_utf8(&mut self.stdout, &data);
+                if !text.is_empty() {
+                    on_output(ExecStream::Stdout, text);
+                }
+            }
+            Some(ChannelMsg::ExtendedData { data, ext }) if ext == STDERR_EXT => {
+                let text = take_utf8(&mut self.stderr, &data);
+                if !text.is_empty() {
+                    on_output(ExecStream::Stderr, text);
+                }
+            }
+            Some(ChannelMsg::ExitStatus { exit_status }) => {
+                self.status.exit_code = Some(exit_status);
+                if self.eof {
+                    return Step::Finished;
+                }
+            }
+            Some(ChannelMsg::ExitSignal { signal_name, .. }) => {
+                self.status.exit_signal = Some(signal_label(&signal_name));
+                if self.eof {
+                    return Step::Finished;
+                }
+            }
+            Some(ChannelMsg::Eof) => {
+                self.eof = true;
+                if self.exited() {
+                    return Step::Finished;
+                }
+            }
+            Some(ChannelMsg::Close) | None => return Step::Closed,
+            _ => {}
+        }
+        Step::Continue
+    }
+
+    fn exited(&self) -> bool {
+        self.status.exit_code.is_some() || self.status.exit_signal.is_some()
+    }
+
+    /// Emit incomplete UTF-8 left over when the output ends.
+    fn flush(&mut self, on_output: &mut impl FnMut(ExecStream, String)) {
+        for (stream, pending) in [
+            (ExecStream::Stdout, &mut self.stdout),
+            (ExecStream::Stderr, &mut self.stderr),
+        ] {
+            if !pending.is_empty() {
+                on_output(stream, String::from_utf8_lossy(pending).into_owned());
+                pending.clear();
+            }
+        }
+    }
+}
+
+/// Signal name without the "SIG" prefix, as sent on the wire.
+fn signal_label(signal: &Sig) -> String {
+    match signal {
+        Sig::Custom(name) => name.clone(),
+        other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use russh::CryptoVec;
+
+    fn collect(msgs: Vec<Option<ChannelMsg>>) -> (ExecState, Vec<Step>, String, String) {
+        let mut state = ExecState::default();
+        let (mut stdout, mut stderr) = (String::new(), String::new());
+        let mut on_output = |stream, text: String| match stream {
+            ExecStream::Stdout => stdout.push_str(&text),
+            ExecStream::Stderr => stderr.push_str(&text),
+        };
+        let steps = msgs
+            .into_iter()
+            .map(|msg| state.handle(msg, &mut on_output))
+            .collect();
+        state.flush(&mut on_output);
+        (state, steps, stdout, stderr)
+    }
+
+    #[test]
+    fn separates_streams_and_finishes_after_eof_and_status() {
+        let (state, steps, stdout, stderr) = collect(vec![
+            Some(ChannelMsg::Data {
+                data: CryptoVec::from_slice(b"out \xc3"),
+            }),
+            Some(ChannelMsg::ExtendedData {
+                data: CryptoVec::from_slice(b"err"),
+                ext: STDERR_EXT,
+            }),
+            Some(ChannelMsg::Data {
+                data: CryptoVec::from_slice(b"\xa9"),
+            }),
+            Some(ChannelMsg::ExitStatus { exit_status: 3 }),
+            Some(ChannelMsg::Eof),
+        ]);
+        assert_eq!(stdout, "out é");
+        assert_eq!(stderr, "err");
+        assert_eq!(state.status.exit_code, Some(3));
+        assert_eq!(steps.last(), Some(&Step::Finished));
+        assert!(steps[..4].iter().all(|s| *s == Step::Continue));
+    }
+
+    #[test]
+    fn reports_exit_signal() {
+        let (state, steps, ..) = collect(vec![
+            Some(ChannelMsg::Eof),
+            Some(ChannelMsg::ExitSignal {
+                signal_name: Sig::KILL,
+                core_dumped: false,
+                error_message: String::new(),
+                lang_tag: String::new(),
+            }),
+        ]);
+        assert_eq!(state.status.exit_signal.as_deref(), Some("KILL"));
+        assert_eq!(state.status.exit_code, None);
+        assert_eq!(steps, [Step::Continue, Step::Finished]);
+    }
+
+    #[test]
+    fn server_close_ends_without_status() {
+        let (state, steps, stdout, _) = collect(vec![
+            Some(ChannelMsg::Data {
+                data: CryptoVec::from_slice(b"partial\xe2\x82"),
+            }),
+            Some(ChannelMsg::Close),
+        ]);
+        assert_eq!(steps, [Step::Continue, Step::Closed]);
+        assert_eq!(state.status.exit_code, None);
+        assert_eq!(stdout, "partial\u{fffd}");
+    }
+
+    #[test]
+    fn exit_event_flattens_status() {
+        let event = ExecEvent::Exit {
+            exec_id: "exec-1".into(),
+            status: ExecStatus {
+                exit_code: Some(0),
+                duration_ms: 12,
+                ..Default::default()
+            },
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "exit");
+        assert_eq!(json["exec_id"], "exec-1");
+        assert_eq!(json["exit_code"], 0);
+        assert_eq!(json["timed_out"], false);
+    }
+}
@@ -0,0 +1,641 @@
+//! SSH port forwarding: local (`-L`), remote (`-R`) and dynamic SOCKS5
+//! (`-D`) forwards attached to an established connection.
+
+use super::SshClient;
+use anyhow::{anyhow, bail, Result};
+use russh::{client, Channel};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+
+const SOCKS5_VERSION: u8 = 0x05;
+const SOCKS5_NO_AUTH: u8 = 0x00;
+const SOCKS5_NO_ACCEPTABLE_METHOD: u8 = 0xFF;
+const SOCKS5_CONNECT: u8 = 0x01;
+const SOCKS5_SUCCEEDED: u8 = 0x00;
+const SOCKS5_CONNECTION_REFUSED: u8 = 0x05;
+const SOCKS5_COMMAND_NOT_SUPPORTED: u8 = 0x07;
+const SOCKS5_ADDRESS_NOT_SUPPORTED: u8 = 0x08;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForwardKind {
+    /// Listen locally, connect from the server (`ssh -L`).
+    Local,
+    /// Listen on the server, connect from here (`ssh -R`).
+    Remote,
+    /// Local SOCKS5 proxy whose connections leave from the server (`ssh -D`).
+    Dynamic,
+}
+
+/// A forward as requested by the frontend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardSpec {
+    pub kind: ForwardKind,
+    /// Listen address; on the server for remote forwards.
+    #[serde(default = "default_bind_host")]
+    pub bind_host: String,
+    /// Listen port. 0 picks a free port; the chosen one is reported back.
+    pub bind_port: u16,
+    /// Destination for local and remote forwards (unused for dynamic ones).
+    #[serde(default)]
+    pub target_host: Option<String>,
+    #[serde(default)]
+    pub target_port: Option<u16>,
+}
+
+fn default_bind_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+impl ForwardSpec {
+    fn target(&self) -> Result<(String, u16)> {
+        match (&self.target_host, self.target_port) {
+            (Some(host), Some(port)) if !host.trim().is_empty() => Ok((host.clone(), port)),
+            _ => bail!(
+                "Target host and port are required for {:?} forwards",
+                self.kind
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ForwardStats {
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    active_connections: AtomicU64,
+    total_connections: AtomicU64,
+}
+
+/// Snapshot of a forward and its counters, as returned to the frontend.
+#[derive(Debug, Clone, Serialize)]
+pub struct ForwardInfo {
+    pub id: String,
+    pub connection_id: String,
+    #[serde(flatten)]
+    pub spec: ForwardSpec,
+    /// Bytes sent from the listening side to the destination.
+    pub bytes_sent: u64,
+    /// Bytes sent from the destination back to the listening side.
+    pub bytes_received: u64,
+    pub active_connections: u64,
+    pub total_connections: u64,
+}
+
+/// A running forward. Dropping it does not stop it; call [`PortForward::stop`].
+pub struct PortForward {
+    id: String,
+    connection_id: String,
+    spec: ForwardSpec,
+    stats: Arc<ForwardStats>,
+    cancel: CancellationToken,
+}
+
+impl PortForward {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn connection_id(&self) -> &str {
+        &self.connection_id
+    }
+
+    pub fn info(&self) -> ForwardInfo {
+        ForwardInfo {
+            id: self.id.clone(),
+            connection_id: self.connection_id.clone(),
+            spec: self.spec.clone(),
+            bytes_sent: self.stats.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.stats.bytes_received.load(Ordering::Relaxed),
+            active_connections: self.stats.active_connections.load(Ordering::Relaxed),
+            total_connections: self.stats.total_connections.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Stop listening and close every connection carried by the forward.
+    pub async fn stop(&self, client: &RwLock<SshClient>) {
+        self.cancel.cancel();
+        if self.spec.kind == ForwardKind::Remote {
+            let client = client.read().await;
+            client.remote_forwards().remove(self.spec.bind_port);
+            if let Err(e) = client
+                .cancel_remote_forward(&self.spec.bind_host, self.spec.bind_port)
+                .await
+            {
+                tracing::debug!("Failed to cancel remote forward {}: {}", self.id, e);
+            }
+        }
+    }
+}
+
+/// Start a forward on `client`. Local and dynamic forwards bind their
+/// listener before returning, so address-in-use errors surface immediately.
+pub async fn start_forward(
+    client: Arc<RwLock<SshClient>>,
+    id: String,
+    connection_id: String,
+    mut spec: ForwardSpec,
+) -> Result<PortForward> {
+    let stats = Arc::new(ForwardStats::default());
+    let cancel = CancellationToken::new();
+
+    match spec.kind {
+        ForwardKind::Local | ForwardKind::Dynamic => {
+            if spec.kind == ForwardKind::Local {
+                spec.target()?;
+            }
+            let listener = TcpListener::bind((spec.bind_host.as_str(), spec.bind_port))
+                .await
+                .map_err(|e| {
+                    anyhow!(
+                        "Failed to listen on {}:{}: {}",
+                        spec.bind_host,
+                        spec.bind_port,
+                        e
+                    )
+                })?;
+            spec.bind_port = listener.local_addr()?.port();
+            tokio::spawn(accept_loop(
+                listener,
+                client,
+                spec.clone(),
+                stats.clone(),
+                cancel.clone(),
+            ));
+        }
+        ForwardKind::Remote => {
+            let (host, port) = spec.target()?;
+            let client = client.read().await;
+            spec.bind_port = client
+                .request_remote_forward(&spec.bind_host, spec.bind_port)
+                .await?;
+            client.remote_forwards().insert(
+                spec.bind_port,
+                RemoteTarget {
+                    host,
+                    port,
+                    stats: stats.clone(),
+                    cancel: cancel.clone(),
+                },
+            );
+        }
+    }
+
+    Ok(PortForward {
+        id,
+        connection_id,
+        spec,
+        stats,
+        cancel,
+    })
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    client: Arc<RwLock<SshClient>>,
+    spec: ForwardSpec,
+    stats: Arc<ForwardStats>,
+    cancel: CancellationToken,
+) {
+    loop {
+        let (socket, peer) = tokio::select! {
+            _ = cancel.cancelled() => break,
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    tracing::warn!("Port forward accept failed on {}:{}: {}", spec.bind_host, spec.bind_port, e);
+                    continue;
+                }
+            },
+        };
+        let client = client.clone();
+        let spec = spec.clone();
+        let stats = stats.clone();
+        let cancel = cancel.clone();
+        tokio::spawn(async move {
+            if let Err(e) = serve_local(socket, peer, &client, &spec, &stats, &cancel).await {
+                tracing::debug!("Port forward connection from {} ended: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn serve_local(
+    mut socket: TcpStream,
+    peer: SocketAddr,
+    client: &RwLock<SshClient>,
+    spec: &ForwardSpec,
+    stats: &ForwardStats,
+    cancel: &CancellationToken,
+) -> Result<()> {
+    let (host, port) = match spec.kind {
+        ForwardKind::Dynamic => socks5_handshake(&mut socket).await?,
+        _ => spec.target()?,
+    };
+    let tunnel = client
+        .read()
+        .await
+        .open_direct_tcpip(&host, port, peer)
+        .await;
+    if spec.kind == ForwardKind::Dynamic {
+        let code = if tunnel.is_ok() {
+            SOCKS5_SUCCEEDED
+        } else {
+            SOCKS5_CONNECTION_REFUSED
+        };
+        socks5_reply(&mut socket, code).await?;
+    }
+    pump(socket, tunnel?, stats, cancel).await
+}
+
+#[derive(Clone)]
+pub(crate) struct RemoteTarget {
+    host: String,
+    port: u16,
+    stats: Arc<ForwardStats>,
+    cancel: CancellationToken,
+}
+
+/// Remote forwards of one SSH connection, keyed by the port bound on the
+/// server. Shared with the russh handler, which receives the server's
+/// `forwarded-tcpip` channels.
+#[derive(Clone, Default)]
+pub(crate) struct RemoteForwards(Arc<Mutex<HashMap<u16, RemoteTarget>>>);
+
+impl RemoteForwards {
+    fn map(&self) -> std::sync::MutexGuard<'_, HashMap<u16, RemoteTarget>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn insert(&self, port: u16, target: RemoteTarget) {
+        self.map().insert(port, target);
+    }
+
+    fn remove(&self, port: u16) {
+        self.map().remove(&port);
+    }
+
+    /// Connect a `forwarded-tcpip` channel opened by the server to the local
+    /// target registered for `connected_port`.
+    pub(crate) fn accept(&self, channel: Channel<client::Msg>, connected_port: u32) {
+        let target = u16::try_from(connected_port)
+            .ok()
+            .and_then(|port| self.map().get(&port).cloned());
+        tokio::spawn(async move {
+            let Some(target) = target else {
+                tracing::debug!(
+                    "Closing forwarded channel for unknown port {}",
+                    connected_port
+                );
+                let _ = channel.close().await;
+                return;
+            };
+            match TcpStream::connect((target.host.as_str(), target.port)).await {
+                Ok(socket) => {
+                    if let Err(e) =
+                        pump(socket, channel.into_stream(), &target.stats, &target.cancel).await
+                    {
+                        tracing::debug!("Remote forward connection ended: {}", e);
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "Remote forward could not reach {}:{}: {}",
+                        target.host,
+                        target.port,
+                        e
+                    );
+                    let _ = channel.close().await;
+                }
+            }
+        });
+    }
+}
+
+/// Copy both directions between `local` and `remote` until each side has
+/// closed or the forward is cancelled, updating the counters as data flows.
+async fn pump<L, R>(
+    local: L,
+    remote: R,
+    stats: &ForwardStats,
+    cancel: &CancellationToken,
+) -> Result<()>
+where
+    L: AsyncRead + AsyncWrite + Unpin,
+    R: AsyncRead + AsyncWrite + Unpin,
+{
+    stats.active_connections.fetch_add(1, Ordering::Relaxed);
+    stats.total_connections.fetch_add(1, Ordering::Relaxed);
+
+    let (mut local_read, mut local_write) = tokio::io::split(local);
+    let (mut remote_read, mut remote_write) = tokio::io::split(remote);
+    let result = tokio::select! {
+        _ = cancel.cancelled() => Ok(()),
+        copied = async {
+            tokio::try_join!(
+                copy_counted(&mut local_read, &mut remote_write, &stats.bytes_sent),
+                copy_counted(&mut remote_read, &mut local_write, &stats.bytes_received),
+            )
+        } => copied.map(|_| ()).map_err(Into::into),
+    };
+
+    stats.active_connections.fetch_sub(1, Ordering::Relaxed);
+    result
+}
+
+async fn copy_counted<Rd, W>(
+    reader: &mut Rd,
+    writer: &mut W,
+    counter: &AtomicU64,
+) -> std::io::Result<()>
+where
+    Rd: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = vec![0u8; 32 * 1024];
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            // Half-close so the peer sees EOF while the other direction drains.
+            let _ = writer.shutdown().await;
+            return Ok(());
+        }
+        writer.write_all(&buf[..n]).await?;
+        counter.fetch_add(n as u64, Ordering::Relaxed);
+    }
+}
+
+/// Run the server side of a SOCKS5 greeting and CONNECT request (RFC 1928,
+/// no authentication). Returns the requested destination; the caller sends
+/// the final reply once it knows whether the destination is reachable.
+async fn socks5_handshake<S>(stream: &mut S) -> Result<(String, u16)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let mut greeting = [0u8; 2];
+    stream.read_exact(&mut greeting).await?;
+    if greeting[0] != SOCKS5_VERSION {
+        bail!("Unsupported SOCKS version {}", greeting[0]);
+    }
+    let mut methods = vec![0u8; greeting[1] as usize];
+    stream.read_exact(&mut methods).await?;
+    if !methods.contains(&SOCKS5_NO_AUTH) {
+        stream
+            .write_all(&[SOCKS5_VERSION, SOCKS5_NO_ACCEPTABLE_METHOD])
+            .await?;
+        bail!("SOCKS5 client requires authentication");
+    }
+    stream.write_all(&[SOCKS5_VERSION, SOCKS5_NO_AUTH]).await?;
+
+    let mut request = [0u8; 4];
+    stream.read_exact(&mut request).await?;
+    if request[1] != SOCKS5_CONNECT {
+        socks5_reply(stream, SOCKS5_COMMAND_NOT_SUPPORTED).await?;
+        bail!("Unsupported SOCKS5 command {}", request[1]);
+    }
+    let host = match request[3] {
+        0x01 => {
+            let mut addr = [0u8; 4];
+            stream.read_exact(&mut addr).await?;
+            Ipv4Addr::from(addr).to_string()
+        }
+        0x03 => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await?;
+            let mut name = vec![0u8; len[0] as usize];
+            stream.read_exact(&mut name).await?;
+            String::from_utf8(name).map_err(|_| anyhow!("Invalid SOCKS5 domain name"))?
+        }
+        0x04 => {
+            let mut addr = [0u8; 16];
+            stream.read_exact(&mut addr).await?;
+            Ipv6Addr::from(addr).to_string()
+        }
+        other => {
+            socks5_reply(stream, SOCKS5_ADDRESS_NOT_SUPPORTED).await?;
+            bail!("Unsupported SOCKS5 address type {}", other);
+        }
+    };
+    let mut port = [0u8; 2];
+    stream.read_exact(&mut port).await?;
+    Ok((host, u16::from_be_bytes(port)))
+}
+
+async fn socks5_reply<S: AsyncWrite + Unpin>(stream: &mut S, code: u8) -> std::io::Result<()> {
+    // The bound address is not meaningful for a tunnelled connection.
+    stream
+        .write_all(&[SOCKS5_VERSION, code, 0x00, 0x01, 0, 0, 0, 0, 0, 0])
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spec_defaults_to_loopback_bind() {
+        let spec: ForwardSpec = serde_json::from_str(
+            r#"{"kind":"local","bind_port":8080,"target_host":"db","target_port":5432}"#,
+        )
+        .unwrap();
+        assert_eq!(spec.kind, ForwardKind::Local);
+        assert_eq!(spec.bind_host, "127.0.0.1");
+        assert_eq!(spec.target().unwrap(), ("db".to_string(), 5432));
+
+        let dynamic: ForwardSpec =
+            serde_json::from_str(r#"{"kind":"dynamic","bind_port":1080}"#).unwrap();
+        assert!(dynamic.target().is_err());
+    }
+
+    #[tokio::test]
+    async fn socks5_handshake_reads_domain_connect() {
+        let (mut client, mut server) = tokio::io::duplex(256);
+        let mut request = vec![5, 1, 0, 5, 1, 0, 3, 11];
+        request.extend_from_slice(b"example.com");
+        request.extend_from_slice(&443u16.to_be_bytes());
+        client.write_all(&request).await.unwrap();
+
+        let target = socks5_handshake(&mut server).await.unwrap();
+        assert_eq!(target, ("example.com".to_string(), 443));
+
+        let mut method = [0u8; 2];
+        client.read_exact(&mut method).await.unwrap();
+        assert_eq!(method, [5, 0]);
+    }
+
+    #[tokio::test]
+    async fn socks5_handshake_reads_ipv4_and_rejects_bind() {
+        let (mut client, mut server) = tokio::io::duplex(256);
+        client
+            .write_all(&[5, 1, 0, 5, 1, 0, 1, 10, 0, 0, 7, 0, 22])
+            .await
+            .unwrap();
+        let target = socks5_handshake(&mut server).await.unwrap();
+        assert_eq!(target, ("10.0.0.7".to_string(), 22));
+
+        let (mut client, mut server) = tokio::io::duplex(256);
+        client.write_all(&[5, 1, 0, 5, 2, 0, 1]).await.unwrap();
+        assert!(socks5_handshake(&mut server).await.is_err());
+        let mut reply = [0u8; 12];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply[..4], [5, 0, 5, SOCKS5_COMMAND_NOT_SUPPORTED]);
+    }
+
+    #[tokio::test]
+    async fn socks5_handshake_requires_no_auth_method() {
+        let (mut client, mut server) = tokio::io::duplex(256);
+        client.write_all(&[5, 1, 2]).await.unwrap();
+        assert!(socks5_handshake(&mut server).await.is_err());
+        let mut reply = [0u8; 2];
+        client.read_exact(&mut reply).await.unwrap();
+        assert_eq!(reply, [5, SOCKS5_NO_ACCEPTABLE_METHOD]);
+    }
+
+    #[tokio::test]
+    async fn pump_counts_bytes_in_both_directions() {
+        let (mut local_peer, local) = tokio::io::duplex(1024);
+        let (mut remote_peer, remote) = tokio::io::duplex(1024);
+        let stats = Arc::new(ForwardStats::default());
+        let cancel = CancellationToken::new();
+
+        let task = {
+            let stats = stats.clone();
+            let cancel = cancel.clone();
+            tokio::spawn(async move { pump(local, remote, &stats, &cancel).await })
+        };
+
+        local_peer.write_all(b"hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        remote_peer.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+        remote_peer.write_all(b"hi").await.unwrap();
+        let mut buf = [0u8; 2];
+        local_peer.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hi");
+
+        drop(local_peer);
+        drop(remote_peer);
+        task.await.unwrap().unwrap();
+
+        assert_eq!(stats.bytes_sent.load(Ordering::Relaxed), 5);
+        assert_eq!(stats.bytes_received.load(Ordering::Relaxed), 2);
+        assert_eq!(stats.total_connections.load(Ordering::Relaxed), 1);
+        assert_eq!(stats.active_connections.load(Ordering::Relaxed), 0);
+    }
+
+    /// Accepts any password and reports each `tcpip-forward` request, with a
+    /// handle the test can open `forwarded-tcpip` channels through.
+    struct ForwardingServer {
+        requests: tokio::sync::mpsc::UnboundedSender<(russh::server::Handle, String, u32)>,
+    }
+
+    #[async_trait::async_trait]
+    impl russh::server::Handler for ForwardingServer {
+        type Error = russh::Error;
+
+        async fn auth_password(
+            &mut self,
+            _user: &str,
+            _password: &str,
+        ) -> Result<russh::server::Auth, Self::Error> {
+            Ok(russh::server::Auth::Accept)
+        }
+
+        async fn tcpip_forward(
+            &mut self,
+            address: &str,
+            port: &mut u32,
+            session: &mut russh::server::Session,
+        ) -> Result<bool, Self::Error> {
+            if *port == 0 {
+                *port = 40022;
+            }
+            let _ = self
+                .requests
+                .send((session.handle(), address.to_string(), *port));
+            Ok(true)
+        }
+    }
+
+    #[tokio::test]
+    async fn remote_forward_starts_on_a_connected_client() {
+        let key = russh_keys::key::KeyPair::generate_ed25519().unwrap();
+        let public_key = key.clone_public_key().unwrap();
+        let config = Arc::new(russh::server::Config {
+            keys: vec![key],
+            ..Default::default()
+        });
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_port = server.local_addr().unwrap().port();
+        let (requests, mut forwarded) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let (socket, _) = server.accept().await.unwrap();
+            let session = russh::server::run_stream(config, socket, ForwardingServer { requests })
+                .await
+                .unwrap();
+            let _ = session.await;
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let verifier =
+            crate::known_hosts::HostKeyVerifier::new(Some(dir.path().join("known_hosts")));
+        verifier
+            .trust("127.0.0.1", server_port, &public_key)
+            .unwrap();
+        let mut client = SshClient::with_host_key_verifier(Arc::new(verifier));
+        client
+            .connect(&super::super::SshConfig {
+                host: "127.0.0.1".to_string(),
+                port: server_port,
+                username: "test".to_string(),
+                auth_method: super::super::AuthMethod::Password {
+                    password: "secret".to_string(),
+                },
+                additional_auth_methods: Vec::new(),
+                compression: false,
+                keepalive_interval: None,
+                keepalive_max: None,
+                proxy: None,
+                jump_hosts: Vec::new(),
+                agent_forwarding: true,
+                reconnect: Default::default(),
+            })
+            .await
+            .unwrap();
+        let client = Arc::new(RwLock::new(client));
+
+        let target = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let spec = ForwardSpec {
+            kind: ForwardKind::Remote,
+            bind_host: "127.0.0.1".to_string(),
+            bind_port: 0,
+            target_host: Some("127.0.0.1".to_string()),
+            target_port: Some(target.local_addr().unwrap().port()),
+        };
+        let forward = start_forward(client.clone(), "fwd".into(), "conn".into(), spec)
+            .await
+            .unwrap();
+        assert_eq!(forward.info().spec.bind_port, 40022);
+
+        // A connection the server accepts on the forwarded port reaches the target.
+        let (handle, address, port) = forwarded.recv().await.unwrap();
+        let channel = handle
+            .channel_open_forwarded_tcpip(address, port, "127.0.0.1", 50000)
+            .await
+            .unwrap();
+        channel.data(&b"ping"[..]).await.unwrap();
+        let (mut socket, _) = target.accept().await.unwrap();
+        let mut buf = [0u8; 4];
+        socket.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+
+        forward.stop(&client).await;
+    }
+}
@@ -0,0 +1,194 @@
+//! ProxyJump support: reach a host through a chain of SSH bastions, running
+//! each hop's handshake over a `direct-tcpip` channel opened by the previous
+//! one (like OpenSSH `-J a,b`).
+
+use super::{authenticate_methods, with_handshake_timeout, AuthMethod, Client};
+use crate::auth_prompt::AuthPrompter;
+use crate::known_hosts::HostKeyVerifier;
+use crate::proxy::ProxyConfig;
+use anyhow::{anyhow, bail, Result};
+use russh::{client, ChannelStream, Disconnect};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// One bastion in a jump chain. Each hop is authenticated and has its host
+/// key verified independently.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JumpHost {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub auth_method: AuthMethod,
+    #[serde(default)]
+    pub additional_auth_methods: Vec<AuthMethod>,
+}
+
+/// Byte stream carried by a `direct-tcpip` channel.
+pub(crate) type Tunnel = ChannelStream<client::Msg>;
+
+/// Authenticated sessions to each jump host, outermost first. Tunnels opened
+/// through the chain stay usable for as long as it is alive.
+pub(crate) struct JumpChain {
+    hops: Vec<client::Handle<Client>>,
+}
+
+impl JumpChain {
+    /// Connect to every hop in order. `proxy`, if any, is only used to reach
+    /// the first hop.
+    pub(crate) async fn connect(
+        jump_hosts: &[JumpHost],
+        proxy: Option<&ProxyConfig>,
+        ssh_config: Arc<client::Config>,
+        verifier: &Arc<HostKeyVerifier>,
+        prompter: &AuthPrompter,
+        timeout: Duration,
+    ) -> Result<Self> {
+        let mut hops: Vec<client::Handle<Client>> = Vec::with_capacity(jump_hosts.len());
+        for hop in jump_hosts {
+            let handler = Client::new(&hop.host, hop.port, verifier.clone());
+            let prompting = handler.prompt_flag();
+            let handshake = match (hops.last(), proxy) {
+                (Some(previous), _) => {
+                    let tunnel = open_tunnel(previous, &hop.host, hop.port).await?;
+                    with_handshake_timeout(
+                        client::connect_stream(ssh_config.clone(), tunnel, handler),
+                        timeout,
+                        &prompting,
+                    )
+                    .await
+                }
+                (None, Some(proxy)) => {
+                    let stream = crate::proxy::connect_via_proxy(
+                        proxy,
+                        &hop.host,
+                        hop.port,
+                        &hop.username,
+                        timeout,
+                    )
+                    .await
+                    .map_err(|e| anyhow!("Proxy connection failed: {e}"))?;
+                    with_handshake_timeout(
+                        client::connect_stream(ssh_config.clone(), stream, handler),
+                        proxy.handshake_timeout(timeout),
+                        &prompting,
+                    )
+                    .await
+                }
+                (None, None) => {
+                    with_handshake_timeout(
+                        client::connect(ssh_config.clone(), (&hop.host[..], hop.port), handler),
+                        timeout,
+                        &prompting,
+                    )
+                    .await
+                }
+            };
+
+            let mut session = handshake
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Connection to jump host {}:{} timed out after {} seconds.",
+                        hop.host,
+                        hop.port,
+                        timeout.as_secs()
+                    )
+                })?
+                .map_err(|e| {
+                    anyhow!(
+                        "Failed to connect to jump host {}:{}: {}",
+                        hop.host,
+                        hop.port,
+                        e
+                    )
+                })?;
+
+            let methods: Vec<&AuthMethod> = std::iter::once(&hop.auth_method)
+                .chain(&hop.additional_auth_methods)
+                .collect();
+            let authenticated = authenticate_methods(
+                &mut session,
+                prompter,
+                &hop.host,
+                hop.port,
+                &hop.username,
+                &methods,
+            )
+            .await
+            .map_err(|e| anyhow!("Jump host {}:{}: {}", hop.host, hop.port, e))?;
+            if !authenticated {
+                bail!(
+                    "Authentication to jump host {}@{}:{} failed. Please check its credentials.",
+                    hop.username,
+                    hop.host,
+                    hop.port
+                );
+            }
+            hops.push(session);
+        }
+        Ok(Self { hops })
+    }
+
+    /// Open a tunnel from the last hop to `host:port`.
+    pub(crate) async fn open_tunnel(&self, host: &str, port: u16) -> Result<Tunnel> {
+        let last = self
+            .hops
+            .last()
+            .ok_or_else(|| anyhow!("Jump host chain is empty"))?;
+        open_tunnel(last, host, port).await
+    }
+
+    /// Close every hop, innermost first.
+    pub(crate) async fn disconnect(self) {
+        for hop in self.hops.into_iter().rev() {
+            let _ = hop
+                .disconnect(Disconnect::ByApplication, "", "English")
+                .await;
+        }
+    }
+}
+
+async fn open_tunnel(session: &client::Handle<Client>, host: &str, port: u16) -> Result<Tunnel> {
+    let channel = session
+        .channel_open_direct_tcpip(host, port as u32, "127.0.0.1", 0)
+        .await
+        .map_err(|e| {
+            anyhow!(
+                "Jump host could not open a tunnel to {}:{}: {}",
+                host,
+                port,
+                e
+            )
+        })?;
+    Ok(channel.into_stream())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jump_host_deserializes_with_default_chain() {
+        let json =
+            r#"{"host":"bastion","port":22,"username":"ops","auth_method":{"type":"Agent"}}"#;
+        let hop: JumpHost = serde_json::from_str(json).unwrap();
+        assert_eq!(hop.host, "bastion");
+        assert!(matches!(hop.auth_method, AuthMethod::Agent));
+        assert!(hop.additional_auth_methods.is_empty());
+    }
+
+    #[tokio::test]
+    async fn empty_chain_connects_nowhere() {
+        let chain = JumpChain::connect(
+            &[],
+            None,
+            Arc::new(client::Config::default()),
+            &Arc::new(HostKeyVerifier::new(None)),
+            &AuthPrompter::new(),
+            Duration::from_secs(1),
+        )
+        .await
+        .unwrap();
+        assert!(chain.open_tunnel("target", 22).await.is_err());
+    }
+}
@@ -0,0 +1,120 @@
+//! Keyboard-interactive authentication (RFC 4256), used by PAM password
+//! prompts and second factors such as TOTP or Duo.
+
+use super::Client;
+use crate::auth_prompt::{AuthPrompt, AuthPromptField, AuthPrompter};
+use anyhow::{anyhow, Result};
+use russh::client::{self, KeyboardInteractiveAuthResponse, Prompt};
+
+/// Answer every field with `password` when the round only asks for a hidden
+/// password (the usual PAM setup). Returns `None` for anything else, e.g.
+/// an OTP prompt, so it can be shown to the user.
+pub(crate) fn password_answers(prompts: &[Prompt], password: &str) -> Option<Vec<String>> {
+    let only_passwords = !prompts.is_empty()
+        && prompts
+            .iter()
+            .all(|p| !p.echo && p.prompt.to_lowercase().contains("password"));
+    only_passwords.then(|| vec![password.to_string(); prompts.len()])
+}
+
+/// Run a keyboard-interactive exchange. A stored `password` answers the first
+/// password-only round; every other round is relayed through `prompter`.
+pub(crate) async fn authenticate_keyboard_interactive(
+    session: &mut client::Handle<Client>,
+    prompter: &AuthPrompter,
+    host: &str,
+    port: u16,
+    username: &str,
+    mut password: Option<&str>,
+) -> Result<bool> {
+    let mut response = session
+        .authenticate_keyboard_interactive_start(username, None::<String>)
+        .await
+        .map_err(|e| anyhow!("Keyboard-interactive authentication failed: {}", e))?;
+
+    loop {
+        let (name, instructions, prompts) = match response {
+            KeyboardInteractiveAuthResponse::Success => return Ok(true),
+            KeyboardInteractiveAuthResponse::Failure => return Ok(false),
+            KeyboardInteractiveAuthResponse::InfoRequest {
+                name,
+                instructions,
+                prompts,
+            } => (name, instructions, prompts),
+        };
+
+        // Servers may send an empty round (e.g. to show instructions); it
+        // still has to be acknowledged with an empty response.
+        let answers = if prompts.is_empty() {
+            Vec::new()
+        } else if let Some(answers) = password.and_then(|p| password_answers(&prompts, p)) {
+            // Only once, so a wrong stored password falls through to the user.
+            password = None;
+            answers
+        } else {
+            prompter
+                .ask(AuthPrompt {
+                    prompt_id: String::new(),
+                    host: host.to_string(),
+                    port,
+                    username: username.to_string(),
+                    name,
+                    instructions,
+                    prompts: prompts
+                        .into_iter()
+                        .map(|p| AuthPromptField {
+                            prompt: p.prompt,
+                            echo: p.echo,
+                        })
+                        .collect(),
+                })
+                .await?
+        };
+
+        response = session
+            .authenticate_keyboard_interactive_respond(answers)
+            .await
+            .map_err(|e| anyhow!("Keyboard-interactive authentication failed: {}", e))?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompt(text: &str, echo: bool) -> Prompt {
+        Prompt {
+            prompt: text.to_string(),
+            echo,
+        }
+    }
+
+    #[test]
+    fn password_answers_fill_password_only_rounds() {
+        let prompts = [prompt("Password: ", false)];
+        assert_eq!(
+            password_answers(&prompts, "secret"),
+            Some(vec!["secret".to_string()])
+        );
+    }
+
+    #[test]
+    fn password_answers_leave_other_prompts_to_the_user() {
+        assert_eq!(
+            password_answers(&[prompt("Verification code: ", false)], "secret"),
+            None
+        );
+        assert_eq!(
+            password_answers(
+                &[prompt("Password: ", false), prompt("OTP: ", false)],
+                "secret"
+            ),
+            None
+        );
+        assert_eq!(
+            password_answers(&[prompt("Password: ", true)], "secret"),
+            None
+        );
+        assert_eq!(password_answers(&[], "secret"), None);
+    }
+}
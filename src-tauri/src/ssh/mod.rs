@@ -1,20 +1,48 @@
+use crate::auth_prompt::AuthPrompter;
+use crate::known_hosts::{HostKeyVerifier, HOST_KEY_PROMPT_TIMEOUT};
 use crate::proxy::ProxyConfig;
+use crate::transfer::{Transfer, TransferDirection};
 use anyhow::Result;
 use russh::*;
 use russh_keys::*;
 use russh_sftp::client::SftpSession;
 use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 
+mod agent;
+mod certificate;
+mod exec;
+mod forward;
+mod jump;
+mod keyboard_interactive;
+mod reconnect;
+
+pub(crate) use agent::authenticate_with_agent;
+pub(crate) use certificate::{authenticate_key, load_user_certificate};
+pub(crate) use exec::{collect as collect_exec, run as run_exec};
+pub use exec::{ExecEvent, ExecOptions, ExecOutput, ExecStatus};
+pub use forward::{start_forward, ForwardInfo, ForwardSpec, PortForward};
+pub use jump::JumpHost;
+pub(crate) use jump::{JumpChain, Tunnel};
+pub(crate) use keyboard_interactive::authenticate_keyboard_interactive;
+pub use reconnect::{ConnectionState, ConnectionStatus, ReconnectPolicy};
+
 /// Preferred host-key algorithms advertised to the server, ordered from most to
 /// least preferred.  RSA variants (including the legacy `ssh-rsa` / SHA-1) are
 /// included so that older servers that only offer RSA host keys are still
 /// reachable.  The `openssl` feature on `russh` / `russh-keys` must be enabled
 /// for the RSA entries to have any effect.
+///
+/// Host certificate algorithms (`*-cert-v01@openssh.com`) are not offered:
+/// russh 0.44 cannot negotiate them, so host certificates are out of scope
+/// and every server is verified by its plain key against known_hosts.
 pub static PREFERRED_HOST_KEY_ALGOS: &[russh_keys::key::Name] = &[
     russh_keys::key::ED25519,
     russh_keys::key::ECDSA_SHA2_NISTP256,
@@ -81,14 +109,29 @@ pub struct SshConfig {
     pub port: u16,
     pub username: String,
     pub auth_method: AuthMethod,
+    /// Further methods tried in order after `auth_method`, for servers that
+    /// require several (`AuthenticationMethods publickey,keyboard-interactive`).
+    #[serde(default)]
+    pub additional_auth_methods: Vec<AuthMethod>,
     /// Enable zlib compression negotiation (default: true, matching the UI).
     pub compression: bool,
     /// Keepalive interval in seconds. `None` disables keepalive.
     pub keepalive_interval: Option<u64>,
     /// Max missed keepalive replies before the connection is closed.
     pub keepalive_max: Option<u32>,
-    /// Optional HTTP/SOCKS proxy tunnel. `None` connects directly.
+    /// Optional HTTP/SOCKS proxy tunnel. `None` connects directly. With jump
+    /// hosts, the proxy is used to reach the first hop.
     pub proxy: Option<ProxyConfig>,
+    /// Bastions to hop through, outermost first (OpenSSH `-J a,b`).
+    #[serde(default)]
+    pub jump_hosts: Vec<JumpHost>,
+    /// Forward the local SSH agent to the remote shell (`ssh -A`). Off by
+    /// default: anyone with root on the server can use the forwarded agent.
+    #[serde(default)]
+    pub agent_forwarding: bool,
+    /// Reconnect automatically when the transport drops.
+    #[serde(default)]
+    pub reconnect: ReconnectPolicy,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -100,6 +143,18 @@ pub enum AuthMethod {
     PublicKey {
         key_path: String,
         passphrase: Option<String>,
+        /// OpenSSH certificate to present with the key. Defaults to
+        /// `<key_path>-cert.pub` when that file exists.
+        #[serde(default)]
+        certificate_path: Option<String>,
+    },
+    /// Use the identities held by the local SSH agent (`SSH_AUTH_SOCK`).
+    Agent,
+    /// Answer server prompts (PAM, OTP, Duo) through the frontend. A stored
+    /// password answers the first password-only round automatically.
+    KeyboardInteractive {
+        #[serde(default)]
+        password: Option<String>,
     },
 }
 
@@ -110,15 +165,28 @@ pub struct SshSession {
     pub connected: bool,
 }
 
+/// An established connection. Requests that only need `&Handle` share the
+/// read lock; russh 0.44 wants `&mut Handle` for `tcpip-forward`, which
+/// takes the write lock for its round trip.
+pub(crate) type SessionHandle = Arc<tokio::sync::RwLock<client::Handle<Client>>>;
+
 pub struct SshClient {
-    session: Option<Arc<client::Handle<Client>>>,
+    session: Option<SessionHandle>,
+    host_key_verifier: Arc<HostKeyVerifier>,
+    auth_prompter: Arc<AuthPrompter>,
+    /// Bastion sessions carrying `session`, kept open for its lifetime.
+    jump_chain: Option<JumpChain>,
+    agent_forwarding: bool,
+    /// Targets for `-R` forwards, shared with the session's handler.
+    remote_forwards: forward::RemoteForwards,
 }
 
 // PTY session handle for interactive shell
 pub struct PtySession {
     pub input_tx: mpsc::Sender<Vec<u8>>,
     pub output_rx: Arc<tokio::sync::Mutex<mpsc::Receiver<Vec<u8>>>>,
-    pub channel_id: ChannelId,
+    /// SSH channel carrying the shell; `None` for local and Telnet sessions.
+    pub channel_id: Option<ChannelId>,
     /// Sender for resize requests (cols, rows) — forwarded to the SSH channel
     pub resize_tx: mpsc::Sender<(u32, u32)>,
     /// Cancellation token — cancelled when this session is torn down.
@@ -126,23 +194,347 @@ pub struct PtySession {
     pub cancel: CancellationToken,
 }
 
-pub struct Client;
+/// russh event handler shared by every SSH transport. Verifies the server's
+/// host key against known_hosts before any credentials are sent.
+pub struct Client {
+    host: String,
+    port: u16,
+    verifier: Arc<HostKeyVerifier>,
+    /// Set once verification starts asking the user, so the caller's connect
+    /// timeout can give the prompt its own budget.
+    prompting: Arc<AtomicBool>,
+    /// Accept `auth-agent@openssh.com` channels from the server.
+    forward_agent: bool,
+    agent_channels: agent::AgentForwarder,
+    remote_forwards: forward::RemoteForwards,
+}
+
+impl Client {
+    pub fn new(host: &str, port: u16, verifier: Arc<HostKeyVerifier>) -> Self {
+        Self {
+            host: host.to_string(),
+            port,
+            verifier,
+            prompting: Arc::new(AtomicBool::new(false)),
+            forward_agent: false,
+            agent_channels: agent::AgentForwarder::default(),
+            remote_forwards: forward::RemoteForwards::default(),
+        }
+    }
+
+    /// Relay agent channels opened by the server to the local SSH agent.
+    /// Without this, such channels are closed immediately.
+    pub fn with_agent_forwarding(mut self, enabled: bool) -> Self {
+        self.forward_agent = enabled;
+        self
+    }
+
+    /// Route `forwarded-tcpip` channels from the server to `remote_forwards`.
+    /// Without this, such channels are closed immediately.
+    pub(crate) fn with_remote_forwards(mut self, remote_forwards: forward::RemoteForwards) -> Self {
+        self.remote_forwards = remote_forwards;
+        self
+    }
+
+    /// Flag that is raised while the user is being asked to trust the host key.
+    pub fn prompt_flag(&self) -> Arc<AtomicBool> {
+        self.prompting.clone()
+    }
+
+    /// Slot for the session handle that forwarded agent replies are written
+    /// through; attach the handle once connected.
+    pub(crate) fn agent_session(&self) -> agent::AgentSession {
+        self.agent_channels.session()
+    }
+}
 
 #[async_trait::async_trait]
 impl client::Handler for Client {
-    type Error = russh::Error;
+    type Error = anyhow::Error;
 
     async fn check_server_key(
         &mut self,
-        _server_public_key: &key::PublicKey,
+        server_public_key: &key::PublicKey,
     ) -> Result<bool, Self::Error> {
-        Ok(true) // In production, verify the server key
+        self.prompting.store(true, Ordering::SeqCst);
+        let result = self
+            .verifier
+            .verify(&self.host, self.port, server_public_key)
+            .await;
+        self.prompting.store(false, Ordering::SeqCst);
+        result.map(|()| true)
+    }
+
+    async fn server_channel_open_agent_forward(
+        &mut self,
+        channel: ChannelId,
+        session: &mut client::Session,
+    ) -> Result<(), Self::Error> {
+        if !self.forward_agent {
+            session.close(channel);
+            return Ok(());
+        }
+        self.agent_channels.open(channel);
+        Ok(())
+    }
+
+    async fn server_channel_open_forwarded_tcpip(
+        &mut self,
+        channel: Channel<client::Msg>,
+        _connected_address: &str,
+        connected_port: u32,
+        _originator_address: &str,
+        _originator_port: u32,
+        _session: &mut client::Session,
+    ) -> Result<(), Self::Error> {
+        self.remote_forwards.accept(channel, connected_port);
+        Ok(())
+    }
+
+    async fn data(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut client::Session,
+    ) -> Result<(), Self::Error> {
+        if !self.agent_channels.handles(channel) {
+            return Ok(());
+        }
+        if !self.agent_channels.relay(channel, data) {
+            self.agent_channels.close(channel);
+            session.close(channel);
+        }
+        Ok(())
+    }
+
+    async fn channel_eof(
+        &mut self,
+        channel: ChannelId,
+        session: &mut client::Session,
+    ) -> Result<(), Self::Error> {
+        if self.agent_channels.handles(channel) {
+            self.agent_channels.close(channel);
+            session.close(channel);
+        }
+        Ok(())
+    }
+
+    async fn channel_close(
+        &mut self,
+        channel: ChannelId,
+        _session: &mut client::Session,
+    ) -> Result<(), Self::Error> {
+        self.agent_channels.close(channel);
+        Ok(())
+    }
+}
+
+/// Await an SSH connect/handshake future bounded by `timeout`.
+///
+/// If the handshake is still waiting on a host-key prompt when the timeout
+/// fires, the user gets `HOST_KEY_PROMPT_TIMEOUT` to answer instead of the
+/// connection being dropped mid-question. Returns `None` on timeout.
+pub(crate) async fn with_handshake_timeout<F: Future>(
+    future: F,
+    timeout: Duration,
+    prompting: &AtomicBool,
+) -> Option<F::Output> {
+    tokio::pin!(future);
+    if let Ok(output) = tokio::time::timeout(timeout, &mut future).await {
+        return Some(output);
+    }
+    if !prompting.load(Ordering::SeqCst) {
+        return None;
+    }
+    tokio::time::timeout(HOST_KEY_PROMPT_TIMEOUT + timeout, future)
+        .await
+        .ok()
+}
+
+/// An authentication method that can't be tried from here, such as an
+/// unreachable SSH agent or a missing key file. Chains skip it and go on to
+/// the next method instead of failing.
+#[derive(Debug)]
+pub(crate) struct MethodUnavailable(pub(crate) String);
+
+impl std::fmt::Display for MethodUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for MethodUnavailable {}
+
+/// Record the outcome of one method in a chain. Unavailable methods count as
+/// not accepted and are kept in `skipped`; any other error ends the chain.
+pub(crate) fn skip_unavailable(
+    result: Result<bool>,
+    skipped: &mut Vec<anyhow::Error>,
+) -> Result<bool> {
+    match result {
+        Err(e) if e.is::<MethodUnavailable>() => {
+            tracing::debug!("Skipping authentication method: {}", e);
+            skipped.push(e);
+            Ok(false)
+        }
+        other => other,
     }
 }
 
+/// When none of `tried` methods was available, the reason to report instead
+/// of a generic authentication failure.
+pub(crate) fn nothing_available(tried: usize, mut skipped: Vec<anyhow::Error>) -> Result<()> {
+    if tried == 0 || skipped.len() < tried {
+        return Ok(());
+    }
+    if skipped.len() == 1 {
+        return Err(skipped.remove(0));
+    }
+    let reasons: Vec<String> = skipped.iter().map(|e| e.to_string()).collect();
+    Err(anyhow::anyhow!(
+        "No authentication method could be used: {}",
+        reasons.join(" ")
+    ))
+}
+
+/// Try `methods` in order until the server accepts one. A method accepted
+/// with partial success is reported as a failure, and the server then expects
+/// the next method in the chain (`AuthenticationMethods a,b`). Methods that
+/// are unavailable locally are skipped.
+pub(crate) async fn authenticate_methods(
+    session: &mut client::Handle<Client>,
+    prompter: &AuthPrompter,
+    host: &str,
+    port: u16,
+    username: &str,
+    methods: &[&AuthMethod],
+) -> Result<bool> {
+    let mut skipped = Vec::new();
+    for method in methods {
+        let result = authenticate_method(session, prompter, host, port, username, method).await;
+        if skip_unavailable(result, &mut skipped)? {
+            return Ok(true);
+        }
+    }
+    nothing_available(methods.len(), skipped)?;
+    Ok(false)
+}
+
+/// Expand a leading `~` in a key or certificate path. Uses dirs::home_dir()
+/// for cross-platform support (HOME is not set on Windows; USERPROFILE is
+/// used instead).
+pub(crate) fn expand_tilde(path: &str) -> String {
+    if path.starts_with("~/") || path.starts_with("~\\") {
+        if let Some(home) = dirs::home_dir() {
+            let home_str = home.to_string_lossy();
+            return path.replacen('~', &home_str, 1);
+        }
+    }
+    path.to_string()
+}
+
+/// Attempt a single authentication method. `Ok(false)` means the server
+/// did not (fully) accept it.
+async fn authenticate_method(
+    session: &mut client::Handle<Client>,
+    prompter: &AuthPrompter,
+    host: &str,
+    port: u16,
+    username: &str,
+    method: &AuthMethod,
+) -> Result<bool> {
+    Ok(match method {
+        AuthMethod::Password { password } => session
+            .authenticate_password(username, password)
+            .await
+            .map_err(|e| anyhow::anyhow!("Password authentication failed: {}", e))?,
+        AuthMethod::PublicKey {
+            key_path,
+            passphrase,
+            certificate_path,
+        } => {
+            let expanded_path = expand_tilde(key_path);
+
+            // Check if file exists
+            if !std::path::Path::new(&expanded_path).exists() {
+                return Err(MethodUnavailable(format!(
+                    "SSH key file not found: {}. Please check the file path and try again.",
+                    key_path
+                ))
+                .into());
+            }
+
+            // Read the key file and normalise CRLF line endings so that keys
+            // created or edited on Windows (which use \r\n) are parsed correctly
+            // by russh-keys' PEM / OpenSSH decoder.
+            let key_content = std::fs::read_to_string(&expanded_path).map_err(|e| {
+                anyhow::anyhow!("Failed to read SSH key file {}: {}", key_path, e)
+            })?;
+            let key_content = key_content.replace("\r\n", "\n");
+
+            // decode_secret_key takes the key *content* as a &str.
+            let key = decode_secret_key(&key_content, passphrase.as_deref())
+                .map_err(|e| {
+                    if e.to_string().contains("encrypted") || e.to_string().contains("passphrase") {
+                        anyhow::anyhow!(
+                            "Failed to decrypt SSH key. The key may be encrypted. Please provide the correct passphrase."
+                        )
+                    } else {
+                        anyhow::anyhow!(
+                            "Failed to load SSH key from {}: {}. Ensure the file is a valid SSH private key (RSA, Ed25519, or ECDSA).",
+                            key_path, e
+                        )
+                    }
+                })?;
+
+            let certificate_path = certificate_path.as_deref().map(expand_tilde);
+            let certificate = load_user_certificate(
+                std::path::Path::new(&expanded_path),
+                certificate_path.as_deref().map(std::path::Path::new),
+                &key,
+            )?;
+
+            authenticate_key(session, username, key, certificate)
+                .await
+                .map_err(|e| anyhow::anyhow!("Public key authentication failed: {}. The key may not be authorized on the server.", e))?
+        }
+        AuthMethod::Agent => authenticate_with_agent(session, username).await?,
+        AuthMethod::KeyboardInteractive { password } => {
+            authenticate_keyboard_interactive(
+                session,
+                prompter,
+                host,
+                port,
+                username,
+                password.as_deref(),
+            )
+            .await?
+        }
+    })
+}
+
 impl SshClient {
+    /// Client that verifies host keys against `~/.ssh/known_hosts` and refuses
+    /// unknown hosts (no prompt handler is installed).
     pub fn new() -> Self {
-        Self { session: None }
+        Self::with_host_key_verifier(Arc::new(HostKeyVerifier::from_default_location()))
+    }
+
+    pub fn with_host_key_verifier(host_key_verifier: Arc<HostKeyVerifier>) -> Self {
+        Self {
+            session: None,
+            host_key_verifier,
+            auth_prompter: Arc::new(AuthPrompter::new()),
+            jump_chain: None,
+            agent_forwarding: false,
+            remote_forwards: forward::RemoteForwards::default(),
+        }
+    }
+
+    /// Relay keyboard-interactive prompts through `auth_prompter`.
+    pub fn with_auth_prompter(mut self, auth_prompter: Arc<AuthPrompter>) -> Self {
+        self.auth_prompter = auth_prompter;
+        self
     }
 
     pub async fn connect(&mut self, config: &SshConfig) -> Result<()> {
@@ -167,93 +559,78 @@ impl SshClient {
         // Connection timeout: 3 seconds
         let connection_timeout = Duration::from_secs(3);
 
-        let mut ssh_session = if let Some(proxy) = &config.proxy {
+        let handler = Client::new(&config.host, config.port, self.host_key_verifier.clone())
+            .with_agent_forwarding(config.agent_forwarding)
+            .with_remote_forwards(self.remote_forwards.clone());
+        let prompting = handler.prompt_flag();
+        let agent_session = handler.agent_session();
+
+        let ssh_config = Arc::new(ssh_config);
+        let mut jump_chain = None;
+        let mut ssh_session = if !config.jump_hosts.is_empty() {
+            let chain = JumpChain::connect(
+                &config.jump_hosts,
+                config.proxy.as_ref(),
+                ssh_config.clone(),
+                &self.host_key_verifier,
+                &self.auth_prompter,
+                connection_timeout,
+            )
+            .await?;
+            let tunnel = chain.open_tunnel(&config.host, config.port).await?;
+            jump_chain = Some(chain);
+            with_handshake_timeout(
+                client::connect_stream(ssh_config, tunnel, handler),
+                connection_timeout,
+                &prompting,
+            )
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Connection timed out after 3 seconds. Please check the host address and network connectivity."))?
+            .map_err(|e| anyhow::anyhow!("Failed to connect to {}:{}: {}", config.host, config.port, e))?
+        } else if let Some(proxy) = &config.proxy {
             // Tunnel through the proxy first, then hand the established stream
             // to russh so the SSH handshake runs over the tunnel.
             let stream = crate::proxy::connect_via_proxy(
                 proxy,
                 &config.host,
                 config.port,
+                &config.username,
                 connection_timeout,
             )
             .await
             .map_err(|e| anyhow::anyhow!("Proxy connection failed: {e}"))?;
-            tokio::time::timeout(
-                connection_timeout,
-                client::connect_stream(Arc::new(ssh_config), stream, Client),
+            let handshake_timeout = proxy.handshake_timeout(connection_timeout);
+            with_handshake_timeout(
+                client::connect_stream(ssh_config, stream, handler),
+                handshake_timeout,
+                &prompting,
             )
             .await
-            .map_err(|_| anyhow::anyhow!("Connection timed out after 3 seconds. Please check the host address and network connectivity."))?
+            .ok_or_else(|| anyhow::anyhow!("Connection timed out after {} seconds. Please check the host address and network connectivity.", handshake_timeout.as_secs()))?
             .map_err(|e| anyhow::anyhow!("Failed to connect to {}:{}: {}", config.host, config.port, e))?
         } else {
-            tokio::time::timeout(
+            with_handshake_timeout(
+                client::connect(ssh_config, (&config.host[..], config.port), handler),
                 connection_timeout,
-                client::connect(Arc::new(ssh_config), (&config.host[..], config.port), Client),
+                &prompting,
             )
             .await
-            .map_err(|_| anyhow::anyhow!("Connection timed out after 3 seconds. Please check the host address and network connectivity."))?
+            .ok_or_else(|| anyhow::anyhow!("Connection timed out after 3 seconds. Please check the host address and network connectivity."))?
             .map_err(|e| anyhow::anyhow!("Failed to connect to {}:{}: {}", config.host, config.port, e))?
         };
 
-        let authenticated = match &config.auth_method {
-            AuthMethod::Password { password } => ssh_session
-                .authenticate_password(&config.username, password)
-                .await
-                .map_err(|e| anyhow::anyhow!("Password authentication failed: {}", e))?,
-            AuthMethod::PublicKey {
-                key_path,
-                passphrase,
-            } => {
-                // Expand tilde in path — use dirs::home_dir() for cross-platform
-                // support (HOME is not set on Windows; USERPROFILE is used instead).
-                let expanded_path = if key_path.starts_with("~/") || key_path.starts_with("~\\") {
-                    if let Some(home) = dirs::home_dir() {
-                        let home_str = home.to_string_lossy();
-                        key_path.replacen('~', &home_str, 1)
-                    } else {
-                        key_path.clone()
-                    }
-                } else {
-                    key_path.clone()
-                };
-
-                // Check if file exists
-                if !std::path::Path::new(&expanded_path).exists() {
-                    return Err(anyhow::anyhow!(
-                        "SSH key file not found: {}. Please check the file path and try again.",
-                        key_path
-                    ));
-                }
-
-                // Read the key file and normalise CRLF line endings so that keys
-                // created or edited on Windows (which use \r\n) are parsed correctly
-                // by russh-keys' PEM / OpenSSH decoder.
-                let key_content = std::fs::read_to_string(&expanded_path).map_err(|e| {
-                    anyhow::anyhow!("Failed to read SSH key file {}: {}", key_path, e)
-                })?;
-                let key_content = key_content.replace("\r\n", "\n");
-
-                // decode_secret_key takes the key *content* as a &str.
-                let key = decode_secret_key(&key_content, passphrase.as_deref())
-                    .map_err(|e| {
-                        if e.to_string().contains("encrypted") || e.to_string().contains("passphrase") {
-                            anyhow::anyhow!(
-                                "Failed to decrypt SSH key. The key may be encrypted. Please provide the correct passphrase."
-                            )
-                        } else {
-                            anyhow::anyhow!(
-                                "Failed to load SSH key from {}: {}. Ensure the file is a valid SSH private key (RSA, Ed25519, or ECDSA).",
-                                key_path, e
-                            )
-                        }
-                    })?;
-
-                ssh_session
-                    .authenticate_publickey(&config.username, Arc::new(key))
-                    .await
-                    .map_err(|e| anyhow::anyhow!("Public key authentication failed: {}. The key may not be authorized on the server.", e))?
-            }
-        };
+        let methods: Vec<&AuthMethod> = std::iter::once(&config.auth_method)
+            .chain(&config.additional_auth_methods)
+            .collect();
+        let authenticated = authenticate_methods(
+            &mut ssh_session,
+            &self.auth_prompter,
+            &config.host,
+            config.port,
+            &config.username,
+            &methods,
+        )
+        .await?;
 
         if !authenticated {
             return Err(anyhow::anyhow!(
@@ -261,67 +638,53 @@ impl SshClient {
             ));
         }
 
-        self.session = Some(Arc::new(ssh_session));
+        let ssh_session = Arc::new(tokio::sync::RwLock::new(ssh_session));
+        if config.agent_forwarding {
+            agent_session.attach(&ssh_session);
+        }
+        self.session = Some(ssh_session);
+        self.jump_chain = jump_chain;
+        self.agent_forwarding = config.agent_forwarding;
         Ok(())
     }
 
-    // Changed to &self instead of &mut self to allow concurrent access
+    /// Run `command` and return its stdout, failing on a non-zero exit.
     pub async fn execute_command(&self, command: &str) -> Result<String> {
-        if let Some(session) = &self.session {
-            let mut channel = session.channel_open_session().await?;
-            channel.exec(true, command).await?;
-
-            let mut output = String::new();
-            let mut code = None;
-            let mut eof_received = false;
-            let mut server_closed = false;
-
-            loop {
-                let msg = channel.wait().await;
-                match msg {
-                    Some(ChannelMsg::Data { ref data }) => {
-                        output.push_str(&String::from_utf8_lossy(data));
-                    }
-                    Some(ChannelMsg::ExitStatus { exit_status }) => {
-                        code = Some(exit_status);
-                        if eof_received {
-                            break;
-                        }
-                    }
-                    Some(ChannelMsg::Eof) => {
-                        eof_received = true;
-                        if code.is_some() {
-                            break;
-                        }
-                    }
-                    Some(ChannelMsg::Close) => {
-                        server_closed = true;
-                        break;
-                    }
-                    None => {
-                        server_closed = true;
-                        break;
-                    }
-                    _ => {}
+        let output = self.exec(command, &ExecOptions::default()).await?;
+        let status = &output.status;
+        // Consider success if we got output and no explicit error code, or code 0
+        match (status.exit_code, &status.exit_signal) {
+            (Some(0), _) => Ok(output.stdout),
+            (None, None) if !output.stdout.is_empty() => Ok(output.stdout),
+            (_, Some(signal)) => Err(anyhow::anyhow!("Command killed by signal {}", signal)),
+            (code, None) => {
+                let stderr = output.stderr.trim();
+                if stderr.is_empty() {
+                    Err(anyhow::anyhow!("Command failed with code: {:?}", code))
+                } else {
+                    Err(anyhow::anyhow!("Command failed with code {:?}: {}", code, stderr))
                 }
             }
+        }
+    }
 
-            // Send SSH_MSG_CHANNEL_CLOSE if the server hasn't already closed the channel.
-            // Without this, russh's session keeps the channel in its internal map until
-            // the session is torn down, causing per-poll memory growth.
-            if !server_closed {
-                let _ = channel.close().await;
-            }
+    /// Run `command` and collect stdout, stderr and how it exited. A
+    /// non-zero exit is reported in the result, not as an error.
+    pub async fn exec(&self, command: &str, options: &ExecOptions) -> Result<ExecOutput> {
+        let channel = self.open_exec(command).await?;
+        exec::collect(channel, options).await
+    }
 
-            // Consider success if we got output and no explicit error code, or code 0
-            match code {
-                Some(0) => Ok(output),
-                None if !output.is_empty() => Ok(output), // No exit code but got output = success
-                _ => Err(anyhow::anyhow!("Command failed with code: {:?}", code)),
-            }
-        } else {
-            Err(anyhow::anyhow!("Not connected"))
-        }
+    /// Start `command` on a new exec channel. The channel doesn't borrow the
+    /// client, so long-running commands can be driven without holding its lock.
+    pub(crate) async fn open_exec(&self, command: &str) -> Result<Channel<client::Msg>> {
+        let session = self
+            .session
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Not connected"))?;
+        let channel = session.read().await.channel_open_session().await?;
+        channel.exec(true, command).await?;
+        Ok(channel)
     }
 
     pub async fn disconnect(&mut self) -> Result<()> {
@@ -330,8 +693,12 @@ impl SshClient {
             match Arc::try_unwrap(session) {
                 Ok(session) => {
                     session
+                        .into_inner()
                         .disconnect(Disconnect::ByApplication, "", "English")
                         .await?;
+                    if let Some(chain) = self.jump_chain.take() {
+                        chain.disconnect().await;
+                    }
                 }
                 Err(arc_session) => {
                     // Other references exist, just drop our reference
@@ -339,6 +706,8 @@ impl SshClient {
                 }
             }
         }
+        // Any remaining hops stay up only while the tunnel they carry is in use.
+        self.jump_chain = None;
         Ok(())
     }
 
@@ -346,6 +715,84 @@ impl SshClient {
         self.session.is_some()
     }
 
+    /// Whether the transport still works. A closed PTY channel alone does not
+    /// tell a dropped connection from a shell that exited, so this opens (and
+    /// immediately drops) a session channel.
+    pub async fn check_alive(&self) -> bool {
+        let Some(session) = &self.session else {
+            return false;
+        };
+        let session = session.read().await;
+        if session.is_closed() {
+            return false;
+        }
+        matches!(
+            tokio::time::timeout(Duration::from_secs(5), session.channel_open_session()).await,
+            Ok(Ok(_))
+        )
+    }
+
+    /// Open a `direct-tcpip` channel to `host:port` as seen from the server.
+    pub(crate) async fn open_direct_tcpip(
+        &self,
+        host: &str,
+        port: u16,
+        originator: SocketAddr,
+    ) -> Result<Tunnel> {
+        let session = self
+            .session
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Not connected"))?;
+        let channel = session
+            .read()
+            .await
+            .channel_open_direct_tcpip(
+                host,
+                port as u32,
+                originator.ip().to_string(),
+                originator.port() as u32,
+            )
+            .await
+            .map_err(|e| anyhow::anyhow!("Server could not connect to {}:{}: {}", host, port, e))?;
+        Ok(channel.into_stream())
+    }
+
+    /// Ask the server to listen on `address:port` (0 lets it choose) and
+    /// return the bound port.
+    pub(crate) async fn request_remote_forward(&self, address: &str, port: u16) -> Result<u16> {
+        let session = self
+            .session
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Not connected"))?;
+        let bound = session
+            .write()
+            .await
+            .tcpip_forward(address, port as u32)
+            .await
+            .map_err(|e| anyhow::anyhow!("Server refused to listen on {}:{}: {}", address, port, e))?;
+        if port != 0 {
+            return Ok(port);
+        }
+        u16::try_from(bound).map_err(|_| anyhow::anyhow!("Server reported invalid port {}", bound))
+    }
+
+    pub(crate) async fn cancel_remote_forward(&self, address: &str, port: u16) -> Result<()> {
+        let session = self
+            .session
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Not connected"))?;
+        session
+            .read()
+            .await
+            .cancel_tcpip_forward(address, port as u32)
+            .await?;
+        Ok(())
+    }
+
+    pub(crate) fn remote_forwards(&self) -> &forward::RemoteForwards {
+        &self.remote_forwards
+    }
+
     /// Create a persistent PTY shell session (like ttyd)
     /// This enables interactive commands like vim, less, more, top, etc.
     pub async fn create_pty_session(&self, cols: u32, rows: u32) -> Result<PtySession> {
@@ -360,7 +807,7 @@ impl SshClient {
             .and_then(|output| bash_version_from_probe(&output));
 
             // Open a new SSH channel
-            let mut channel = session.channel_open_session().await?;
+            let mut channel = session.read().await.channel_open_session().await?;
             let bash_terminal_modes = [(Pty::ECHO, 0), (Pty::ECHONL, 0)];
             let terminal_modes = if bash_version.is_some() {
                 bash_terminal_modes.as_slice()
@@ -382,6 +829,12 @@ impl SshClient {
                 )
                 .await?;
 
+            // Agent forwarding must be requested before the shell starts so
+            // that SSH_AUTH_SOCK is set in its environment.
+            if self.agent_forwarding {
+                channel.agent_forward(true).await?;
+            }
+
             // Start interactive shell
             channel.request_shell(true).await?;
 
@@ -475,7 +928,7 @@ impl SshClient {
             Ok(PtySession {
                 input_tx,
                 output_rx: Arc::new(tokio::sync::Mutex::new(output_rx)),
-                channel_id,
+                channel_id: Some(channel_id),
                 resize_tx,
                 cancel: CancellationToken::new(),
             })
@@ -489,48 +942,22 @@ impl SshClient {
             .session
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("Not connected"))?;
-        let channel = session.channel_open_session().await?;
+        let channel = session.read().await.channel_open_session().await?;
         channel.request_subsystem(true, "sftp").await?;
         Ok(SftpSession::new(channel.into_stream()).await?)
     }
 
     pub async fn download_file(&self, remote_path: &str, local_path: &str) -> Result<u64> {
-        if let Some(session) = &self.session {
-            // Open SFTP subsystem
-            let channel = session.channel_open_session().await?;
-            channel.request_subsystem(true, "sftp").await?;
-            let sftp = SftpSession::new(channel.into_stream()).await?;
-
-            // Open remote file for reading
-            let mut remote_file = sftp.open(remote_path).await?;
-
-            // Read file content
-            let mut buffer = Vec::new();
-            let mut temp_buf = vec![0u8; 8192];
-            let mut total_bytes = 0u64;
-
-            loop {
-                let n = remote_file.read(&mut temp_buf).await?;
-                if n == 0 {
-                    break;
-                }
-                buffer.extend_from_slice(&temp_buf[..n]);
-                total_bytes += n as u64;
-            }
-
-            // Write to local file
-            tokio::fs::write(local_path, buffer).await?;
-
-            Ok(total_bytes)
-        } else {
-            Err(anyhow::anyhow!("Not connected"))
-        }
+        let sftp = self.open_sftp_session().await?;
+        let mut transfer =
+            Transfer::untracked(TransferDirection::Download, remote_path, local_path);
+        crate::sftp_client::sftp_download(&sftp, remote_path, local_path, &mut transfer).await
     }
 
     pub async fn download_file_to_memory(&self, remote_path: &str) -> Result<Vec<u8>> {
         if let Some(session) = &self.session {
             // Open SFTP subsystem
-            let channel = session.channel_open_session().await?;
+            let channel = session.read().await.channel_open_session().await?;
             channel.request_subsystem(true, "sftp").await?;
             let sftp = SftpSession::new(channel.into_stream()).await?;
 
@@ -556,35 +983,9 @@ impl SshClient {
     }
 
     pub async fn upload_file(&self, local_path: &str, remote_path: &str) -> Result<u64> {
-        if let Some(session) = &self.session {
-            // Read local file
-            let data = tokio::fs::read(local_path).await?;
-            let total_bytes = data.len() as u64;
-
-            // Open SFTP subsystem
-            let channel = session.channel_open_session().await?;
-            channel.request_subsystem(true, "sftp").await?;
-            let sftp = SftpSession::new(channel.into_stream()).await?;
-
-            // Create remote file for writing
-            let mut remote_file = sftp.create(remote_path).await?;
-
-            // Write data in chunks
-            let mut offset = 0;
-            let chunk_size = 8192;
-
-            while offset < data.len() {
-                let end = std::cmp::min(offset + chunk_size, data.len());
-                remote_file.write_all(&data[offset..end]).await?;
-                offset = end;
-            }
-
-            remote_file.flush().await?;
-
-            Ok(total_bytes)
-        } else {
-            Err(anyhow::anyhow!("Not connected"))
-        }
+        let sftp = self.open_sftp_session().await?;
+        let mut transfer = Transfer::untracked(TransferDirection::Upload, remote_path, local_path);
+        crate::sftp_client::sftp_upload(&sftp, local_path, remote_path, &mut transfer).await
     }
 
     pub async fn upload_file_from_bytes(&self, data: &[u8], remote_path: &str) -> Result<u64> {
@@ -592,7 +993,7 @@ impl SshClient {
             let total_bytes = data.len() as u64;
 
             // Open SFTP subsystem
-            let channel = session.channel_open_session().await?;
+            let channel = session.read().await.channel_open_session().await?;
             channel.request_subsystem(true, "sftp").await?;
             let sftp = SftpSession::new(channel.into_stream()).await?;
 
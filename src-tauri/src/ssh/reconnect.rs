@@ -0,0 +1,146 @@
+//! Automatic reconnection after the transport drops (keepalive timeout,
+//! network change): backoff schedule, the optional tmux/screen session the
+//! shell runs in, and the status events reported to the UI.
+
+use crate::shell;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How a lost SSH connection is re-established.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReconnectPolicy {
+    pub enabled: bool,
+    /// Attempts before giving up; 0 keeps retrying until the tab is closed.
+    pub max_attempts: u32,
+    /// Delay before the first attempt, doubled after each failure.
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// Run the shell inside a named tmux/screen session and re-attach to it
+    /// after reconnecting, so remote programs survive the drop.
+    pub reattach: Option<SessionReattach>,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_attempts: 5,
+            initial_delay_ms: 1000,
+            max_delay_ms: 30_000,
+            reattach: None,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Delay before `attempt` (1-based).
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 1u64 << attempt.saturating_sub(1).min(20);
+        Duration::from_millis(
+            self.initial_delay_ms
+                .saturating_mul(factor)
+                .min(self.max_delay_ms.max(self.initial_delay_ms)),
+        )
+    }
+
+    pub fn allows_attempt(&self, attempt: u32) -> bool {
+        self.max_attempts == 0 || attempt <= self.max_attempts
+    }
+}
+
+/// Terminal multiplexer session to attach the PTY shell to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum SessionReattach {
+    Tmux { session: String },
+    Screen { session: String },
+}
+
+impl SessionReattach {
+    /// Shell input that attaches to the session, creating it if needed.
+    pub fn attach_command(&self) -> Vec<u8> {
+        let command = match self {
+            SessionReattach::Tmux { session } => {
+                format!("tmux new-session -A -s {}", shell::quote(session))
+            }
+            SessionReattach::Screen { session } => {
+                format!("screen -D -R -S {}", shell::quote(session))
+            }
+        };
+        format!("{}\r", command).into_bytes()
+    }
+}
+
+/// State reported through the `ssh-connection-status` event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionState {
+    /// The transport dropped; reconnecting starts next.
+    Disconnected,
+    /// Waiting `retry_in_ms` before `attempt`.
+    Reconnecting,
+    /// Connected again and the PTY restarted with a new generation.
+    Reconnected,
+    /// Gave up; `error` holds the last failure.
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionStatus {
+    pub connection_id: String,
+    pub state: ConnectionState,
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub retry_in_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delay_doubles_up_to_the_cap() {
+        let policy = ReconnectPolicy::default();
+        let delays: Vec<u64> = (1..=7)
+            .map(|attempt| policy.delay(attempt).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, [1000, 2000, 4000, 8000, 16_000, 30_000, 30_000]);
+        assert_eq!(policy.delay(u32::MAX), Duration::from_millis(30_000));
+    }
+
+    #[test]
+    fn zero_max_attempts_retries_forever() {
+        let policy = ReconnectPolicy {
+            max_attempts: 0,
+            ..Default::default()
+        };
+        assert!(policy.allows_attempt(1000));
+        assert!(!ReconnectPolicy::default().allows_attempt(6));
+    }
+
+    #[test]
+    fn attach_commands_quote_the_session_name() {
+        let tmux = SessionReattach::Tmux {
+            session: "work".into(),
+        };
+        assert_eq!(tmux.attach_command(), b"tmux new-session -A -s 'work'\r");
+        let screen = SessionReattach::Screen {
+            session: "it's".into(),
+        };
+        assert_eq!(screen.attach_command(), b"screen -D -R -S 'it'\\''s'\r");
+    }
+
+    #[test]
+    fn policy_deserializes_partial_json() {
+        let policy: ReconnectPolicy =
+            serde_json::from_str(r#"{"reattach":{"type":"Tmux","session":"main"}}"#).unwrap();
+        assert!(policy.enabled);
+        assert_eq!(policy.max_attempts, 5);
+        assert!(matches!(
+            policy.reattach,
+            Some(SessionReattach::Tmux { .. })
+        ));
+    }
+}
@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use crate::ssh::{AuthMethod, SshClient, SshConfig};
+    use crate::ssh::{AuthMethod, ReconnectPolicy, SshClient, SshConfig};
     use std::sync::Arc;
     use tokio::sync::RwLock;
 
@@ -18,10 +18,14 @@ mod tests {
             auth_method: AuthMethod::Password {
                 password: TEST_PASSWORD.to_string(),
             },
+            additional_auth_methods: Vec::new(),
             compression: true,
             keepalive_interval: None,
             keepalive_max: None,
             proxy: None,
+            jump_hosts: Vec::new(),
+            agent_forwarding: false,
+            reconnect: ReconnectPolicy::default(),
         }
     }
 
@@ -34,6 +38,14 @@ mod tests {
         assert_eq!(config.username, "testuser");
     }
 
+    #[test]
+    fn test_ssh_config_agent_auth_defaults_forwarding_off() {
+        let json = r#"{"host":"server","port":22,"username":"deploy","auth_method":{"type":"Agent"},"compression":true,"keepalive_interval":null,"keepalive_max":null,"proxy":null}"#;
+        let config: SshConfig = serde_json::from_str(json).unwrap();
+        assert!(matches!(config.auth_method, AuthMethod::Agent));
+        assert!(!config.agent_forwarding);
+    }
+
     // Note: The following tests are integration tests that require a running SSH server.
     // They are marked as ignored to prevent CI failures.
     // To run these tests locally, start an SSH server and run: cargo test -- --ignored --nocapture
@@ -99,10 +111,14 @@ mod tests {
             auth_method: AuthMethod::Password {
                 password: "wrongpassword".to_string(),
             },
+            additional_auth_methods: Vec::new(),
             compression: true,
             keepalive_interval: None,
             keepalive_max: None,
             proxy: None,
+            jump_hosts: Vec::new(),
+            agent_forwarding: false,
+            reconnect: ReconnectPolicy::default(),
         };
 
         let result = client_write.connect(&config).await;
@@ -174,11 +190,13 @@ mod tests {
 
 #[cfg(test)]
 mod shell_integration_tests {
+    use crate::known_hosts::HostKeyVerifier;
     use crate::sftp_client::list_sftp_dir;
     use crate::ssh::{
         bash_shell_integration_command, bash_version_from_probe, AuthMethod, BashVersion,
-        PtySession, SshClient, SshConfig,
+        PtySession, ReconnectPolicy, SshClient, SshConfig,
     };
+    use std::sync::Arc;
     use std::time::Duration;
     use tokio::time::{timeout, Instant};
 
@@ -285,7 +303,20 @@ mod shell_integration_tests {
     #[tokio::test]
     #[ignore]
     async fn docker_ssh_reports_cwd_and_lists_sftp_directories() {
-        let mut client = SshClient::new();
+        // The container's host key is regenerated on every build, so trust it
+        // on first use against a throwaway known_hosts file.
+        let known_hosts = tempfile::tempdir().expect("create temp dir");
+        let verifier = Arc::new(HostKeyVerifier::new(Some(
+            known_hosts.path().join("known_hosts"),
+        )));
+        let responder = Arc::downgrade(&verifier);
+        verifier.set_prompt_handler(move |prompt| {
+            if let Some(v) = responder.upgrade() {
+                v.respond(&prompt.prompt_id, true);
+            }
+        });
+
+        let mut client = SshClient::with_host_key_verifier(verifier);
         client
             .connect(&SshConfig {
                 host: std::env::var("RSHELL_TEST_SSH_HOST")
@@ -295,10 +326,14 @@ mod shell_integration_tests {
                 auth_method: AuthMethod::Password {
                     password: "testpass".to_string(),
                 },
+                additional_auth_methods: Vec::new(),
                 compression: true,
                 keepalive_interval: Some(60),
                 keepalive_max: Some(3),
                 proxy: None,
+                jump_hosts: Vec::new(),
+                agent_forwarding: false,
+                reconnect: ReconnectPolicy::default(),
             })
             .await
             .expect("connect to Docker SSH server");
@@ -418,7 +453,7 @@ mod key_loading_tests {
 
     #[tokio::test]
     async fn test_connect_missing_key_file_returns_error() {
-        use crate::ssh::{AuthMethod, SshClient, SshConfig};
+        use crate::ssh::{AuthMethod, ReconnectPolicy, SshClient, SshConfig};
 
         let config = SshConfig {
             host: "127.0.0.1".to_string(),
@@ -427,11 +462,16 @@ mod key_loading_tests {
             auth_method: AuthMethod::PublicKey {
                 key_path: "/nonexistent/path/id_rsa".to_string(),
                 passphrase: None,
+                certificate_path: None,
             },
+            additional_auth_methods: Vec::new(),
             compression: true,
             keepalive_interval: None,
             keepalive_max: None,
             proxy: None,
+            jump_hosts: Vec::new(),
+            agent_forwarding: false,
+            reconnect: ReconnectPolicy::default(),
         };
 
         let mut client = SshClient::new();
@@ -445,6 +485,24 @@ mod key_loading_tests {
         );
     }
 
+    #[test]
+    fn unavailable_methods_are_skipped_but_reported_when_nothing_was_tried() {
+        use crate::ssh::{nothing_available, skip_unavailable, MethodUnavailable};
+
+        let unavailable = || Err(MethodUnavailable("no agent".to_string()).into());
+        let mut skipped = Vec::new();
+        assert!(!skip_unavailable(unavailable(), &mut skipped).unwrap());
+        assert!(!skip_unavailable(Ok(false), &mut skipped).unwrap());
+        assert!(skip_unavailable(Err(anyhow::anyhow!("rejected")), &mut skipped).is_err());
+        // One method reached the server, so the usual failure applies.
+        assert!(nothing_available(2, skipped).is_ok());
+
+        let mut skipped = Vec::new();
+        skip_unavailable(unavailable(), &mut skipped).unwrap();
+        let err = nothing_available(1, skipped).unwrap_err();
+        assert_eq!(err.to_string(), "no agent");
+    }
+
     // ── 5. Key loaded from a temp file (via read+decode) succeeds ────────────
     //    This mirrors the code path that was fixed: read file → normalise → decode.
 
@@ -0,0 +1,689 @@
+//! Reader for the OpenSSH client configuration (`~/.ssh/config`), so hosts
+//! already set up for `ssh` can be connected to by alias.
+//!
+//! Only the options that map onto [`SshConfig`] are interpreted; everything
+//! else is ignored, as is any `Match` criterion that cannot be evaluated
+//! without running the real client (`exec`, `user`, `canonical`, ...).
+
+use crate::known_hosts::{hosts_match, wildcard_match};
+use crate::proxy::{ProxyConfig, ProxyType};
+use crate::ssh::{AuthMethod, JumpHost, ReconnectPolicy, SshConfig};
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Guards against `Include` loops.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Keys OpenSSH tries when no `IdentityFile` is configured.
+const DEFAULT_IDENTITY_FILES: &[&str] = &["~/.ssh/id_ed25519", "~/.ssh/id_ecdsa", "~/.ssh/id_rsa"];
+
+/// One condition a block must satisfy to apply to a host.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Criterion {
+    /// `Host` patterns, comma-joined.
+    Host(String),
+    /// `Match all`.
+    All,
+    /// `Match host`: checked against the HostName resolved so far.
+    MatchHost(String),
+    /// `Match originalhost`: checked against the alias as typed.
+    OriginalHost(String),
+    /// A `Match` criterion that is not evaluated here; never matches.
+    Unsupported,
+}
+
+impl Criterion {
+    fn matches(&self, alias: &str, host_name: &str) -> bool {
+        match self {
+            Criterion::Host(patterns) | Criterion::OriginalHost(patterns) => {
+                hosts_match(patterns, alias)
+            }
+            Criterion::MatchHost(patterns) => hosts_match(patterns, host_name),
+            Criterion::All => true,
+            Criterion::Unsupported => false,
+        }
+    }
+}
+
+/// Options that apply when every criterion matches. Blocks from an included
+/// file also carry the criteria of the block containing the `Include`.
+#[derive(Debug, Clone)]
+struct Section {
+    criteria: Vec<Criterion>,
+    /// Lowercased keyword and the raw rest of the line.
+    options: Vec<(String, String)>,
+}
+
+/// The options `ssh` would use for one host, before defaults are applied.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HostConfig {
+    pub alias: String,
+    pub host_name: Option<String>,
+    pub port: Option<u16>,
+    pub user: Option<String>,
+    pub identity_files: Vec<String>,
+    pub proxy_jump: Option<String>,
+    pub proxy_command: Option<String>,
+    pub server_alive_interval: Option<u64>,
+    pub server_alive_count_max: Option<u32>,
+    pub compression: Option<bool>,
+}
+
+impl HostConfig {
+    /// The host to connect to: `HostName` with `%h` expanded, or the alias.
+    pub fn target_host(&self) -> String {
+        match &self.host_name {
+            Some(name) => expand_tokens(name, &self.alias, "", ""),
+            None => self.alias.clone(),
+        }
+    }
+
+    /// Record one option. As in OpenSSH the first value obtained wins,
+    /// except for `IdentityFile`, which accumulates.
+    fn apply(&mut self, keyword: &str, value: &str) {
+        let first = split_args(value).into_iter().next().unwrap_or_default();
+        match keyword {
+            "hostname" => set_once(&mut self.host_name, Some(first)),
+            "port" => set_once(&mut self.port, first.parse().ok()),
+            "user" => set_once(&mut self.user, Some(first)),
+            "identityfile" => self.identity_files.push(first),
+            "proxyjump" => set_once(&mut self.proxy_jump, Some(first)),
+            "proxycommand" => set_once(&mut self.proxy_command, Some(value.to_string())),
+            "serveraliveinterval" => set_once(&mut self.server_alive_interval, first.parse().ok()),
+            "serveralivecountmax" => set_once(&mut self.server_alive_count_max, first.parse().ok()),
+            "compression" => set_once(
+                &mut self.compression,
+                match first.to_lowercase().as_str() {
+                    "yes" => Some(true),
+                    "no" => Some(false),
+                    _ => None,
+                },
+            ),
+            _ => {}
+        }
+    }
+}
+
+fn set_once<T>(slot: &mut Option<T>, value: Option<T>) {
+    if slot.is_none() {
+        *slot = value;
+    }
+}
+
+/// A parsed OpenSSH client configuration, with `Include`s inlined.
+#[derive(Debug, Clone, Default)]
+pub struct SshConfigFile {
+    sections: Vec<Section>,
+}
+
+impl SshConfigFile {
+    /// Load `~/.ssh/config`. A missing file is an empty configuration.
+    pub fn load_default() -> Result<Self> {
+        let Some(ssh_dir) = dirs::home_dir().map(|home| home.join(".ssh")) else {
+            return Ok(Self::default());
+        };
+        Self::load(&ssh_dir.join("config"), &ssh_dir)
+    }
+
+    /// Load `path`, resolving relative `Include` paths against `include_dir`.
+    pub fn load(path: &Path, include_dir: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(Self::parse(&content, include_dir)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => bail!("Failed to read {}: {}", path.display(), e),
+        }
+    }
+
+    pub fn parse(content: &str, include_dir: &Path) -> Self {
+        let mut file = Self::default();
+        file.parse_into(content, include_dir, &[], 0);
+        file
+    }
+
+    fn parse_into(&mut self, content: &str, include_dir: &Path, outer: &[Criterion], depth: usize) {
+        // Options before the first Host/Match apply to every host.
+        self.sections.push(Section {
+            criteria: outer.to_vec(),
+            options: Vec::new(),
+        });
+
+        for line in content.lines() {
+            let Some((keyword, rest)) = split_line(line) else {
+                continue;
+            };
+            match keyword.as_str() {
+                "host" => {
+                    let mut criteria = outer.to_vec();
+                    criteria.push(Criterion::Host(split_args(rest).join(",")));
+                    self.sections.push(Section {
+                        criteria,
+                        options: Vec::new(),
+                    });
+                }
+                "match" => {
+                    let mut criteria = outer.to_vec();
+                    criteria.extend(parse_match(rest));
+                    self.sections.push(Section {
+                        criteria,
+                        options: Vec::new(),
+                    });
+                }
+                "include" => {
+                    let current = self
+                        .sections
+                        .last()
+                        .map(|s| s.criteria.clone())
+                        .unwrap_or_default();
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        tracing::warn!("ssh config: Include nested too deeply, skipping {}", rest);
+                        continue;
+                    }
+                    for pattern in split_args(rest) {
+                        for path in include_paths(&pattern, include_dir) {
+                            match std::fs::read_to_string(&path) {
+                                Ok(content) => {
+                                    self.parse_into(&content, include_dir, &current, depth + 1)
+                                }
+                                Err(e) => tracing::warn!(
+                                    "ssh config: failed to read {}: {}",
+                                    path.display(),
+                                    e
+                                ),
+                            }
+                        }
+                    }
+                    // Lines after the Include still belong to the current block.
+                    self.sections.push(Section {
+                        criteria: current,
+                        options: Vec::new(),
+                    });
+                }
+                _ => {
+                    if let Some(section) = self.sections.last_mut() {
+                        section.options.push((keyword, rest.to_string()));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Aliases named by `Host` lines, skipping wildcard and negated patterns.
+    pub fn hosts(&self) -> Vec<String> {
+        let mut hosts: Vec<String> = Vec::new();
+        for section in &self.sections {
+            let Some(Criterion::Host(patterns)) = section.criteria.last() else {
+                continue;
+            };
+            for pattern in patterns.split(',') {
+                let literal = !pattern.contains(['*', '?', '!']);
+                if literal && !pattern.is_empty() && !hosts.iter().any(|h| h == pattern) {
+                    hosts.push(pattern.to_string());
+                }
+            }
+        }
+        hosts
+    }
+
+    /// Collect the options that apply to `alias`.
+    pub fn resolve(&self, alias: &str) -> HostConfig {
+        let original = alias.to_lowercase();
+        let mut config = HostConfig {
+            alias: alias.to_string(),
+            ..HostConfig::default()
+        };
+        for section in &self.sections {
+            let host_name = config.target_host().to_lowercase();
+            if section
+                .criteria
+                .iter()
+                .all(|c| c.matches(&original, &host_name))
+            {
+                for (keyword, value) in &section.options {
+                    config.apply(keyword, value);
+                }
+            }
+        }
+        config
+    }
+
+    /// Build a ready-to-connect [`SshConfig`] for `alias`, filling in
+    /// OpenSSH's defaults for anything the file leaves unset.
+    pub fn ssh_config(&self, alias: &str) -> Result<SshConfig> {
+        let host = self.resolve(alias);
+        let target = host.target_host();
+        let username = host.user.clone().unwrap_or_else(local_username);
+        let mut methods = auth_methods(&host, &target, &username).into_iter();
+        let auth_method = methods.next().unwrap_or(AuthMethod::Agent);
+
+        let jump_hosts = match host.proxy_jump.as_deref() {
+            None | Some("none") => Vec::new(),
+            Some(spec) => spec
+                .split(',')
+                .map(|hop| self.jump_host(hop))
+                .collect::<Result<_>>()?,
+        };
+        let proxy = match host.proxy_command.as_deref() {
+            None | Some("none") => None,
+            Some(command) => Some(netcat_proxy(command).unwrap_or_else(|| ProxyConfig {
+                proxy_type: ProxyType::Command,
+                host: String::new(),
+                port: 0,
+                username: None,
+                password: None,
+                command: Some(command.to_string()),
+            })),
+        };
+        let keepalive_interval = host.server_alive_interval.filter(|&secs| secs > 0);
+
+        Ok(SshConfig {
+            host: target,
+            port: host.port.unwrap_or(22),
+            username,
+            auth_method,
+            additional_auth_methods: methods.collect(),
+            compression: host.compression.unwrap_or(false),
+            keepalive_interval,
+            keepalive_max: keepalive_interval.map(|_| host.server_alive_count_max.unwrap_or(3)),
+            proxy,
+            jump_hosts,
+            agent_forwarding: false,
+            reconnect: ReconnectPolicy::default(),
+        })
+    }
+
+    /// Turn one `ProxyJump` entry (`[user@]host[:port]` or an `ssh://` URI)
+    /// into a hop, resolving the host as an alias of its own.
+    fn jump_host(&self, spec: &str) -> Result<JumpHost> {
+        let spec = spec.trim();
+        let spec = spec.strip_prefix("ssh://").unwrap_or(spec);
+        let (user, host_port) = match spec.rsplit_once('@') {
+            Some((user, rest)) => (Some(user.to_string()), rest),
+            None => (None, spec),
+        };
+        let (host, port) = split_host_port(host_port)
+            .ok_or_else(|| anyhow!("Invalid ProxyJump host \"{}\"", spec))?;
+
+        let hop = self.resolve(host);
+        let target = hop.target_host();
+        let username = user
+            .or_else(|| hop.user.clone())
+            .unwrap_or_else(local_username);
+        let mut methods = auth_methods(&hop, &target, &username).into_iter();
+        Ok(JumpHost {
+            port: port.or(hop.port).unwrap_or(22),
+            auth_method: methods.next().unwrap_or(AuthMethod::Agent),
+            additional_auth_methods: methods.collect(),
+            host: target,
+            username,
+        })
+    }
+}
+
+/// Split a line into its lowercased keyword and the rest. Both `Key value`
+/// and `Key=value` are accepted.
+fn split_line(line: &str) -> Option<(String, &str)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let end = line
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(line.len());
+    let (keyword, rest) = line.split_at(end);
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest).trim();
+    Some((keyword.to_lowercase(), rest))
+}
+
+/// Split arguments on whitespace, honouring double quotes.
+fn split_args(value: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut in_arg = false;
+    for c in value.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_arg = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    args
+}
+
+/// Criteria of a `Match` line. `host` and `originalhost` take comma lists.
+fn parse_match(rest: &str) -> Vec<Criterion> {
+    let mut criteria = Vec::new();
+    let mut args = split_args(rest).into_iter();
+    while let Some(keyword) = args.next() {
+        let criterion = match keyword.to_lowercase().as_str() {
+            "all" => Criterion::All,
+            "host" => Criterion::MatchHost(args.next().unwrap_or_default()),
+            "originalhost" => Criterion::OriginalHost(args.next().unwrap_or_default()),
+            // Flags without an argument.
+            "canonical" | "final" => Criterion::Unsupported,
+            _ => {
+                args.next();
+                Criterion::Unsupported
+            }
+        };
+        criteria.push(criterion);
+    }
+    criteria
+}
+
+/// Files named by an `Include` argument. Relative paths are taken from
+/// `include_dir`; wildcards are supported in the file name.
+fn include_paths(pattern: &str, include_dir: &Path) -> Vec<PathBuf> {
+    let expanded = crate::ssh::expand_tilde(pattern);
+    let path = Path::new(&expanded);
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        include_dir.join(path)
+    };
+
+    let Some(name) = path.file_name().map(|n| n.to_string_lossy().into_owned()) else {
+        return Vec::new();
+    };
+    if !name.contains(['*', '?']) {
+        return vec![path];
+    }
+    let Some(dir) = path.parent() else {
+        return Vec::new();
+    };
+    let mut matches: Vec<PathBuf> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| wildcard_match(&name, &entry.file_name().to_string_lossy()))
+        .map(|entry| entry.path())
+        .filter(|p| p.is_file())
+        .collect();
+    matches.sort();
+    matches
+}
+
+/// Split `host[:port]`, allowing `[v6addr]:port`.
+fn split_host_port(value: &str) -> Option<(&str, Option<u16>)> {
+    if let Some(rest) = value.strip_prefix('[') {
+        let (host, tail) = rest.split_once(']')?;
+        let port = match tail.strip_prefix(':') {
+            Some(port) => Some(port.parse().ok()?),
+            None => None,
+        };
+        return Some((host, port));
+    }
+    match value.split_once(':') {
+        Some((host, port)) => Some((host, Some(port.parse().ok()?))),
+        None => Some((value, None)),
+    }
+}
+
+/// Expand the `%h`, `%r`, `%u`, `%d` and `%%` tokens OpenSSH allows in
+/// HostName and IdentityFile.
+fn expand_tokens(value: &str, host: &str, remote_user: &str, local_user: &str) -> String {
+    let mut out = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('h') => out.push_str(host),
+            Some('r') => out.push_str(remote_user),
+            Some('u') => out.push_str(local_user),
+            Some('d') => out.push_str(
+                &dirs::home_dir()
+                    .map(|h| h.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+            ),
+            Some('%') => out.push('%'),
+            Some(other) => {
+                out.push('%');
+                out.push(other);
+            }
+            None => out.push('%'),
+        }
+    }
+    out
+}
+
+fn local_username() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+/// Configured identity files in order; without any, the SSH agent followed
+/// by whichever default keys exist. A missing file or an unreachable agent is
+/// skipped when the chain runs, as OpenSSH does.
+fn auth_methods(host: &HostConfig, target: &str, username: &str) -> Vec<AuthMethod> {
+    let public_key = |key_path: String| AuthMethod::PublicKey {
+        key_path,
+        passphrase: None,
+        certificate_path: None,
+    };
+    if !host.identity_files.is_empty() {
+        let local_user = local_username();
+        return host
+            .identity_files
+            .iter()
+            .map(|file| public_key(expand_tokens(file, target, username, &local_user)))
+            .collect();
+    }
+
+    std::iter::once(AuthMethod::Agent)
+        .chain(
+            DEFAULT_IDENTITY_FILES
+                .iter()
+                .filter(|path| Path::new(&crate::ssh::expand_tilde(path)).exists())
+                .map(|path| public_key(path.to_string())),
+        )
+        .collect()
+}
+
+/// Recognise the netcat-style ProxyCommands that only tunnel through a
+/// SOCKS or HTTP proxy (`nc -X 5 -x proxy:1080 %h %p`,
+/// `ncat --proxy proxy:3128 --proxy-type http %h %p`), so they work without
+/// netcat installed. Anything else runs as a `ProxyType::Command`.
+fn netcat_proxy(command: &str) -> Option<ProxyConfig> {
+    let args = split_args(command);
+    let program = Path::new(args.first()?).file_name()?.to_str()?;
+    if !matches!(program, "nc" | "ncat" | "netcat") {
+        return None;
+    }
+    if args[args.len().saturating_sub(2)..] != ["%h", "%p"] {
+        return None;
+    }
+
+    let mut proxy_type = if program == "ncat" {
+        ProxyType::Http
+    } else {
+        ProxyType::Socks5
+    };
+    let mut address = None;
+    let mut credentials = None;
+    let mut options = args[1..args.len() - 2].iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "-x" | "--proxy" => address = Some(options.next()?.clone()),
+            "-X" | "--proxy-type" => {
+                proxy_type = match options.next()?.to_lowercase().as_str() {
+                    "5" | "socks5" => ProxyType::Socks5,
+                    "4" | "socks4" => ProxyType::Socks4,
+                    "connect" | "http" => ProxyType::Http,
+                    _ => return None,
+                }
+            }
+            "--proxy-auth" => credentials = Some(options.next()?.clone()),
+            _ => return None,
+        }
+    }
+
+    let default_port = match proxy_type {
+        ProxyType::Http => 3128,
+        _ => 1080,
+    };
+    let (host, port) = split_host_port(&address?)
+        .map(|(host, port)| (host.to_string(), port.unwrap_or(default_port)))?;
+    let (username, password) = match credentials {
+        Some(credentials) => match credentials.split_once(':') {
+            Some((user, pass)) => (Some(user.to_string()), Some(pass.to_string())),
+            None => (Some(credentials), None),
+        },
+        None => (None, None),
+    };
+    Some(ProxyConfig {
+        proxy_type,
+        host,
+        port,
+        username,
+        password,
+        command: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn parse(content: &str) -> SshConfigFile {
+        SshConfigFile::parse(content, Path::new("/nonexistent"))
+    }
+
+    #[test]
+    fn first_value_wins_and_identity_files_accumulate() {
+        let config = parse(
+            "Host web\n  HostName web.internal\n  Port 2222\n  IdentityFile ~/.ssh/web\n\n\
+             Host *\n  Port 22\n  User ops\n  IdentityFile=~/.ssh/id_ed25519\n  Compression yes\n",
+        );
+        let web = config.resolve("web");
+        assert_eq!(web.host_name.as_deref(), Some("web.internal"));
+        assert_eq!(web.port, Some(2222));
+        assert_eq!(web.user.as_deref(), Some("ops"));
+        assert_eq!(web.identity_files, ["~/.ssh/web", "~/.ssh/id_ed25519"]);
+        assert_eq!(web.compression, Some(true));
+
+        let other = config.resolve("other");
+        assert_eq!(other.target_host(), "other");
+        assert_eq!(other.port, Some(22));
+    }
+
+    #[test]
+    fn host_patterns_support_wildcards_negation_and_tokens() {
+        let config = parse(
+            "Host *.prod !bastion.prod\n  HostName %h.example.com\n  User deploy\n\
+             Host \"quoted alias\"\n  Port 2200\n",
+        );
+        assert_eq!(
+            config.resolve("db.prod").target_host(),
+            "db.prod.example.com"
+        );
+        assert_eq!(config.resolve("bastion.prod").user, None);
+        assert_eq!(config.resolve("quoted alias").port, Some(2200));
+        assert_eq!(config.hosts(), ["quoted alias"]);
+    }
+
+    #[test]
+    fn match_host_sees_the_resolved_host_name() {
+        let config = parse(
+            "Host db\n  HostName db.corp.example.com\n\
+             Match host *.corp.example.com\n  ProxyJump gw\n\
+             Match originalhost db exec \"true\"\n  Port 1\n\
+             Match originalhost db\n  User dba\n",
+        );
+        let db = config.resolve("db");
+        assert_eq!(db.proxy_jump.as_deref(), Some("gw"));
+        assert_eq!(db.port, None);
+        assert_eq!(db.user.as_deref(), Some("dba"));
+    }
+
+    #[test]
+    fn include_expands_globs_inside_the_enclosing_block() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("config.d")).unwrap();
+        std::fs::write(
+            dir.path().join("config.d").join("10-app"),
+            "Host app\n  HostName app.internal\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("config.d").join("ignored.bak"), "Port 9\n").unwrap();
+        std::fs::write(dir.path().join("shared"), "Port 2022\n").unwrap();
+        std::fs::write(
+            dir.path().join("config"),
+            "Include config.d/1*\nHost legacy\n  Include shared\n  User old\n",
+        )
+        .unwrap();
+
+        let config = SshConfigFile::load(&dir.path().join("config"), dir.path()).unwrap();
+        assert_eq!(config.resolve("app").target_host(), "app.internal");
+        assert_eq!(config.resolve("app").port, None);
+        let legacy = config.resolve("legacy");
+        assert_eq!(legacy.port, Some(2022));
+        assert_eq!(legacy.user.as_deref(), Some("old"));
+    }
+
+    #[test]
+    fn ssh_config_fills_keepalive_proxy_and_jump_hosts() {
+        let config = parse(
+            "Host gw\n  HostName gw.example.com\n  User jump\n  Port 2222\n\
+             Host app\n  HostName 10.0.0.5\n  User app\n  IdentityFile /keys/app\n\
+             \x20 ProxyJump gw,admin@[fd00::1]:22\n  ServerAliveInterval 30\n\
+             \x20 ServerAliveCountMax 5\n  Compression no\n\
+             Host socks\n  User u\n  ProxyCommand nc -X 5 -x proxy.local:1081 %h %p\n",
+        );
+
+        let app = config.ssh_config("app").unwrap();
+        assert_eq!(app.host, "10.0.0.5");
+        assert_eq!(app.port, 22);
+        assert_eq!(app.username, "app");
+        assert!(
+            matches!(&app.auth_method, AuthMethod::PublicKey { key_path, .. } if key_path == "/keys/app")
+        );
+        assert_eq!(app.keepalive_interval, Some(30));
+        assert_eq!(app.keepalive_max, Some(5));
+        assert!(!app.compression);
+        assert_eq!(app.jump_hosts.len(), 2);
+        assert_eq!(app.jump_hosts[0].host, "gw.example.com");
+        assert_eq!(app.jump_hosts[0].port, 2222);
+        assert_eq!(app.jump_hosts[0].username, "jump");
+        assert_eq!(app.jump_hosts[1].host, "fd00::1");
+        assert_eq!(app.jump_hosts[1].username, "admin");
+
+        let socks = config.ssh_config("socks").unwrap();
+        let proxy = socks.proxy.unwrap();
+        assert_eq!(proxy.proxy_type, ProxyType::Socks5);
+        assert_eq!((proxy.host.as_str(), proxy.port), ("proxy.local", 1081));
+        assert_eq!(socks.keepalive_interval, None);
+    }
+
+    #[test]
+    fn other_proxy_commands_run_as_commands() {
+        let config = parse("Host x\n  ProxyCommand ssh -W %h:%p gw\n");
+        let proxy = config.ssh_config("x").unwrap().proxy.unwrap();
+        assert_eq!(proxy.proxy_type, ProxyType::Command);
+        assert_eq!(proxy.command.as_deref(), Some("ssh -W %h:%p gw"));
+
+        let http = netcat_proxy("ncat --proxy p:8080 --proxy-auth a:b %h %p").unwrap();
+        assert_eq!(http.proxy_type, ProxyType::Http);
+        assert_eq!(http.password.as_deref(), Some("b"));
+    }
+}
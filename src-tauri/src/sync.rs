@@ -0,0 +1,501 @@
+//! Directory synchronization: comparing a local and a remote tree and
+//! planning what to copy, create or delete to bring them in line.
+
+use crate::dir_transfer::{validate_remote_relative_path, TreeEntry};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncMode {
+    /// Make the remote tree match the local one.
+    #[default]
+    MirrorToRemote,
+    /// Make the local tree match the remote one.
+    MirrorToLocal,
+    /// Copy what is missing or changed in either direction. Without a record
+    /// of the previous sync a deletion can't be told from a new file on the
+    /// other side, so this mode never deletes.
+    TwoWay,
+}
+
+/// How a two-way sync settles a file that differs on both sides.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Leave both copies alone and report the conflict.
+    #[default]
+    Skip,
+    PreferLocal,
+    PreferRemote,
+    /// Keep the copy with the later modification time; a tie is a conflict.
+    PreferNewer,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncOptions {
+    pub mode: SyncMode,
+    pub conflict_policy: ConflictPolicy,
+    /// Mirror modes only: delete destination entries the source lacks.
+    pub delete_extraneous: bool,
+    /// Hash files whose sizes match but whose times don't, instead of
+    /// treating them as changed.
+    pub compare_checksum: bool,
+    /// Same globs as directory transfers; excluded entries are neither
+    /// copied nor deleted.
+    pub exclude_patterns: Vec<String>,
+    /// Modification times this close count as equal. FAT and FTP listings
+    /// only keep a coarse time.
+    pub mtime_tolerance_secs: u64,
+}
+
+impl SyncOptions {
+    /// Whether a plan made with these options can contain `action`.
+    fn allows(&self, action: SyncAction) -> bool {
+        use SyncAction::*;
+        match (self.mode, action) {
+            (_, Conflict) => true,
+            (SyncMode::MirrorToRemote, Upload | CreateRemoteDir) => true,
+            (SyncMode::MirrorToRemote, DeleteRemote) => self.delete_extraneous,
+            (SyncMode::MirrorToLocal, Download | CreateLocalDir) => true,
+            (SyncMode::MirrorToLocal, DeleteLocal) => self.delete_extraneous,
+            (SyncMode::TwoWay, Upload | Download | CreateLocalDir | CreateRemoteDir) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self {
+            mode: SyncMode::default(),
+            conflict_policy: ConflictPolicy::default(),
+            delete_extraneous: false,
+            compare_checksum: false,
+            exclude_patterns: Vec::new(),
+            mtime_tolerance_secs: 2,
+        }
+    }
+}
+
+/// A local directory and the remote directory it is synced with.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SyncRequest {
+    pub connection_id: String,
+    pub local_path: String,
+    pub remote_path: String,
+    #[serde(default)]
+    pub options: SyncOptions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncAction {
+    Upload,
+    Download,
+    CreateLocalDir,
+    CreateRemoteDir,
+    DeleteLocal,
+    DeleteRemote,
+    /// Needs a decision; applying the plan skips it.
+    Conflict,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncItem {
+    pub relative_path: String,
+    pub action: SyncAction,
+    pub is_dir: bool,
+    /// Bytes an upload or download copies; 0 otherwise.
+    pub size: u64,
+    pub reason: String,
+}
+
+/// What applying a sync would do, in the order it does it: directories and
+/// copies parents first, then deletions children first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncPlan {
+    pub items: Vec<SyncItem>,
+}
+
+impl SyncPlan {
+    /// Check a plan handed back by the frontend before applying it: every
+    /// path has to stay inside both trees, and every action has to be one
+    /// `options` could have planned.
+    pub fn check(&self, options: &SyncOptions) -> Result<()> {
+        for item in &self.items {
+            validate_remote_relative_path(&item.relative_path)?;
+            if !options.allows(item.action) {
+                return Err(anyhow::anyhow!(
+                    "{:?} of '{}' is not allowed in a {:?} sync",
+                    item.action,
+                    item.relative_path,
+                    options.mode
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncFailure {
+    pub relative_path: String,
+    pub action: SyncAction,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncReport {
+    pub applied: usize,
+    /// Conflicts left for the user.
+    pub conflicts: usize,
+    pub failures: Vec<SyncFailure>,
+    pub bytes_transferred: u64,
+}
+
+type Pair<'a> = (Option<&'a TreeEntry>, Option<&'a TreeEntry>);
+
+fn pair_up<'a>(local: &'a [TreeEntry], remote: &'a [TreeEntry]) -> BTreeMap<&'a str, Pair<'a>> {
+    let mut pairs: BTreeMap<&str, Pair> = BTreeMap::new();
+    for entry in local {
+        pairs.entry(&entry.relative_path).or_default().0 = Some(entry);
+    }
+    for entry in remote {
+        pairs.entry(&entry.relative_path).or_default().1 = Some(entry);
+    }
+    pairs
+}
+
+/// Whether both times are known and within the tolerance.
+fn times_match(local: &TreeEntry, remote: &TreeEntry, options: &SyncOptions) -> bool {
+    match (local.modified, remote.modified) {
+        (Some(l), Some(r)) => l.abs_diff(r) <= options.mtime_tolerance_secs,
+        _ => false,
+    }
+}
+
+/// Files present on both sides with equal sizes but different times, which
+/// `compare_checksum` settles by hashing.
+pub fn checksum_candidates(
+    local: &[TreeEntry],
+    remote: &[TreeEntry],
+    options: &SyncOptions,
+) -> Vec<String> {
+    if !options.compare_checksum {
+        return Vec::new();
+    }
+    pair_up(local, remote)
+        .into_iter()
+        .filter_map(|(path, pair)| match pair {
+            (Some(l), Some(r))
+                if !l.is_dir && !r.is_dir && l.size == r.size && !times_match(l, r, options) =>
+            {
+                Some(path.to_string())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Compare the trees and decide what to do with each difference. `identical`
+/// holds the checksum candidates whose hashes matched.
+pub fn plan(
+    local: &[TreeEntry],
+    remote: &[TreeEntry],
+    options: &SyncOptions,
+    identical: &HashSet<String>,
+) -> SyncPlan {
+    let mut items = Vec::new();
+    let mut deletions = Vec::new();
+    for (path, pair) in pair_up(local, remote) {
+        let item = match pair {
+            (Some(l), None) => only_local(l, options),
+            (None, Some(r)) => only_remote(r, options),
+            (Some(l), Some(r)) if l.is_dir != r.is_dir => Some(item(
+                r,
+                SyncAction::Conflict,
+                "A file on one side and a directory on the other",
+            )),
+            (Some(l), Some(_)) if l.is_dir => None,
+            (Some(l), Some(r)) => {
+                let reason = if l.size != r.size {
+                    "Sizes differ"
+                } else if times_match(l, r, options) || identical.contains(path) {
+                    continue;
+                } else if options.compare_checksum {
+                    "Contents differ"
+                } else {
+                    "Modification times differ"
+                };
+                Some(changed(l, r, reason, options))
+            }
+            (None, None) => None,
+        };
+        match item {
+            Some(item)
+                if matches!(
+                    item.action,
+                    SyncAction::DeleteLocal | SyncAction::DeleteRemote
+                ) =>
+            {
+                deletions.push(item)
+            }
+            Some(item) => items.push(item),
+            None => {}
+        }
+    }
+    deletions.reverse();
+    items.extend(deletions);
+    SyncPlan { items }
+}
+
+fn item(entry: &TreeEntry, action: SyncAction, reason: &str) -> SyncItem {
+    let copies = matches!(action, SyncAction::Upload | SyncAction::Download);
+    SyncItem {
+        relative_path: entry.relative_path.clone(),
+        action,
+        is_dir: entry.is_dir,
+        size: if copies { entry.size } else { 0 },
+        reason: reason.to_string(),
+    }
+}
+
+fn only_local(entry: &TreeEntry, options: &SyncOptions) -> Option<SyncItem> {
+    let reason = "Only on the local side";
+    match options.mode {
+        SyncMode::MirrorToRemote | SyncMode::TwoWay if entry.is_dir => {
+            Some(item(entry, SyncAction::CreateRemoteDir, reason))
+        }
+        SyncMode::MirrorToRemote | SyncMode::TwoWay => {
+            Some(item(entry, SyncAction::Upload, reason))
+        }
+        SyncMode::MirrorToLocal if options.delete_extraneous => {
+            Some(item(entry, SyncAction::DeleteLocal, reason))
+        }
+        SyncMode::MirrorToLocal => None,
+    }
+}
+
+fn only_remote(entry: &TreeEntry, options: &SyncOptions) -> Option<SyncItem> {
+    let reason = "Only on the remote side";
+    match options.mode {
+        SyncMode::MirrorToLocal | SyncMode::TwoWay if entry.is_dir => {
+            Some(item(entry, SyncAction::CreateLocalDir, reason))
+        }
+        SyncMode::MirrorToLocal | SyncMode::TwoWay => {
+            Some(item(entry, SyncAction::Download, reason))
+        }
+        SyncMode::MirrorToRemote if options.delete_extraneous => {
+            Some(item(entry, SyncAction::DeleteRemote, reason))
+        }
+        SyncMode::MirrorToRemote => None,
+    }
+}
+
+/// A file that differs between the sides.
+fn changed(local: &TreeEntry, remote: &TreeEntry, reason: &str, options: &SyncOptions) -> SyncItem {
+    let upload = item(local, SyncAction::Upload, reason);
+    let download = item(remote, SyncAction::Download, reason);
+    match options.mode {
+        SyncMode::MirrorToRemote => upload,
+        SyncMode::MirrorToLocal => download,
+        SyncMode::TwoWay => match options.conflict_policy {
+            ConflictPolicy::PreferLocal => upload,
+            ConflictPolicy::PreferRemote => download,
+            ConflictPolicy::PreferNewer if !times_match(local, remote, options) => {
+                match (local.modified, remote.modified) {
+                    (Some(l), Some(r)) if l > r => upload,
+                    (Some(l), Some(r)) if r > l => download,
+                    _ => item(local, SyncAction::Conflict, reason),
+                }
+            }
+            ConflictPolicy::PreferNewer | ConflictPolicy::Skip => {
+                item(local, SyncAction::Conflict, reason)
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, size: u64, modified: u64) -> TreeEntry {
+        TreeEntry {
+            relative_path: path.into(),
+            is_dir: false,
+            size,
+            modified: Some(modified),
+        }
+    }
+
+    fn dir(path: &str) -> TreeEntry {
+        TreeEntry {
+            relative_path: path.into(),
+            is_dir: true,
+            size: 0,
+            modified: None,
+        }
+    }
+
+    fn actions(plan: &SyncPlan) -> Vec<(&str, SyncAction)> {
+        plan.items
+            .iter()
+            .map(|item| (item.relative_path.as_str(), item.action))
+            .collect()
+    }
+
+    fn options(mode: SyncMode) -> SyncOptions {
+        SyncOptions {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn mirror_copies_changes_and_deletes_children_first() {
+        let local = [
+            file("same.txt", 10, 1000),
+            file("touched.txt", 10, 1001),
+            file("grown.txt", 20, 1000),
+            dir("new"),
+            file("new/a.txt", 5, 1000),
+        ];
+        let remote = [
+            file("same.txt", 10, 1000),
+            file("touched.txt", 10, 1000),
+            file("grown.txt", 10, 1000),
+            dir("old"),
+            file("old/b.txt", 1, 1000),
+        ];
+        let mut mirror = options(SyncMode::MirrorToRemote);
+        let plan = plan(&local, &remote, &mirror, &HashSet::new());
+        assert_eq!(
+            actions(&plan),
+            [
+                ("grown.txt", SyncAction::Upload),
+                ("new", SyncAction::CreateRemoteDir),
+                ("new/a.txt", SyncAction::Upload),
+            ]
+        );
+        assert_eq!(plan.items.iter().map(|item| item.size).sum::<u64>(), 25);
+
+        mirror.delete_extraneous = true;
+        mirror.mtime_tolerance_secs = 0;
+        let plan = super::plan(&local, &remote, &mirror, &HashSet::new());
+        assert_eq!(
+            actions(&plan)[4..],
+            [
+                ("old/b.txt", SyncAction::DeleteRemote),
+                ("old", SyncAction::DeleteRemote),
+            ]
+        );
+        assert_eq!(plan.items[3].reason, "Modification times differ");
+    }
+
+    #[test]
+    fn checksums_settle_files_whose_times_differ() {
+        let local = [
+            file("a", 10, 1000),
+            file("b", 10, 1000),
+            file("c", 11, 1000),
+        ];
+        let remote = [
+            file("a", 10, 5000),
+            file("b", 10, 5000),
+            file("c", 10, 5000),
+        ];
+        let mut mirror = options(SyncMode::MirrorToLocal);
+        assert!(checksum_candidates(&local, &remote, &mirror).is_empty());
+
+        mirror.compare_checksum = true;
+        assert_eq!(checksum_candidates(&local, &remote, &mirror), ["a", "b"]);
+        let identical = HashSet::from(["a".to_string()]);
+        let plan = plan(&local, &remote, &mirror, &identical);
+        assert_eq!(
+            actions(&plan),
+            [("b", SyncAction::Download), ("c", SyncAction::Download)]
+        );
+        assert_eq!(plan.items[0].reason, "Contents differ");
+        assert_eq!(plan.items[1].reason, "Sizes differ");
+    }
+
+    #[test]
+    fn two_way_applies_the_conflict_policy() {
+        let local = [
+            file("local-newer", 1, 2000),
+            file("remote-newer", 1, 1000),
+            file("only-local", 1, 1000),
+            file("kind", 1, 1000),
+        ];
+        let remote = [
+            file("local-newer", 2, 1000),
+            file("remote-newer", 2, 2000),
+            file("only-remote", 1, 1000),
+            dir("kind"),
+        ];
+        let mut two_way = options(SyncMode::TwoWay);
+        two_way.delete_extraneous = true;
+        let skip = plan(&local, &remote, &two_way, &HashSet::new());
+        assert_eq!(
+            actions(&skip),
+            [
+                ("kind", SyncAction::Conflict),
+                ("local-newer", SyncAction::Conflict),
+                ("only-local", SyncAction::Upload),
+                ("only-remote", SyncAction::Download),
+                ("remote-newer", SyncAction::Conflict),
+            ]
+        );
+
+        two_way.conflict_policy = ConflictPolicy::PreferNewer;
+        let newer = plan(&local, &remote, &two_way, &HashSet::new());
+        assert_eq!(newer.items[1].action, SyncAction::Upload);
+        assert_eq!(newer.items[4].action, SyncAction::Download);
+        assert_eq!(newer.items[0].action, SyncAction::Conflict);
+
+        two_way.conflict_policy = ConflictPolicy::PreferRemote;
+        let remote_wins = plan(&local, &remote, &two_way, &HashSet::new());
+        assert_eq!(remote_wins.items[1].action, SyncAction::Download);
+        assert_eq!(remote_wins.items[1].size, 2);
+    }
+
+    #[test]
+    fn check_rejects_escaping_paths_and_actions_the_mode_never_plans() {
+        let plan_of = |relative_path: &str, action| SyncPlan {
+            items: vec![SyncItem {
+                relative_path: relative_path.into(),
+                action,
+                is_dir: false,
+                size: 0,
+                reason: String::new(),
+            }],
+        };
+        let two_way = options(SyncMode::TwoWay);
+        assert!(plan_of("a/b.txt", SyncAction::Upload)
+            .check(&two_way)
+            .is_ok());
+        assert!(plan_of("../b.txt", SyncAction::Upload)
+            .check(&two_way)
+            .is_err());
+        assert!(plan_of("/etc/passwd", SyncAction::Download)
+            .check(&two_way)
+            .is_err());
+        assert!(plan_of("a", SyncAction::DeleteLocal)
+            .check(&two_way)
+            .is_err());
+
+        let mut mirror = options(SyncMode::MirrorToRemote);
+        assert!(plan_of("a", SyncAction::DeleteRemote)
+            .check(&mirror)
+            .is_err());
+        mirror.delete_extraneous = true;
+        assert!(plan_of("a", SyncAction::DeleteRemote)
+            .check(&mirror)
+            .is_ok());
+        assert!(plan_of("a", SyncAction::Download).check(&mirror).is_err());
+    }
+}
@@ -0,0 +1,612 @@
+//! Telnet terminal sessions (RFC 854) for equipment without SSH. Negotiates
+//! BINARY, ECHO, SGA, TTYPE and NAWS, can answer login/password prompts,
+//! and exposes the connection as a `PtySession` like an SSH shell.
+
+use crate::ssh::PtySession;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+// Commands
+const SE: u8 = 240;
+const SB: u8 = 250;
+const WILL: u8 = 251;
+const WONT: u8 = 252;
+const DO: u8 = 253;
+const DONT: u8 = 254;
+const IAC: u8 = 255;
+
+// Options
+const BINARY: u8 = 0;
+const ECHO: u8 = 1;
+const SGA: u8 = 3;
+const TTYPE: u8 = 24;
+const NAWS: u8 = 31;
+
+// TTYPE subnegotiation
+const TTYPE_IS: u8 = 0;
+const TTYPE_SEND: u8 = 1;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelnetConfig {
+    pub host: String,
+    pub port: u16,
+    /// Sent when a login prompt appears.
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Sent when a password prompt appears after the username.
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Case-insensitive text that marks the login prompt (default:
+    /// "login:" or "username:").
+    #[serde(default)]
+    pub login_prompt: Option<String>,
+    /// Case-insensitive text that marks the password prompt (default:
+    /// "password:").
+    #[serde(default)]
+    pub password_prompt: Option<String>,
+    #[serde(default = "default_terminal_type")]
+    pub terminal_type: String,
+}
+
+fn default_terminal_type() -> String {
+    "xterm-256color".to_string()
+}
+
+pub async fn connect(config: &TelnetConfig) -> Result<TcpStream> {
+    let address = (config.host.as_str(), config.port);
+    let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(address))
+        .await
+        .map_err(|_| {
+            anyhow!(
+                "Connection to {}:{} timed out after {} seconds.",
+                config.host,
+                config.port,
+                CONNECT_TIMEOUT.as_secs()
+            )
+        })?
+        .map_err(|e| {
+            anyhow!(
+                "Failed to connect to {}:{}: {}",
+                config.host,
+                config.port,
+                e
+            )
+        })?;
+    stream.set_nodelay(true)?;
+    Ok(stream)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParseState {
+    Data,
+    /// Previous byte was CR (NVT mode): a following NUL is dropped.
+    Cr,
+    Iac,
+    Negotiate(u8),
+    Sub,
+    SubIac,
+}
+
+/// Telnet option state and byte-stream filter. Feed it what the server sent;
+/// it returns the terminal data and queues negotiation replies.
+pub struct TelnetProtocol {
+    terminal_type: String,
+    state: ParseState,
+    subnegotiation: Vec<u8>,
+    /// Options we perform (our WILL answered with DO).
+    local: [bool; 256],
+    /// Options the server performs (its WILL answered with our DO).
+    remote: [bool; 256],
+    /// WILL/DO we sent that await an answer, so the answer is not
+    /// acknowledged again (RFC 1143).
+    pending_local: [bool; 256],
+    pending_remote: [bool; 256],
+    size: (u16, u16),
+    replies: Vec<u8>,
+}
+
+impl TelnetProtocol {
+    pub fn new(terminal_type: &str, cols: u32, rows: u32) -> Self {
+        Self {
+            terminal_type: terminal_type.to_uppercase(),
+            state: ParseState::Data,
+            subnegotiation: Vec::new(),
+            local: [false; 256],
+            remote: [false; 256],
+            pending_local: [false; 256],
+            pending_remote: [false; 256],
+            size: clamp_size(cols, rows),
+            replies: Vec::new(),
+        }
+    }
+
+    /// Options we offer as soon as the connection opens.
+    pub fn initial_negotiation(&mut self) -> Vec<u8> {
+        for option in [TTYPE, NAWS] {
+            self.pending_local[option as usize] = true;
+        }
+        for option in [SGA, ECHO] {
+            self.pending_remote[option as usize] = true;
+        }
+        vec![
+            IAC, WILL, TTYPE, IAC, WILL, NAWS, IAC, DO, SGA, IAC, DO, ECHO,
+        ]
+    }
+
+    /// Strip Telnet commands from `input`, returning terminal data.
+    pub fn receive(&mut self, input: &[u8]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(input.len());
+        for &byte in input {
+            self.state = match (self.state, byte) {
+                (ParseState::Data | ParseState::Cr, IAC) => ParseState::Iac,
+                (ParseState::Cr, 0) if !self.remote[BINARY as usize] => ParseState::Data,
+                (ParseState::Data | ParseState::Cr, b'\r') => {
+                    data.push(byte);
+                    ParseState::Cr
+                }
+                (ParseState::Data | ParseState::Cr, _) => {
+                    data.push(byte);
+                    ParseState::Data
+                }
+                (ParseState::Iac, IAC) => {
+                    data.push(IAC);
+                    ParseState::Data
+                }
+                (ParseState::Iac, WILL | WONT | DO | DONT) => ParseState::Negotiate(byte),
+                (ParseState::Iac, SB) => {
+                    self.subnegotiation.clear();
+                    ParseState::Sub
+                }
+                (ParseState::Iac, _) => ParseState::Data,
+                (ParseState::Negotiate(command), option) => {
+                    self.negotiate(command, option);
+                    ParseState::Data
+                }
+                (ParseState::Sub, IAC) => ParseState::SubIac,
+                (ParseState::Sub, _) => {
+                    self.subnegotiation.push(byte);
+                    ParseState::Sub
+                }
+                (ParseState::SubIac, SE) => {
+                    self.subnegotiate();
+                    ParseState::Data
+                }
+                (ParseState::SubIac, _) => {
+                    self.subnegotiation.push(byte);
+                    ParseState::Sub
+                }
+            };
+        }
+        data
+    }
+
+    /// Negotiation replies queued by `receive` and `resize`.
+    pub fn take_replies(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.replies)
+    }
+
+    /// Escape terminal input for the wire: IAC is doubled and, outside
+    /// BINARY mode, a bare CR becomes CR NUL as NVT requires.
+    pub fn encode_input(&self, input: &[u8]) -> Vec<u8> {
+        let binary = self.local[BINARY as usize];
+        let mut out = Vec::with_capacity(input.len() + 4);
+        for (i, &byte) in input.iter().enumerate() {
+            out.push(byte);
+            if byte == IAC {
+                out.push(IAC);
+            } else if byte == b'\r' && !binary && input.get(i + 1) != Some(&b'\n') {
+                out.push(0);
+            }
+        }
+        out
+    }
+
+    /// Record a new window size, sending it if NAWS is active.
+    pub fn resize(&mut self, cols: u32, rows: u32) {
+        self.size = clamp_size(cols, rows);
+        if self.local[NAWS as usize] {
+            self.send_window_size();
+        }
+    }
+
+    /// Handle WILL/WONT/DO/DONT. Only state changes are answered, so the
+    /// two sides cannot loop.
+    fn negotiate(&mut self, command: u8, option: u8) {
+        let index = option as usize;
+        match command {
+            WILL => {
+                let requested = std::mem::take(&mut self.pending_remote[index]);
+                if !matches!(option, BINARY | ECHO | SGA) {
+                    self.reply(DONT, option);
+                } else if !self.remote[index] {
+                    self.remote[index] = true;
+                    if !requested {
+                        self.reply(DO, option);
+                    }
+                }
+            }
+            WONT => {
+                let requested = std::mem::take(&mut self.pending_remote[index]);
+                if self.remote[index] || requested {
+                    self.remote[index] = false;
+                    if !requested {
+                        self.reply(DONT, option);
+                    }
+                }
+            }
+            DO => {
+                let requested = std::mem::take(&mut self.pending_local[index]);
+                if !matches!(option, BINARY | SGA | TTYPE | NAWS) {
+                    self.reply(WONT, option);
+                    return;
+                }
+                if !self.local[index] {
+                    self.local[index] = true;
+                    if !requested {
+                        self.reply(WILL, option);
+                    }
+                }
+                // The size goes out on every DO NAWS, even a repeated one.
+                if option == NAWS {
+                    self.send_window_size();
+                }
+            }
+            DONT => {
+                let requested = std::mem::take(&mut self.pending_local[index]);
+                if self.local[index] || requested {
+                    self.local[index] = false;
+                    if !requested {
+                        self.reply(WONT, option);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn subnegotiate(&mut self) {
+        if self.subnegotiation.as_slice() == [TTYPE, TTYPE_SEND] {
+            let mut reply = vec![IAC, SB, TTYPE, TTYPE_IS];
+            reply.extend_from_slice(self.terminal_type.as_bytes());
+            reply.extend_from_slice(&[IAC, SE]);
+            self.replies.extend(reply);
+        }
+    }
+
+    fn send_window_size(&mut self) {
+        let (cols, rows) = self.size;
+        let mut reply = vec![IAC, SB, NAWS];
+        for byte in cols.to_be_bytes().into_iter().chain(rows.to_be_bytes()) {
+            reply.push(byte);
+            if byte == IAC {
+                reply.push(IAC);
+            }
+        }
+        reply.extend_from_slice(&[IAC, SE]);
+        self.replies.extend(reply);
+    }
+
+    fn reply(&mut self, command: u8, option: u8) {
+        self.replies.extend_from_slice(&[IAC, command, option]);
+    }
+}
+
+fn clamp_size(cols: u32, rows: u32) -> (u16, u16) {
+    (
+        cols.clamp(1, u16::MAX as u32) as u16,
+        rows.clamp(1, u16::MAX as u32) as u16,
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LoginStage {
+    Username,
+    Password,
+    Done,
+}
+
+/// Answers the first login and password prompts with the configured
+/// credentials, then stays out of the way.
+struct LoginAutomation {
+    username: Option<String>,
+    password: Option<String>,
+    login_prompts: Vec<String>,
+    password_prompt: String,
+    stage: LoginStage,
+    /// Tail of recent output, lowercased, for spotting prompts split
+    /// across reads.
+    recent: String,
+}
+
+impl LoginAutomation {
+    fn new(config: &TelnetConfig) -> Self {
+        let login_prompts = match &config.login_prompt {
+            Some(prompt) => vec![prompt.to_lowercase()],
+            None => vec!["login:".to_string(), "username:".to_string()],
+        };
+        let stage = match (&config.username, &config.password) {
+            (Some(_), _) => LoginStage::Username,
+            (None, Some(_)) => LoginStage::Password,
+            (None, None) => LoginStage::Done,
+        };
+        Self {
+            username: config.username.clone(),
+            password: config.password.clone(),
+            login_prompts,
+            password_prompt: config
+                .password_prompt
+                .as_deref()
+                .unwrap_or("password:")
+                .to_lowercase(),
+            stage,
+            recent: String::new(),
+        }
+    }
+
+    /// Look at server output; returns what to type, if a prompt was seen.
+    fn feed(&mut self, output: &[u8]) -> Option<Vec<u8>> {
+        if self.stage == LoginStage::Done {
+            return None;
+        }
+        self.recent
+            .push_str(&String::from_utf8_lossy(output).to_lowercase());
+        if self.recent.len() > 512 {
+            let cut = self.recent.len() - 256;
+            let cut = (cut..self.recent.len())
+                .find(|&i| self.recent.is_char_boundary(i))
+                .unwrap_or(0);
+            self.recent.drain(..cut);
+        }
+        let tail = self.recent.trim_end();
+
+        let answer = match self.stage {
+            LoginStage::Username if self.login_prompts.iter().any(|p| tail.ends_with(p)) => {
+                self.stage = if self.password.is_some() {
+                    LoginStage::Password
+                } else {
+                    LoginStage::Done
+                };
+                self.username.clone()
+            }
+            LoginStage::Password | LoginStage::Username
+                if tail.ends_with(&self.password_prompt) =>
+            {
+                self.stage = LoginStage::Done;
+                self.password.clone()
+            }
+            _ => None,
+        }?;
+        self.recent.clear();
+        Some(format!("{}\r", answer).into_bytes())
+    }
+}
+
+/// Run a Telnet session on `stream` as a `cols`x`rows` terminal.
+pub fn spawn_telnet_session(
+    stream: TcpStream,
+    config: &TelnetConfig,
+    cols: u32,
+    rows: u32,
+) -> PtySession {
+    let (input_tx, mut input_rx) = mpsc::channel::<Vec<u8>>(1000);
+    let (output_tx, output_rx) = mpsc::channel::<Vec<u8>>(128);
+    let (resize_tx, mut resize_rx) = mpsc::channel::<(u32, u32)>(16);
+    let cancel = CancellationToken::new();
+
+    let mut protocol = TelnetProtocol::new(&config.terminal_type, cols, rows);
+    let mut login = LoginAutomation::new(config);
+    let session_cancel = cancel.clone();
+    tokio::spawn(async move {
+        let (mut reader, mut writer) = stream.into_split();
+        let offer = protocol.initial_negotiation();
+        if writer.write_all(&offer).await.is_err() {
+            return;
+        }
+        let mut buf = vec![0u8; 16 * 1024];
+        loop {
+            let outgoing = tokio::select! {
+                _ = session_cancel.cancelled() => break,
+                read = reader.read(&mut buf) => {
+                    let n = match read {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => n,
+                    };
+                    let data = protocol.receive(&buf[..n]);
+                    let mut outgoing = protocol.take_replies();
+                    if !data.is_empty() {
+                        if let Some(answer) = login.feed(&data) {
+                            outgoing.extend(protocol.encode_input(&answer));
+                        }
+                        if output_tx.send(data).await.is_err() {
+                            break;
+                        }
+                    }
+                    outgoing
+                }
+                input = input_rx.recv() => match input {
+                    Some(data) => protocol.encode_input(&data),
+                    None => break,
+                },
+                resize = resize_rx.recv() => match resize {
+                    Some((cols, rows)) => {
+                        protocol.resize(cols, rows);
+                        protocol.take_replies()
+                    }
+                    None => break,
+                },
+            };
+            if !outgoing.is_empty() && writer.write_all(&outgoing).await.is_err() {
+                break;
+            }
+        }
+        let _ = writer.shutdown().await;
+        tracing::info!("Telnet session closed");
+    });
+
+    PtySession {
+        input_tx,
+        output_rx: Arc::new(tokio::sync::Mutex::new(output_rx)),
+        channel_id: None,
+        resize_tx,
+        cancel,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    const NOP: u8 = 241;
+
+    fn config() -> TelnetConfig {
+        TelnetConfig {
+            host: "127.0.0.1".to_string(),
+            port: 23,
+            username: Some("admin".to_string()),
+            password: Some("secret".to_string()),
+            login_prompt: None,
+            password_prompt: None,
+            terminal_type: default_terminal_type(),
+        }
+    }
+
+    #[test]
+    fn strips_commands_and_answers_negotiation() {
+        let mut protocol = TelnetProtocol::new("xterm", 80, 24);
+        let input = [
+            b'h', IAC, WILL, ECHO, b'i', IAC, DO, NAWS, IAC, DO, 5, IAC, IAC, IAC, NOP,
+        ];
+        assert_eq!(protocol.receive(&input), [b'h', b'i', IAC]);
+        assert!(protocol.remote[ECHO as usize]);
+        assert_eq!(
+            protocol.take_replies(),
+            [IAC, DO, ECHO, IAC, WILL, NAWS, IAC, SB, NAWS, 0, 80, 0, 24, IAC, SE, IAC, WONT, 5]
+        );
+
+        // Repeating an agreed option gets no second answer.
+        protocol.receive(&[IAC, WILL, ECHO]);
+        assert!(protocol.take_replies().is_empty());
+        protocol.receive(&[IAC, WONT, ECHO]);
+        assert_eq!(protocol.take_replies(), [IAC, DONT, ECHO]);
+        assert!(!protocol.remote[ECHO as usize]);
+    }
+
+    #[test]
+    fn answers_to_our_offers_are_not_acknowledged_again() {
+        let mut protocol = TelnetProtocol::new("xterm", 80, 24);
+        protocol.initial_negotiation();
+        protocol.receive(&[
+            IAC, WILL, SGA, IAC, WONT, ECHO, IAC, DO, TTYPE, IAC, DONT, NAWS,
+        ]);
+        assert!(protocol.take_replies().is_empty());
+        assert!(!protocol.remote[ECHO as usize]);
+
+        // A later change of mind is answered.
+        protocol.receive(&[IAC, WILL, ECHO]);
+        assert_eq!(protocol.take_replies(), [IAC, DO, ECHO]);
+    }
+
+    #[test]
+    fn answers_terminal_type_and_sends_resizes() {
+        let mut protocol = TelnetProtocol::new("xterm-256color", 80, 24);
+        protocol.receive(&[IAC, DO, TTYPE]);
+        // Split across reads on purpose.
+        protocol.receive(&[IAC, SB, TTYPE]);
+        protocol.receive(&[TTYPE_SEND, IAC, SE]);
+        let mut expected = vec![IAC, WILL, TTYPE, IAC, SB, TTYPE, TTYPE_IS];
+        expected.extend_from_slice(b"XTERM-256COLOR");
+        expected.extend_from_slice(&[IAC, SE]);
+        assert_eq!(protocol.take_replies(), expected);
+
+        protocol.resize(300, 50);
+        assert!(protocol.take_replies().is_empty(), "NAWS not agreed yet");
+        protocol.receive(&[IAC, DO, NAWS]);
+        protocol.take_replies();
+        protocol.resize(255, 50);
+        assert_eq!(
+            protocol.take_replies(),
+            [IAC, SB, NAWS, 0, IAC, IAC, 0, 50, IAC, SE]
+        );
+    }
+
+    #[test]
+    fn encodes_input_for_nvt_and_binary() {
+        let mut protocol = TelnetProtocol::new("xterm", 80, 24);
+        assert_eq!(protocol.encode_input(b"ls\r"), b"ls\r\0");
+        assert_eq!(protocol.encode_input(b"a\r\nb"), b"a\r\nb");
+        assert_eq!(protocol.encode_input(&[IAC]), [IAC, IAC]);
+        assert_eq!(protocol.receive(b"x\r\0y\r\n"), b"x\ry\r\n");
+
+        protocol.receive(&[IAC, DO, BINARY, IAC, WILL, BINARY]);
+        assert_eq!(protocol.encode_input(b"ls\r"), b"ls\r");
+        assert_eq!(protocol.receive(b"\r\0"), b"\r\0");
+    }
+
+    #[test]
+    fn login_automation_answers_each_prompt_once() {
+        let mut login = LoginAutomation::new(&config());
+        assert_eq!(login.feed(b"Welcome\r\n"), None);
+        assert_eq!(login.feed(b"Router Log"), None);
+        assert_eq!(login.feed(b"in: "), Some(b"admin\r".to_vec()));
+        assert_eq!(login.feed(b"\r\nPassword: "), Some(b"secret\r".to_vec()));
+        assert_eq!(login.feed(b"\r\nlogin: "), None);
+
+        let mut custom = LoginAutomation::new(&TelnetConfig {
+            login_prompt: Some("User Name:".to_string()),
+            password: None,
+            ..config()
+        });
+        assert_eq!(custom.feed(b"login: "), None);
+        assert_eq!(custom.feed(b"user name:"), Some(b"admin\r".to_vec()));
+        assert_eq!(custom.feed(b"password:"), None);
+    }
+
+    #[tokio::test]
+    async fn session_logs_in_and_relays_data() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut negotiation = [0u8; 12];
+            socket.read_exact(&mut negotiation).await.unwrap();
+            socket.write_all(b"login: ").await.unwrap();
+            let mut name = [0u8; 7];
+            socket.read_exact(&mut name).await.unwrap();
+            assert_eq!(&name, b"admin\r\0");
+            socket.write_all(b"Password: ").await.unwrap();
+            let mut password = [0u8; 8];
+            socket.read_exact(&mut password).await.unwrap();
+            assert_eq!(&password, b"secret\r\0");
+            socket.write_all(b"\r\nrouter# ").await.unwrap();
+            let mut command = [0u8; 3];
+            socket.read_exact(&mut command).await.unwrap();
+            command
+        });
+
+        let config = TelnetConfig { port, ..config() };
+        let session = spawn_telnet_session(connect(&config).await.unwrap(), &config, 80, 24);
+        let mut seen = Vec::new();
+        while !seen.ends_with(b"router# ") {
+            let chunk = tokio::time::timeout(
+                Duration::from_secs(5),
+                session.output_rx.lock().await.recv(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+            seen.extend(chunk);
+        }
+        session.input_tx.send(vec![b'?', IAC]).await.unwrap();
+        assert_eq!(server.await.unwrap(), [b'?', IAC, IAC]);
+        session.cancel.cancel();
+    }
+}
@@ -0,0 +1,327 @@
+//! Bookkeeping shared by file transfers over SFTP, FTP and SSH: ids,
+//! throttled progress with rate and ETA, cancellation, and where to resume a
+//! partial file.
+
+use crate::checksum::HashAlgorithm;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio_util::sync::CancellationToken;
+
+pub const CHUNK_SIZE: usize = 32 * 1024;
+
+/// Minimum gap between progress events for one transfer.
+pub(crate) const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferDirection {
+    Upload,
+    Download,
+}
+
+/// One file to copy between the local machine and a connection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferSpec {
+    pub connection_id: String,
+    pub direction: TransferDirection,
+    pub remote_path: String,
+    pub local_path: String,
+    /// Continue a partial destination file instead of starting over.
+    #[serde(default)]
+    pub resume: bool,
+    /// Hash both copies once copied and fail if they differ.
+    #[serde(default)]
+    pub verify: Option<HashAlgorithm>,
+}
+
+/// Reported through the `transfer-progress` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferProgress {
+    pub transfer_id: String,
+    pub connection_id: String,
+    pub direction: TransferDirection,
+    pub remote_path: String,
+    pub local_path: String,
+    /// Includes the bytes already present when resuming.
+    pub bytes_transferred: u64,
+    pub total_bytes: Option<u64>,
+    /// Offset the transfer resumed from; 0 for a fresh copy.
+    pub resumed_from: u64,
+    pub bytes_per_second: f64,
+    pub eta_seconds: Option<f64>,
+    pub finished: bool,
+    /// Directory transfers only: files finished so far and files in the tree.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files_done: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files_total: Option<u64>,
+}
+
+pub type ProgressSink = Arc<dyn Fn(TransferProgress) + Send + Sync>;
+
+/// Handed to a client's copy loop: counts bytes, reports progress at most
+/// every `PROGRESS_INTERVAL`, and stops the loop once cancelled.
+pub struct Transfer {
+    id: String,
+    spec: TransferSpec,
+    cancel: CancellationToken,
+    sink: Option<ProgressSink>,
+    started: Instant,
+    last_report: Option<Instant>,
+    offset: u64,
+    transferred: u64,
+    total: Option<u64>,
+}
+
+impl Transfer {
+    pub fn new(
+        id: String,
+        spec: TransferSpec,
+        cancel: CancellationToken,
+        sink: Option<ProgressSink>,
+    ) -> Self {
+        Self {
+            id,
+            spec,
+            cancel,
+            sink,
+            started: Instant::now(),
+            last_report: None,
+            offset: 0,
+            transferred: 0,
+            total: None,
+        }
+    }
+
+    /// A transfer nobody watches or cancels.
+    pub fn untracked(direction: TransferDirection, remote_path: &str, local_path: &str) -> Self {
+        let spec = TransferSpec {
+            connection_id: String::new(),
+            direction,
+            remote_path: remote_path.to_string(),
+            local_path: local_path.to_string(),
+            resume: false,
+            verify: None,
+        };
+        Self::new(String::new(), spec, CancellationToken::new(), None)
+    }
+
+    pub fn spec(&self) -> &TransferSpec {
+        &self.spec
+    }
+
+    pub fn resume_requested(&self) -> bool {
+        self.spec.resume
+    }
+
+    /// Record where copying starts and how big the source is.
+    pub fn start(&mut self, offset: u64, total: Option<u64>) {
+        self.started = Instant::now();
+        self.offset = offset;
+        self.transferred = 0;
+        self.total = total;
+        self.report(false);
+    }
+
+    pub fn ensure_active(&self) -> Result<()> {
+        if self.cancel.is_cancelled() {
+            Err(anyhow::anyhow!("Transfer cancelled"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Count `n` more bytes copied; fails once the transfer is cancelled.
+    pub fn advance(&mut self, n: usize) -> Result<()> {
+        self.transferred += n as u64;
+        let due = self
+            .last_report
+            .is_none_or(|last| last.elapsed() >= PROGRESS_INTERVAL);
+        if due {
+            self.report(false);
+        }
+        self.ensure_active()
+    }
+
+    /// Report completion and return the bytes copied by this run.
+    pub fn finish(&mut self) -> u64 {
+        self.report(true);
+        self.transferred
+    }
+
+    pub fn progress(&self, finished: bool) -> TransferProgress {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let bytes_per_second = if elapsed > 0.0 {
+            self.transferred as f64 / elapsed
+        } else {
+            0.0
+        };
+        let done = self.offset + self.transferred;
+        let eta_seconds = match self.total {
+            _ if finished => Some(0.0),
+            Some(total) if bytes_per_second > 0.0 => {
+                Some(total.saturating_sub(done) as f64 / bytes_per_second)
+            }
+            _ => None,
+        };
+        TransferProgress {
+            transfer_id: self.id.clone(),
+            connection_id: self.spec.connection_id.clone(),
+            direction: self.spec.direction,
+            remote_path: self.spec.remote_path.clone(),
+            local_path: self.spec.local_path.clone(),
+            bytes_transferred: done,
+            total_bytes: self.total,
+            resumed_from: self.offset,
+            bytes_per_second,
+            eta_seconds,
+            finished,
+            files_done: None,
+            files_total: None,
+        }
+    }
+
+    fn report(&mut self, finished: bool) {
+        self.last_report = Some(Instant::now());
+        if let Some(sink) = &self.sink {
+            sink(self.progress(finished));
+        }
+    }
+
+    async fn cancelled(&self) {
+        self.cancel.cancelled().await
+    }
+}
+
+/// Where to resume, given the destination's current size and the source's
+/// size: the destination size if it is a prefix candidate, else 0.
+pub fn resume_offset(existing: Option<u64>, source_len: Option<u64>) -> u64 {
+    match (existing, source_len) {
+        (Some(existing), Some(total)) if existing <= total => existing,
+        _ => 0,
+    }
+}
+
+/// `name` inside the remote directory `dir`.
+pub fn join_remote_path(dir: &str, name: &str) -> String {
+    format!("{}/{}", dir.trim_end_matches('/'), name)
+}
+
+/// Copy `reader` to `writer` in `CHUNK_SIZE` pieces, counting them against
+/// `transfer`. A stalled read is abandoned as soon as it is cancelled.
+pub async fn copy_with_progress<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    transfer: &mut Transfer,
+) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = tokio::select! {
+            n = reader.read(&mut buf) => n?,
+            _ = transfer.cancelled() => return transfer.ensure_active(),
+        };
+        if n == 0 {
+            break;
+        }
+        writer.write_all(&buf[..n]).await?;
+        transfer.advance(n)?;
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    fn tracked(sink: ProgressSink, cancel: CancellationToken) -> Transfer {
+        let spec = TransferSpec {
+            connection_id: "conn".into(),
+            direction: TransferDirection::Download,
+            remote_path: "/srv/a.bin".into(),
+            local_path: "/tmp/a.bin".into(),
+            resume: true,
+            verify: None,
+        };
+        Transfer::new("t1".into(), spec, cancel, Some(sink))
+    }
+
+    #[test]
+    fn resumes_only_from_a_shorter_destination() {
+        assert_eq!(resume_offset(Some(100), Some(300)), 100);
+        assert_eq!(resume_offset(Some(300), Some(300)), 300);
+        assert_eq!(resume_offset(Some(400), Some(300)), 0);
+        assert_eq!(resume_offset(None, Some(300)), 0);
+        assert_eq!(resume_offset(Some(100), None), 0);
+    }
+
+    #[tokio::test]
+    async fn copies_and_reports_throttled_progress() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink_events = events.clone();
+        let sink: ProgressSink = Arc::new(move |p| sink_events.lock().unwrap().push(p));
+        let mut transfer = tracked(sink, CancellationToken::new());
+
+        let source = vec![7u8; CHUNK_SIZE * 4 + 10];
+        let mut dest = Vec::new();
+        transfer.start(1000, Some(1000 + source.len() as u64));
+        copy_with_progress(&mut source.as_slice(), &mut dest, &mut transfer)
+            .await
+            .unwrap();
+        assert_eq!(transfer.finish(), source.len() as u64);
+        assert_eq!(dest, source);
+
+        let events = events.lock().unwrap();
+        // The start, then nothing until the final report.
+        assert_eq!(events.len(), 2);
+        let last = events.last().unwrap();
+        assert!(last.finished);
+        assert_eq!(last.transfer_id, "t1");
+        assert_eq!(last.resumed_from, 1000);
+        assert_eq!(last.bytes_transferred, 1000 + source.len() as u64);
+        assert_eq!(last.eta_seconds, Some(0.0));
+    }
+
+    #[tokio::test]
+    async fn cancellation_stops_the_copy() {
+        let cancel = CancellationToken::new();
+        let mut transfer = tracked(Arc::new(|_| {}), cancel.clone());
+        transfer.start(0, None);
+        cancel.cancel();
+
+        // A reader that never yields data.
+        let (mut reader, _writer) = tokio::io::duplex(64);
+        let mut dest = Vec::new();
+        let err = copy_with_progress(&mut reader, &mut dest, &mut transfer)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Transfer cancelled");
+    }
+
+    #[test]
+    fn joins_remote_paths() {
+        assert_eq!(join_remote_path("/srv/", "a.txt"), "/srv/a.txt");
+        assert_eq!(join_remote_path("/srv", "a.txt"), "/srv/a.txt");
+        assert_eq!(join_remote_path("/", "etc"), "/etc");
+    }
+
+    #[test]
+    fn eta_follows_the_rate() {
+        let mut transfer = Transfer::untracked(TransferDirection::Upload, "/r", "/l");
+        transfer.start(0, Some(1000));
+        transfer.started = Instant::now() - Duration::from_secs(2);
+        transfer.transferred = 400;
+        let progress = transfer.progress(false);
+        assert!((progress.bytes_per_second - 200.0).abs() < 5.0);
+        let eta = progress.eta_seconds.unwrap();
+        assert!((eta - 3.0).abs() < 0.1, "{eta}");
+    }
+}
@@ -0,0 +1,458 @@
+//! Queue of file and directory transfers: ordering, per-connection and
+//! global concurrency limits, pause/resume/retry, and persistence of
+//! unfinished jobs across restarts.
+
+use crate::dir_transfer::DirectoryTransferOptions;
+use crate::transfer::TransferSpec;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Running,
+    Paused,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransferJob {
+    pub id: String,
+    /// `transfer_id` of the `transfer-progress` events of the current or
+    /// last run. Each start gets a new one, so a stopped run still winding
+    /// down can't be mistaken for the next.
+    #[serde(default)]
+    pub transfer_id: Option<String>,
+    #[serde(flatten)]
+    pub spec: TransferSpec,
+    /// Copy the whole tree under the paths instead of a single file.
+    #[serde(default)]
+    pub directory: bool,
+    /// Used by directory jobs.
+    #[serde(default)]
+    pub options: DirectoryTransferOptions,
+    pub state: JobState,
+    /// Times the job has been started.
+    #[serde(default)]
+    pub attempts: u32,
+    #[serde(default)]
+    pub bytes_transferred: u64,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueueLimits {
+    /// Jobs running at once on one connection.
+    pub per_connection: usize,
+    /// Jobs running at once overall.
+    pub global: usize,
+}
+
+impl Default for QueueLimits {
+    fn default() -> Self {
+        Self {
+            per_connection: 2,
+            global: 4,
+        }
+    }
+}
+
+/// Reported through the `transfer-queue-event` event.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum QueueEvent {
+    Updated { job: TransferJob },
+    Removed { job_id: String },
+    Reordered { job_ids: Vec<String> },
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct QueueFile {
+    next_id: u64,
+    #[serde(default)]
+    limits: QueueLimits,
+    jobs: Vec<TransferJob>,
+}
+
+pub struct TransferQueue {
+    jobs: Vec<TransferJob>,
+    next_id: u64,
+    limits: QueueLimits,
+    /// Where unfinished jobs are saved; `None` keeps the queue in memory.
+    path: Option<PathBuf>,
+}
+
+impl TransferQueue {
+    /// Open the queue saved at `path`. Jobs that were waiting or running when
+    /// the app exited come back paused: their connections are gone until
+    /// the user reconnects.
+    pub fn load(path: Option<PathBuf>) -> Self {
+        let saved = path
+            .as_deref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str::<QueueFile>(&json).ok())
+            .unwrap_or_default();
+        let mut jobs = saved.jobs;
+        for job in &mut jobs {
+            if matches!(job.state, JobState::Queued | JobState::Running) {
+                job.state = JobState::Paused;
+                job.spec.resume = true;
+            }
+        }
+        Self {
+            jobs,
+            next_id: saved.next_id.max(1),
+            limits: saved.limits,
+            path,
+        }
+    }
+
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("r-shell").join("transfer-queue.json"))
+    }
+
+    pub fn jobs(&self) -> &[TransferJob] {
+        &self.jobs
+    }
+
+    pub fn limits(&self) -> QueueLimits {
+        self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: QueueLimits) {
+        self.limits = QueueLimits {
+            per_connection: limits.per_connection.max(1),
+            global: limits.global.max(1),
+        };
+    }
+
+    pub fn add(
+        &mut self,
+        spec: TransferSpec,
+        directory: bool,
+        options: DirectoryTransferOptions,
+    ) -> TransferJob {
+        let job = TransferJob {
+            id: format!("job-{}", self.next_id),
+            spec,
+            directory,
+            options,
+            state: JobState::Queued,
+            transfer_id: None,
+            attempts: 0,
+            bytes_transferred: 0,
+            error: None,
+        };
+        self.next_id += 1;
+        self.jobs.push(job.clone());
+        job
+    }
+
+    /// Mark the queued jobs that fit within the limits as running, in queue
+    /// order, and return them.
+    pub fn start_runnable(&mut self) -> Vec<TransferJob> {
+        let mut running: Vec<String> = self
+            .jobs
+            .iter()
+            .filter(|job| job.state == JobState::Running)
+            .map(|job| job.spec.connection_id.clone())
+            .collect();
+        let mut started = Vec::new();
+        for job in &mut self.jobs {
+            if running.len() >= self.limits.global {
+                break;
+            }
+            if job.state != JobState::Queued {
+                continue;
+            }
+            let on_connection = running
+                .iter()
+                .filter(|id| **id == job.spec.connection_id)
+                .count();
+            if on_connection >= self.limits.per_connection {
+                continue;
+            }
+            job.state = JobState::Running;
+            job.attempts += 1;
+            job.transfer_id = Some(format!("{}-{}", job.id, job.attempts));
+            job.error = None;
+            running.push(job.spec.connection_id.clone());
+            started.push(job.clone());
+        }
+        started
+    }
+
+    /// Record how the run `transfer_id` of a job ended. A run that copied
+    /// everything completes the job even if a pause or cancel came too late
+    /// to stop it; one that failed after being paused or cancelled leaves
+    /// that state. Runs the job has since been restarted past are ignored.
+    pub fn finish(
+        &mut self,
+        job_id: &str,
+        transfer_id: &str,
+        result: Result<u64, String>,
+    ) -> Option<TransferJob> {
+        let job = self
+            .jobs
+            .iter_mut()
+            .find(|job| job.id == job_id && job.transfer_id.as_deref() == Some(transfer_id))?;
+        match (job.state, result) {
+            (_, Ok(bytes)) => {
+                job.state = JobState::Completed;
+                job.bytes_transferred = bytes;
+            }
+            (JobState::Running, Err(error)) => {
+                job.state = JobState::Failed;
+                job.error = Some(error);
+            }
+            _ => {}
+        }
+        Some(job.clone())
+    }
+
+    /// Hold a queued or running job. Returns the state it was in, so a
+    /// running transfer can be stopped.
+    pub fn pause(&mut self, job_id: &str) -> Result<(JobState, TransferJob)> {
+        self.transition(job_id, &[JobState::Queued, JobState::Running], |job| {
+            job.state = JobState::Paused;
+        })
+    }
+
+    /// Queue a paused job again, continuing its partial file.
+    pub fn resume(&mut self, job_id: &str) -> Result<(JobState, TransferJob)> {
+        self.transition(job_id, &[JobState::Paused], |job| {
+            job.state = JobState::Queued;
+            job.spec.resume = true;
+        })
+    }
+
+    /// Queue a failed or cancelled job again, continuing its partial file.
+    pub fn retry(&mut self, job_id: &str) -> Result<(JobState, TransferJob)> {
+        self.transition(job_id, &[JobState::Failed, JobState::Cancelled], |job| {
+            job.state = JobState::Queued;
+            job.spec.resume = true;
+            job.error = None;
+        })
+    }
+
+    pub fn cancel(&mut self, job_id: &str) -> Result<(JobState, TransferJob)> {
+        self.transition(
+            job_id,
+            &[JobState::Queued, JobState::Running, JobState::Paused],
+            |job| job.state = JobState::Cancelled,
+        )
+    }
+
+    /// Move a job to `index` in the queue, returning the new order.
+    pub fn move_job(&mut self, job_id: &str, index: usize) -> Result<Vec<String>> {
+        let from = self.position(job_id)?;
+        let job = self.jobs.remove(from);
+        let index = index.min(self.jobs.len());
+        self.jobs.insert(index, job);
+        Ok(self.jobs.iter().map(|job| job.id.clone()).collect())
+    }
+
+    /// Drop a job that isn't running.
+    pub fn remove(&mut self, job_id: &str) -> Result<()> {
+        let index = self.position(job_id)?;
+        if self.jobs[index].state == JobState::Running {
+            return Err(anyhow::anyhow!(
+                "Pause or cancel the job before removing it"
+            ));
+        }
+        self.jobs.remove(index);
+        Ok(())
+    }
+
+    /// Write the unfinished jobs to disk.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let file = QueueFile {
+            next_id: self.next_id,
+            limits: self.limits,
+            jobs: self
+                .jobs
+                .iter()
+                .filter(|job| !matches!(job.state, JobState::Completed | JobState::Cancelled))
+                .cloned()
+                .collect(),
+        };
+        write_atomically(path, &serde_json::to_vec_pretty(&file)?)
+    }
+
+    fn position(&self, job_id: &str) -> Result<usize> {
+        self.jobs
+            .iter()
+            .position(|job| job.id == job_id)
+            .ok_or_else(|| anyhow::anyhow!("Transfer job not found: {}", job_id))
+    }
+
+    fn transition(
+        &mut self,
+        job_id: &str,
+        from: &[JobState],
+        apply: impl FnOnce(&mut TransferJob),
+    ) -> Result<(JobState, TransferJob)> {
+        let index = self.position(job_id)?;
+        let job = &mut self.jobs[index];
+        let previous = job.state;
+        if !from.contains(&previous) {
+            return Err(anyhow::anyhow!("Transfer job {} is {:?}", job_id, previous));
+        }
+        apply(job);
+        Ok((previous, job.clone()))
+    }
+}
+
+fn write_atomically(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transfer::TransferDirection;
+
+    fn spec(connection_id: &str, name: &str) -> TransferSpec {
+        TransferSpec {
+            connection_id: connection_id.into(),
+            direction: TransferDirection::Download,
+            remote_path: format!("/srv/{name}"),
+            local_path: format!("/tmp/{name}"),
+            resume: false,
+            verify: None,
+        }
+    }
+
+    fn ids(jobs: &[TransferJob]) -> Vec<&str> {
+        jobs.iter().map(|job| job.id.as_str()).collect()
+    }
+
+    #[test]
+    fn respects_per_connection_and_global_limits() {
+        let mut queue = TransferQueue::load(None);
+        queue.set_limits(QueueLimits {
+            per_connection: 2,
+            global: 3,
+        });
+        for name in ["a", "b", "c"] {
+            queue.add(spec("one", name), false, Default::default());
+        }
+        queue.add(spec("two", "d"), false, Default::default());
+        queue.add(spec("two", "e"), false, Default::default());
+
+        let started = queue.start_runnable();
+        assert_eq!(ids(&started), ["job-1", "job-2", "job-4"]);
+        assert!(queue.start_runnable().is_empty());
+
+        assert_eq!(started[0].transfer_id.as_deref(), Some("job-1-1"));
+        queue.finish("job-1", "job-1-1", Ok(10));
+        assert_eq!(ids(&queue.start_runnable()), ["job-3"]);
+        assert_eq!(queue.jobs()[0].state, JobState::Completed);
+        assert_eq!(queue.jobs()[0].bytes_transferred, 10);
+    }
+
+    #[test]
+    fn pause_resume_retry_and_reorder() {
+        let mut queue = TransferQueue::load(None);
+        queue.add(spec("one", "a"), false, Default::default());
+        queue.add(spec("one", "b"), false, Default::default());
+        queue.add(spec("one", "c"), false, Default::default());
+        queue.start_runnable();
+
+        let (previous, job) = queue.pause("job-1").unwrap();
+        assert_eq!(previous, JobState::Running);
+        assert_eq!(job.state, JobState::Paused);
+        // The stopped transfer reports back after the pause.
+        queue.finish("job-1", "job-1-1", Err("Transfer cancelled".into()));
+        assert_eq!(queue.jobs()[0].state, JobState::Paused);
+        assert!(queue.retry("job-1").is_err());
+
+        let (_, job) = queue.resume("job-1").unwrap();
+        assert_eq!(job.state, JobState::Queued);
+        assert!(job.spec.resume);
+
+        queue.finish("job-2", "job-2-1", Err("Permission denied".into()));
+        let (_, job) = queue.retry("job-2").unwrap();
+        assert_eq!((job.state, job.error), (JobState::Queued, None));
+
+        let order = queue.move_job("job-3", 0).unwrap();
+        assert_eq!(order, ["job-3", "job-1", "job-2"]);
+        assert!(queue.remove("job-missing").is_err());
+        queue.remove("job-1").unwrap();
+        assert_eq!(ids(queue.jobs()), ["job-3", "job-2"]);
+    }
+
+    #[test]
+    fn finish_follows_the_latest_run() {
+        let mut queue = TransferQueue::load(None);
+        queue.add(spec("one", "a"), false, Default::default());
+        queue.add(spec("one", "b"), false, Default::default());
+        queue.start_runnable();
+
+        // Resumed before the stopped run reported back: the old run's
+        // failure must not touch the restarted job.
+        queue.pause("job-1").unwrap();
+        queue.resume("job-1").unwrap();
+        let restarted = queue.start_runnable();
+        assert_eq!(restarted[0].transfer_id.as_deref(), Some("job-1-2"));
+        assert!(queue
+            .finish("job-1", "job-1-1", Err("Transfer cancelled".into()))
+            .is_none());
+        assert_eq!(queue.jobs()[0].state, JobState::Running);
+
+        // Paused too late to stop the copy: it still completes.
+        queue.pause("job-2").unwrap();
+        let job = queue.finish("job-2", "job-2-1", Ok(7)).unwrap();
+        assert_eq!((job.state, job.bytes_transferred), (JobState::Completed, 7));
+    }
+
+    #[test]
+    fn unfinished_jobs_survive_a_restart_paused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("queue.json");
+        let mut queue = TransferQueue::load(Some(path.clone()));
+        queue.add(spec("one", "done"), false, Default::default());
+        queue.add(spec("one", "partial"), true, Default::default());
+        queue.add(spec("one", "waiting"), false, Default::default());
+        queue.set_limits(QueueLimits {
+            per_connection: 1,
+            global: 1,
+        });
+        queue.start_runnable();
+        queue.finish("job-1", "job-1-1", Ok(5));
+        queue.start_runnable();
+        queue.save().unwrap();
+
+        let restored = TransferQueue::load(Some(path));
+        assert_eq!(ids(restored.jobs()), ["job-2", "job-3"]);
+        assert!(restored
+            .jobs()
+            .iter()
+            .all(|job| job.state == JobState::Paused && job.spec.resume));
+        assert!(restored.jobs()[0].directory);
+        assert_eq!(restored.limits().global, 1);
+
+        let mut restored = restored;
+        assert_eq!(
+            restored
+                .add(spec("one", "new"), false, Default::default())
+                .id,
+            "job-4"
+        );
+    }
+}
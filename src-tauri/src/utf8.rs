@@ -0,0 +1,46 @@
+//! Decoding text from byte streams whose reads may split a character.
+
+/// Append `data` to `pending` and return the text up to the last complete
+/// UTF-8 character; an unfinished trailing sequence stays in `pending`.
+/// Invalid bytes are replaced rather than dropped.
+pub fn take_utf8(pending: &mut Vec<u8>, data: &[u8]) -> String {
+    pending.extend_from_slice(data);
+    let len = pending.len();
+    let mut split = len;
+    for start in (len.saturating_sub(3)..len).rev() {
+        let byte = pending[start];
+        if byte & 0xC0 == 0x80 {
+            continue;
+        }
+        let needed = match byte {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        if start + needed > len {
+            split = start;
+        }
+        break;
+    }
+    let rest = pending.split_off(split);
+    let text = String::from_utf8_lossy(pending).into_owned();
+    *pending = rest;
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf8_split_across_reads_is_kept_whole() {
+        let mut pending = Vec::new();
+        let bytes = "é€".as_bytes();
+        assert_eq!(take_utf8(&mut pending, &bytes[..1]), "");
+        assert_eq!(take_utf8(&mut pending, &bytes[1..3]), "é");
+        assert_eq!(take_utf8(&mut pending, &bytes[3..]), "€");
+        assert!(pending.is_empty());
+        assert_eq!(take_utf8(&mut pending, b"\xffok"), "\u{fffd}ok");
+    }
+}
@@ -1,8 +1,930 @@
 use crate::desktop_protocol::{DesktopProtocol, FrameUpdate, VncConfig};
-use anyhow::Result;
+use anyhow::{bail, Result};
 use async_trait::async_trait;
-use tokio::sync::mpsc;
+use des::cipher::{generic_array::GenericArray, BlockEncrypt, KeyInit};
+use flate2::{Decompress, FlushDecompress, Status};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Mutex};
 use tokio_util::sync::CancellationToken;
+use zune_jpeg::zune_core::bytestream::ZCursor;
+use zune_jpeg::zune_core::colorspace::ColorSpace;
+use zune_jpeg::zune_core::options::DecoderOptions;
+use zune_jpeg::JpegDecoder;
+
+/// Timeout for the TCP connect plus the whole RFB handshake.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// RFB security types
+const SECURITY_INVALID: u8 = 0;
+const SECURITY_NONE: u8 = 1;
+const SECURITY_VNC_AUTH: u8 = 2;
+
+// RFB encodings
+const ENCODING_RAW: i32 = 0;
+const ENCODING_COPY_RECT: i32 = 1;
+const ENCODING_ZLIB: i32 = 6;
+const ENCODING_TIGHT: i32 = 7;
+/// Tight JPEG quality pseudo-encoding (level 0-9 is added to this base).
+const ENCODING_JPEG_QUALITY_0: i32 = -32;
+/// Tight compression level pseudo-encoding (level 0-9 is added to this base).
+const ENCODING_COMPRESS_LEVEL_0: i32 = -256;
+
+// Client → server message types
+const MSG_SET_PIXEL_FORMAT: u8 = 0;
+const MSG_SET_ENCODINGS: u8 = 2;
+const MSG_FRAMEBUFFER_UPDATE_REQUEST: u8 = 3;
+const MSG_KEY_EVENT: u8 = 4;
+const MSG_POINTER_EVENT: u8 = 5;
+const MSG_CLIENT_CUT_TEXT: u8 = 6;
+
+// Server → client message types
+const MSG_FRAMEBUFFER_UPDATE: u8 = 0;
+const MSG_SET_COLOUR_MAP_ENTRIES: u8 = 1;
+const MSG_BELL: u8 = 2;
+const MSG_SERVER_CUT_TEXT: u8 = 3;
+
+/// Tight sends data shorter than this uncompressed.
+const TIGHT_MIN_TO_COMPRESS: usize = 12;
+/// Largest server clipboard we keep; bigger payloads are drained and dropped.
+const MAX_CUT_TEXT: usize = 1024 * 1024;
+/// Largest failure reason string we accept from the server.
+const MAX_REASON_LEN: usize = 64 * 1024;
+
+/// Protocol version agreed with the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RfbVersion {
+    V3_3,
+    V3_7,
+    V3_8,
+}
+
+impl RfbVersion {
+    fn as_bytes(self) -> &'static [u8; 12] {
+        match self {
+            RfbVersion::V3_3 => b"RFB 003.003\n",
+            RfbVersion::V3_7 => b"RFB 003.007\n",
+            RfbVersion::V3_8 => b"RFB 003.008\n",
+        }
+    }
+}
+
+/// Pick the version to speak from the server's ProtocolVersion message.
+///
+/// Servers advertising 3.4–3.6 (and Apple's 3.889) are handled like the
+/// nearest standard version below them, as the spec requires.
+fn negotiate_version(server: &[u8; 12]) -> Result<RfbVersion> {
+    let text = std::str::from_utf8(server).unwrap_or("");
+    let parsed = text
+        .strip_prefix("RFB ")
+        .and_then(|rest| rest.strip_suffix('\n'))
+        .and_then(|rest| rest.split_once('.'))
+        .and_then(|(major, minor)| Some((major.parse::<u32>().ok()?, minor.parse::<u32>().ok()?)));
+
+    match parsed {
+        Some((3, minor)) if minor >= 8 => Ok(RfbVersion::V3_8),
+        Some((3, 7)) => Ok(RfbVersion::V3_7),
+        Some((3, minor)) if minor >= 3 => Ok(RfbVersion::V3_3),
+        Some((major, _)) if major > 3 => Ok(RfbVersion::V3_8),
+        _ => bail!(
+            "Not a VNC server (unexpected greeting {:?})",
+            String::from_utf8_lossy(server).trim_end()
+        ),
+    }
+}
+
+/// RFB pixel format, as carried in ServerInit and SetPixelFormat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PixelFormat {
+    bits_per_pixel: u8,
+    depth: u8,
+    big_endian: bool,
+    true_colour: bool,
+    red_max: u16,
+    green_max: u16,
+    blue_max: u16,
+    red_shift: u8,
+    green_shift: u8,
+    blue_shift: u8,
+}
+
+impl PixelFormat {
+    /// The true-colour format we request for a `VncConfig::color_depth`.
+    fn for_color_depth(color_depth: u8) -> Result<Self> {
+        let format = match color_depth {
+            24 | 32 => PixelFormat {
+                bits_per_pixel: 32,
+                depth: 24,
+                big_endian: false,
+                true_colour: true,
+                red_max: 255,
+                green_max: 255,
+                blue_max: 255,
+                red_shift: 16,
+                green_shift: 8,
+                blue_shift: 0,
+            },
+            16 => PixelFormat {
+                bits_per_pixel: 16,
+                depth: 16,
+                big_endian: false,
+                true_colour: true,
+                red_max: 31,
+                green_max: 63,
+                blue_max: 31,
+                red_shift: 11,
+                green_shift: 5,
+                blue_shift: 0,
+            },
+            // BGR233, the classic 8-bit true-colour layout used by vncviewer.
+            8 => PixelFormat {
+                bits_per_pixel: 8,
+                depth: 8,
+                big_endian: false,
+                true_colour: true,
+                red_max: 7,
+                green_max: 7,
+                blue_max: 3,
+                red_shift: 0,
+                green_shift: 3,
+                blue_shift: 6,
+            },
+            other => bail!(
+                "Unsupported VNC color depth: {} (expected 24, 16 or 8)",
+                other
+            ),
+        };
+        Ok(format)
+    }
+
+    fn parse(bytes: &[u8; 16]) -> Self {
+        PixelFormat {
+            bits_per_pixel: bytes[0],
+            depth: bytes[1],
+            big_endian: bytes[2] != 0,
+            true_colour: bytes[3] != 0,
+            red_max: u16::from_be_bytes([bytes[4], bytes[5]]),
+            green_max: u16::from_be_bytes([bytes[6], bytes[7]]),
+            blue_max: u16::from_be_bytes([bytes[8], bytes[9]]),
+            red_shift: bytes[10],
+            green_shift: bytes[11],
+            blue_shift: bytes[12],
+        }
+    }
+
+    fn to_bytes(self) -> [u8; 16] {
+        let mut bytes = [0u8; 16];
+        bytes[0] = self.bits_per_pixel;
+        bytes[1] = self.depth;
+        bytes[2] = self.big_endian as u8;
+        bytes[3] = self.true_colour as u8;
+        bytes[4..6].copy_from_slice(&self.red_max.to_be_bytes());
+        bytes[6..8].copy_from_slice(&self.green_max.to_be_bytes());
+        bytes[8..10].copy_from_slice(&self.blue_max.to_be_bytes());
+        bytes[10] = self.red_shift;
+        bytes[11] = self.green_shift;
+        bytes[12] = self.blue_shift;
+        bytes
+    }
+
+    fn bytes_per_pixel(&self) -> usize {
+        (self.bits_per_pixel as usize).div_ceil(8)
+    }
+
+    /// Whether Tight uses its packed 3-byte TPIXEL for this format.
+    fn is_tight_rgb24(&self) -> bool {
+        self.bits_per_pixel == 32
+            && self.depth == 24
+            && self.true_colour
+            && self.red_max == 255
+            && self.green_max == 255
+            && self.blue_max == 255
+    }
+
+    fn read_pixel(&self, bytes: &[u8]) -> u32 {
+        match (bytes.len(), self.big_endian) {
+            (1, _) => bytes[0] as u32,
+            (2, false) => u16::from_le_bytes([bytes[0], bytes[1]]) as u32,
+            (2, true) => u16::from_be_bytes([bytes[0], bytes[1]]) as u32,
+            (4, false) => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            (4, true) => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            _ => 0,
+        }
+    }
+
+    fn components(&self, pixel: u32) -> [u16; 3] {
+        [
+            ((pixel >> self.red_shift) & self.red_max as u32) as u16,
+            ((pixel >> self.green_shift) & self.green_max as u32) as u16,
+            ((pixel >> self.blue_shift) & self.blue_max as u32) as u16,
+        ]
+    }
+
+    fn maxes(&self) -> [u16; 3] {
+        [self.red_max, self.green_max, self.blue_max]
+    }
+
+    fn to_rgba(self, pixel: u32) -> [u8; 4] {
+        let [r, g, b] = self.components(pixel);
+        [
+            scale_component(r, self.red_max),
+            scale_component(g, self.green_max),
+            scale_component(b, self.blue_max),
+            255,
+        ]
+    }
+}
+
+fn scale_component(value: u16, max: u16) -> u8 {
+    if max == 0 {
+        0
+    } else {
+        ((value as u32 * 255) / max as u32) as u8
+    }
+}
+
+/// Desktop parameters announced by the server in ServerInit.
+#[derive(Debug, Clone)]
+struct ServerInit {
+    width: u16,
+    height: u16,
+    pixel_format: PixelFormat,
+    name: String,
+}
+
+/// Reverse the bit order of each password byte, as VNC's DES key schedule
+/// expects (a quirk inherited from the original implementation).
+fn vnc_des_key(password: &str) -> [u8; 8] {
+    let mut key = [0u8; 8];
+    for (slot, byte) in key.iter_mut().zip(password.bytes()) {
+        *slot = byte.reverse_bits();
+    }
+    key
+}
+
+/// Answer a VNC authentication challenge: DES-encrypt both 8-byte halves
+/// with the (truncated, bit-reversed) password.
+fn vnc_auth_response(password: &str, challenge: &[u8; 16]) -> [u8; 16] {
+    let cipher = des::Des::new(GenericArray::from_slice(&vnc_des_key(password)));
+    let mut response = *challenge;
+    for block in response.chunks_exact_mut(8) {
+        cipher.encrypt_block(GenericArray::from_mut_slice(block));
+    }
+    response
+}
+
+async fn read_reason<R: AsyncRead + Unpin>(stream: &mut R) -> Result<String> {
+    let len = stream.read_u32().await? as usize;
+    if len > MAX_REASON_LEN {
+        bail!(
+            "VNC server sent an oversized failure reason ({} bytes)",
+            len
+        );
+    }
+    let mut reason = vec![0u8; len];
+    stream.read_exact(&mut reason).await?;
+    Ok(String::from_utf8_lossy(&reason).into_owned())
+}
+
+/// Run the RFB handshake up to and including ServerInit.
+async fn handshake<S>(stream: &mut S, password: Option<&str>) -> Result<ServerInit>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    // ProtocolVersion
+    let mut greeting = [0u8; 12];
+    stream.read_exact(&mut greeting).await?;
+    let version = negotiate_version(&greeting)?;
+    stream.write_all(version.as_bytes()).await?;
+    stream.flush().await?;
+
+    // Security negotiation
+    let security = if version == RfbVersion::V3_3 {
+        // 3.3: the server dictates the security type.
+        let security = stream.read_u32().await?;
+        if security == SECURITY_INVALID as u32 {
+            bail!(
+                "VNC server refused the connection: {}",
+                read_reason(stream).await?
+            );
+        }
+        match u8::try_from(security) {
+            Ok(t @ (SECURITY_NONE | SECURITY_VNC_AUTH)) => t,
+            _ => bail!("Unsupported VNC security type {}", security),
+        }
+    } else {
+        let count = stream.read_u8().await? as usize;
+        if count == 0 {
+            bail!(
+                "VNC server refused the connection: {}",
+                read_reason(stream).await?
+            );
+        }
+        let mut offered = vec![0u8; count];
+        stream.read_exact(&mut offered).await?;
+        let chosen = if offered.contains(&SECURITY_NONE) {
+            SECURITY_NONE
+        } else if offered.contains(&SECURITY_VNC_AUTH) {
+            SECURITY_VNC_AUTH
+        } else {
+            bail!(
+                "VNC server offers no supported security type (offered {:?}; supported: None, VNC password)",
+                offered
+            );
+        };
+        stream.write_u8(chosen).await?;
+        stream.flush().await?;
+        chosen
+    };
+
+    if security == SECURITY_VNC_AUTH {
+        let password = password
+            .filter(|p| !p.is_empty())
+            .ok_or_else(|| anyhow::anyhow!("VNC server requires a password"))?;
+        let mut challenge = [0u8; 16];
+        stream.read_exact(&mut challenge).await?;
+        stream
+            .write_all(&vnc_auth_response(password, &challenge))
+            .await?;
+        stream.flush().await?;
+    }
+
+    // SecurityResult: always sent by 3.8, only after authentication before that.
+    if (security == SECURITY_VNC_AUTH || version == RfbVersion::V3_8)
+        && stream.read_u32().await? != 0
+    {
+        let reason = if version == RfbVersion::V3_8 {
+            read_reason(stream).await?
+        } else {
+            "invalid password".to_string()
+        };
+        bail!("VNC authentication failed: {}", reason);
+    }
+
+    // ClientInit — ask to share the desktop with other viewers.
+    stream.write_u8(1).await?;
+    stream.flush().await?;
+
+    // ServerInit
+    let width = stream.read_u16().await?;
+    let height = stream.read_u16().await?;
+    let mut format = [0u8; 16];
+    stream.read_exact(&mut format).await?;
+    let name = read_reason(stream).await?;
+
+    Ok(ServerInit {
+        width,
+        height,
+        pixel_format: PixelFormat::parse(&format),
+        name,
+    })
+}
+
+// ---------------------------------------------------------------------------
+// Client → server message encoding
+// ---------------------------------------------------------------------------
+
+fn set_pixel_format_msg(format: PixelFormat) -> Vec<u8> {
+    let mut msg = vec![MSG_SET_PIXEL_FORMAT, 0, 0, 0];
+    msg.extend_from_slice(&format.to_bytes());
+    msg
+}
+
+fn set_encodings_msg(encodings: &[i32]) -> Vec<u8> {
+    let mut msg = vec![MSG_SET_ENCODINGS, 0];
+    msg.extend_from_slice(&(encodings.len() as u16).to_be_bytes());
+    for encoding in encodings {
+        msg.extend_from_slice(&encoding.to_be_bytes());
+    }
+    msg
+}
+
+fn framebuffer_update_request_msg(incremental: bool, width: u16, height: u16) -> Vec<u8> {
+    let mut msg = vec![MSG_FRAMEBUFFER_UPDATE_REQUEST, incremental as u8];
+    msg.extend_from_slice(&0u16.to_be_bytes());
+    msg.extend_from_slice(&0u16.to_be_bytes());
+    msg.extend_from_slice(&width.to_be_bytes());
+    msg.extend_from_slice(&height.to_be_bytes());
+    msg
+}
+
+fn key_event_msg(down: bool, keysym: u32) -> Vec<u8> {
+    let mut msg = vec![MSG_KEY_EVENT, down as u8, 0, 0];
+    msg.extend_from_slice(&keysym.to_be_bytes());
+    msg
+}
+
+fn pointer_event_msg(button_mask: u8, x: u16, y: u16) -> Vec<u8> {
+    let mut msg = vec![MSG_POINTER_EVENT, button_mask];
+    msg.extend_from_slice(&x.to_be_bytes());
+    msg.extend_from_slice(&y.to_be_bytes());
+    msg
+}
+
+/// ClientCutText carries ISO 8859-1; characters outside it become `?`.
+fn client_cut_text_msg(text: &str) -> Vec<u8> {
+    let latin1: Vec<u8> = text
+        .chars()
+        .map(|c| u8::try_from(c as u32).unwrap_or(b'?'))
+        .collect();
+    let mut msg = vec![MSG_CLIENT_CUT_TEXT, 0, 0, 0];
+    msg.extend_from_slice(&(latin1.len() as u32).to_be_bytes());
+    msg.extend_from_slice(&latin1);
+    msg
+}
+
+/// Encodings we advertise, most preferred first.
+fn preferred_encodings(format: &PixelFormat) -> Vec<i32> {
+    let mut encodings = vec![
+        ENCODING_TIGHT,
+        ENCODING_ZLIB,
+        ENCODING_COPY_RECT,
+        ENCODING_RAW,
+    ];
+    encodings.push(ENCODING_COMPRESS_LEVEL_0 + 6);
+    // Tight only sends JPEG for 24-bit true colour.
+    if format.is_tight_rgb24() {
+        encodings.push(ENCODING_JPEG_QUALITY_0 + 7);
+    }
+    encodings
+}
+
+/// Map a browser `KeyboardEvent.keyCode` to an X11 keysym.
+///
+/// Letters map to their lowercase keysym; the server applies Shift itself
+/// because modifier presses are forwarded separately. Values above 0xFF are
+/// assumed to already be keysyms and pass through unchanged.
+fn keysym_for_key_code(key_code: u32) -> u32 {
+    match key_code {
+        8 => 0xff08,                            // BackSpace
+        9 => 0xff09,                            // Tab
+        13 => 0xff0d,                           // Return
+        16 => 0xffe1,                           // Shift_L
+        17 => 0xffe3,                           // Control_L
+        18 => 0xffe9,                           // Alt_L
+        19 => 0xff13,                           // Pause
+        20 => 0xffe5,                           // Caps_Lock
+        27 => 0xff1b,                           // Escape
+        32 => 0x0020,                           // space
+        33 => 0xff55,                           // Prior
+        34 => 0xff56,                           // Next
+        35 => 0xff57,                           // End
+        36 => 0xff50,                           // Home
+        37 => 0xff51,                           // Left
+        38 => 0xff52,                           // Up
+        39 => 0xff53,                           // Right
+        40 => 0xff54,                           // Down
+        45 => 0xff63,                           // Insert
+        46 => 0xffff,                           // Delete
+        48..=57 => key_code,                    // 0-9
+        65..=90 => key_code + 0x20,             // a-z
+        91 => 0xffeb,                           // Super_L
+        92 => 0xffec,                           // Super_R
+        93 => 0xff67,                           // Menu
+        96..=105 => 0xffb0 + (key_code - 96),   // KP_0-KP_9
+        106 => 0xffaa,                          // KP_Multiply
+        107 => 0xffab,                          // KP_Add
+        109 => 0xffad,                          // KP_Subtract
+        110 => 0xffae,                          // KP_Decimal
+        111 => 0xffaf,                          // KP_Divide
+        112..=123 => 0xffbe + (key_code - 112), // F1-F12
+        144 => 0xff7f,                          // Num_Lock
+        145 => 0xff14,                          // Scroll_Lock
+        186 => 0x003b,                          // semicolon
+        187 => 0x003d,                          // equal
+        188 => 0x002c,                          // comma
+        189 => 0x002d,                          // minus
+        190 => 0x002e,                          // period
+        191 => 0x002f,                          // slash
+        192 => 0x0060,                          // grave
+        219 => 0x005b,                          // bracketleft
+        220 => 0x005c,                          // backslash
+        221 => 0x005d,                          // bracketright
+        222 => 0x0027,                          // apostrophe
+        other => other,
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Framebuffer decoding
+// ---------------------------------------------------------------------------
+
+/// Read a Tight "compact length" (1–3 bytes, 7 bits per byte).
+async fn read_compact_len<R: AsyncRead + Unpin>(stream: &mut R) -> Result<usize> {
+    let mut len = 0usize;
+    for i in 0..3 {
+        let byte = stream.read_u8().await?;
+        if i == 2 {
+            len |= (byte as usize) << 14;
+            break;
+        }
+        len |= ((byte & 0x7f) as usize) << (7 * i);
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    Ok(len)
+}
+
+/// Inflate exactly `expected` bytes from `input` using a persistent stream.
+fn inflate(stream: &mut Decompress, input: &[u8], expected: usize) -> Result<Vec<u8>> {
+    let mut output = vec![0u8; expected];
+    let mut consumed = 0usize;
+    let mut produced = 0usize;
+    while produced < expected {
+        let before_in = stream.total_in();
+        let before_out = stream.total_out();
+        let status = stream
+            .decompress(
+                &input[consumed..],
+                &mut output[produced..],
+                FlushDecompress::Sync,
+            )
+            .map_err(|e| anyhow::anyhow!("Corrupt zlib data from VNC server: {}", e))?;
+        let read = (stream.total_in() - before_in) as usize;
+        let written = (stream.total_out() - before_out) as usize;
+        consumed += read;
+        produced += written;
+        if (read == 0 && written == 0) || status == Status::StreamEnd {
+            break;
+        }
+    }
+    if produced < expected {
+        bail!(
+            "Truncated zlib data from VNC server (expected {} bytes, got {})",
+            expected,
+            produced
+        );
+    }
+    Ok(output)
+}
+
+/// Decoder state that lives for the whole connection: the client-side copy
+/// of the framebuffer (needed by CopyRect) and the zlib streams, which the
+/// server never resets between rectangles.
+struct FrameDecoder {
+    pixel_format: PixelFormat,
+    width: u16,
+    height: u16,
+    /// RGBA copy of the remote framebuffer.
+    framebuffer: Vec<u8>,
+    zlib: Decompress,
+    tight: [Decompress; 4],
+}
+
+impl FrameDecoder {
+    fn new(pixel_format: PixelFormat, width: u16, height: u16) -> Self {
+        Self {
+            pixel_format,
+            width,
+            height,
+            framebuffer: vec![0; width as usize * height as usize * 4],
+            zlib: Decompress::new(true),
+            tight: std::array::from_fn(|_| Decompress::new(true)),
+        }
+    }
+
+    /// Read the body of a FramebufferUpdate (after the message-type byte)
+    /// and decode every rectangle.
+    async fn read_update<R: AsyncRead + Unpin>(
+        &mut self,
+        stream: &mut R,
+    ) -> Result<Vec<FrameUpdate>> {
+        let _padding = stream.read_u8().await?;
+        let count = stream.read_u16().await?;
+        let mut updates = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let x = stream.read_u16().await?;
+            let y = stream.read_u16().await?;
+            let width = stream.read_u16().await?;
+            let height = stream.read_u16().await?;
+            let encoding = stream.read_i32().await?;
+            updates.push(
+                self.read_rect(stream, x, y, width, height, encoding)
+                    .await?,
+            );
+        }
+        Ok(updates)
+    }
+
+    async fn read_rect<R: AsyncRead + Unpin>(
+        &mut self,
+        stream: &mut R,
+        x: u16,
+        y: u16,
+        width: u16,
+        height: u16,
+        encoding: i32,
+    ) -> Result<FrameUpdate> {
+        if x as usize + width as usize > self.width as usize
+            || y as usize + height as usize > self.height as usize
+        {
+            bail!(
+                "VNC rectangle {}x{}+{}+{} lies outside the {}x{} framebuffer",
+                width,
+                height,
+                x,
+                y,
+                self.width,
+                self.height
+            );
+        }
+
+        let pixels = width as usize * height as usize;
+        let bpp = self.pixel_format.bytes_per_pixel();
+        let rgba = match encoding {
+            ENCODING_RAW => {
+                let mut data = vec![0u8; pixels * bpp];
+                stream.read_exact(&mut data).await?;
+                self.pixels_to_rgba(&data)
+            }
+            ENCODING_COPY_RECT => {
+                let src_x = stream.read_u16().await?;
+                let src_y = stream.read_u16().await?;
+                self.copy_region(src_x, src_y, width, height)?
+            }
+            ENCODING_ZLIB => {
+                let len = stream.read_u32().await? as usize;
+                let mut compressed = vec![0u8; len];
+                stream.read_exact(&mut compressed).await?;
+                let data = inflate(&mut self.zlib, &compressed, pixels * bpp)?;
+                self.pixels_to_rgba(&data)
+            }
+            ENCODING_TIGHT => self.read_tight(stream, width, height).await?,
+            other => bail!("Unsupported VNC encoding {}", other),
+        };
+
+        self.blit(x, y, width, height, &rgba);
+        Ok(FrameUpdate {
+            x,
+            y,
+            width,
+            height,
+            rgba_data: rgba,
+        })
+    }
+
+    fn pixels_to_rgba(&self, data: &[u8]) -> Vec<u8> {
+        let format = self.pixel_format;
+        data.chunks_exact(format.bytes_per_pixel())
+            .flat_map(|pixel| format.to_rgba(format.read_pixel(pixel)))
+            .collect()
+    }
+
+    fn copy_region(&self, src_x: u16, src_y: u16, width: u16, height: u16) -> Result<Vec<u8>> {
+        if src_x as usize + width as usize > self.width as usize
+            || src_y as usize + height as usize > self.height as usize
+        {
+            bail!("VNC CopyRect source lies outside the framebuffer");
+        }
+        let stride = self.width as usize * 4;
+        let row_len = width as usize * 4;
+        let mut rgba = Vec::with_capacity(row_len * height as usize);
+        for row in 0..height as usize {
+            let start = (src_y as usize + row) * stride + src_x as usize * 4;
+            rgba.extend_from_slice(&self.framebuffer[start..start + row_len]);
+        }
+        Ok(rgba)
+    }
+
+    fn blit(&mut self, x: u16, y: u16, width: u16, height: u16, rgba: &[u8]) {
+        let stride = self.width as usize * 4;
+        let row_len = width as usize * 4;
+        for row in 0..height as usize {
+            let dst = (y as usize + row) * stride + x as usize * 4;
+            self.framebuffer[dst..dst + row_len]
+                .copy_from_slice(&rgba[row * row_len..(row + 1) * row_len]);
+        }
+    }
+
+    fn tpixel_size(&self) -> usize {
+        if self.pixel_format.is_tight_rgb24() {
+            3
+        } else {
+            self.pixel_format.bytes_per_pixel()
+        }
+    }
+
+    fn tpixel_to_rgba(&self, tpixel: &[u8]) -> [u8; 4] {
+        if self.pixel_format.is_tight_rgb24() {
+            [tpixel[0], tpixel[1], tpixel[2], 255]
+        } else {
+            let format = self.pixel_format;
+            format.to_rgba(format.read_pixel(tpixel))
+        }
+    }
+
+    /// Tight pixel split into components, in the format's native range.
+    fn tpixel_components(&self, tpixel: &[u8]) -> [u16; 3] {
+        if self.pixel_format.is_tight_rgb24() {
+            [tpixel[0] as u16, tpixel[1] as u16, tpixel[2] as u16]
+        } else {
+            let format = self.pixel_format;
+            format.components(format.read_pixel(tpixel))
+        }
+    }
+
+    async fn read_tight<R: AsyncRead + Unpin>(
+        &mut self,
+        stream: &mut R,
+        width: u16,
+        height: u16,
+    ) -> Result<Vec<u8>> {
+        let pixels = width as usize * height as usize;
+        let control = stream.read_u8().await?;
+        for (i, zlib) in self.tight.iter_mut().enumerate() {
+            if control & (1 << i) != 0 {
+                zlib.reset(true);
+            }
+        }
+
+        let kind = control >> 4;
+        let tps = self.tpixel_size();
+        match kind {
+            // FillCompression: one pixel for the whole rectangle.
+            0x08 => {
+                let mut tpixel = vec![0u8; tps];
+                stream.read_exact(&mut tpixel).await?;
+                let colour = self.tpixel_to_rgba(&tpixel);
+                Ok(colour.repeat(pixels))
+            }
+            // JpegCompression
+            0x09 => {
+                let len = read_compact_len(stream).await?;
+                let mut jpeg = vec![0u8; len];
+                stream.read_exact(&mut jpeg).await?;
+                decode_jpeg(&jpeg, width, height)
+            }
+            // BasicCompression: bits 4-5 pick the zlib stream, bit 6 flags an
+            // explicit filter id.
+            kind if kind & 0x08 == 0 => {
+                let stream_id = (kind & 0x03) as usize;
+                let filter = if kind & 0x04 != 0 {
+                    stream.read_u8().await?
+                } else {
+                    0
+                };
+                match filter {
+                    // Copy
+                    0 => {
+                        let data = self
+                            .read_tight_data(stream, stream_id, pixels * tps)
+                            .await?;
+                        Ok(data
+                            .chunks_exact(tps)
+                            .flat_map(|tpixel| self.tpixel_to_rgba(tpixel))
+                            .collect())
+                    }
+                    // Palette
+                    1 => {
+                        let colours = stream.read_u8().await? as usize + 1;
+                        let mut raw_palette = vec![0u8; colours * tps];
+                        stream.read_exact(&mut raw_palette).await?;
+                        let palette: Vec<[u8; 4]> = raw_palette
+                            .chunks_exact(tps)
+                            .map(|tpixel| self.tpixel_to_rgba(tpixel))
+                            .collect();
+                        let row_len = if colours == 2 {
+                            (width as usize).div_ceil(8)
+                        } else {
+                            width as usize
+                        };
+                        let data = self
+                            .read_tight_data(stream, stream_id, row_len * height as usize)
+                            .await?;
+                        decode_palette(&data, &palette, width, height)
+                    }
+                    // Gradient
+                    2 => {
+                        let data = self
+                            .read_tight_data(stream, stream_id, pixels * tps)
+                            .await?;
+                        Ok(self.decode_gradient(&data, width, height))
+                    }
+                    other => bail!("Unknown Tight filter {}", other),
+                }
+            }
+            other => bail!("Invalid Tight compression type {:#x}", other),
+        }
+    }
+
+    /// Read `len` bytes of Tight pixel data, inflating with `stream_id`
+    /// unless the payload is short enough to be sent raw.
+    async fn read_tight_data<R: AsyncRead + Unpin>(
+        &mut self,
+        stream: &mut R,
+        stream_id: usize,
+        len: usize,
+    ) -> Result<Vec<u8>> {
+        if len < TIGHT_MIN_TO_COMPRESS {
+            let mut data = vec![0u8; len];
+            stream.read_exact(&mut data).await?;
+            return Ok(data);
+        }
+        let compressed_len = read_compact_len(stream).await?;
+        let mut compressed = vec![0u8; compressed_len];
+        stream.read_exact(&mut compressed).await?;
+        inflate(&mut self.tight[stream_id], &compressed, len)
+    }
+
+    /// Undo Tight's gradient filter: each component was sent as the
+    /// difference from `left + above - above_left`, clamped to its range.
+    fn decode_gradient(&self, data: &[u8], width: u16, height: u16) -> Vec<u8> {
+        let tps = self.tpixel_size();
+        let maxes = if self.pixel_format.is_tight_rgb24() {
+            [255u16; 3]
+        } else {
+            self.pixel_format.maxes()
+        };
+        let width = width as usize;
+        let mut previous = vec![[0u16; 3]; width];
+        let mut current = vec![[0u16; 3]; width];
+        let mut rgba = Vec::with_capacity(width * height as usize * 4);
+
+        for row in data.chunks_exact(width * tps).take(height as usize) {
+            for (x, tpixel) in row.chunks_exact(tps).enumerate() {
+                let diff = self.tpixel_components(tpixel);
+                for c in 0..3 {
+                    let left = if x > 0 { current[x - 1][c] as i32 } else { 0 };
+                    let above_left = if x > 0 { previous[x - 1][c] as i32 } else { 0 };
+                    let predicted =
+                        (left + previous[x][c] as i32 - above_left).clamp(0, maxes[c] as i32);
+                    current[x][c] = ((predicted as u32 + diff[c] as u32) & maxes[c] as u32) as u16;
+                }
+                rgba.extend_from_slice(&[
+                    scale_component(current[x][0], maxes[0]),
+                    scale_component(current[x][1], maxes[1]),
+                    scale_component(current[x][2], maxes[2]),
+                    255,
+                ]);
+            }
+            std::mem::swap(&mut previous, &mut current);
+        }
+        rgba
+    }
+}
+
+fn decode_palette(data: &[u8], palette: &[[u8; 4]], width: u16, height: u16) -> Result<Vec<u8>> {
+    let width = width as usize;
+    let mut rgba = Vec::with_capacity(width * height as usize * 4);
+    if palette.len() == 2 {
+        let row_len = width.div_ceil(8);
+        for row in data.chunks_exact(row_len).take(height as usize) {
+            for x in 0..width {
+                let bit = (row[x / 8] >> (7 - (x % 8))) & 1;
+                rgba.extend_from_slice(&palette[bit as usize]);
+            }
+        }
+    } else {
+        for &index in data {
+            let colour = palette
+                .get(index as usize)
+                .ok_or_else(|| anyhow::anyhow!("Tight palette index {} out of range", index))?;
+            rgba.extend_from_slice(colour);
+        }
+    }
+    Ok(rgba)
+}
+
+fn decode_jpeg(data: &[u8], width: u16, height: u16) -> Result<Vec<u8>> {
+    let options = DecoderOptions::default().jpeg_set_out_colorspace(ColorSpace::RGBA);
+    let mut decoder = JpegDecoder::new_with_options(ZCursor::new(data), options);
+    let rgba = decoder
+        .decode()
+        .map_err(|e| anyhow::anyhow!("Invalid Tight JPEG data: {:?}", e))?;
+    let info = decoder
+        .info()
+        .ok_or_else(|| anyhow::anyhow!("Invalid Tight JPEG data: missing header"))?;
+    if info.width as usize != width as usize || info.height as usize != height as usize {
+        bail!(
+            "Tight JPEG is {}x{}, expected {}x{}",
+            info.width,
+            info.height,
+            width,
+            height
+        );
+    }
+    Ok(rgba)
+}
+
+/// Read half of the connection plus the state needed to decode it. Owned by
+/// whichever frame loop is currently running.
+struct RfbReader {
+    stream: BufReader<OwnedReadHalf>,
+    decoder: FrameDecoder,
+}
+
+/// Discard `len` bytes from the stream.
+async fn skip_bytes<R: AsyncRead + Unpin>(stream: &mut R, len: u64) -> Result<()> {
+    let copied = tokio::io::copy(&mut stream.take(len), &mut tokio::io::sink()).await?;
+    if copied != len {
+        bail!("VNC connection closed mid-message");
+    }
+    Ok(())
+}
 
 /// VNC (RFB protocol) remote desktop client.
 ///
@@ -10,43 +932,85 @@ use tokio_util::sync::CancellationToken;
 /// protocol is simple enough (handshake → auth → framebuffer updates) that we
 /// avoid heavy C dependencies and implement it directly.
 ///
-/// Supported encodings: Raw, CopyRect, Zlib (minimum set).
+/// Supported versions: RFB 3.3, 3.7 and 3.8.
+/// Supported encodings: Raw, CopyRect, Zlib and Tight (including JPEG).
 /// Supported auth: VNC password challenge-response and no-auth.
-///
-/// The actual protocol implementation will be added in a follow-up.
 pub struct VncClient {
     config: VncConfig,
     desktop_width: u16,
     desktop_height: u16,
-    connected: bool,
+    connected: AtomicBool,
+    /// Taken by `start_frame_loop` while it runs, returned when it stops.
+    reader: Mutex<Option<RfbReader>>,
+    writer: Mutex<Option<OwnedWriteHalf>>,
+    /// Stops a running frame loop on disconnect.
+    shutdown: CancellationToken,
 }
 
 impl VncClient {
-    /// Attempt to establish a VNC connection.
-    ///
-    /// Currently returns an error because the RFB protocol implementation is
-    /// pending.  The function validates the config so callers get a meaningful
-    /// message.
+    /// Connect, authenticate and negotiate the pixel format and encodings.
     pub async fn connect(config: &VncConfig) -> Result<Self> {
         if config.host.is_empty() {
             return Err(anyhow::anyhow!("VNC host cannot be empty"));
         }
+        let pixel_format = PixelFormat::for_color_depth(config.color_depth)?;
+
+        let (stream, server_init) = tokio::time::timeout(CONNECT_TIMEOUT, async {
+            let mut stream = TcpStream::connect((config.host.as_str(), config.port))
+                .await
+                .map_err(|e| {
+                    anyhow::anyhow!(
+                        "Failed to connect to {}:{}: {}",
+                        config.host,
+                        config.port,
+                        e
+                    )
+                })?;
+            stream.set_nodelay(true)?;
+            let server_init = handshake(&mut stream, config.password.as_deref()).await?;
+            Ok::<_, anyhow::Error>((stream, server_init))
+        })
+        .await
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "VNC connection to {}:{} timed out after {} seconds",
+                config.host,
+                config.port,
+                CONNECT_TIMEOUT.as_secs()
+            )
+        })??;
 
-        // TODO: implement actual VNC/RFB connection:
-        // 1. TCP connect to host:port
-        // 2. RFB version handshake (3.8)
-        // 3. Security type negotiation (VNC auth or no-auth)
-        // 4. VNC auth: DES challenge-response with password
-        // 5. ClientInit (shared flag)
-        // 6. ServerInit (desktop size, pixel format, name)
-        // 7. SetPixelFormat based on requested color depth
-        // 8. SetEncodings (Raw, CopyRect, Zlib)
-        Err(anyhow::anyhow!(
-            "VNC protocol support is not yet implemented. \
-             Connection to {}:{} cannot be established.",
+        tracing::info!(
+            "VNC connected to {}:{} ({:?}, {}x{}, server format {}bpp)",
             config.host,
-            config.port
-        ))
+            config.port,
+            server_init.name,
+            server_init.width,
+            server_init.height,
+            server_init.pixel_format.bits_per_pixel
+        );
+
+        let (read_half, mut write_half) = stream.into_split();
+        write_half
+            .write_all(&set_pixel_format_msg(pixel_format))
+            .await?;
+        write_half
+            .write_all(&set_encodings_msg(&preferred_encodings(&pixel_format)))
+            .await?;
+        write_half.flush().await?;
+
+        Ok(Self {
+            config: config.clone(),
+            desktop_width: server_init.width,
+            desktop_height: server_init.height,
+            connected: AtomicBool::new(true),
+            reader: Mutex::new(Some(RfbReader {
+                stream: BufReader::new(read_half),
+                decoder: FrameDecoder::new(pixel_format, server_init.width, server_init.height),
+            })),
+            writer: Mutex::new(Some(write_half)),
+            shutdown: CancellationToken::new(),
+        })
     }
 
     /// Create a client instance in disconnected state (for testing).
@@ -56,8 +1020,73 @@ impl VncClient {
             desktop_width: 1024,
             desktop_height: 768,
             config,
-            connected: false,
+            connected: AtomicBool::new(false),
+            reader: Mutex::new(None),
+            writer: Mutex::new(None),
+            shutdown: CancellationToken::new(),
+        }
+    }
+
+    async fn send(&self, msg: &[u8]) -> Result<()> {
+        if !self.connected.load(Ordering::SeqCst) {
+            return Err(anyhow::anyhow!("VNC client is not connected"));
         }
+        let mut writer = self.writer.lock().await;
+        let writer = writer
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("VNC client is not connected"))?;
+        writer.write_all(msg).await?;
+        writer.flush().await?;
+        Ok(())
+    }
+
+    /// Handle one server message whose type byte has already been read.
+    async fn handle_server_message(
+        &self,
+        reader: &mut RfbReader,
+        msg_type: u8,
+        frame_tx: &mpsc::UnboundedSender<FrameUpdate>,
+    ) -> Result<bool> {
+        let stream = &mut reader.stream;
+        match msg_type {
+            MSG_FRAMEBUFFER_UPDATE => {
+                let updates = reader.decoder.read_update(stream).await?;
+                for update in updates {
+                    if frame_tx.send(update).is_err() {
+                        // Receiver gone — nobody is watching any more.
+                        return Ok(false);
+                    }
+                }
+                self.send(&framebuffer_update_request_msg(
+                    true,
+                    self.desktop_width,
+                    self.desktop_height,
+                ))
+                .await?;
+            }
+            MSG_SET_COLOUR_MAP_ENTRIES => {
+                // We always request true colour, so the map is irrelevant.
+                let _padding = stream.read_u8().await?;
+                let _first = stream.read_u16().await?;
+                let count = stream.read_u16().await?;
+                skip_bytes(stream, count as u64 * 6).await?;
+            }
+            MSG_BELL => {}
+            MSG_SERVER_CUT_TEXT => {
+                let mut padding = [0u8; 3];
+                stream.read_exact(&mut padding).await?;
+                let len = stream.read_u32().await? as usize;
+                if len > MAX_CUT_TEXT {
+                    skip_bytes(stream, len as u64).await?;
+                } else {
+                    let mut text = vec![0u8; len];
+                    stream.read_exact(&mut text).await?;
+                    tracing::debug!("VNC server clipboard updated ({} bytes)", len);
+                }
+            }
+            other => bail!("Unsupported VNC server message type {}", other),
+        }
+        Ok(true)
     }
 }
 
@@ -65,62 +1094,79 @@ impl VncClient {
 impl DesktopProtocol for VncClient {
     async fn start_frame_loop(
         &self,
-        _frame_tx: mpsc::UnboundedSender<FrameUpdate>,
-        _cancel: CancellationToken,
+        frame_tx: mpsc::UnboundedSender<FrameUpdate>,
+        cancel: CancellationToken,
     ) -> Result<()> {
-        if !self.connected {
+        if !self.connected.load(Ordering::SeqCst) {
             return Err(anyhow::anyhow!("VNC client is not connected"));
         }
-        // TODO: request incremental framebuffer updates and decode them
-        Ok(())
-    }
+        let mut slot = self
+            .reader
+            .try_lock()
+            .map_err(|_| anyhow::anyhow!("VNC frame loop is already running"))?;
+        let reader = slot
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("VNC client is not connected"))?;
 
-    async fn send_key(&self, _key_code: u32, _down: bool) -> Result<()> {
-        if !self.connected {
-            return Err(anyhow::anyhow!("VNC client is not connected"));
+        self.request_full_frame().await?;
+
+        loop {
+            // Only wait for cancellation between messages so the stream is
+            // never left mid-message for the next frame loop.
+            let msg_type = tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = self.shutdown.cancelled() => break,
+                msg_type = reader.stream.read_u8() => msg_type?,
+            };
+            if !self
+                .handle_server_message(reader, msg_type, &frame_tx)
+                .await?
+            {
+                break;
+            }
         }
-        // TODO: send RFB KeyEvent message
         Ok(())
     }
 
-    async fn send_pointer(&self, _x: u16, _y: u16, _button_mask: u8) -> Result<()> {
-        if !self.connected {
-            return Err(anyhow::anyhow!("VNC client is not connected"));
-        }
-        // TODO: send RFB PointerEvent message
-        Ok(())
+    async fn send_key(&self, key_code: u32, down: bool) -> Result<()> {
+        self.send(&key_event_msg(down, keysym_for_key_code(key_code)))
+            .await
+    }
+
+    async fn send_pointer(&self, x: u16, y: u16, button_mask: u8) -> Result<()> {
+        self.send(&pointer_event_msg(button_mask, x, y)).await
     }
 
     async fn request_full_frame(&self) -> Result<()> {
-        if !self.connected {
-            return Err(anyhow::anyhow!("VNC client is not connected"));
-        }
-        // TODO: send non-incremental FramebufferUpdateRequest
-        Ok(())
+        self.send(&framebuffer_update_request_msg(
+            false,
+            self.desktop_width,
+            self.desktop_height,
+        ))
+        .await
     }
 
-    async fn set_clipboard(&self, _text: String) -> Result<()> {
-        if !self.connected {
-            return Err(anyhow::anyhow!("VNC client is not connected"));
-        }
-        // TODO: send ClientCutText message
-        Ok(())
+    async fn set_clipboard(&self, text: String) -> Result<()> {
+        self.send(&client_cut_text_msg(&text)).await
     }
 
     fn desktop_size(&self) -> (u16, u16) {
         (self.desktop_width, self.desktop_height)
     }
 
-    async fn resize(&mut self, _width: u16, _height: u16) -> Result<()> {
+    async fn resize(&self, _width: u16, _height: u16) -> Result<()> {
         // VNC does not support server-side resize.
         // The frontend handles this by scaling the existing framebuffer client-side.
         Ok(())
     }
 
-    async fn disconnect(&mut self) -> Result<()> {
-        if self.connected {
-            // TODO: close TCP connection
-            self.connected = false;
+    async fn disconnect(&self) -> Result<()> {
+        if self.connected.swap(false, Ordering::SeqCst) {
+            self.shutdown.cancel();
+            if let Some(mut writer) = self.writer.lock().await.take() {
+                let _ = writer.shutdown().await;
+            }
+            self.reader.lock().await.take();
             tracing::info!(
                 "VNC disconnected from {}:{}",
                 self.config.host,
@@ -130,3 +1176,546 @@ impl DesktopProtocol for VncClient {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::{Compress, Compression, FlushCompress};
+    use std::io::Write;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+
+    fn config(port: u16, password: Option<&str>, color_depth: u8) -> VncConfig {
+        VncConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            password: password.map(str::to_string),
+            color_depth,
+        }
+    }
+
+    fn server_init_bytes(width: u16, height: u16, name: &str) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&width.to_be_bytes());
+        bytes.extend_from_slice(&height.to_be_bytes());
+        bytes.extend_from_slice(&PixelFormat::for_color_depth(24).unwrap().to_bytes());
+        bytes.extend_from_slice(&(name.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        bytes
+    }
+
+    /// Minimal RFB server: greets with `version`, runs the security phase the
+    /// way a real server of that version would, then sends ServerInit and
+    /// hands the socket back for the test to script the rest.
+    async fn fake_server(
+        version: &'static str,
+        security: &'static [u8],
+        password: Option<&'static str>,
+    ) -> (u16, tokio::task::JoinHandle<Result<TcpStream>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await?;
+            socket.write_all(version.as_bytes()).await?;
+            let mut reply = [0u8; 12];
+            socket.read_exact(&mut reply).await?;
+            let v38 = &reply == b"RFB 003.008\n";
+            let v33 = &reply == b"RFB 003.003\n";
+
+            let chosen = if v33 {
+                socket.write_u32(security[0] as u32).await?;
+                security[0]
+            } else {
+                socket.write_u8(security.len() as u8).await?;
+                socket.write_all(security).await?;
+                socket.read_u8().await?
+            };
+
+            let mut ok = true;
+            if chosen == SECURITY_VNC_AUTH {
+                let challenge = *b"0123456789abcdef";
+                socket.write_all(&challenge).await?;
+                let mut response = [0u8; 16];
+                socket.read_exact(&mut response).await?;
+                ok = response == vnc_auth_response(password.unwrap_or(""), &challenge);
+            }
+            if chosen == SECURITY_VNC_AUTH || v38 {
+                socket.write_u32(if ok { 0 } else { 1 }).await?;
+                if !ok {
+                    if v38 {
+                        let reason = b"bad password";
+                        socket.write_u32(reason.len() as u32).await?;
+                        socket.write_all(reason).await?;
+                    }
+                    return Ok(socket);
+                }
+            }
+
+            let shared = socket.read_u8().await?;
+            assert_eq!(shared, 1, "client should request a shared session");
+            socket
+                .write_all(&server_init_bytes(4, 2, "fake desktop"))
+                .await?;
+            Ok(socket)
+        });
+        (port, handle)
+    }
+
+    /// Read the SetPixelFormat + SetEncodings the client sends after init.
+    async fn read_client_setup(socket: &mut TcpStream) -> (PixelFormat, Vec<i32>) {
+        let mut msg = [0u8; 20];
+        socket.read_exact(&mut msg).await.unwrap();
+        assert_eq!(msg[0], MSG_SET_PIXEL_FORMAT);
+        let format = PixelFormat::parse(msg[4..20].try_into().unwrap());
+
+        assert_eq!(socket.read_u8().await.unwrap(), MSG_SET_ENCODINGS);
+        socket.read_u8().await.unwrap();
+        let count = socket.read_u16().await.unwrap();
+        let mut encodings = Vec::new();
+        for _ in 0..count {
+            encodings.push(socket.read_i32().await.unwrap());
+        }
+        (format, encodings)
+    }
+
+    fn rect_header(x: u16, y: u16, w: u16, h: u16, encoding: i32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for v in [x, y, w, h] {
+            bytes.extend_from_slice(&v.to_be_bytes());
+        }
+        bytes.extend_from_slice(&encoding.to_be_bytes());
+        bytes
+    }
+
+    fn rgb24() -> PixelFormat {
+        PixelFormat::for_color_depth(24).unwrap()
+    }
+
+    fn zlib_sync(compress: &mut Compress, data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len() + 64);
+        compress
+            .compress_vec(data, &mut out, FlushCompress::Sync)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_version_negotiation() {
+        assert_eq!(
+            negotiate_version(b"RFB 003.003\n").unwrap(),
+            RfbVersion::V3_3
+        );
+        assert_eq!(
+            negotiate_version(b"RFB 003.005\n").unwrap(),
+            RfbVersion::V3_3
+        );
+        assert_eq!(
+            negotiate_version(b"RFB 003.007\n").unwrap(),
+            RfbVersion::V3_7
+        );
+        assert_eq!(
+            negotiate_version(b"RFB 003.008\n").unwrap(),
+            RfbVersion::V3_8
+        );
+        assert_eq!(
+            negotiate_version(b"RFB 003.889\n").unwrap(),
+            RfbVersion::V3_8
+        );
+        assert_eq!(
+            negotiate_version(b"RFB 004.001\n").unwrap(),
+            RfbVersion::V3_8
+        );
+        assert!(negotiate_version(b"SSH-2.0-xyz\n").is_err());
+        assert!(negotiate_version(b"RFB 002.000\n").is_err());
+    }
+
+    #[test]
+    fn test_des_key_reverses_bits_and_truncates() {
+        assert_eq!(vnc_des_key("a"), [0x86, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(vnc_des_key("123456789")[7], b'8'.reverse_bits());
+    }
+
+    #[test]
+    fn test_vnc_auth_response_matches_des_of_each_half() {
+        let challenge = [7u8; 16];
+        let response = vnc_auth_response("secret", &challenge);
+        // Identical halves must encrypt identically (ECB), and differently
+        // from the plaintext.
+        assert_eq!(response[..8], response[8..]);
+        assert_ne!(response, challenge);
+        assert_ne!(response, vnc_auth_response("Secret", &challenge));
+    }
+
+    #[test]
+    fn test_pixel_format_for_color_depth() {
+        let f24 = rgb24();
+        assert_eq!((f24.bits_per_pixel, f24.depth), (32, 24));
+        assert!(f24.is_tight_rgb24());
+        let f16 = PixelFormat::for_color_depth(16).unwrap();
+        assert_eq!(
+            (f16.bits_per_pixel, f16.red_max, f16.green_max),
+            (16, 31, 63)
+        );
+        let f8 = PixelFormat::for_color_depth(8).unwrap();
+        assert_eq!(f8.bytes_per_pixel(), 1);
+        assert!(PixelFormat::for_color_depth(12).is_err());
+        assert_eq!(PixelFormat::parse(&f16.to_bytes()), f16);
+    }
+
+    #[test]
+    fn test_pixel_conversion_to_rgba() {
+        let f16 = PixelFormat::for_color_depth(16).unwrap();
+        // Pure red in RGB565, little-endian.
+        assert_eq!(f16.to_rgba(f16.read_pixel(&[0x00, 0xf8])), [255, 0, 0, 255]);
+        let f8 = PixelFormat::for_color_depth(8).unwrap();
+        // BGR233: blue occupies the top two bits.
+        assert_eq!(f8.to_rgba(0xc0), [0, 0, 255, 255]);
+        assert_eq!(
+            rgb24().to_rgba(rgb24().read_pixel(&[0x30, 0x20, 0x10, 0])),
+            [0x10, 0x20, 0x30, 255]
+        );
+    }
+
+    #[test]
+    fn test_client_messages() {
+        assert_eq!(
+            key_event_msg(true, 0xff0d),
+            vec![4, 1, 0, 0, 0, 0, 0xff, 0x0d]
+        );
+        assert_eq!(pointer_event_msg(1, 10, 300), vec![5, 1, 0, 10, 1, 44]);
+        assert_eq!(
+            framebuffer_update_request_msg(true, 800, 600),
+            vec![3, 1, 0, 0, 0, 0, 0x03, 0x20, 0x02, 0x58]
+        );
+        assert_eq!(
+            client_cut_text_msg("hé€"),
+            vec![6, 0, 0, 0, 0, 0, 0, 3, b'h', 0xe9, b'?']
+        );
+        assert_eq!(
+            set_encodings_msg(&[7, -32]),
+            vec![2, 0, 0, 2, 0, 0, 0, 7, 0xff, 0xff, 0xff, 0xe0]
+        );
+    }
+
+    #[test]
+    fn test_keysym_mapping() {
+        assert_eq!(keysym_for_key_code(65), 'a' as u32);
+        assert_eq!(keysym_for_key_code(49), '1' as u32);
+        assert_eq!(keysym_for_key_code(13), 0xff0d);
+        assert_eq!(keysym_for_key_code(46), 0xffff);
+        assert_eq!(keysym_for_key_code(112), 0xffbe);
+        assert_eq!(keysym_for_key_code(0xff51), 0xff51);
+    }
+
+    #[test]
+    fn test_jpeg_quality_only_for_true_colour() {
+        assert!(preferred_encodings(&rgb24()).contains(&(ENCODING_JPEG_QUALITY_0 + 7)));
+        let f16 = PixelFormat::for_color_depth(16).unwrap();
+        assert!(!preferred_encodings(&f16).contains(&(ENCODING_JPEG_QUALITY_0 + 7)));
+    }
+
+    #[tokio::test]
+    async fn test_decode_raw_and_copy_rect() {
+        let mut decoder = FrameDecoder::new(rgb24(), 4, 2);
+        let mut data = vec![0u8, 0, 2];
+        data.extend(rect_header(0, 0, 2, 1, ENCODING_RAW));
+        data.extend([0x00, 0x00, 0xff, 0, 0x00, 0xff, 0x00, 0]); // red, green
+        data.extend(rect_header(2, 1, 2, 1, ENCODING_COPY_RECT));
+        data.extend([0, 0, 0, 0]); // from (0, 0)
+
+        let updates = decoder.read_update(&mut &data[..]).await.unwrap();
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].rgba_data, vec![255, 0, 0, 255, 0, 255, 0, 255]);
+        assert_eq!((updates[1].x, updates[1].y), (2, 1));
+        assert_eq!(updates[1].rgba_data, updates[0].rgba_data);
+    }
+
+    #[tokio::test]
+    async fn test_decode_rejects_rect_outside_framebuffer() {
+        let mut decoder = FrameDecoder::new(rgb24(), 4, 2);
+        let mut data = vec![0u8, 0, 1];
+        data.extend(rect_header(3, 0, 2, 1, ENCODING_RAW));
+        assert!(decoder.read_update(&mut &data[..]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_decode_zlib_keeps_stream_between_rects() {
+        let mut decoder = FrameDecoder::new(rgb24(), 2, 1);
+        let mut compress = Compress::new(Compression::default(), true);
+        let mut data = Vec::new();
+        for pixel in [[1u8, 2, 3, 0], [4, 5, 6, 0]] {
+            let chunk = zlib_sync(&mut compress, &[pixel, pixel].concat());
+            data.extend(rect_header(0, 0, 2, 1, ENCODING_ZLIB));
+            data.extend((chunk.len() as u32).to_be_bytes());
+            data.extend(chunk);
+        }
+
+        let mut stream = &data[..];
+        let first = decoder.read_rect_from_header(&mut stream).await.unwrap();
+        let second = decoder.read_rect_from_header(&mut stream).await.unwrap();
+        assert_eq!(first.rgba_data, vec![3, 2, 1, 255, 3, 2, 1, 255]);
+        assert_eq!(second.rgba_data, vec![6, 5, 4, 255, 6, 5, 4, 255]);
+    }
+
+    #[tokio::test]
+    async fn test_decode_tight_fill_and_short_copy() {
+        let mut decoder = FrameDecoder::new(rgb24(), 4, 2);
+        let mut data = rect_header(0, 0, 4, 2, ENCODING_TIGHT);
+        data.extend([0x80, 10, 20, 30]);
+        // Copy filter, 2 pixels = 6 bytes < 12, so sent uncompressed.
+        data.extend(rect_header(0, 0, 2, 1, ENCODING_TIGHT));
+        data.extend([0x00, 1, 2, 3, 4, 5, 6]);
+
+        let mut stream = &data[..];
+        let fill = decoder.read_rect_from_header(&mut stream).await.unwrap();
+        assert_eq!(fill.rgba_data, [10, 20, 30, 255].repeat(8));
+        let copy = decoder.read_rect_from_header(&mut stream).await.unwrap();
+        assert_eq!(copy.rgba_data, vec![1, 2, 3, 255, 4, 5, 6, 255]);
+    }
+
+    #[tokio::test]
+    async fn test_decode_tight_compressed_copy_and_mono_palette() {
+        let mut decoder = FrameDecoder::new(rgb24(), 4, 2);
+        let pixels: Vec<u8> = (0..24).collect();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&pixels).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let mut data = rect_header(0, 0, 4, 2, ENCODING_TIGHT);
+        data.push(0x10); // reset nothing, basic compression on stream 1
+        data.push(compressed.len() as u8);
+        data.extend(&compressed);
+        // Two-colour palette, 1 bit per pixel: rows 0b1010_0000, 0b0101_0000.
+        data.extend(rect_header(0, 0, 4, 2, ENCODING_TIGHT));
+        data.extend([0x40, 1, 1, 0, 0, 0, 0, 0, 255, 0b1010_0000, 0b0101_0000]);
+
+        let mut stream = &data[..];
+        let copy = decoder.read_rect_from_header(&mut stream).await.unwrap();
+        assert_eq!(&copy.rgba_data[..8], &[0, 1, 2, 255, 3, 4, 5, 255]);
+        let palette = decoder.read_rect_from_header(&mut stream).await.unwrap();
+        let black = [0, 0, 0, 255];
+        let blue = [0, 0, 255, 255];
+        assert_eq!(
+            palette.rgba_data,
+            [blue, black, blue, black, black, blue, black, blue].concat()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decode_tight_gradient() {
+        let mut decoder = FrameDecoder::new(rgb24(), 2, 2);
+        // Target image (one component shown, same on R/G/B):
+        //   10 20
+        //   30 45
+        // Predictions: 0, 10 | 10, 20+30-10=40 → diffs 10, 10, 20, 5.
+        let diffs: Vec<u8> = [10u8, 10, 20, 5].iter().flat_map(|&d| [d, d, d]).collect();
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&diffs).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut data = rect_header(0, 0, 2, 2, ENCODING_TIGHT);
+        data.extend([0x40, 2, compressed.len() as u8]);
+        data.extend(compressed);
+
+        let update = decoder.read_rect_from_header(&mut &data[..]).await.unwrap();
+        let values: Vec<u8> = update.rgba_data.chunks(4).map(|p| p[0]).collect();
+        assert_eq!(values, vec![10, 20, 30, 45]);
+    }
+
+    #[tokio::test]
+    async fn test_decode_tight_with_16bit_pixels() {
+        let format = PixelFormat::for_color_depth(16).unwrap();
+        let mut decoder = FrameDecoder::new(format, 1, 1);
+        let mut data = rect_header(0, 0, 1, 1, ENCODING_TIGHT);
+        data.extend([0x80, 0x1f, 0x00]); // fill with pure blue (RGB565 LE)
+        let update = decoder.read_rect_from_header(&mut &data[..]).await.unwrap();
+        assert_eq!(update.rgba_data, vec![0, 0, 255, 255]);
+    }
+
+    #[tokio::test]
+    async fn test_compact_len() {
+        assert_eq!(read_compact_len(&mut &[0x05u8][..]).await.unwrap(), 5);
+        assert_eq!(
+            read_compact_len(&mut &[0x90u8, 0x01][..]).await.unwrap(),
+            0x90
+        );
+        assert_eq!(
+            read_compact_len(&mut &[0xff, 0xff, 0xff][..])
+                .await
+                .unwrap(),
+            0x3f_ffff
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_rejects_empty_host() {
+        let result = VncClient::connect(&VncConfig {
+            host: String::new(),
+            port: 5900,
+            password: None,
+            color_depth: 24,
+        })
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_3_8_no_auth() {
+        let (port, server) = fake_server("RFB 003.008\n", &[SECURITY_NONE], None).await;
+        let client = VncClient::connect(&config(port, None, 16)).await.unwrap();
+        assert_eq!(client.desktop_size(), (4, 2));
+
+        let mut socket = server.await.unwrap().unwrap();
+        let (format, encodings) = read_client_setup(&mut socket).await;
+        assert_eq!(format, PixelFormat::for_color_depth(16).unwrap());
+        assert_eq!(
+            &encodings[..4],
+            &[
+                ENCODING_TIGHT,
+                ENCODING_ZLIB,
+                ENCODING_COPY_RECT,
+                ENCODING_RAW
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_handshake_3_7_vnc_auth() {
+        let (port, server) =
+            fake_server("RFB 003.007\n", &[SECURITY_VNC_AUTH], Some("hunter2")).await;
+        let client = VncClient::connect(&config(port, Some("hunter2"), 24)).await;
+        assert!(client.is_ok(), "{:?}", client.err());
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handshake_3_3_vnc_auth_wrong_password() {
+        let (port, server) =
+            fake_server("RFB 003.003\n", &[SECURITY_VNC_AUTH], Some("right")).await;
+        let err = VncClient::connect(&config(port, Some("wrong"), 24))
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("authentication failed"), "{err}");
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handshake_3_8_reports_server_reason() {
+        let (port, server) =
+            fake_server("RFB 003.008\n", &[SECURITY_VNC_AUTH], Some("right")).await;
+        let err = VncClient::connect(&config(port, Some("wrong"), 24))
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("bad password"), "{err}");
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_handshake_requires_password_for_vnc_auth() {
+        let (port, _server) = fake_server("RFB 003.008\n", &[SECURITY_VNC_AUTH], Some("x")).await;
+        let err = VncClient::connect(&config(port, None, 24))
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("requires a password"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejects_unsupported_security() {
+        let (port, _server) = fake_server("RFB 003.008\n", &[19], None).await;
+        let err = VncClient::connect(&config(port, None, 24))
+            .await
+            .err()
+            .unwrap();
+        assert!(
+            err.to_string().contains("no supported security type"),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_frame_loop_and_input_events() {
+        let (port, server) = fake_server("RFB 003.008\n", &[SECURITY_NONE], None).await;
+        let client = Arc::new(VncClient::connect(&config(port, None, 24)).await.unwrap());
+        let mut socket = server.await.unwrap().unwrap();
+        read_client_setup(&mut socket).await;
+
+        let (frame_tx, mut frame_rx) = mpsc::unbounded_channel();
+        let cancel = CancellationToken::new();
+        let frame_loop = {
+            let client = client.clone();
+            let cancel = cancel.clone();
+            tokio::spawn(async move { client.start_frame_loop(frame_tx, cancel).await })
+        };
+
+        // The loop opens with a non-incremental full-screen request.
+        let mut request = [0u8; 10];
+        socket.read_exact(&mut request).await.unwrap();
+        assert_eq!(
+            request.to_vec(),
+            framebuffer_update_request_msg(false, 4, 2)
+        );
+
+        let mut update = vec![MSG_BELL, MSG_FRAMEBUFFER_UPDATE, 0, 0, 1];
+        update.extend(rect_header(1, 1, 1, 1, ENCODING_RAW));
+        update.extend([0x33, 0x22, 0x11, 0]);
+        socket.write_all(&update).await.unwrap();
+
+        let frame = frame_rx.recv().await.unwrap();
+        assert_eq!((frame.x, frame.y, frame.width, frame.height), (1, 1, 1, 1));
+        assert_eq!(frame.rgba_data, vec![0x11, 0x22, 0x33, 255]);
+
+        // ...and follows every update with an incremental one.
+        socket.read_exact(&mut request).await.unwrap();
+        assert_eq!(request.to_vec(), framebuffer_update_request_msg(true, 4, 2));
+
+        client.send_key(65, true).await.unwrap();
+        let mut key = [0u8; 8];
+        socket.read_exact(&mut key).await.unwrap();
+        assert_eq!(key.to_vec(), key_event_msg(true, 'a' as u32));
+
+        client.send_pointer(3, 1, 0b100).await.unwrap();
+        let mut pointer = [0u8; 6];
+        socket.read_exact(&mut pointer).await.unwrap();
+        assert_eq!(pointer.to_vec(), pointer_event_msg(0b100, 3, 1));
+
+        client.set_clipboard("hi".to_string()).await.unwrap();
+        let mut cut = [0u8; 10];
+        socket.read_exact(&mut cut).await.unwrap();
+        assert_eq!(cut.to_vec(), client_cut_text_msg("hi"));
+
+        cancel.cancel();
+        frame_loop.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_disconnected_client_rejects_operations() {
+        let client = VncClient::new_disconnected(config(5900, None, 24));
+        assert!(client.send_key(65, true).await.is_err());
+        assert!(client.send_pointer(0, 0, 0).await.is_err());
+        assert!(client.request_full_frame().await.is_err());
+        assert!(client.set_clipboard("x".into()).await.is_err());
+        let (tx, _rx) = mpsc::unbounded_channel();
+        assert!(client
+            .start_frame_loop(tx, CancellationToken::new())
+            .await
+            .is_err());
+    }
+
+    impl FrameDecoder {
+        /// Read one rectangle header + body, as inside a FramebufferUpdate.
+        async fn read_rect_from_header<R: AsyncRead + Unpin>(
+            &mut self,
+            stream: &mut R,
+        ) -> Result<FrameUpdate> {
+            let x = stream.read_u16().await?;
+            let y = stream.read_u16().await?;
+            let w = stream.read_u16().await?;
+            let h = stream.read_u16().await?;
+            let encoding = stream.read_i32().await?;
+            self.read_rect(stream, x, y, w, h, encoding).await
+        }
+    }
+}
@@ -1,4 +1,7 @@
 use crate::connection_manager::ConnectionManager;
+use crate::desktop_protocol::FrameUpdate;
+use crate::input_broadcast::{BroadcastGroups, GroupMember};
+use crate::playback::{PlaybackCommand, PlayerEvent, Timeline};
 use crate::WEBSOCKET_PORT;
 use anyhow::Result;
 use futures::{SinkExt, StreamExt};
@@ -62,11 +65,74 @@ pub enum WsMessage {
         generation: u64,
     },
 
+    // ===== Broadcast input messages =====
+    /// Copy input typed into `connection_id` to every other terminal in
+    /// `group`, and theirs to it. Leaves any group it was already in.
+    JoinBroadcastGroup {
+        connection_id: String,
+        group: String,
+    },
+    LeaveBroadcastGroup { connection_id: String },
+    /// Keep a member in its group without sending or receiving its input
+    SetBroadcastExcluded {
+        connection_id: String,
+        excluded: bool,
+    },
+    /// Group membership after a join, leave or exclusion change
+    BroadcastGroupUpdated {
+        group: String,
+        members: Vec<GroupMember>,
+    },
+
+    // ===== Playback messages =====
+    /// Replay a recording into terminal `connection_id`: an asciicast v2
+    /// file, or a `script` typescript when `timing_path` is set.
+    StartPlayback {
+        connection_id: String,
+        path: String,
+        #[serde(default)]
+        timing_path: Option<String>,
+        #[serde(default)]
+        speed: Option<f64>,
+        /// Cap pauses to this many seconds (defaults to the file's own limit).
+        #[serde(default)]
+        idle_time_limit: Option<f64>,
+    },
+    /// Recording loaded — terminal size and length in seconds
+    PlaybackStarted {
+        connection_id: String,
+        width: u32,
+        height: u32,
+        duration: f64,
+    },
+    /// Play, pause, seek, change speed or stop
+    PlaybackControl {
+        connection_id: String,
+        command: PlaybackCommand,
+    },
+    /// Player state, sent after every command and when the end is reached
+    PlaybackStatus {
+        connection_id: String,
+        position: f64,
+        duration: f64,
+        speed: f64,
+        paused: bool,
+        finished: bool,
+    },
+    /// Terminal size change from the recording
+    PlaybackResize {
+        connection_id: String,
+        cols: u32,
+        rows: u32,
+    },
+
     // ===== Desktop (RDP/VNC) messages =====
     /// Start a desktop streaming session
     StartDesktop {
         connection_id: String,
+        #[serde(default)]
         width: u16,
+        #[serde(default)]
         height: u16,
     },
     /// Desktop session started confirmation
@@ -100,6 +166,8 @@ pub enum WsMessage {
 /// Handles bidirectional communication between frontend and PTY connections
 pub struct WebSocketServer {
     connection_manager: Arc<ConnectionManager>,
+    /// Shared by all sockets so a group can span several windows
+    broadcast_groups: Mutex<BroadcastGroups>,
 }
 
 // ---------------------------------------------------------------------------
@@ -126,6 +194,9 @@ const CONTROL_SEND_TIMEOUT_MS: u64 = 100;
 /// Command byte that identifies a binary PTY output frame sent to the frontend.
 const BINARY_OUTPUT_CMD: u8 = 0x01;
 
+/// Command byte that identifies a binary desktop frame update.
+const BINARY_DESKTOP_FRAME_CMD: u8 = 0x02;
+
 // ---------------------------------------------------------------------------
 // Types
 // ---------------------------------------------------------------------------
@@ -137,6 +208,9 @@ type WsTx = mpsc::Sender<Message>;
 /// ready, bounding the WKWebView message queue to INITIAL_WINDOW frames.
 type OutputCredits = Arc<Semaphore>;
 type OutputControls = Arc<Mutex<HashMap<String, OutputCredits>>>;
+/// Current PTY generation per connection on this socket.  Shared with the
+/// reader tasks, which bump it when they restart a PTY after a reconnect.
+type ActivePtyGenerations = Arc<Mutex<HashMap<String, u64>>>;
 
 #[derive(Debug, PartialEq, Eq)]
 enum SendOutcome {
@@ -177,6 +251,23 @@ fn encode_output_frame(connection_id: &str, data: &[u8]) -> Vec<u8> {
     frame
 }
 
+/// Encode a binary desktop frame update:
+///   [0x02][id_len: u16 BE][connection_id bytes][x][y][width][height][RGBA]
+/// with each coordinate a u16 BE.
+fn encode_desktop_frame(connection_id: &str, update: &FrameUpdate) -> Vec<u8> {
+    let id_bytes = connection_id.as_bytes();
+    let id_len = id_bytes.len().min(u16::MAX as usize);
+    let mut frame = Vec::with_capacity(11 + id_len + update.rgba_data.len());
+    frame.push(BINARY_DESKTOP_FRAME_CMD);
+    frame.extend_from_slice(&(id_len as u16).to_be_bytes());
+    frame.extend_from_slice(&id_bytes[..id_len]);
+    for value in [update.x, update.y, update.width, update.height] {
+        frame.extend_from_slice(&value.to_be_bytes());
+    }
+    frame.extend_from_slice(&update.rgba_data);
+    frame
+}
+
 /// Send a JSON control message with a timeout.
 /// Control messages are best-effort — a saturated channel returns `Dropped`.
 async fn send_control(tx: &WsTx, msg: &WsMessage) -> Result<SendOutcome> {
@@ -225,7 +316,39 @@ fn should_remove_pty_state(active_gen: Option<u64>, closed_gen: Option<u64>) ->
 
 impl WebSocketServer {
     pub fn new(connection_manager: Arc<ConnectionManager>) -> Self {
-        Self { connection_manager }
+        Self {
+            connection_manager,
+            broadcast_groups: Mutex::new(BroadcastGroups::default()),
+        }
+    }
+
+    /// Write input to `connection_id`, copying it to the other members of
+    /// its broadcast group. A member that fails to accept input is logged
+    /// and skipped; only the source's own error is returned.
+    async fn write_input(&self, connection_id: &str, data: Vec<u8>) -> Result<()> {
+        let targets = self
+            .broadcast_groups
+            .lock()
+            .await
+            .fan_out_targets(connection_id);
+        for target in targets {
+            if let Err(e) = self
+                .connection_manager
+                .write_to_pty(&target, data.clone())
+                .await
+            {
+                tracing::warn!("Failed to broadcast input to {}: {}", target, e);
+            }
+        }
+        self.connection_manager
+            .write_to_pty(connection_id, data)
+            .await
+    }
+
+    async fn send_group_update(&self, tx: &WsTx, group: String) -> Result<()> {
+        let members = self.broadcast_groups.lock().await.members(&group);
+        send_control(tx, &WsMessage::BroadcastGroupUpdated { group, members }).await?;
+        Ok(())
     }
 
     /// Start the WebSocket server, trying ports 9001-9010 to find an available one
@@ -283,7 +406,7 @@ impl WebSocketServer {
         // all the way back to the SSH channel and the remote process.
         let (tx, mut rx) = mpsc::channel::<Message>(WS_OUTPUT_QUEUE_CAPACITY);
         let output_controls: OutputControls = Arc::new(Mutex::new(HashMap::new()));
-        let mut active_pty_generations: HashMap<String, u64> = HashMap::new();
+        let active_pty_generations: ActivePtyGenerations = Arc::new(Mutex::new(HashMap::new()));
 
         // Forward messages from the bounded channel to the WebSocket.
         let ws_sender_task = tokio::spawn(async move {
@@ -311,11 +434,7 @@ impl WebSocketServer {
                             }
                             let connection_id = String::from_utf8_lossy(&data[1..37]).to_string();
                             let input_data = data[37..].to_vec();
-                            if let Err(e) = self
-                                .connection_manager
-                                .write_to_pty(&connection_id, input_data)
-                                .await
-                            {
+                            if let Err(e) = self.write_input(&connection_id, input_data).await {
                                 tracing::error!("Failed to write to PTY: {}", e);
                             }
                         }
@@ -337,19 +456,26 @@ impl WebSocketServer {
                         }
                     };
                     match self
-                        .handle_message(ws_msg, tx.clone(), output_controls.clone())
+                        .handle_message(
+                            ws_msg,
+                            tx.clone(),
+                            output_controls.clone(),
+                            active_pty_generations.clone(),
+                        )
                         .await
                     {
                         Ok(PtyLifecycleEvent::Started { connection_id, generation }) => {
-                            active_pty_generations.insert(connection_id, generation);
+                            active_pty_generations.lock().await.insert(connection_id, generation);
                         }
                         Ok(PtyLifecycleEvent::Closed { connection_id, generation }) => {
+                            let mut active = active_pty_generations.lock().await;
                             if should_remove_pty_state(
-                                active_pty_generations.get(&connection_id).copied(),
+                                active.get(&connection_id).copied(),
                                 generation,
                             ) {
-                                active_pty_generations.remove(&connection_id);
+                                active.remove(&connection_id);
                                 output_controls.lock().await.remove(&connection_id);
+                                self.broadcast_groups.lock().await.leave(&connection_id);
                             }
                         }
                         Ok(PtyLifecycleEvent::None) => {}
@@ -375,7 +501,9 @@ impl WebSocketServer {
 
         // Clean up all active PTY sessions so the SSH channel and reader task
         // are torn down promptly when the browser tab closes.
-        for (connection_id, generation) in active_pty_generations {
+        let active = std::mem::take(&mut *active_pty_generations.lock().await);
+        for (connection_id, generation) in active {
+            self.broadcast_groups.lock().await.leave(&connection_id);
             if let Err(e) = self
                 .connection_manager
                 .close_pty_connection(&connection_id, Some(generation))
@@ -400,6 +528,7 @@ impl WebSocketServer {
         msg: WsMessage,
         tx: WsTx,
         output_controls: OutputControls,
+        active_pty_generations: ActivePtyGenerations,
     ) -> Result<PtyLifecycleEvent> {
         match msg {
             WsMessage::StartPty {
@@ -419,7 +548,7 @@ impl WebSocketServer {
                     .start_pty_connection(&connection_id, cols, rows)
                     .await?;
 
-                let cancel_token = self
+                let mut cancel_token = self
                     .connection_manager
                     .get_pty_cancel_token(&connection_id)
                     .await
@@ -541,9 +670,40 @@ impl WebSocketServer {
                                     connection_id_clone,
                                     e
                                 );
-                                let error_msg = WsMessage::Error {
-                                    message: format!("Connection lost: {}", e),
+                                // If the transport dropped, reconnect and keep
+                                // streaming from a new PTY generation.
+                                let message = match connection_manager
+                                    .reconnect_pty(&connection_id_clone, &cancel_token)
+                                    .await
+                                {
+                                    Ok(Some(generation)) => {
+                                        let Some(token) = connection_manager
+                                            .get_pty_cancel_token(&connection_id_clone)
+                                            .await
+                                        else {
+                                            break;
+                                        };
+                                        cancel_token = token;
+                                        if let Some(active) = active_pty_generations
+                                            .lock()
+                                            .await
+                                            .get_mut(&connection_id_clone)
+                                        {
+                                            *active = generation;
+                                        }
+                                        let started = WsMessage::PtyStarted {
+                                            connection_id: connection_id_clone.clone(),
+                                            generation,
+                                        };
+                                        let _ = send_control(&tx_clone, &started).await;
+                                        continue;
+                                    }
+                                    Ok(None) => format!("Connection lost: {}", e),
+                                    Err(reconnect_error) => {
+                                        format!("Connection lost: {}", reconnect_error)
+                                    }
                                 };
+                                let error_msg = WsMessage::Error { message };
                                 let _ = send_control(&tx_clone, &error_msg).await;
                                 break;
                             }
@@ -567,9 +727,7 @@ impl WebSocketServer {
                     connection_id,
                     data.len()
                 );
-                self.connection_manager
-                    .write_to_pty(&connection_id, data)
-                    .await?;
+                self.write_input(&connection_id, data).await?;
                 Ok(PtyLifecycleEvent::None)
             }
             WsMessage::Resize {
@@ -616,6 +774,11 @@ impl WebSocketServer {
                 self.connection_manager
                     .close_pty_connection(&connection_id, generation)
                     .await?;
+                // A terminal showing a playback closes the same way.
+                let _ = self
+                    .connection_manager
+                    .control_playback(&connection_id, PlaybackCommand::Stop)
+                    .await;
                 let response = WsMessage::Success {
                     message: format!("PTY connection closed: {}", connection_id),
                 };
@@ -626,6 +789,158 @@ impl WebSocketServer {
                 })
             }
 
+            // ===== Broadcast input handling =====
+            WsMessage::JoinBroadcastGroup {
+                connection_id,
+                group,
+            } => {
+                tracing::info!("{} joined broadcast group {}", connection_id, group);
+                let previous = {
+                    let mut groups = self.broadcast_groups.lock().await;
+                    let previous = groups.leave(&connection_id);
+                    groups.join(&connection_id, &group);
+                    previous
+                };
+                if let Some(previous) = previous.filter(|previous| *previous != group) {
+                    self.send_group_update(&tx, previous).await?;
+                }
+                self.send_group_update(&tx, group).await?;
+                Ok(PtyLifecycleEvent::None)
+            }
+            WsMessage::LeaveBroadcastGroup { connection_id } => {
+                let group = self
+                    .broadcast_groups
+                    .lock()
+                    .await
+                    .leave(&connection_id)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("{} is not in a broadcast group", connection_id)
+                    })?;
+                self.send_group_update(&tx, group).await?;
+                Ok(PtyLifecycleEvent::None)
+            }
+            WsMessage::SetBroadcastExcluded {
+                connection_id,
+                excluded,
+            } => {
+                let group = self
+                    .broadcast_groups
+                    .lock()
+                    .await
+                    .set_excluded(&connection_id, excluded)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!("{} is not in a broadcast group", connection_id)
+                    })?;
+                self.send_group_update(&tx, group).await?;
+                Ok(PtyLifecycleEvent::None)
+            }
+
+            // ===== Playback message handling =====
+            WsMessage::StartPlayback {
+                connection_id,
+                path,
+                timing_path,
+                speed,
+                idle_time_limit,
+            } => {
+                tracing::info!("Starting playback of {} in {}", path, connection_id);
+                let mut timeline = tokio::task::spawn_blocking(move || {
+                    Timeline::load(
+                        std::path::Path::new(&crate::ssh::expand_tilde(&path)),
+                        timing_path
+                            .map(|p| crate::ssh::expand_tilde(&p))
+                            .as_deref()
+                            .map(std::path::Path::new),
+                    )
+                })
+                .await??;
+                if let Some(limit) = idle_time_limit.or(timeline.idle_time_limit) {
+                    timeline.compress_idle(limit);
+                }
+                let started = WsMessage::PlaybackStarted {
+                    connection_id: connection_id.clone(),
+                    width: timeline.width,
+                    height: timeline.height,
+                    duration: timeline.duration(),
+                };
+
+                let (handle, mut events) =
+                    crate::playback::spawn_player(timeline, speed.unwrap_or(1.0));
+                self.connection_manager
+                    .register_playback(&connection_id, handle.clone())
+                    .await;
+
+                // Same credit flow as a live PTY.
+                let credits: OutputCredits = Arc::new(Semaphore::new(0));
+                output_controls.lock().await.insert(connection_id.clone(), Arc::clone(&credits));
+                send_control(&tx, &started).await?;
+
+                let connection_manager = self.connection_manager.clone();
+                tokio::spawn(async move {
+                    let cancel = handle.cancel.clone();
+                    loop {
+                        let event = tokio::select! {
+                            biased;
+                            _ = cancel.cancelled() => break,
+                            _ = tx.closed() => break,
+                            event = events.recv() => match event {
+                                Some(event) => event,
+                                None => break,
+                            },
+                        };
+                        match event {
+                            PlayerEvent::Output(mut data) => {
+                                let ok = tokio::select! {
+                                    biased;
+                                    _ = cancel.cancelled() => false,
+                                    r = credits.acquire() => r.map(|p| { p.forget(); true }).unwrap_or(false),
+                                };
+                                if !ok
+                                    || flush_output(&tx, &connection_id, &mut data, &cancel).await
+                                        == SendOutcome::Closed
+                                {
+                                    break;
+                                }
+                            }
+                            PlayerEvent::Resize { cols, rows } => {
+                                let resize = WsMessage::PlaybackResize {
+                                    connection_id: connection_id.clone(),
+                                    cols,
+                                    rows,
+                                };
+                                let _ = send_control(&tx, &resize).await;
+                            }
+                            PlayerEvent::Status(status) => {
+                                let status = WsMessage::PlaybackStatus {
+                                    connection_id: connection_id.clone(),
+                                    position: status.position,
+                                    duration: status.duration,
+                                    speed: status.speed,
+                                    paused: status.paused,
+                                    finished: status.finished,
+                                };
+                                let _ = send_control(&tx, &status).await;
+                            }
+                        }
+                    }
+                    connection_manager
+                        .finish_playback(&connection_id, &handle)
+                        .await;
+                    tracing::info!("Playback task exiting for {}", connection_id);
+                });
+                Ok(PtyLifecycleEvent::None)
+            }
+            WsMessage::PlaybackControl {
+                connection_id,
+                command,
+            } => {
+                tracing::debug!("Playback control for {}: {:?}", connection_id, command);
+                self.connection_manager
+                    .control_playback(&connection_id, command)
+                    .await?;
+                Ok(PtyLifecycleEvent::None)
+            }
+
             // ===== Desktop (RDP/VNC) message handling =====
             WsMessage::StartDesktop {
                 connection_id,
@@ -648,6 +963,35 @@ impl WebSocketServer {
                         height: h,
                     };
                     send_control(&tx, &started).await?;
+
+                    // The frame loop runs until CloseDesktop, a newer
+                    // StartDesktop or this socket going away.
+                    let cancel = CancellationToken::new();
+                    let (frame_tx, mut frame_rx) = mpsc::unbounded_channel::<FrameUpdate>();
+                    let manager = self.connection_manager.clone();
+                    let stream_id = connection_id.clone();
+                    let stream_cancel = cancel.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = manager
+                            .start_desktop_stream(&stream_id, frame_tx, stream_cancel)
+                            .await
+                        {
+                            tracing::error!("Desktop stream for {} ended: {}", stream_id, e);
+                        }
+                    });
+                    tokio::spawn(async move {
+                        while let Some(update) = frame_rx.recv().await {
+                            let frame = encode_desktop_frame(&connection_id, &update);
+                            let sent = tokio::select! {
+                                _ = cancel.cancelled() => false,
+                                result = tx.send(Message::Binary(frame.into())) => result.is_ok(),
+                            };
+                            if !sent {
+                                break;
+                            }
+                        }
+                        cancel.cancel();
+                    });
                 } else {
                     let error = WsMessage::Error {
                         message: format!("Desktop connection not found: {}", connection_id),